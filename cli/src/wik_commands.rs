@@ -58,6 +58,13 @@ pub enum WikLoggedInCommands {
         app_name: String,
         config_key: String,
     },
+    /// List the apps, or the config keys of the app
+    Ls {
+        app_name: Option<String>,
+
+        #[arg(long)]
+        prefix: Option<String>,
+    },
     /// Set config
     Set {
         app_name: String,
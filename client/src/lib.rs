@@ -1,4 +1,11 @@
+use well_i_known_core::api::data::BulkDataResponse;
 use well_i_known_core::crypto::cryptography::{Decryption, WikRsaKeyPair};
+use well_i_known_core::modal::config_schema::{AppConfigSchema, TypedConfigValue};
+use well_i_known_core::modal::util::config_key::{key_below, normalize_config_key, KEY_SEPARATOR};
+
+use anyhow::{anyhow, bail, Context, Result};
+use std::collections::HashMap;
+use std::time::Duration;
 
 pub fn add(left: usize, right: usize) -> usize {
     left + right
@@ -9,13 +16,185 @@ pub fn read_key(){
     key.private_key.decrypt_string("hello").unwrap();
 }
 
+/// Decrypt the app's config fetched in bulk with the requester's private key.
+/// Return key => plaintext value, including the non-secret entries.
+pub fn decrypt_bulk_data(key: &WikRsaKeyPair, response: &BulkDataResponse) -> Result<HashMap<String, String>> {
+    let mut config: HashMap<String, String> = response.data.iter()
+        .map(|(config_key, ciphertext)| {
+            let value = key.private_key.decrypt_string(ciphertext)
+                .with_context(|| format!("Fail to decrypt '{}' of app '{}'.", config_key, response.app))?;
+            Ok((config_key.clone(), value))
+        })
+        .collect::<Result<_>>()?;
+    config.extend(response.plain.clone());
+    Ok(config)
+}
+
+/// Nest the (decrypted) config in the subtree of the prefix by the segments of the keys,
+/// e.g. `db/primary/password` below `db` is `{"primary": {"password": ...}}`.
+/// Fails if a key is both a value and the path of other keys, e.g. `db/primary` and `db/primary/password`.
+/// The prefix may use either separator, e.g. `db.primary`.
+pub fn nest_config(config: &HashMap<String, String>, prefix: &str) -> Result<serde_json::Value> {
+    let prefix = normalize_config_key(prefix);
+    let mut tree = serde_json::Map::new();
+    for (config_key, value) in config {
+        let path = match key_below(config_key, &prefix) {
+            Some("") => bail!("Key '{}' is a value, not a subtree.", config_key),
+            Some(path) => path,
+            None => continue,
+        };
+
+        let segments: Vec<&str> = path.split(KEY_SEPARATOR).collect();
+        let (leaf, parents) = segments.split_last().unwrap();
+        let mut node = &mut tree;
+        for segment in parents {
+            node = match node.entry(*segment).or_insert_with(|| serde_json::Value::Object(serde_json::Map::new())) {
+                serde_json::Value::Object(child) => child,
+                _ => bail!("Key '{}' is below the value of another key.", config_key),
+            };
+        }
+        if node.insert(leaf.to_string(), serde_json::Value::String(value.clone())).is_some() {
+            bail!("Key '{}' is the path of other keys.", config_key);
+        }
+    }
+    Ok(serde_json::Value::Object(tree))
+}
+
+/// The decrypted config of an app, with the accessors typed by the app's schema.
+/// The default in the schema is used for a key not set.
+pub struct TypedConfig {
+    schema: AppConfigSchema,
+    values: HashMap<String, String>,    // key => plaintext value
+}
+
+impl TypedConfig {
+    pub fn new(schema: AppConfigSchema, values: HashMap<String, String>) -> Self {
+        Self { schema, values }
+    }
+
+    /// Get the value of the key parsed by its type in the schema (string if the key is not in the schema).
+    /// Return none if the key is not set and has no default. The key may use either separator, e.g. `db.url`.
+    pub fn get(&self, key: &str) -> Result<Option<TypedConfigValue>> {
+        let key = normalize_config_key(key);
+        let key_schema = self.schema.get_key(&key);
+        let value = self.values.get(&key)
+            .or_else(|| key_schema.and_then(|key_schema| key_schema.default.as_ref()));
+
+        match (value, key_schema) {
+            (None, _) => Ok(None),
+            (Some(value), Some(key_schema)) => key_schema.parse(value).map(Some).map_err(|error| anyhow!(error)),
+            (Some(value), None) => Ok(Some(TypedConfigValue::String(value.clone()))),
+        }
+    }
+
+    fn get_set(&self, key: &str) -> Result<TypedConfigValue> {
+        self.get(key)?.with_context(|| format!("Key '{}' is not set.", key))
+    }
+
+    pub fn get_string(&self, key: &str) -> Result<String> {
+        match self.get_set(key)? {
+            TypedConfigValue::String(value) => Ok(value),
+            value => bail!("Key '{}' is not a string: {:?}", key, value),
+        }
+    }
+
+    pub fn get_int(&self, key: &str) -> Result<i64> {
+        match self.get_set(key)? {
+            TypedConfigValue::Int(value) => Ok(value),
+            value => bail!("Key '{}' is not an integer: {:?}", key, value),
+        }
+    }
+
+    pub fn get_bool(&self, key: &str) -> Result<bool> {
+        match self.get_set(key)? {
+            TypedConfigValue::Bool(value) => Ok(value),
+            value => bail!("Key '{}' is not a bool: {:?}", key, value),
+        }
+    }
+
+    pub fn get_duration(&self, key: &str) -> Result<Duration> {
+        match self.get_set(key)? {
+            TypedConfigValue::Duration(value) => Ok(value),
+            value => bail!("Key '{}' is not a duration: {:?}", key, value),
+        }
+    }
+
+    pub fn get_json(&self, key: &str) -> Result<serde_json::Value> {
+        match self.get_set(key)? {
+            TypedConfigValue::Json(value) => Ok(value),
+            value => bail!("Key '{}' is not JSON: {:?}", key, value),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use well_i_known_core::crypto::cryptography::Encryption;
 
     #[test]
     fn it_works() {
         let result = add(2, 2);
         assert_eq!(result, 4);
     }
+
+    #[test]
+    fn test_decrypt_bulk_data() {
+        let key = WikRsaKeyPair::new().unwrap();
+        let response = BulkDataResponse {
+            app: "u_app".to_string(),
+            env: "default".to_string(),
+            data: HashMap::from([
+                ("db_url".to_string(), key.public_key.encrypt_string("sqlite://app.db").unwrap()),
+                ("db_user".to_string(), key.public_key.encrypt_string("app").unwrap()),
+            ]),
+            plain: HashMap::from([("log_level".to_string(), "info".to_string())]),
+            revisions: HashMap::from([("db_url".to_string(), 1), ("db_user".to_string(), 3), ("log_level".to_string(), 1)]),
+            expires_at: HashMap::new(),
+        };
+
+        let config = decrypt_bulk_data(&key, &response).unwrap();
+        assert_eq!(config.get("db_url").unwrap(), "sqlite://app.db");
+        assert_eq!(config.get("db_user").unwrap(), "app");
+        assert_eq!(config.get("log_level").unwrap(), "info");
+    }
+
+    #[test]
+    fn test_nest_config() {
+        let config = HashMap::from([
+            ("db/primary/url".to_string(), "sqlite://primary.db".to_string()),
+            ("db/primary/password".to_string(), "secret".to_string()),
+            ("db/replica/url".to_string(), "sqlite://replica.db".to_string()),
+            ("log_level".to_string(), "info".to_string()),
+        ]);
+        let tree = nest_config(&config, "db").unwrap();
+        assert_eq!(tree, serde_json::json!({
+            "primary": {"url": "sqlite://primary.db", "password": "secret"},
+            "replica": {"url": "sqlite://replica.db"},
+        }));
+        assert_eq!(nest_config(&config, "db.primary").unwrap(), serde_json::json!({
+            "url": "sqlite://primary.db", "password": "secret",
+        }));
+
+        let config = HashMap::from([
+            ("db/primary".to_string(), "sqlite://primary.db".to_string()),
+            ("db/primary/password".to_string(), "secret".to_string()),
+        ]);
+        assert!(nest_config(&config, "").is_err());
+    }
+
+    #[test]
+    fn test_typed_config() {
+        let schema: AppConfigSchema = serde_json::from_str(r#"{"keys": [
+            {"key": "max_connections", "type": "int"},
+            {"key": "timeout", "type": "duration", "default": "30s"},
+            {"key": "debug", "type": "bool"}
+        ]}"#).unwrap();
+        let config = TypedConfig::new(schema, HashMap::from([("max_connections".to_string(), "20".to_string())]));
+
+        assert_eq!(config.get_int("max_connections").unwrap(), 20);
+        assert_eq!(config.get_duration("timeout").unwrap(), Duration::from_secs(30));
+        assert!(config.get_bool("debug").is_err());
+        assert!(config.get_string("max_connections").is_err());
+    }
 }
@@ -1,7 +1,49 @@
-use serde::{self, Deserialize};
+use crate::api::data::all_environments;
+use serde::{self, Serialize, Deserialize};
 
 #[derive(Deserialize, Debug)]
 pub struct AdminAccessParam {
     pub admin: String,          // admin username
     pub app: String,            // app name
+    #[serde(default = "all_environments")]
+    pub env: String,            // environment of the app, all environments ("*") if none
+    pub duration: Option<i64>,  // seconds the access right is valid for, never expires if none
+}
+
+/// GET admin access rights query param
+#[derive(Deserialize, Debug)]
+pub struct ListAdminAccessQuery {
+    pub admin: Option<String>,  // filter by admin username
+    pub app: Option<String>,    // filter by app name
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AdminAccessResponse {
+    pub admin: String,
+    pub app: String,
+    pub env: String,                    // "*" for all environments
+    pub expires_at: Option<i64>,        // UTC timestamp, never expires if none
+    pub remaining_seconds: Option<i64>, // time left before the access right expires
+}
+
+/// Post body parameter for emergency access to an app without a prior access right
+#[derive(Deserialize, Debug)]
+pub struct BreakGlassParam {
+    pub app: String,            // app name
+    pub justification: String,  // reason of the emergency access, reviewed by root
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BreakGlassResponse {
+    pub expires_at: i64,        // UTC timestamp of the temporary access right expiry
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BreakGlassEventResponse {
+    pub id: i64,
+    pub admin: String,
+    pub app: String,
+    pub justification: String,
+    pub created_at: i64,        // UTC timestamp
+    pub expires_at: i64,        // UTC timestamp
 }
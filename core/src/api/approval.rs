@@ -0,0 +1,73 @@
+use crate::api::data::all_environments;
+use serde::{self, Serialize, Deserialize};
+use std::collections::HashMap;
+use strum_macros::{Display, EnumString};
+
+/// Destructive operations that only execute after a second admin approved them.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type")]
+pub enum FlaggedOperation {
+    DeleteUser { username: String },
+    DeleteAdminAccess {
+        admin: String,
+        app: String,
+        #[serde(default = "all_environments")]
+        env: String,
+    },
+    DeleteGroupAccess { group: String, app: String },
+    DeleteGroup { group: String },      // bulk revoke of all the group's access
+    DeleteGroupMember { group: String, admin: String },     // revoke of the admin's access through the group
+    DeleteSubtree { app: String, env: String, prefix: String },     // bulk delete of the config keys in the subtree
+    ApplyBatch {                        // batch with a bulk delete of the config keys, applied as a whole
+        app: String,
+        env: String,
+        #[serde(default)]
+        set: SealedValues,              // secret keys set by the batch
+        #[serde(default)]
+        plain: SealedValues,            // non-secret keys set by the batch
+        delete: Vec<String>,
+        #[serde(default)]
+        revisions: HashMap<String, i64>,    // key => expected revision, checked when executed
+        #[serde(default)]
+        expires_at: HashMap<String, i64>,   // key set => expiry timestamp
+    },
+}
+
+/// Values set by a pending batch: key => value encrypted with the root's public key until executed.
+/// Only the keys are printed (e.g. in the logs and the approval request).
+#[derive(Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(transparent)]
+pub struct SealedValues(pub HashMap<String, String>);
+
+impl core::fmt::Debug for SealedValues {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let mut keys: Vec<&String> = self.0.keys().collect();
+        keys.sort();
+        write!(f, "{:?}", keys)
+    }
+}
+
+#[derive(Debug, PartialEq, EnumString, Display)]
+pub enum ApprovalStatus {
+    Pending,
+    Approved,
+    Rejected,
+    Failed,     // approved but fail to execute
+}
+
+/// Post body parameter for approving / rejecting a pending operation
+#[derive(Deserialize, Debug)]
+pub struct ApprovalParam {
+    pub id: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PendingOperationResponse {
+    pub id: i64,
+    pub operation: FlaggedOperation,
+    pub requester: String,
+    pub created_at: i64,        // UTC timestamp
+    pub status: String,         // ApprovalStatus
+    pub approver: Option<String>,   // admin who approved / rejected the operation, none while pending
+    pub decided_at: Option<i64>,    // UTC timestamp
+}
@@ -1,21 +1,319 @@
-use serde::{self, Deserialize};
+use crate::modal::config_schema::AppConfigSchema;
+use crate::modal::util::config_key::{deserialize_config_key, deserialize_config_key_map, deserialize_config_keys,
+    deserialize_optional_config_key, deserialize_optional_config_keys};
+use serde::{self, Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Response header of GET config (and of its versions), "false" if the value is a non-secret entry in plaintext
+pub const SECRET_HEADER: &str = "x-wik-secret";
+
+/// Environment of the config when none is given, the only one unless the server configures others
+pub const DEFAULT_ENVIRONMENT: &str = "default";
+/// Access right granted for all environments of the app
+pub const ALL_ENVIRONMENTS: &str = "*";
+
+pub fn default_env() -> String {
+    DEFAULT_ENVIRONMENT.to_string()
+}
+
+pub fn all_environments() -> String {
+    ALL_ENVIRONMENTS.to_string()
+}
 
 /// GET config query param
 #[derive(Deserialize, Debug)]
 pub struct GetDataQuery {
     pub app: String,
+    #[serde(default = "default_env")]
+    pub env: String,        // environment of the config, e.g. dev / staging / prod
+    #[serde(deserialize_with = "deserialize_config_key")]
     pub key: String,
 }
 
+/// GET all config of the app in one call
+#[derive(Deserialize, Debug)]
+pub struct GetBulkDataQuery {
+    pub app: String,
+    #[serde(default = "default_env")]
+    pub env: String,
+    #[serde(default, deserialize_with = "deserialize_optional_config_keys")]
+    pub keys: Option<Vec<String>>,  // only the given keys, all keys if none
+    #[serde(default, deserialize_with = "deserialize_optional_config_key")]
+    pub prefix: Option<String>,     // only the keys in the subtree, e.g. "db/primary" for "db/primary/password"
+}
+
+/// Ciphertexts of the app's config, encrypted with the requester's public key
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BulkDataResponse {
+    pub app: String,
+    #[serde(default = "default_env")]
+    pub env: String,
+    pub data: HashMap<String, String>,  // key => ciphertext, the keys not found are omitted
+    #[serde(default)]
+    pub plain: HashMap<String, String>, // key => plaintext value, of the non-secret keys
+    #[serde(default)]
+    pub revisions: HashMap<String, i64>,    // key => revision, of the returned keys
+    #[serde(default)]
+    pub expires_at: HashMap<String, i64>,   // key => expiry timestamp, of the returned keys and the expired keys withheld by the server
+}
+
 #[derive(Deserialize, Debug)]
 pub struct UpdateDataParam {
     pub app: String,
+    #[serde(default = "default_env")]
+    pub env: String,
+    #[serde(deserialize_with = "deserialize_config_key")]
     pub key: String,
     pub value: String,      // plaintext
+    #[serde(default = "default_secret")]
+    pub secret: bool,       // false: stored once in plaintext, readable by everyone with access
+    pub revision: Option<i64>,  // expected revision (or the If-Match header), fails if the key is changed since
+    pub expires_at: Option<i64>,    // UTC timestamp the value expires at (e.g. of a certificate), never if none
+}
+
+fn default_secret() -> bool {
+    true
+}
+
+/// Set and delete several keys of the app atomically
+#[derive(Deserialize)]
+pub struct BatchDataParam {
+    pub app: String,
+    #[serde(default = "default_env")]
+    pub env: String,
+    #[serde(default, deserialize_with = "deserialize_config_key_map")]
+    pub set: HashMap<String, String>,   // key => value in plaintext, encrypted as a secret
+    #[serde(default, deserialize_with = "deserialize_config_key_map")]
+    pub plain: HashMap<String, String>, // key => value of a non-secret entry
+    #[serde(default, deserialize_with = "deserialize_config_keys")]
+    pub delete: Vec<String>,
+    #[serde(default, deserialize_with = "deserialize_config_key_map")]
+    pub revisions: HashMap<String, i64>,    // key => expected revision, only the given keys are checked
+    #[serde(default, deserialize_with = "deserialize_config_key_map")]
+    pub expires_at: HashMap<String, i64>,   // key set => expiry timestamp, the other keys set never expire
+}
+
+/// New revisions of the keys changed by the batch
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BatchDataResponse {
+    pub app: String,
+    #[serde(default = "default_env")]
+    pub env: String,
+    pub revisions: HashMap<String, i64>,
+}
+
+impl core::fmt::Debug for BatchDataParam {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        // do not log / print the values
+        let mut set_keys: Vec<&String> = self.set.keys().collect();
+        set_keys.sort();
+        let mut plain_keys: Vec<&String> = self.plain.keys().collect();
+        plain_keys.sort();
+        write!(f, "BatchDataParam {{ app: {}, env: {}, set: {:?}, plain: {:?}, delete: {:?}, revisions: {:?}, expires_at: {:?} }}",
+            self.app, self.env, set_keys, plain_keys, self.delete, self.revisions, self.expires_at)
+    }
 }
 
 #[derive(Deserialize, Debug)]
 pub struct DeleteDataParam {
     pub app: String,
+    #[serde(default = "default_env")]
+    pub env: String,
+    #[serde(deserialize_with = "deserialize_config_key")]
     pub key: String,
+    pub revision: Option<i64>,  // expected revision (or the If-Match header), fails if the key is changed since
+}
+
+/// Delete all keys in the subtree of the prefix atomically, answered with a BatchDataResponse
+#[derive(Deserialize, Debug)]
+pub struct DeleteSubtreeParam {
+    pub app: String,
+    #[serde(default = "default_env")]
+    pub env: String,
+    #[serde(deserialize_with = "deserialize_config_key")]
+    pub prefix: String,
+}
+
+/// Sort order of the listed names
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+/// Filtering, sorting and pagination of the listed names
+#[derive(Deserialize, Debug, Default)]
+pub struct ListPageParam {
+    pub prefix: Option<String>,     // only the names starting with the prefix
+    #[serde(default)]
+    pub order: SortOrder,
+    pub offset: Option<usize>,      // default 0
+    pub limit: Option<usize>,       // default / at most LIST_MAX_LIMIT
+}
+
+pub const LIST_MAX_LIMIT: usize = 1000;
+
+impl ListPageParam {
+    /// Filter, sort and take the requested page of the names.
+    pub fn apply(&self, mut names: Vec<String>) -> ListPageResponse {
+        if let Some(prefix) = &self.prefix {
+            names.retain(|name| name.starts_with(prefix.as_str()));
+        }
+        names.sort();
+        if self.order == SortOrder::Desc {
+            names.reverse();
+        }
+
+        let total = names.len();
+        let offset = self.offset.unwrap_or(0);
+        let limit = self.limit.unwrap_or(LIST_MAX_LIMIT).min(LIST_MAX_LIMIT);
+        let items = names.into_iter().skip(offset).take(limit).collect();
+        ListPageResponse { items, total, offset, values: HashMap::new() }
+    }
+}
+
+/// A page of the listed names
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ListPageResponse {
+    pub items: Vec<String>,
+    pub total: usize,               // count of all names matching the filter
+    pub offset: usize,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub values: HashMap<String, String>,    // listed name => plaintext value, of the non-secret config keys
+}
+
+/// GET the apps visible to the requester
+#[derive(Deserialize, Debug)]
+pub struct ListAppsQuery {
+    #[serde(flatten)]
+    pub page: ListPageParam,
+}
+
+/// GET the config keys (without values) of the app
+#[derive(Deserialize, Debug)]
+pub struct ListKeysQuery {
+    pub app: String,
+    #[serde(default = "default_env")]
+    pub env: String,
+    #[serde(default, deserialize_with = "deserialize_optional_config_key")]
+    pub subtree: Option<String>,    // only the keys in the subtree of the prefix
+    pub depth: Option<usize>,       // keys deeper below the subtree are listed once by their path, e.g. "db/primary/"
+    #[serde(flatten)]
+    pub page: ListPageParam,
+}
+
+/// GET the versions (without values) of the config key
+#[derive(Deserialize, Debug)]
+pub struct ListVersionsQuery {
+    pub app: String,
+    #[serde(default = "default_env")]
+    pub env: String,
+    #[serde(deserialize_with = "deserialize_config_key")]
+    pub key: String,
+}
+
+/// GET the value of one version of the config key
+#[derive(Deserialize, Debug)]
+pub struct GetVersionQuery {
+    pub app: String,
+    #[serde(default = "default_env")]
+    pub env: String,
+    #[serde(deserialize_with = "deserialize_config_key")]
+    pub key: String,
+    pub version: i64,
+}
+
+/// Set the value of the config key back to one of its versions (as a new version)
+#[derive(Deserialize, Debug)]
+pub struct RollbackDataParam {
+    pub app: String,
+    #[serde(default = "default_env")]
+    pub env: String,
+    #[serde(deserialize_with = "deserialize_config_key")]
+    pub key: String,
+    pub version: i64,
+}
+
+/// Copy the value of the config key from the environment to the next one (in the server's order of environments)
+#[derive(Deserialize, Debug)]
+pub struct PromoteDataParam {
+    pub app: String,
+    #[serde(deserialize_with = "deserialize_config_key")]
+    pub key: String,
+    pub env: String,                // source environment
+    pub revision: Option<i64>,      // expected revision of the key in the target environment
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PromoteDataResponse {
+    pub app: String,
+    pub key: String,
+    pub env: String,                // target environment
+    pub revision: i64,              // new revision of the key in the target environment
+}
+
+/// GET the keys expiring soon (or expired) of the apps visible to the requester
+#[derive(Deserialize, Debug)]
+pub struct ExpiringDataQuery {
+    pub app: Option<String>,        // all visible apps if none
+    pub within: Option<i64>,        // seconds from now, the longest warning lead time of the server if none
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ExpiringDataResponse {
+    pub app: String,
+    pub env: String,
+    pub key: String,
+    pub expires_at: i64,            // UTC timestamp
+    pub remaining_seconds: i64,     // 0 if expired
+}
+
+/// Set the number of versions kept per config key of the app
+#[derive(Deserialize, Debug)]
+pub struct HistoryRetentionParam {
+    pub app: String,
+    pub retention: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ConfigVersionResponse {
+    pub version: i64,
+    pub author: String,
+    pub created_at: i64,        // UTC timestamp
+    #[serde(default = "default_secret")]
+    pub secret: bool,           // false: the version of a non-secret entry, its value is in plaintext
+}
+
+/// Set the schema of the app's config
+#[derive(Deserialize, Serialize, Debug)]
+pub struct AppSchemaParam {
+    pub app: String,
+    pub schema: AppConfigSchema,
+}
+
+/// GET / DELETE the schema of the app's config
+#[derive(Deserialize, Debug)]
+pub struct AppSchemaQuery {
+    pub app: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_list_page() {
+        let names = vec!["db_user", "api_key", "db_url", "db_pass"].into_iter().map(String::from).collect();
+        let page = ListPageParam {
+            prefix: Some("db_".to_string()),
+            order: SortOrder::Desc,
+            offset: Some(1),
+            limit: Some(1),
+        };
+        let response = page.apply(names);
+        assert_eq!(response.items, vec!["db_url".to_string()]);
+        assert_eq!(response.total, 3);
+    }
 }
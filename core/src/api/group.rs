@@ -0,0 +1,18 @@
+use serde::{self, Deserialize};
+
+#[derive(Deserialize, Debug)]
+pub struct GroupParam {
+    pub group: String,          // group name
+}
+
+#[derive(Deserialize, Debug)]
+pub struct GroupMemberParam {
+    pub group: String,          // group name
+    pub admin: String,          // admin username
+}
+
+#[derive(Deserialize, Debug)]
+pub struct GroupAccessParam {
+    pub group: String,          // group name
+    pub app: String,            // app name
+}
@@ -1,4 +1,8 @@
 pub mod user;
 pub mod admin;
+pub mod approval;
+pub mod group;
 pub mod data;
+pub mod notification;
+pub mod token;
 pub mod operation;
@@ -0,0 +1,15 @@
+use serde::{self, Serialize, Deserialize};
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct NotificationResponse {
+    pub id: i64,
+    pub recipient: String,
+    pub category: String,
+    pub message: String,
+    pub created_at: i64,        // UTC timestamp
+}
+
+#[derive(Deserialize, Debug)]
+pub struct DeleteNotificationParam {
+    pub id: i64,
+}
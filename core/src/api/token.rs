@@ -0,0 +1,67 @@
+use crate::modal::util::config_key::deserialize_optional_config_keys;
+use serde::{self, Serialize, Deserialize};
+
+/// Restriction of what an API token can do.
+/// API tokens can only access the data API.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ApiTokenScope {
+    #[serde(default)]
+    pub read_only: bool,            // only get the data, cannot alter / delete
+    #[serde(default, deserialize_with = "deserialize_optional_config_keys")]
+    pub keys: Option<Vec<String>>,  // only these keys can be accessed, all keys if none
+}
+
+impl ApiTokenScope {
+    pub fn allows_key(&self, key: &str) -> bool {
+        match &self.keys {
+            Some(keys) => keys.iter().any(|allowed| allowed == key),
+            None => true,
+        }
+    }
+}
+
+/// Post body parameter for creating an API token
+#[derive(Deserialize, Debug)]
+pub struct CreateApiTokenParam {
+    pub name: String,
+    pub username: Option<String>,   // owner of the token, the requester if none
+    pub scope: Option<ApiTokenScope>,
+    pub duration: Option<i64>,      // seconds the token is valid for, never expires if none
+}
+
+/// GET API tokens query param
+#[derive(Deserialize, Debug)]
+pub struct ListApiTokenQuery {
+    pub username: Option<String>,   // filter by owner, own tokens if none (all tokens for root)
+}
+
+/// Delete body parameter for revoking an API token
+#[derive(Deserialize, Debug)]
+pub struct DeleteApiTokenParam {
+    pub id: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ApiTokenResponse {
+    pub id: String,
+    pub name: String,
+    pub username: String,
+    pub scope: ApiTokenScope,
+    pub created_at: i64,            // UTC timestamp
+    pub expires_at: Option<i64>,    // UTC timestamp, never expires if none
+}
+
+/// Response of creating an API token.
+/// The token is only shown once, the server keeps its hash only.
+#[derive(Serialize, Deserialize)]
+pub struct CreateApiTokenResponse {
+    pub token: String,
+    pub detail: ApiTokenResponse,
+}
+
+impl core::fmt::Debug for CreateApiTokenResponse {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        // do not log / print the token
+        write!(f, "CreateApiTokenResponse {{ detail: {:?} }}", self.detail)
+    }
+}
@@ -4,7 +4,7 @@ use serde::{self, Serialize, Deserialize};
 #[derive(Deserialize)]
 pub struct UpdateUserParam {
     pub username: String,
-    pub password: String,           // plaintext
+    pub password: Option<String>,   // plaintext, not needed for user login with private key only
     pub role: Option<String>,       // only for new user
     pub public_key: Option<String>, // only for new user
 }
@@ -21,14 +21,83 @@ pub struct DeleteUserParam {
     pub username: String,
 }
 
+#[derive(Deserialize, Debug)]
+pub struct UnlockUserParam {
+    pub username: String,
+}
+
 #[derive(Deserialize, Debug)]
 pub struct ValidateUserParam {
     pub username: String,
 }
 
+/// Post body parameter for activating the user with the answer of the validation handshake
+#[derive(Deserialize)]
+pub struct ActivateUserParam {
+    pub challenge_id: String,
+    pub plaintext: String,          // the challenge decrypted with the user's private key
+}
+
+impl core::fmt::Debug for ActivateUserParam {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        // do not log / print the answer
+        write!(f, "ActivateUserParam {{ challenge_id: {} }}", self.challenge_id)
+    }
+}
+
+/// Post body parameter for disabling / enabling the user
+#[derive(Deserialize, Debug)]
+pub struct UserStatusParam {
+    pub username: String,
+    pub disabled: bool,
+}
+
+/// Post body parameter for root issuing a password reset token to the user
+#[derive(Deserialize, Debug)]
+pub struct CreateResetTokenParam {
+    pub username: String,
+}
+
+/// Post body parameter for the user choosing a new password with the reset token
+#[derive(Deserialize)]
+pub struct ResetPasswordParam {
+    pub token: String,
+    pub password: String,           // plaintext
+}
+
+impl core::fmt::Debug for ResetPasswordParam {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        // do not log / print the token nor the password
+        write!(f, "ResetPasswordParam {{ }}")
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UserResponse {
+    pub username: String,
+    pub role: String,
+    pub active: bool,               // if the public key is validated by the handshake
+    pub disabled: bool,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ValidateUserResponse {
-    pub plaintext: String,
-    // encrypted version of the plaintext by the user's (id by jwt) public key 
+    pub challenge_id: String,
+    // random plaintext encrypted by the user's public key,
+    // the user activates by answering the decrypted plaintext
     pub encrypted: String,
 }
+
+/// One-time password reset token, delivered to the user out of band
+#[derive(Serialize, Deserialize)]
+pub struct ResetTokenResponse {
+    pub token: String,
+    pub expires_at: i64,            // UTC timestamp
+}
+
+impl core::fmt::Debug for ResetTokenResponse {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        // do not log / print the token
+        write!(f, "ResetTokenResponse {{ expires_at: {} }}", self.expires_at)
+    }
+}
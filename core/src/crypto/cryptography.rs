@@ -1,7 +1,7 @@
 use std::path::{Path, PathBuf};
 
 use rand::{distributions::Alphanumeric, Rng};
-use rsa::{pkcs1::{DecodeRsaPrivateKey, EncodeRsaPrivateKey, EncodeRsaPublicKey}, pkcs8::DecodePublicKey, Pkcs1v15Encrypt, RsaPrivateKey, RsaPublicKey};
+use rsa::{pkcs1::{DecodeRsaPrivateKey, EncodeRsaPrivateKey, EncodeRsaPublicKey}, pkcs8::DecodePublicKey, traits::PublicKeyParts, Pkcs1v15Encrypt, RsaPrivateKey, RsaPublicKey};
 use base64::{Engine as _, engine::general_purpose};
 use anyhow::Result;
 
@@ -32,6 +32,20 @@ impl WikRsaPublicKey {
         Ok(WikRsaPublicKey { key })
     }
 
+    /// Parse the public key from a PEM string, e.g. a public key submitted by a user.
+    pub fn from_pem_str(pem: &str) -> Result<Self> {
+        let key = RsaPublicKey::from_public_key_pem(pem)?;
+        Ok(WikRsaPublicKey { key })
+    }
+
+    /// Get the modulus and exponent of the key, encoded in base64url (as in JWK).
+    /// Return (n, e)
+    pub fn get_jwk_components(&self) -> (String, String) {
+        let n = general_purpose::URL_SAFE_NO_PAD.encode(self.key.n().to_bytes_be());
+        let e = general_purpose::URL_SAFE_NO_PAD.encode(self.key.e().to_bytes_be());
+        (n, e)
+    }
+
     /// Generate a random string and encrypt it with the public key.
     /// Return (plaintext, encrypted_string)
     pub fn generate_validate_string(&self) -> (String, String) {
@@ -88,6 +102,12 @@ impl WikRsaKeyPair {
         })
     }
 
+    /// Encode the private key in PKCS#1 DER.
+    pub fn to_private_key_der(&self) -> Result<Vec<u8>> {
+        let der = self.private_key.to_pkcs1_der()?;
+        Ok(der.as_bytes().to_vec())
+    }
+
     /// Save the public key and the private to separated pem files.
     pub fn save(&self, directory: &PathBuf, 
         private_key_filename: &str, public_key_file_name: &str) -> Result<()> {
@@ -127,6 +147,7 @@ impl Decryption for RsaPrivateKey {
 mod tests {
     use super::*;
     use indoc::indoc;
+    use rsa::pkcs8::EncodePublicKey;
 
     fn get_test_path(filename: &str) -> PathBuf {
         let base_dir = env!("CARGO_MANIFEST_DIR");
@@ -176,6 +197,15 @@ mod tests {
         assert_eq!(my_message, decrypted_message);
     }
 
+    #[test]
+    fn public_key_from_pem_str() {
+        let key_pair = get_example_key_pair();
+        let pem = key_pair.public_key.key.to_public_key_pem(rsa::pkcs8::LineEnding::LF).unwrap();
+        let public_key = WikRsaPublicKey::from_pem_str(&pem).unwrap();
+        assert_eq!(public_key.key, key_pair.public_key.key);
+        assert!(WikRsaPublicKey::from_pem_str("not a public key").is_err());
+    }
+
     #[test]
     fn new_key_pair_from_file() {
         let key_file = get_test_path("resources/test/test-private-key.pem");
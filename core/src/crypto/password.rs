@@ -1,7 +1,7 @@
 use bcrypt::{hash, verify};
 use anyhow::Result;
 use base64::{Engine as _, engine::general_purpose};
-use rand::Rng;
+use rand::{distributions::Alphanumeric, Rng};
 
 pub struct Password {
     pub hash: String,
@@ -14,6 +14,21 @@ fn generate_salt() -> String {
     general_purpose::STANDARD_NO_PAD.encode(salt)
 }
 
+/// generate a random 32 bytes token which is safe to be used in url / as an identifier
+pub fn generate_random_token() -> String {
+    let token = rand::thread_rng().gen::<[u8; 32]>();
+    general_purpose::URL_SAFE_NO_PAD.encode(token)
+}
+
+/// generate a random alphanumeric id of the given length
+pub fn generate_random_id(length: usize) -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(length)
+        .map(char::from)
+        .collect()
+}
+
 fn hash_password(password: &str, salt: &str) -> Result<String>{
     let to_be_hashed = format!("{}{}", password, salt);
     match hash(to_be_hashed, 6) {
@@ -0,0 +1,220 @@
+use crate::modal::util::config_key::{deserialize_config_key, validate_config_key};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::time::Duration;
+use strum_macros::{Display, EnumString};
+
+/// Type of a config value. The values are stored as strings, and parsed by the type:
+/// - String: any string
+/// - Int: a 64-bit integer, e.g. "42"
+/// - Bool: "true" or "false"
+/// - Duration: e.g. "30s", "5m", "1h 30m"
+/// - Json: any JSON document
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Display, EnumString)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum ConfigValueType {
+    String,
+    Int,
+    Bool,
+    Duration,
+    Json,
+}
+
+/// A config value parsed by its type.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypedConfigValue {
+    String(String),
+    Int(i64),
+    Bool(bool),
+    Duration(Duration),
+    Json(serde_json::Value),
+}
+
+/// Definition of a config key in the schema of an app.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ConfigKeySchema {
+    #[serde(deserialize_with = "deserialize_config_key")]
+    pub key: String,            // normalized, e.g. `db.url` is `db/url`
+    #[serde(rename = "type")]
+    pub value_type: ConfigValueType,
+    #[serde(default)]
+    pub required: bool,             // cannot be deleted, unless it has a default
+    pub default: Option<String>,    // used by the clients when the key is not set
+    pub pattern: Option<String>,    // regex the whole value must match
+    pub min: Option<i64>,           // Int: the value, Duration: seconds, String: length
+    pub max: Option<i64>,
+}
+
+impl ConfigKeySchema {
+    /// Parse the value by the type of the key, and check the constraints.
+    pub fn parse(&self, value: &str) -> Result<TypedConfigValue, String> {
+        if let Some(pattern) = &self.pattern {
+            let regex = Regex::new(&format!("^(?:{})$", pattern))
+                .map_err(|error| format!("Invalid pattern of key '{}': {}", self.key, error))?;
+            if !regex.is_match(value) {
+                return Err(format!("Value of key '{}' does not match the pattern '{}'.", self.key, pattern));
+            }
+        }
+
+        let (typed_value, measure) = match self.value_type {
+            ConfigValueType::String => (TypedConfigValue::String(value.to_string()), value.chars().count() as i64),
+            ConfigValueType::Int => {
+                let int = value.trim().parse::<i64>()
+                    .map_err(|_| format!("Value of key '{}' must be an integer.", self.key))?;
+                (TypedConfigValue::Int(int), int)
+            },
+            ConfigValueType::Bool => {
+                let bool = value.trim().parse::<bool>()
+                    .map_err(|_| format!("Value of key '{}' must be true or false.", self.key))?;
+                return Ok(TypedConfigValue::Bool(bool));
+            },
+            ConfigValueType::Duration => {
+                let duration = humantime::parse_duration(value.trim())
+                    .map_err(|_| format!("Value of key '{}' must be a duration, e.g. 30s, 5m, 1h 30m.", self.key))?;
+                (TypedConfigValue::Duration(duration), duration.as_secs() as i64)
+            },
+            ConfigValueType::Json => {
+                let json = serde_json::from_str(value)
+                    .map_err(|error| format!("Value of key '{}' must be JSON: {}", self.key, error))?;
+                return Ok(TypedConfigValue::Json(json));
+            },
+        };
+
+        if self.min.is_some_and(|min| measure < min) || self.max.is_some_and(|max| measure > max) {
+            return Err(format!("Value of key '{}' must be within [{}, {}].", self.key,
+                self.min.map_or("-".to_string(), |min| min.to_string()),
+                self.max.map_or("-".to_string(), |max| max.to_string())));
+        }
+        Ok(typed_value)
+    }
+}
+
+/// Schema of the config of an app, the writes of the app's config are validated against it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct AppConfigSchema {
+    pub keys: Vec<ConfigKeySchema>,
+    #[serde(default)]
+    pub allow_unknown_keys: bool,   // accept the keys not in the schema (as strings)
+}
+
+impl AppConfigSchema {
+    pub fn get_key(&self, key: &str) -> Option<&ConfigKeySchema> {
+        self.keys.iter().find(|key_schema| key_schema.key == key)
+    }
+
+    /// Check the schema itself: unique keys, valid patterns & ranges, and defaults matching the schema.
+    pub fn validate_definition(&self) -> Result<(), String> {
+        let mut keys = HashSet::new();
+        for key_schema in &self.keys {
+            if !keys.insert(&key_schema.key) {
+                return Err(format!("Key '{}' is defined more than once.", key_schema.key));
+            }
+            validate_config_key(&key_schema.key)?;
+            if let Some(pattern) = &key_schema.pattern {
+                Regex::new(pattern).map_err(|error| format!("Invalid pattern of key '{}': {}", key_schema.key, error))?;
+            }
+            if let (Some(min), Some(max)) = (key_schema.min, key_schema.max) {
+                if min > max {
+                    return Err(format!("Min of key '{}' is greater than the max.", key_schema.key));
+                }
+            }
+            if let Some(default) = &key_schema.default {
+                key_schema.parse(default).map_err(|error| format!("Invalid default. {}", error))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Validate the value set for the key.
+    pub fn validate_value(&self, key: &str, value: &str) -> Result<(), String> {
+        match self.get_key(key) {
+            Some(key_schema) => key_schema.parse(value).map(|_| ()),
+            None if self.allow_unknown_keys => Ok(()),
+            None => Err(format!("Key '{}' is not defined in the schema.", key)),
+        }
+    }
+
+    /// Validate the deletion of the key, the required keys without default cannot be deleted.
+    pub fn validate_delete(&self, key: &str) -> Result<(), String> {
+        match self.get_key(key) {
+            Some(key_schema) if key_schema.required && key_schema.default.is_none() => {
+                Err(format!("Key '{}' is required.", key))
+            },
+            _ => Ok(()),
+        }
+    }
+
+    /// The required keys without default, which must be set.
+    pub fn required_keys(&self) -> Vec<&str> {
+        self.keys.iter()
+            .filter(|key_schema| key_schema.required && key_schema.default.is_none())
+            .map(|key_schema| key_schema.key.as_str())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key_schema(key: &str, value_type: ConfigValueType) -> ConfigKeySchema {
+        ConfigKeySchema {
+            key: key.to_string(),
+            value_type,
+            required: false,
+            default: None,
+            pattern: None,
+            min: None,
+            max: None,
+        }
+    }
+
+    #[test]
+    fn test_validate_config_value() {
+        let schema = AppConfigSchema {
+            keys: vec![
+                ConfigKeySchema { min: Some(1), max: Some(100), ..key_schema("max_connections", ConfigValueType::Int) },
+                ConfigKeySchema { pattern: Some("[a-z]+".to_string()), ..key_schema("db_user", ConfigValueType::String) },
+                ConfigKeySchema { required: true, ..key_schema("timeout", ConfigValueType::Duration) },
+                key_schema("debug", ConfigValueType::Bool),
+                key_schema("features", ConfigValueType::Json),
+            ],
+            allow_unknown_keys: false,
+        };
+        assert!(schema.validate_definition().is_ok());
+
+        assert!(schema.validate_value("max_connections", "lots").is_err());
+        assert!(schema.validate_value("max_connections", "101").is_err());
+        assert!(schema.validate_value("max_connections", "20").is_ok());
+        assert!(schema.validate_value("db_user", "app_1").is_err());
+        assert!(schema.validate_value("db_user", "app").is_ok());
+        assert!(schema.validate_value("debug", "yes").is_err());
+        assert!(schema.validate_value("features", "{\"beta\": true}").is_ok());
+        assert!(schema.validate_value("unknown", "value").is_err());
+        assert_eq!(
+            schema.get_key("timeout").unwrap().parse("1h 30m"),
+            Ok(TypedConfigValue::Duration(Duration::from_secs(5400))),
+        );
+        assert!(schema.validate_delete("timeout").is_err());
+        assert!(schema.validate_delete("debug").is_ok());
+    }
+
+    #[test]
+    fn test_validate_schema_definition() {
+        let schema = AppConfigSchema {
+            keys: vec![ConfigKeySchema { default: Some("lots".to_string()), ..key_schema("max_connections", ConfigValueType::Int) }],
+            allow_unknown_keys: false,
+        };
+        assert!(schema.validate_definition().is_err());
+        let schema = AppConfigSchema {
+            keys: vec![key_schema("db//url", ConfigValueType::String)],
+            allow_unknown_keys: false,
+        };
+        assert!(schema.validate_definition().is_err());
+
+        let schema: AppConfigSchema = serde_json::from_str(r#"{"keys": [{"key": "db_url", "type": "string", "required": true}]}"#).unwrap();
+        assert_eq!(schema.required_keys(), vec!["db_url"]);
+    }
+}
@@ -1,3 +1,5 @@
 pub mod util;
 pub mod config_data;
+pub mod config_schema;
+pub mod notification;
 pub mod user;
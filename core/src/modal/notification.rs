@@ -0,0 +1,14 @@
+use strum_macros::{Display, EnumString};
+
+/// Type of the notifications sent by the server to the users.
+#[derive(Debug, PartialEq, EnumString, Display, Clone)]
+pub enum NotificationCategory {
+    #[strum(ascii_case_insensitive)]
+    BreakGlass,
+    #[strum(ascii_case_insensitive)]
+    ApprovalRequest,
+    #[strum(ascii_case_insensitive)]
+    DataChange,
+    #[strum(ascii_case_insensitive)]
+    DataExpiry,
+}
@@ -12,6 +12,8 @@ pub enum UserRole {
     Admin,
     #[strum(ascii_case_insensitive)]
     App,
+    #[strum(ascii_case_insensitive)]
+    Auditor,    // metadata only, never receives any ciphertext
 }
 
 // User modal for the client side (for self's user data)
@@ -0,0 +1,172 @@
+use serde::{Deserialize, Deserializer};
+use std::collections::HashMap;
+
+/// Separator between the segments of a hierarchical config key, e.g. `db/primary/password`.
+pub const KEY_SEPARATOR: char = '/';
+/// Alternative separator accepted in the requests, e.g. `db.primary.password`, replaced by `KEY_SEPARATOR`.
+pub const KEY_ALT_SEPARATOR: char = '.';
+
+pub const KEY_MAX_LENGTH: usize = 256;
+
+/// Normalize the config key (or prefix) to the canonical separator,
+/// so `db.primary.password` and `db/primary/password` are the same key.
+pub fn normalize_config_key(key: &str) -> String {
+    key.replace(KEY_ALT_SEPARATOR, &KEY_SEPARATOR.to_string())
+}
+
+/// Validate the (normalized) config key.
+/// Rules:
+/// 1. Key must be a string with length between 1 and 256.
+/// 2. Key is a path of segments separated by '/', e.g. `db/primary/password`.
+/// 3. Segments must not be empty, and only allow \[a-z,A-Z,0-9,_,-\].
+pub fn validate_config_key(key: &str) -> Result<(), String> {
+    if key.is_empty() || key.len() > KEY_MAX_LENGTH {
+        return Err(format!("Key must be between 1 and {} characters.", KEY_MAX_LENGTH));
+    }
+
+    for segment in key.split(KEY_SEPARATOR) {
+        if segment.is_empty() {
+            return Err(format!("Key '{}' has an empty segment, e.g. a leading, trailing or double separator.", key));
+        }
+        if !segment.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+            return Err(format!("Key '{}' can only contain alphanumeric characters, underscores and hyphens between the separators.", key));
+        }
+    }
+
+    Ok(())
+}
+
+/// Validate the prefix of a subtree of keys: a key, optionally ending with a separator.
+pub fn validate_key_prefix(prefix: &str) -> Result<(), String> {
+    validate_config_key(prefix.strip_suffix(KEY_SEPARATOR).unwrap_or(prefix))
+}
+
+/// Get the path of the key below the prefix (without the leading separator), none if the key is not in the subtree.
+/// The subtree of a prefix is the key itself (path "") and the keys below it,
+/// e.g. `db/primary` and `db/primary/password` are in the subtree of `db/primary`, but not `db/primary_2`.
+/// A prefix ending with a separator only matches the keys below it, an empty prefix matches all keys.
+pub fn key_below<'a>(key: &'a str, prefix: &str) -> Option<&'a str> {
+    let rest = key.strip_prefix(prefix)?;
+    if prefix.is_empty() || prefix.ends_with(KEY_SEPARATOR) {
+        return Some(rest);
+    }
+    match rest.strip_prefix(KEY_SEPARATOR) {
+        Some(path) => Some(path),
+        None if rest.is_empty() => Some(rest),
+        None => None,
+    }
+}
+
+pub fn is_in_subtree(key: &str, prefix: &str) -> bool {
+    key_below(key, prefix).is_some()
+}
+
+/// Cut the key of the subtree at the depth (levels) below the prefix, ending with the separator,
+/// e.g. `db/primary/password` is `db/primary/` at depth 1 below `db`.
+/// The key is kept as it is if it is not deeper than the depth.
+pub fn truncate_key(key: &str, prefix: &str, depth: usize) -> String {
+    let path = key_below(key, prefix).unwrap_or(key);
+    let start = key.len() - path.len();
+
+    let mut levels = 0;
+    for (index, c) in path.char_indices() {
+        if c == KEY_SEPARATOR {
+            levels += 1;
+            if levels >= depth {
+                return key[..start + index + 1].to_string();
+            }
+        }
+    }
+    key.to_string()
+}
+
+/// Deserialize a config key of a request, normalized.
+pub fn deserialize_config_key<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    let key = String::deserialize(deserializer)?;
+    Ok(normalize_config_key(&key))
+}
+
+pub fn deserialize_optional_config_key<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    let key = Option::<String>::deserialize(deserializer)?;
+    Ok(key.map(|key| normalize_config_key(&key)))
+}
+
+pub fn deserialize_config_keys<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    let keys = Vec::<String>::deserialize(deserializer)?;
+    Ok(keys.iter().map(|key| normalize_config_key(key)).collect())
+}
+
+pub fn deserialize_optional_config_keys<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Vec<String>>, D::Error> {
+    let keys = Option::<Vec<String>>::deserialize(deserializer)?;
+    Ok(keys.map(|keys| keys.iter().map(|key| normalize_config_key(key)).collect()))
+}
+
+/// Deserialize a map by the config keys of a request, normalized.
+/// Fails if two keys of the map are the same key once normalized, e.g. `db.url` and `db/url`.
+pub fn deserialize_config_key_map<'de, D, V>(deserializer: D) -> Result<HashMap<String, V>, D::Error>
+where
+    D: Deserializer<'de>,
+    V: Deserialize<'de>,
+{
+    let map = HashMap::<String, V>::deserialize(deserializer)?;
+    let mut normalized = HashMap::with_capacity(map.len());
+    for (key, value) in map {
+        let key = normalize_config_key(&key);
+        if normalized.contains_key(&key) {
+            return Err(serde::de::Error::custom(format!("Key '{}' is given more than once.", key)));
+        }
+        normalized.insert(key, value);
+    }
+    Ok(normalized)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_config_key() {
+        assert_eq!(validate_config_key("db_url"), Ok(()));
+        assert_eq!(validate_config_key("db/primary/password"), Ok(()));
+        assert!(validate_config_key("feature.beta-search").is_err());
+        assert_eq!(validate_config_key(&normalize_config_key("feature.beta-search")), Ok(()));
+        assert!(validate_config_key("").is_err());
+        assert!(validate_config_key("a".repeat(257).as_str()).is_err());
+        assert!(validate_config_key("/db/url").is_err());
+        assert!(validate_config_key("db//url").is_err());
+        assert!(validate_config_key("db/url/").is_err());
+        assert!(validate_config_key("db url").is_err());
+        assert_eq!(validate_key_prefix("db/"), Ok(()));
+        assert!(validate_key_prefix("db//").is_err());
+    }
+
+    #[test]
+    fn test_normalize_config_key() {
+        assert_eq!(normalize_config_key("db.primary.password"), "db/primary/password");
+        assert_eq!(normalize_config_key("db.primary/password"), "db/primary/password");
+        assert_eq!(normalize_config_key("db_url"), "db_url");
+
+        let map: HashMap<String, i64> = deserialize_config_key_map(
+            &mut serde_json::Deserializer::from_str(r#"{"db.url": 1, "db/user": 2}"#)).unwrap();
+        assert_eq!(map.get("db/url"), Some(&1));
+        assert_eq!(map.get("db/user"), Some(&2));
+        let map: Result<HashMap<String, i64>, _> = deserialize_config_key_map(
+            &mut serde_json::Deserializer::from_str(r#"{"db.url": 1, "db/url": 2}"#));
+        assert!(map.is_err());
+    }
+
+    #[test]
+    fn test_subtree() {
+        assert_eq!(key_below("db/primary/password", "db/primary"), Some("password"));
+        assert_eq!(key_below("db/primary/password", "db/"), Some("primary/password"));
+        assert_eq!(key_below("db/primary", "db/primary"), Some(""));
+        assert_eq!(key_below("db/primary", ""), Some("db/primary"));
+        assert!(!is_in_subtree("db/primary_2/password", "db/primary"));
+        assert!(!is_in_subtree("db", "db/"));
+
+        assert_eq!(truncate_key("db/primary/password", "db", 1), "db/primary/");
+        assert_eq!(truncate_key("db/primary/password", "", 1), "db/");
+        assert_eq!(truncate_key("db/primary/password", "db", 2), "db/primary/password");
+        assert_eq!(truncate_key("db/url", "db/", 1), "db/url");
+    }
+}
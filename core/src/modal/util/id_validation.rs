@@ -1,4 +1,5 @@
-/// Validate the ID in this application, including username & config key.
+/// Validate the ID in this application, e.g. username & group name.
+/// (The config keys are paths, see `config_key::validate_config_key`.)
 /// Rules:
 /// 1. ID must be a string with length between 4 and 30.
 /// 2. Only allow \[a-z,A-Z,0-9,_\] in the ID.
@@ -1 +1,2 @@
-pub mod id_validation;
\ No newline at end of file
+pub mod id_validation;
+pub mod config_key;
@@ -0,0 +1,112 @@
+use axum::{middleware::AddExtension, Extension};
+use axum_server::{accept::Accept, tls_rustls::RustlsAcceptor};
+use futures_util::future::BoxFuture;
+use std::collections::HashMap;
+use std::io;
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_rustls::server::TlsStream;
+use tower::Layer;
+use tracing::*;
+use x509_parser::prelude::*;
+
+/// The user identified by the verified client certificate of the TLS connection (mTLS).
+/// Added to the extensions of every request of the connection.
+#[derive(Clone, Debug)]
+pub struct ClientCertificate {
+    pub username: Option<String>,   // none if the client sent no certificate
+}
+
+/// Get the username from the client certificate (DER).
+/// `identity`: "cn" (subject common name) / "san" (the first DNS subject alternative name mapped by `san_usernames`)
+pub fn get_username_from_cert(cert: &[u8], identity: &str, san_usernames: &HashMap<String, String>) -> Option<String> {
+    let (_, cert) = X509Certificate::from_der(cert)
+        .map_err(|error| warn!("Fail to parse the client certificate. Error: {}", error))
+        .ok()?;
+
+    match identity {
+        "cn" => cert.subject()
+            .iter_common_name()
+            .next()
+            .and_then(|common_name| common_name.as_str().ok())
+            .map(|common_name| common_name.to_string()),
+        "san" => cert.subject_alternative_name()
+            .ok()??
+            .value
+            .general_names
+            .iter()
+            .find_map(|name| match name {
+                GeneralName::DNSName(name) => san_usernames.get(*name).cloned(),
+                _ => None,
+            })
+            .or_else(|| {
+                warn!("No username is mapped to the SANs of the client certificate.");
+                None
+            }),
+        identity => {
+            warn!("Unsupported client certificate identity '{}'.", identity);
+            None
+        },
+    }
+}
+
+/// TLS acceptor passing the identity of the client certificate to the requests.
+/// The certificate itself is verified against the client CA during the handshake.
+#[derive(Clone)]
+pub struct ClientCertAcceptor {
+    inner: RustlsAcceptor,
+    identity: String,
+    san_usernames: Arc<HashMap<String, String>>,
+}
+
+impl ClientCertAcceptor {
+    pub fn new(inner: RustlsAcceptor, identity: &str, san_usernames: &HashMap<String, String>) -> Self {
+        Self { inner, identity: identity.to_string(), san_usernames: Arc::new(san_usernames.clone()) }
+    }
+}
+
+impl<I, S> Accept<I, S> for ClientCertAcceptor
+where
+    I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    S: Send + 'static,
+{
+    type Stream = TlsStream<I>;
+    type Service = AddExtension<S, ClientCertificate>;
+    type Future = BoxFuture<'static, io::Result<(Self::Stream, Self::Service)>>;
+
+    fn accept(&self, stream: I, service: S) -> Self::Future {
+        let acceptor = self.inner.clone();
+        let identity = self.identity.clone();
+        let san_usernames = self.san_usernames.clone();
+
+        Box::pin(async move {
+            let (stream, service) = acceptor.accept(stream, service).await?;
+            // the end entity certificate comes first in the chain
+            let username = stream.get_ref().1
+                .peer_certificates()
+                .and_then(|certs| certs.first())
+                .and_then(|cert| get_username_from_cert(&cert.0, &identity, &san_usernames));
+            let service = Extension(ClientCertificate { username }).layer(service);
+
+            Ok((stream, service))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::db_test_util::get_test_path;
+
+    #[test]
+    fn test_get_username_from_cert() {
+        let pem = std::fs::read(get_test_path("resources/test/client-cert.pem")).unwrap();
+        let cert = rustls_pemfile::certs(&mut pem.as_slice()).next().unwrap().unwrap();
+
+        let san_usernames = HashMap::from([("app.wellik.test".to_string(), "u_app".to_string())]);
+        assert_eq!(get_username_from_cert(&cert, "cn", &san_usernames), Some("u_app".to_string()));
+        assert_eq!(get_username_from_cert(&cert, "san", &san_usernames), Some("u_app".to_string()));
+        assert_eq!(get_username_from_cert(&cert, "san", &HashMap::new()), None);
+        assert_eq!(get_username_from_cert(&cert, "email", &san_usernames), None);
+    }
+}
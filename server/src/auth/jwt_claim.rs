@@ -1,5 +1,6 @@
 use chrono::{Duration, Utc, TimeZone};
 use serde::{Deserialize, Serialize};
+use well_i_known_core::api::token::ApiTokenScope;
 use well_i_known_core::modal::user::UserRole;
 use std::fmt::{Display, Debug};
 use std::ops::Add;
@@ -14,6 +15,9 @@ pub struct JwtClaims {
     pub sub: String,        // Subject (whom the token refers to)
     pub exp: usize,         // Expiration time (as UTC timestamp)
     pub role: String,       // User role
+    pub jti: String,        // JWT ID (the session the token belongs to, for revocation)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<ApiTokenScope>,   // restriction of the API token, none for the login sessions
 }
 
 impl Display for JwtClaims {
@@ -26,26 +30,66 @@ impl Display for JwtClaims {
 impl Debug for JwtClaims {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         // e.g. User: <test, role = admin, exp = 2024-04-05 09:13:51 UTC>
-        let expired = Utc.timestamp_opt(self.exp as i64, 0).unwrap();
-        write!(f, "User: <{}, role = {}, exp = {}>", self.sub, self.role, expired)
+        let expired = match self.exp {
+            0 => "never".to_string(),
+            exp => Utc.timestamp_opt(exp as i64, 0).unwrap().to_string(),
+        };
+        write!(f, "User: <{}, role = {}, exp = {}, jti = {}>", self.sub, self.role, expired, self.jti)
     }
 }
 
 impl JwtClaims {
-    pub fn new(sub: &str, role: &str) -> Self {
-        let expired_ts = Utc::now().add(Duration::days(1));
+    /// Claims of an access token of the session `jti`, valid for `duration` seconds.
+    pub fn new(sub: &str, role: &str, jti: &str, duration: i64) -> Self {
+        let expired_ts = Utc::now().add(Duration::seconds(duration));
         Self { 
             sub: sub.to_string(), 
             exp: expired_ts.timestamp() as usize,
             role: role.to_string(),
+            jti: jti.to_string(),
+            scope: None,
+        }
+    }
+
+    /// Claims of the user authenticated by the client certificate (mTLS).
+    /// These claims are never encoded into a JWT, they are only passed to the API controllers.
+    pub fn from_client_certificate(sub: &str, role: &str) -> Self {
+        Self {
+            sub: sub.to_string(),
+            exp: 0,     // valid as long as the TLS connection
+            role: role.to_string(),
+            jti: "mtls".to_string(),
+            scope: None,
+        }
+    }
+
+    /// Claims of the user authenticated by the token of the external OIDC provider.
+    /// These claims are never encoded into a JWT, they are only passed to the API controllers.
+    pub fn from_oidc(sub: &str, role: &str, exp: i64) -> Self {
+        Self {
+            sub: sub.to_string(),
+            exp: exp as usize,
+            role: role.to_string(),
+            jti: "oidc".to_string(),
+            scope: None,
+        }
+    }
+
+    /// Claims of the user authenticated by the API token `token_id`.
+    /// These claims are never encoded into a JWT, they are only passed to the API controllers.
+    pub fn from_api_token(sub: &str, role: &str, token_id: &str, expires_at: Option<i64>, scope: ApiTokenScope) -> Self {
+        Self {
+            sub: sub.to_string(),
+            exp: expires_at.unwrap_or(0) as usize,     // 0 if the API token never expires
+            role: role.to_string(),
+            jti: format!("api:{}", token_id),
+            scope: Some(scope),
         }
     }
 
     /// Generate a JWT token from the claims with the key.
     pub fn gen_token(&self, jwt_key: &JwtKeys) -> Result<String, ApiError> {
-        let token = jsonwebtoken::encode(&jsonwebtoken::Header::default(), &self, &jwt_key.encoding)
-            .map_err(|_| ApiError::TokenCreation)?;
-        Ok(token)
+        jwt_key.encode(self)
     }
 
     pub fn get_role(&self) -> UserRole {
@@ -60,13 +104,14 @@ mod tests {
     #[test]
     fn test_jwt_claims() {
         let jwt_key = JwtKeys::new(b"secret");
-        let claims = JwtClaims::new("test", "admin");
+        let claims = JwtClaims::new("test", "admin", "jti", 900);
         println!("Claims: {} | {:?}", claims, claims);
         let token = claims.gen_token(&jwt_key).unwrap();
-        let decoded = jsonwebtoken::decode::<JwtClaims>(&token, &jwt_key.decoding, &jsonwebtoken::Validation::default());
+        let decoded = jwt_key.decode::<JwtClaims>(&token);
         assert!(decoded.is_ok());
-        let decoded_claims = decoded.unwrap().claims;
+        let decoded_claims = decoded.unwrap();
         assert_eq!(claims.sub, decoded_claims.sub);
         assert_eq!(claims.role, decoded_claims.role);
+        assert_eq!(claims.jti, decoded_claims.jti);
     }
 }
@@ -1,23 +1,41 @@
+use crate::auth::client_cert::ClientCertificate;
 use crate::auth::jwt_claim::JwtClaims;
+use crate::repository::api_token::{ApiTokenRepository, API_TOKEN_PREFIX};
+use crate::repository::auth_token::{AuthTokenRepository, SessionTokens};
+use crate::repository::login_challenge::LoginChallengeRepository;
+use crate::repository::login_throttle::LoginThrottleRepository;
 use crate::repository::user::UserRepository;
 use crate::{error::ApiError, server_state::ServerState};
 
+use std::net::SocketAddr;
 use std::sync::Arc;
 use std::fmt::Debug;
-use axum::{extract::State, Json, async_trait, extract::{FromRef, FromRequestParts}, http::request::Parts, RequestPartsExt};
+use axum::{extract::{ConnectInfo, State}, Json, async_trait, extract::{FromRef, FromRequestParts}, http::request::Parts, RequestPartsExt};
 use axum_extra::{
     headers::{authorization::Bearer, Authorization},
     TypedHeader,
 };
-use jsonwebtoken::{decode, Validation};
+use jsonwebtoken::jwk::JwkSet;
 use serde::{Deserialize, Serialize};
 use tracing::*;
 
+/// The only API that accepts the API tokens (including its sub paths).
+const API_TOKEN_PATH: &str = "/data";
+
 /// Response sent to the user after authorization
-#[derive(Debug, Serialize)]
+#[derive(Serialize)]
 pub struct AuthBody {
     access_token: String,
     token_type: String,
+    expires_in: i64,        // seconds the access token is valid for
+    refresh_token: String,
+}
+
+impl Debug for AuthBody {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // don't print the tokens
+        write!(f, "AuthBody {{ token_type: {}, expires_in: {} }}", self.token_type, self.expires_in)
+    }
 }
 
 /// Payload sent by the user to authorize
@@ -34,11 +52,53 @@ impl Debug for AuthPayload {
     }
 }
 
+/// Payload sent by the user to request a login challenge
+#[derive(Deserialize, Debug)]
+pub struct ChallengePayload {
+    pub username: String,
+}
+
+/// Response of the login challenge
+#[derive(Debug, Serialize)]
+pub struct ChallengeBody {
+    challenge_id: String,
+    encrypted: String,      // nonce encrypted with the user's public key
+}
+
+/// Payload sent by the user to answer the login challenge
+#[derive(Deserialize)]
+pub struct ChallengeAnswerPayload {
+    pub challenge_id: String,
+    pub plaintext: String,  // nonce decrypted with the user's private key
+}
+
+impl Debug for ChallengeAnswerPayload {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // don't print the answer
+        write!(f, "ChallengeAnswerPayload {{ challenge_id: {} }}", self.challenge_id)
+    }
+}
+
+/// Payload sent by the user to exchange the refresh token for new tokens
+#[derive(Deserialize)]
+pub struct RefreshPayload {
+    pub refresh_token: String,
+}
+
+impl Debug for RefreshPayload {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // don't print the token
+        write!(f, "RefreshPayload {{ .. }}")
+    }
+}
+
 impl AuthBody {
-    fn new(access_token: String) -> Self {
+    fn new(tokens: SessionTokens) -> Self {
         Self {
-            access_token,
+            access_token: tokens.access_token,
             token_type: "Bearer".to_string(),
+            expires_in: tokens.expires_in,
+            refresh_token: tokens.refresh_token,
         }
     }
 }
@@ -58,19 +118,72 @@ where
     type Rejection = ApiError;
 
     async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let state = Arc::from_ref(state);
+
         // Extract the token from the authorization header
-        let TypedHeader(Authorization(bearer)) = parts
+        let bearer = parts
             .extract::<TypedHeader<Authorization<Bearer>>>()
-            .await
-            .map_err(|_| ApiError::InvalidToken)?;
+            .await;
+        let TypedHeader(Authorization(bearer)) = match bearer {
+            Ok(bearer) => bearer,
+            Err(_) => {
+                // no token: fallback to the client certificate of the connection (mTLS)
+                let username = parts.extensions.get::<ClientCertificate>()
+                    .and_then(|client_cert| client_cert.username.clone())
+                    .ok_or(ApiError::InvalidToken)?;
+                let role = match UserRepository::get_user_role(&state.db_conn, &username).await {
+                    Ok(role) => role,
+                    Err(ApiError::RecordNotFound) => {
+                        warn!("Client certificate of '{}' does not belong to any user.", username);
+                        return Err(ApiError::InvalidToken);
+                    },
+                    Err(error) => return Err(error),
+                };
+                UserRepository::throw_if_not_active(&state.db_conn, &username).await?;
+                return Ok(JwtClaims::from_client_certificate(&username, &role.to_string()));
+            }
+        };
+
+        // API token: alternative credential of the data API only
+        if bearer.token().starts_with(API_TOKEN_PREFIX) {
+            let path = parts.uri.path();
+            if path != API_TOKEN_PATH && !path.starts_with(&format!("{}/", API_TOKEN_PATH)) {
+                warn!("API token is used to access {}, which only accepts login tokens.", path);
+                return Err(ApiError::InvalidToken);
+            }
+            let claims = ApiTokenRepository::authenticate(&state.db_conn, bearer.token()).await?;
+            UserRepository::throw_if_not_active(&state.db_conn, &claims.sub).await?;
+            return Ok(claims);
+        }
+
         // Decode the user data
-        let state = Arc::from_ref(state);
-        let token_data = decode::<JwtClaims>(
-            bearer.token(), 
-            &state.jwt_keys.decoding, &Validation::default())
-            .map_err(|_| ApiError::InvalidToken)?;
+        let claims = match state.jwt_keys.decode::<JwtClaims>(bearer.token()) {
+            Ok(claims) => claims,
+            Err(error) => match &state.oidc {
+                // not issued by wellik: try the external OIDC provider
+                Some(oidc) => {
+                    let identity = oidc.verify(bearer.token())?;
+                    UserRepository::provision_federated_user(
+                        &state.db_conn,
+                        &identity.username,
+                        &identity.role,
+                        state.config.config.oidc.auto_provision,
+                    ).await?;
+                    // the identity is vouched by the provider, the user can sign in before the handshake
+                    UserRepository::throw_if_disabled(&state.db_conn, &identity.username).await?;
+                    return Ok(JwtClaims::from_oidc(&identity.username, &identity.role.to_string(), identity.exp));
+                },
+                None => return Err(error),
+            },
+        };
 
-        Ok(token_data.claims)
+        // reject the token if its session is revoked (logout / password change / user deleted)
+        if !AuthTokenRepository::check_session_active(&state.db_conn, &claims.jti).await? {
+            warn!("Token of revoked session {} is used by {}.", claims.jti, claims.sub);
+            return Err(ApiError::InvalidToken);
+        }
+
+        Ok(claims)
     }
 }
 
@@ -80,6 +193,7 @@ where
 #[instrument(skip(state))]
 pub async fn authorize_handler(
     State(state): State<Arc<ServerState>>,
+    ConnectInfo(source): ConnectInfo<SocketAddr>,
     Json(payload): Json<AuthPayload>
 ) -> Result<Json<AuthBody>, ApiError> {
     info!("Accept authorize user request.");
@@ -89,12 +203,122 @@ pub async fn authorize_handler(
         return Err(ApiError::MissingCredentials);
     }
 
-    let user_role = UserRepository::auth_user(&state.db_conn, &payload.username, &payload.password).await?;
+    // reject without checking the password if there are too many failures
+    let source_ip = source.ip().to_string();
+    LoginThrottleRepository::throw_if_throttled(&state.db_conn, &payload.username, &source_ip).await?;
+
+    let user_role = match UserRepository::auth_user(&state.db_conn, &payload.username, &payload.password).await {
+        Ok(user_role) => user_role,
+        Err(ApiError::WrongCredentials) => {
+            LoginThrottleRepository::record_failure(
+                &state.db_conn,
+                &state.config.config.login_throttle,
+                &payload.username,
+                &source_ip,
+            ).await?;
+            return Err(ApiError::WrongCredentials);
+        },
+        Err(error) => return Err(error),
+    };
+    // an inactive user does not clear the failed attempts
+    UserRepository::throw_if_not_active(&state.db_conn, &payload.username).await?;
+    LoginThrottleRepository::record_success(&state.db_conn, &payload.username).await?;
     info!("User authorized");
 
-    // Create the authorization token
-    let token = JwtClaims::new(&payload.username, &user_role.to_string()).gen_token(&state.jwt_keys)?;
+    // Create the authorization tokens
+    let tokens = AuthTokenRepository::create_session(
+        &state.db_conn,
+        &state.config,
+        &state.jwt_keys,
+        &payload.username,
+        &user_role,
+    ).await?;
+
+    // Send the authorized tokens
+    Ok(Json(AuthBody::new(tokens)))
+}
+
+/// Handler for the first step of the private key login.
+/// Returns a nonce encrypted with the user's public key.
+#[instrument(skip(state))]
+pub async fn login_challenge_handler(
+    State(state): State<Arc<ServerState>>,
+    Json(payload): Json<ChallengePayload>
+) -> Result<Json<ChallengeBody>, ApiError> {
+    if payload.username.is_empty() {
+        warn!("Missing credentials");
+        return Err(ApiError::MissingCredentials);
+    }
+
+    let (challenge_id, encrypted) = LoginChallengeRepository::create_challenge(
+        &state.db_conn,
+        &state.config,
+        &payload.username,
+    ).await?;
+
+    Ok(Json(ChallengeBody { challenge_id, encrypted }))
+}
+
+/// Handler for the second step of the private key login.
+/// Returns the JWT token if the user answered the decrypted nonce.
+#[instrument(skip(state))]
+pub async fn login_challenge_answer_handler(
+    State(state): State<Arc<ServerState>>,
+    Json(payload): Json<ChallengeAnswerPayload>
+) -> Result<Json<AuthBody>, ApiError> {
+    let (username, user_role) = LoginChallengeRepository::verify_challenge(
+        &state.db_conn,
+        &payload.challenge_id,
+        &payload.plaintext,
+    ).await?;
+    UserRepository::throw_if_not_active(&state.db_conn, &username).await?;
+    info!("User {} authorized by private key.", username);
+
+    let tokens = AuthTokenRepository::create_session(
+        &state.db_conn,
+        &state.config,
+        &state.jwt_keys,
+        &username,
+        &user_role,
+    ).await?;
+
+    Ok(Json(AuthBody::new(tokens)))
+}
+
+/// Handler for exchanging the refresh token for new tokens.
+/// The used refresh token is revoked.
+#[instrument(skip(state))]
+pub async fn refresh_token_handler(
+    State(state): State<Arc<ServerState>>,
+    Json(payload): Json<RefreshPayload>
+) -> Result<Json<AuthBody>, ApiError> {
+    let tokens = AuthTokenRepository::refresh_session(
+        &state.db_conn,
+        &state.config,
+        &state.jwt_keys,
+        &payload.refresh_token,
+    ).await?;
+
+    Ok(Json(AuthBody::new(tokens)))
+}
+
+/// Handler for logout.
+/// Revoke the session of the token, so both its access and refresh tokens are no longer accepted.
+#[instrument(skip(state))]
+pub async fn logout_handler(
+    claims: JwtClaims,
+    State(state): State<Arc<ServerState>>,
+) -> Result<(), ApiError> {
+    AuthTokenRepository::revoke_session(&state.db_conn, &claims.jti).await?;
+    info!("User {} logged out.", claims.sub);
+    Ok(())
+}
 
-    // Send the authorized token
-    Ok(Json(AuthBody::new(token)))
+/// Handler for publishing the public keys of the JWT tokens,
+/// so that other services can verify the tokens offline.
+#[instrument(skip(state))]
+pub async fn jwks_handler(
+    State(state): State<Arc<ServerState>>,
+) -> Json<JwkSet> {
+    Json(state.jwt_keys.get_jwks())
 }
@@ -1,16 +1,240 @@
-use jsonwebtoken::{DecodingKey, EncodingKey};
+use crate::error::ApiError;
+use well_i_known_core::crypto::cryptography::WikRsaKeyPair;
+use well_i_known_core::crypto::password::generate_random_token;
 
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use anyhow::Result;
+use chrono::Utc;
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use jsonwebtoken::jwk::{AlgorithmParameters, CommonParameters, Jwk, JwkSet, KeyAlgorithm, PublicKeyUse, RSAKeyParameters, RSAKeyType};
+use serde::{de::DeserializeOwned, Serialize};
+use tracing::*;
+
+/// Prefix of the key id, followed by the UTC timestamp the key is created at and a random suffix.
+const KID_PREFIX: &str = "wik-";
+const PRIVATE_KEY_FILE_SUFFIX: &str = "-key.pem";
+const PUBLIC_KEY_FILE_SUFFIX: &str = "-cert.pem";
+
+/// One key for signing / verifying the JWT tokens.
+struct JwtSigningKey {
+    kid: String,
+    created_at: i64,        // UTC timestamp
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+    jwk: Option<Jwk>,       // public part of the asymmetric key, published in the JWKS
+}
+
+impl JwtSigningKey {
+    fn from_rsa(kid: &str, created_at: i64, key_pair: &WikRsaKeyPair) -> Result<Self> {
+        let (n, e) = key_pair.public_key.get_jwk_components();
+        let jwk = Jwk {
+            common: CommonParameters {
+                public_key_use: Some(PublicKeyUse::Signature),
+                key_algorithm: Some(KeyAlgorithm::RS256),
+                key_id: Some(kid.to_string()),
+                ..Default::default()
+            },
+            algorithm: AlgorithmParameters::RSA(RSAKeyParameters {
+                key_type: RSAKeyType::RSA,
+                n: n.clone(),
+                e: e.clone(),
+            }),
+        };
+
+        Ok(Self {
+            kid: kid.to_string(),
+            created_at,
+            encoding: EncodingKey::from_rsa_der(&key_pair.to_private_key_der()?),
+            decoding: DecodingKey::from_rsa_components(&n, &e)?,
+            jwk: Some(jwk),
+        })
+    }
+}
+
+/// Keys for signing / verifying the JWT tokens.
+/// - HS256: a single shared secret (legacy, tokens cannot be verified by other services)
+/// - RS256: key pairs under `certs/jwt`. The newest key signs the tokens, the retired keys
+///   are kept for verification until all tokens signed by them have expired.
 #[derive(Clone)]
 pub struct JwtKeys {
-    pub encoding: EncodingKey,
-    pub decoding: DecodingKey,
+    algorithm: Algorithm,
+    keys: Arc<RwLock<Vec<JwtSigningKey>>>,  // ordered by created_at, the last one is the active key
 }
 
 impl JwtKeys {
+    /// Keys with the HMAC secret.
     pub fn new(secret: &[u8]) -> Self {
-        Self {
+        let key = JwtSigningKey {
+            kid: "hmac".to_string(),
+            created_at: 0,
             encoding: EncodingKey::from_secret(secret),
             decoding: DecodingKey::from_secret(secret),
+            jwk: None,
+        };
+
+        Self {
+            algorithm: Algorithm::HS256,
+            keys: Arc::new(RwLock::new(vec![key])),
+        }
+    }
+
+    /// Load the RSA key pairs from the directory,
+    /// and create / rotate the active key if needed.
+    pub fn from_rsa_key_dir(key_dir: &Path, rotation_interval: i64, overlap: i64) -> Result<Self> {
+        // the directory is missing for the server initialized before the RS256 support
+        fs::create_dir_all(key_dir)?;
+
+        let mut keys = Vec::new();
+        for entry in fs::read_dir(key_dir)? {
+            let filename = entry?.file_name().to_string_lossy().to_string();
+            let kid = match filename.strip_suffix(PRIVATE_KEY_FILE_SUFFIX) {
+                Some(kid) => kid,
+                None => continue,
+            };
+            let created_at = kid.strip_prefix(KID_PREFIX)
+                .and_then(|kid| kid.split_once('-'))
+                .and_then(|(ts, _)| ts.parse::<i64>().ok());
+            let created_at = match created_at {
+                Some(created_at) => created_at,
+                None => {
+                    warn!("Skip unknown JWT key file {}.", filename);
+                    continue;
+                }
+            };
+
+            let key_pair = WikRsaKeyPair::from_private_key_file(&key_dir.join(&filename))?;
+            keys.push(JwtSigningKey::from_rsa(kid, created_at, &key_pair)?);
+        }
+        keys.sort_by_key(|key| key.created_at);
+        info!("Loaded {} JWT signing keys.", keys.len());
+
+        let jwt_keys = Self {
+            algorithm: Algorithm::RS256,
+            keys: Arc::new(RwLock::new(keys)),
+        };
+        jwt_keys.rotate_if_needed(key_dir, rotation_interval, overlap)?;
+        Ok(jwt_keys)
+    }
+
+    /// Create a new active key if the current one is older than the rotation interval,
+    /// then drop the retired keys that were replaced more than `overlap` seconds ago
+    /// (i.e. no valid token is signed by them anymore).
+    /// Return true if a new key is created.
+    pub fn rotate_if_needed(&self, key_dir: &Path, rotation_interval: i64, overlap: i64) -> Result<bool> {
+        if self.algorithm != Algorithm::RS256 {
+            return Ok(false);
+        }
+
+        let now = Utc::now().timestamp();
+        let needs_new_key = match self.keys.read().unwrap().last() {
+            Some(active) => now - active.created_at >= rotation_interval,
+            None => true,
+        };
+
+        let mut new_key = None;
+        if needs_new_key {
+            let kid = format!("{}{}-{}", KID_PREFIX, now, &generate_random_token()[..8]);
+            let key_pair = WikRsaKeyPair::new()?;
+            key_pair.save(
+                &key_dir.to_path_buf(),
+                &format!("{}{}", kid, PRIVATE_KEY_FILE_SUFFIX),
+                &format!("{}{}", kid, PUBLIC_KEY_FILE_SUFFIX),
+            )?;
+            new_key = Some(JwtSigningKey::from_rsa(&kid, now, &key_pair)?);
+            info!("Created JWT signing key {}.", kid);
         }
+
+        let mut keys = self.keys.write().unwrap();
+        if let Some(new_key) = new_key {
+            keys.push(new_key);
+        }
+
+        // a key is retired when its successor is created
+        let retired_before = now - overlap;
+        let expired_count = keys.windows(2)
+            .take_while(|pair| pair[1].created_at <= retired_before)
+            .count();
+        for key in keys.drain(..expired_count) {
+            info!("Removing retired JWT signing key {}.", key.kid);
+            for suffix in [PRIVATE_KEY_FILE_SUFFIX, PUBLIC_KEY_FILE_SUFFIX] {
+                if let Err(error) = fs::remove_file(key_dir.join(format!("{}{}", key.kid, suffix))) {
+                    warn!("Fail to delete JWT key file of {}. Error: {}", key.kid, error);
+                }
+            }
+        }
+
+        Ok(needs_new_key)
+    }
+
+    /// Sign the claims with the active key.
+    pub fn encode<T: Serialize>(&self, claims: &T) -> Result<String, ApiError> {
+        let keys = self.keys.read().unwrap();
+        let active = keys.last().ok_or(ApiError::TokenCreation)?;
+
+        let mut header = Header::new(self.algorithm);
+        if active.jwk.is_some() {
+            header.kid = Some(active.kid.clone());
+        }
+
+        jsonwebtoken::encode(&header, claims, &active.encoding)
+            .map_err(|_| ApiError::TokenCreation)
+    }
+
+    /// Verify the token with the key of its key id, and decode the claims.
+    pub fn decode<T: DeserializeOwned>(&self, token: &str) -> Result<T, ApiError> {
+        let header = jsonwebtoken::decode_header(token)
+            .map_err(|_| ApiError::InvalidToken)?;
+        let keys = self.keys.read().unwrap();
+        let key = match self.algorithm {
+            Algorithm::HS256 => keys.last(),
+            _ => keys.iter().find(|key| Some(&key.kid) == header.kid.as_ref()),
+        };
+        let key = key.ok_or(ApiError::InvalidToken)?;
+
+        let token_data = jsonwebtoken::decode::<T>(token, &key.decoding, &Validation::new(self.algorithm))
+            .map_err(|_| ApiError::InvalidToken)?;
+        Ok(token_data.claims)
+    }
+
+    /// The public keys for verifying the tokens.
+    /// Empty for HMAC as the secret cannot be published.
+    pub fn get_jwks(&self) -> JwkSet {
+        let keys = self.keys.read().unwrap();
+        JwkSet {
+            keys: keys.iter().filter_map(|key| key.jwk.clone()).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::db_test_util::get_test_path;
+    use crate::auth::jwt_claim::JwtClaims;
+
+    #[test]
+    fn test_rsa_key_rotation() {
+        let key_dir = get_test_path("output/test_rsa_key_rotation");
+        if key_dir.exists() {
+            fs::remove_dir_all(&key_dir).unwrap();
+        }
+        fs::create_dir_all(&key_dir).unwrap();
+
+        let jwt_keys = JwtKeys::from_rsa_key_dir(&key_dir, 3600, 900).unwrap();
+        assert_eq!(jwt_keys.get_jwks().keys.len(), 1);
+        let token = JwtClaims::new("test", "admin", "jti", 900).gen_token(&jwt_keys).unwrap();
+        let header = jsonwebtoken::decode_header(&token).unwrap();
+        assert_eq!(header.kid, jwt_keys.get_jwks().keys[0].common.key_id);
+
+        // the old key is kept for verification after rotation
+        assert!(jwt_keys.rotate_if_needed(&key_dir, 0, 900).unwrap());
+        assert!(jwt_keys.decode::<JwtClaims>(&token).is_ok());
+
+        // reload from the key files
+        let reloaded = JwtKeys::from_rsa_key_dir(&key_dir, 3600, 900).unwrap();
+        assert!(reloaded.decode::<JwtClaims>(&token).is_ok());
+        fs::remove_dir_all(&key_dir).unwrap();
     }
 }
@@ -1,4 +1,6 @@
+pub mod client_cert;
 pub mod jwt_key;
 pub mod jwt_controller;
 pub mod jwt_claim;
+pub mod oidc;
 pub mod role_validation;
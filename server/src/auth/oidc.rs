@@ -0,0 +1,188 @@
+use crate::auth::role_validation::RoleValidationUtil;
+use crate::config::server_config::WIKServerOidcConfig;
+use crate::error::ApiError;
+use well_i_known_core::modal::user::UserRole;
+
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use jsonwebtoken::jwk::JwkSet;
+use serde_json::Value;
+use tracing::*;
+
+/// Roles that can be granted by the OIDC provider, from the most privileged.
+/// Root is never federated.
+const FEDERATED_ROLES: [UserRole; 3] = [UserRole::Admin, UserRole::Auditor, UserRole::App];
+
+/// The wellik user of a valid OIDC token.
+#[derive(Debug)]
+pub struct OidcIdentity {
+    pub username: String,
+    pub role: UserRole,
+    pub exp: i64,           // UTC timestamp the token expires at
+}
+
+/// Verifier of the tokens issued by the external OIDC provider.
+#[derive(Clone)]
+pub struct OidcVerifier {
+    config: WIKServerOidcConfig,
+    jwks: Arc<RwLock<JwkSet>>,
+    loaded_at: Arc<RwLock<i64>>,    // UTC timestamp the JWKS is loaded at
+}
+
+impl OidcVerifier {
+    /// Load the public keys of the provider.
+    /// Return none if OIDC is not configured.
+    pub async fn new(config: &WIKServerOidcConfig) -> Result<Option<Self>> {
+        if config.issuer.is_none() {
+            return Ok(None);
+        }
+
+        let jwks = OidcVerifier::load_jwks(config).await?;
+        info!("Loaded {} OIDC keys of issuer {}.", jwks.keys.len(), config.issuer.as_ref().unwrap());
+        Ok(Some(Self {
+            config: config.clone(),
+            jwks: Arc::new(RwLock::new(jwks)),
+            loaded_at: Arc::new(RwLock::new(Utc::now().timestamp())),
+        }))
+    }
+
+    async fn load_jwks(config: &WIKServerOidcConfig) -> Result<JwkSet> {
+        match (&config.jwks_url, &config.jwks_file) {
+            (Some(jwks_url), _) => Ok(reqwest::get(jwks_url).await?
+                .error_for_status()?
+                .json::<JwkSet>().await?),
+            (None, Some(jwks_file)) => Ok(serde_json::from_slice(&std::fs::read(jwks_file)?)?),
+            (None, None) => Err(anyhow!("Either jwks_url or jwks_file is required for OIDC.")),
+        }
+    }
+
+    /// Reload the public keys when they are older than the refresh interval,
+    /// so the keys rotated by the provider are picked up.
+    /// Return true if reloaded.
+    pub async fn refresh_if_needed(&self) -> Result<bool> {
+        let now = Utc::now().timestamp();
+        if now - *self.loaded_at.read().unwrap() < self.config.jwks_refresh_interval {
+            return Ok(false);
+        }
+
+        let jwks = OidcVerifier::load_jwks(&self.config).await?;
+        *self.jwks.write().unwrap() = jwks;
+        *self.loaded_at.write().unwrap() = now;
+        Ok(true)
+    }
+
+    /// Validate the token (signature, issuer, audience and expiry) and map its claims to the wellik user.
+    pub fn verify(&self, token: &str) -> Result<OidcIdentity, ApiError> {
+        let header = jsonwebtoken::decode_header(token)
+            .map_err(|_| ApiError::InvalidToken)?;
+        // the provider's keys are public, never accept a token signed with them as an HMAC secret
+        if matches!(header.alg, Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512) {
+            warn!("OIDC token with HMAC algorithm {:?} is rejected.", header.alg);
+            return Err(ApiError::InvalidToken);
+        }
+
+        let decoding_key = {
+            let jwks = self.jwks.read().unwrap();
+            let jwk = match &header.kid {
+                Some(kid) => jwks.find(kid),
+                None => jwks.keys.first(),
+            };
+            let jwk = jwk.ok_or(ApiError::InvalidToken)?;
+            DecodingKey::from_jwk(jwk).map_err(|_| ApiError::InvalidToken)?
+        };
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[self.config.issuer.as_ref().unwrap()]);
+        validation.set_audience(&[&self.config.audience]);
+        let claims = jsonwebtoken::decode::<HashMap<String, Value>>(token, &decoding_key, &validation)
+            .map_err(|error| {
+                warn!("Invalid OIDC token. Error: {}", error);
+                ApiError::InvalidToken
+            })?
+            .claims;
+
+        self.map_claims(&claims)
+    }
+
+    /// Map the token claims to the wellik username and role with the configured mapping.
+    /// The most privileged role is taken if the claim maps to several roles.
+    fn map_claims(&self, claims: &HashMap<String, Value>) -> Result<OidcIdentity, ApiError> {
+        let username = claims.get(&self.config.username_claim)
+            .and_then(Value::as_str)
+            .ok_or_else(|| {
+                warn!("OIDC token has no '{}' claim for the username.", self.config.username_claim);
+                ApiError::InvalidToken
+            })?;
+
+        let role_values: Vec<&str> = match claims.get(&self.config.role_claim) {
+            Some(Value::String(value)) => vec![value.as_str()],
+            Some(Value::Array(values)) => values.iter().filter_map(Value::as_str).collect(),
+            _ => vec![],
+        };
+        let roles: Vec<UserRole> = role_values.iter()
+            .filter_map(|value| self.config.role_mapping.get(*value))
+            .filter_map(|role| UserRole::from_str(role).ok())
+            .collect();
+        let role = FEDERATED_ROLES.iter().find(|role| roles.contains(role));
+        RoleValidationUtil::throw_if_unauthorized(role.is_some(), username, "sign in without a mapped wellik role")?;
+
+        Ok(OidcIdentity {
+            username: username.to_string(),
+            role: role.unwrap().clone(),
+            exp: claims.get("exp").and_then(Value::as_i64).unwrap_or(0),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::jwt_key::JwtKeys;
+    use crate::db::db_test_util::get_test_path;
+    use std::fs;
+
+    #[tokio::test]
+    async fn test_verify_oidc_token() {
+        // an RS256 key set stands in for the provider
+        let key_dir = get_test_path("output/test_verify_oidc_token");
+        if key_dir.exists() {
+            fs::remove_dir_all(&key_dir).unwrap();
+        }
+        let provider_keys = JwtKeys::from_rsa_key_dir(&key_dir, 3600, 900).unwrap();
+        let jwks_file = key_dir.join("jwks.json");
+        fs::write(&jwks_file, serde_json::to_string(&provider_keys.get_jwks()).unwrap()).unwrap();
+
+        let config = WIKServerOidcConfig {
+            issuer: Some("https://idp.test".to_string()),
+            jwks_file: Some(jwks_file.to_str().unwrap().to_string()),
+            role_mapping: HashMap::from([("wellik-admins".to_string(), "Admin".to_string())]),
+            ..Default::default()
+        };
+        let verifier = OidcVerifier::new(&config).await.unwrap().unwrap();
+
+        let exp = Utc::now().timestamp() + 900;
+        let token = provider_keys.encode(&serde_json::json!({
+            "iss": "https://idp.test", "aud": "wellik", "exp": exp,
+            "preferred_username": "u_admin", "groups": ["staff", "wellik-admins"],
+        })).unwrap();
+        let identity = verifier.verify(&token).unwrap();
+        assert_eq!(identity.username, "u_admin");
+        assert_eq!(identity.role, UserRole::Admin);
+
+        let token = provider_keys.encode(&serde_json::json!({
+            "iss": "https://other.test", "aud": "wellik", "exp": exp,
+            "preferred_username": "u_admin", "groups": ["wellik-admins"],
+        })).unwrap();
+        assert!(matches!(verifier.verify(&token), Err(ApiError::InvalidToken)));
+
+        let token = provider_keys.encode(&serde_json::json!({
+            "iss": "https://idp.test", "aud": "wellik", "exp": exp,
+            "preferred_username": "u_staff", "groups": ["staff"],
+        })).unwrap();
+        assert!(matches!(verifier.verify(&token), Err(ApiError::Unauthorized { .. })));
+    }
+}
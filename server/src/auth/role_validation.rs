@@ -4,6 +4,7 @@ use well_i_known_core::modal::user::UserRole;
 use tracing::*;
 
 const ADMIN_ROLES: [UserRole; 2] = [UserRole::Admin, UserRole::Root];
+const METADATA_VIEWER_ROLES: [UserRole; 3] = [UserRole::Admin, UserRole::Root, UserRole::Auditor];
 
 pub struct RoleValidationUtil {}
 impl RoleValidationUtil {
@@ -23,6 +24,16 @@ impl RoleValidationUtil {
         RoleValidationUtil::authorized_role(requester_role, &[UserRole::Root])
     }
 
+    pub fn is_auditor(requester_role: &str) -> bool {
+        RoleValidationUtil::authorized_role(requester_role, &[UserRole::Auditor])
+    }
+
+    /// Check if the given role can view the metadata (users, access rights, events),
+    /// i.e. admin, root or auditor.
+    pub fn can_view_metadata(requester_role: &str) -> bool {
+        RoleValidationUtil::authorized_role(requester_role, &METADATA_VIEWER_ROLES)
+    }
+
     /// Check if the given role is admin or the user himself.
     pub fn is_admin_or_self(requester_role: &str, requester_username: &str, request_username: &str) -> bool {
         RoleValidationUtil::is_admin(requester_role) || requester_username == request_username
@@ -31,7 +42,7 @@ impl RoleValidationUtil {
     /// Check if the given account can be created by the requester.
     /// 1. Root can create any account.
     /// 2. Admin can create App account.
-    /// 3. App cannot create any account.
+    /// 3. App / Auditor cannot create any account.
     pub fn can_create_account(requester_role: &UserRole, role_to_create: &UserRole) -> bool {
         match requester_role {
             UserRole::Root => true,
@@ -40,9 +51,33 @@ impl RoleValidationUtil {
                     UserRole::Root => false,
                     UserRole::Admin => false,
                     UserRole::App => true,
+                    UserRole::Auditor => false,
                 }
             },
             UserRole::App => false,
+            UserRole::Auditor => false,
+        }
+    }
+
+    /// Check if the requester can issue an API token to the owner.
+    /// 1. Anyone can issue tokens to himself.
+    /// 2. Only root can issue tokens to another user,
+    ///    an admin's token of an App account would bypass the admin's own access rights to the app.
+    pub fn can_issue_api_token(requester_role: &UserRole, requester_username: &str, owner_username: &str) -> bool {
+        requester_username == owner_username || requester_role == &UserRole::Root
+    }
+
+    /// Check if the requester can list / revoke the API tokens of the owner.
+    /// 1. Anyone can manage his own tokens.
+    /// 2. Root can manage the tokens of any user.
+    /// 3. Admin can manage the tokens of App accounts.
+    pub fn can_manage_api_token(requester_role: &UserRole, requester_username: &str,
+        owner_role: &UserRole, owner_username: &str) -> bool {
+        requester_username == owner_username || match requester_role {
+            UserRole::Root => true,
+            UserRole::Admin => owner_role == &UserRole::App,
+            UserRole::App => false,
+            UserRole::Auditor => false,
         }
     }
 
@@ -73,4 +108,20 @@ mod tests {
         assert_eq!(RoleValidationUtil::authorized_role("App", &roles), false);
         assert_eq!(RoleValidationUtil::authorized_role("abc", &roles), false);
     }
+
+    #[test]
+    fn test_only_root_can_create_auditor() {
+        assert!(RoleValidationUtil::can_create_account(&UserRole::Root, &UserRole::Auditor));
+        assert!(!RoleValidationUtil::can_create_account(&UserRole::Admin, &UserRole::Auditor));
+        assert!(!RoleValidationUtil::can_create_account(&UserRole::Auditor, &UserRole::App));
+    }
+
+    #[test]
+    fn test_only_root_can_issue_api_token_to_others() {
+        assert!(RoleValidationUtil::can_issue_api_token(&UserRole::Admin, "u_admin", "u_admin"));
+        assert!(RoleValidationUtil::can_issue_api_token(&UserRole::Root, "u_root", "u_app"));
+        assert!(!RoleValidationUtil::can_issue_api_token(&UserRole::Admin, "u_admin", "u_app"));
+        // the admin can still revoke the tokens of App accounts
+        assert!(RoleValidationUtil::can_manage_api_token(&UserRole::Admin, "u_admin", &UserRole::App, "u_app"));
+    }
 }
\ No newline at end of file
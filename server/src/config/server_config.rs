@@ -1,3 +1,4 @@
+use well_i_known_core::api::data::DEFAULT_ENVIRONMENT;
 use well_i_known_core::modal::user::UserKeyModal;
 use crate::db::db_connection::DbConnection;
 
@@ -6,9 +7,14 @@ use tracing_appender::rolling::RollingFileAppender;
 use serde::{Deserialize, Serialize};
 use figment::{Figment, providers::{Format, Json, Serialized}};
 use axum_server::tls_rustls::RustlsConfig;
+use rustls::server::{AllowAnyAnonymousOrAuthenticatedClient, AllowAnyAuthenticatedClient};
+use rustls::{Certificate, PrivateKey, RootCertStore, ServerConfig};
+use rustls_pemfile::Item;
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::net::SocketAddr;
-use anyhow::Result;
+use std::sync::Arc;
+use anyhow::{anyhow, Result};
 
 // set a const string for environment variable name
 const WIK_SERVER_HOME_ENV_VAR_NAME: &str = "WELLIK_HOME";
@@ -68,6 +74,10 @@ impl WIKServerEnvironmentConfig {
         self.base_dir.join("certs").join("root")
     }
 
+    pub fn get_jwt_certs_dir_path(&self) -> PathBuf {
+        self.base_dir.join("certs").join("jwt")
+    }
+
     pub fn get_users_certs_dir_path(&self) -> PathBuf {
         self.base_dir.join("certs").join("users")
     }
@@ -97,10 +107,24 @@ pub struct WIKServerConfig {
     // pub server_ip: Option<String>,
     pub server_ip: String,
     pub server_port: u16,
-    pub jwt_secret: String,
+    pub jwt_algorithm: String,          // RS256 (key pairs under certs/jwt) or HS256 (jwt_secret)
+    pub jwt_secret: String,             // only used by HS256
+    pub jwt_key_rotation_interval: i64, // seconds before the RS256 signing key is replaced
+    pub access_token_duration: i64,     // seconds the access token (JWT) is valid for
+    pub refresh_token_duration: i64,    // seconds the refresh token is valid for
+    pub login_challenge_duration: i64,  // seconds the login challenge can be answered within
     pub db_path: String,
+    pub background_task_interval: u64,  // seconds between each run of the background tasks
+    pub break_glass_duration: i64,      // seconds the break-glass emergency access is valid for
+    pub password_reset_token_duration: i64, // seconds the password reset token can be redeemed within
+    pub config_history_retention: i64,  // versions kept per config key, unless set for the app
+    pub environments: Vec<String>,      // environments of every app's config, in the order of promotion (e.g. dev, staging, prod)
+    pub config_expiry: WIKServerConfigExpiryConfig,
     pub tls: WIKServerTlsConfig,
     pub logging: WIKServerLoggerConfig,
+    pub login_throttle: WIKServerLoginThrottleConfig,
+    pub oidc: WIKServerOidcConfig,
+    pub password_policy: WIKServerPasswordPolicyConfig,
 }
 
 impl Default for WIKServerConfig {
@@ -108,19 +132,55 @@ impl Default for WIKServerConfig {
         WIKServerConfig {
             server_ip: "127.0.0.1".to_string(),             // default to localhost
             server_port: 3001,
-            jwt_secret: "secret".to_string(),               // should be overwritten
+            jwt_algorithm: "RS256".to_string(),
+            jwt_secret: "secret".to_string(),               // should be overwritten if HS256 is used
+            jwt_key_rotation_interval: 30 * 24 * 3600,
+            access_token_duration: 900,
+            refresh_token_duration: 7 * 24 * 3600,
+            login_challenge_duration: 60,
             db_path: "./data/wellik.sqlite".to_string(),
+            background_task_interval: 60,
+            break_glass_duration: 3600,
+            password_reset_token_duration: 24 * 3600,
+            config_history_retention: 10,
+            environments: vec![DEFAULT_ENVIRONMENT.to_string()],
+            config_expiry: WIKServerConfigExpiryConfig::default(),
             tls: WIKServerTlsConfig::default(),
             logging: WIKServerLoggerConfig::default(),
+            login_throttle: WIKServerLoginThrottleConfig::default(),
+            oidc: WIKServerOidcConfig::default(),
+            password_policy: WIKServerPasswordPolicyConfig::default(),
+        }
+    }
+}
+
+/// Expiry of the config values set with an `expires_at` (e.g. certificates, vendor API keys).
+/// The users with access to the value are notified once at each lead time before the expiry, and once it has expired.
+#[derive(Deserialize, Serialize, Clone)]
+pub struct WIKServerConfigExpiryConfig {
+    pub warning_lead_times: Vec<i64>,   // seconds before the expiry
+    pub refuse_expired: bool,           // refuse to serve the values that have expired
+}
+
+impl Default for WIKServerConfigExpiryConfig {
+    fn default() -> Self {
+        WIKServerConfigExpiryConfig {
+            warning_lead_times: vec![30 * 24 * 3600, 7 * 24 * 3600, 24 * 3600],
+            refuse_expired: false,
         }
     }
 }
 
 /// Certs for the HTTPS server.
+/// Client certificates signed by `client_ca_file` (if set) authenticate the users (mTLS).
 #[derive(Deserialize, Serialize, Clone)]
 pub struct WIKServerTlsConfig {
     cert_file: String,
     key_file: String,
+    pub client_ca_file: Option<String>,     // CA of the client certificates, mTLS is disabled if none
+    pub client_cert_required: bool,         // reject the connections without a client certificate
+    pub client_cert_identity: String,       // "cn" (subject common name) as the username / "san" (DNS subject alternative name) mapped to the username
+    pub client_cert_san_usernames: HashMap<String, String>,     // DNS SAN => username, for the "san" identity
 }
 
 impl Default for WIKServerTlsConfig {
@@ -128,6 +188,94 @@ impl Default for WIKServerTlsConfig {
         WIKServerTlsConfig {
             cert_file: "./certs/tls/cert.pem".to_string(),
             key_file: "./certs/tls/key.pem".to_string(),
+            client_ca_file: None,
+            client_cert_required: false,
+            client_cert_identity: "cn".to_string(),
+            client_cert_san_usernames: HashMap::new(),
+        }
+    }
+}
+
+/// Throttling of the failed login attempts.
+/// After n failures, the next attempt (of the username / source IP) is only allowed
+/// after `backoff_base * 2^(n-1)` seconds (at most `backoff_max`).
+#[derive(Deserialize, Serialize, Clone)]
+pub struct WIKServerLoginThrottleConfig {
+    pub backoff_base: i64,          // seconds
+    pub backoff_max: i64,           // seconds
+    pub lockout_threshold: i64,     // failures of a username before the account is locked
+    pub lockout_duration: i64,      // seconds the account is locked, also the window the failures are counted in
+}
+
+impl Default for WIKServerLoginThrottleConfig {
+    fn default() -> Self {
+        WIKServerLoginThrottleConfig {
+            backoff_base: 1,
+            backoff_max: 300,
+            lockout_threshold: 10,
+            lockout_duration: 900,
+        }
+    }
+}
+
+/// Federation of the users from an external OIDC identity provider.
+/// The ID / access tokens issued by the provider are accepted as bearer tokens,
+/// the token claims are mapped to the wellik username and role.
+#[derive(Deserialize, Serialize, Clone)]
+pub struct WIKServerOidcConfig {
+    pub issuer: Option<String>,                 // OIDC is disabled if none
+    pub audience: String,
+    pub jwks_url: Option<String>,               // public keys of the provider
+    pub jwks_file: Option<String>,              // local public keys (e.g. for testing), used if no jwks_url
+    pub jwks_refresh_interval: i64,             // seconds before the public keys are reloaded
+    pub username_claim: String,                 // claim of the wellik username
+    pub role_claim: String,                     // claim (string / list of strings) mapped to the wellik role
+    pub role_mapping: HashMap<String, String>,  // role claim value => wellik role (Admin / Auditor / App)
+    pub auto_provision: bool,                   // create the unknown admins on their first request
+}
+
+impl Default for WIKServerOidcConfig {
+    fn default() -> Self {
+        WIKServerOidcConfig {
+            issuer: None,
+            audience: "wellik".to_string(),
+            jwks_url: None,
+            jwks_file: None,
+            jwks_refresh_interval: 3600,
+            username_claim: "preferred_username".to_string(),
+            role_claim: "groups".to_string(),
+            role_mapping: HashMap::new(),
+            auto_provision: true,
+        }
+    }
+}
+
+/// Rules of the passwords set by the users (including root).
+#[derive(Deserialize, Serialize, Clone)]
+pub struct WIKServerPasswordPolicyConfig {
+    pub min_length: usize,          // characters
+    pub require_lowercase: bool,
+    pub require_uppercase: bool,
+    pub require_digit: bool,
+    pub require_symbol: bool,       // any character other than letters and digits
+    pub deny_list: Vec<String>,     // common passwords that are rejected (case insensitive)
+    pub history_size: usize,        // latest passwords of the user (including the current one) that cannot be reused, 0 to allow reuse
+}
+
+impl Default for WIKServerPasswordPolicyConfig {
+    fn default() -> Self {
+        WIKServerPasswordPolicyConfig {
+            min_length: 12,
+            require_lowercase: true,
+            require_uppercase: true,
+            require_digit: true,
+            require_symbol: false,
+            deny_list: [
+                "password", "password1", "password123", "passw0rd", "123456789012", "1234567890ab",
+                "qwertyuiop123", "iloveyou1234", "welcome12345", "letmein12345", "changeme1234",
+                "administrator1", "adminadmin123", "rootroot1234", "abc123abc123",
+            ].iter().map(|password| password.to_string()).collect(),
+            history_size: 5,
         }
     }
 }
@@ -142,27 +290,106 @@ impl WIKServerConfig {
         }
         
         // Figment::new()
-        Figment::from(Serialized::defaults(WIKServerConfig::default()))
+        let config: WIKServerConfig = Figment::from(Serialized::defaults(WIKServerConfig::default()))
             .merge(Json::file(json_config_filepath))
-            .extract().expect("Fail to read config.")
+            .extract().expect("Fail to read config.");
+        if let Err(error) = config.validate() {
+            panic!("Invalid config: {}", error);
+        }
+        config
+    }
+
+    /// Check the values that the server cannot start with.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.background_task_interval == 0 {
+            return Err("background_task_interval must be at least 1 second.".to_string());
+        }
+        match self.tls.client_cert_identity.as_str() {
+            "cn" => {},
+            // the DNS names are not valid usernames
+            "san" if self.tls.client_cert_san_usernames.is_empty() => {
+                return Err("client_cert_san_usernames must map the SANs to the usernames for the \"san\" identity.".to_string());
+            },
+            "san" => {},
+            identity => return Err(format!("Unsupported client_cert_identity '{}'.", identity)),
+        }
+        Ok(())
     }
 
     /// Get the ip for hosting the server.
     pub fn get_server_ip(&self) -> SocketAddr {
         format!("{}:{}", &self.server_ip, self.server_port).parse().unwrap()
     }
+
+    /// Check if the environment is one of the configured environments.
+    pub fn has_environment(&self, env: &str) -> bool {
+        self.environments.iter().any(|environment| environment == env)
+    }
+
+    /// Get the environment the config of the given environment is promoted to, none if it is the last one.
+    pub fn next_environment(&self, env: &str) -> Option<&str> {
+        let index = self.environments.iter().position(|environment| environment == env)?;
+        self.environments.get(index + 1).map(|environment| environment.as_str())
+    }
 }
 
 impl WIKServerTlsConfig {
     /// Get the cert for the HTTPS.
     pub async fn get_rustls_config(&self) -> RustlsConfig {
-        RustlsConfig::from_pem_file(
-            PathBuf::from(self.cert_file.clone()),
-            PathBuf::from(self.key_file.clone())
-            ).await
-            .unwrap()
+        match &self.client_ca_file {
+            None => RustlsConfig::from_pem_file(
+                PathBuf::from(self.cert_file.clone()),
+                PathBuf::from(self.key_file.clone())
+                ).await
+                .unwrap(),
+            Some(client_ca_file) => RustlsConfig::from_config(Arc::new(
+                self.get_mtls_server_config(client_ca_file).unwrap()
+            )),
+        }
         // unwrap as failing to get this config is FATAL
     }
+
+    /// Server config verifying the client certificates with the client CA.
+    fn get_mtls_server_config(&self, client_ca_file: &str) -> Result<ServerConfig> {
+        let certs = read_pem_certs(&self.cert_file)?;
+        let key = std::fs::read(&self.key_file)?;
+        let key = rustls_pemfile::read_all(&mut key.as_slice())
+            .find_map(|item| match item.ok()? {
+                Item::Pkcs1Key(key) => Some(PrivateKey(key.secret_pkcs1_der().to_vec())),
+                Item::Pkcs8Key(key) => Some(PrivateKey(key.secret_pkcs8_der().to_vec())),
+                Item::Sec1Key(key) => Some(PrivateKey(key.secret_sec1_der().to_vec())),
+                _ => None,
+            })
+            .ok_or(anyhow!("No private key found in {}.", self.key_file))?;
+
+        let mut client_roots = RootCertStore::empty();
+        for cert in read_pem_certs(client_ca_file)? {
+            client_roots.add(&cert)?;
+        }
+        let client_verifier = if self.client_cert_required {
+            AllowAnyAuthenticatedClient::new(client_roots).boxed()
+        } else {
+            // clients without a certificate can still login with password
+            AllowAnyAnonymousOrAuthenticatedClient::new(client_roots).boxed()
+        };
+
+        let mut config = ServerConfig::builder()
+            .with_safe_defaults()
+            .with_client_cert_verifier(client_verifier)
+            .with_single_cert(certs, key)?;
+        config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+        Ok(config)
+    }
+}
+
+/// Read all certificates in the PEM file.
+fn read_pem_certs(pem_file: &str) -> Result<Vec<Certificate>> {
+    let pem = std::fs::read(pem_file)?;
+    let certs = rustls_pemfile::certs(&mut pem.as_slice())
+        .map(|cert| cert.map(|cert| Certificate(cert.to_vec())))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    Ok(certs)
 }
 
 /// Log files & levels config.
@@ -4,12 +4,15 @@ use crate::auth::jwt_claim::JwtClaims;
 use crate::server_state::ServerState;
 use crate::auth::role_validation::RoleValidationUtil;
 use crate::error::ApiError;
-use crate::repository::{user::UserRepository, access_right::AccessRightRepository};
+use crate::repository::{user::UserRepository, access_right::AccessRightRepository, approval::ApprovalRepository, break_glass::BreakGlassRepository};
 use well_i_known_core::api::admin::*;
+use well_i_known_core::api::approval::{FlaggedOperation, PendingOperationResponse};
 use well_i_known_core::modal::user::UserRole;
 
 use axum::extract::State;
+use axum::http::StatusCode;
 use axum::Json;
+use chrono::Utc;
 use tracing::*;
 
 /// Authorization for the requester calling the admin access right API.
@@ -44,22 +47,48 @@ pub async fn create_admin_access_handler(
 ) -> Result<(), ApiError> {
     basic_auth_for_admin_api(&server_state, &claims, &payload).await?;
 
-    // check if access right exists
+    // compute the expiry time of a time-bound access right
+    let expires_at = match payload.duration {
+        Some(duration) if duration <= 0 => {
+            warn!("Invalid access right duration {}.", duration);
+            return Err(ApiError::InvalidArgument {
+                argument: "duration".to_string(),
+                message: "Duration must be a positive number of seconds.".to_string(),
+            });
+        },
+        Some(duration) => match Utc::now().timestamp().checked_add(duration) {
+            Some(expires_at) => Some(expires_at),
+            None => {
+                warn!("Access right duration {} is too long.", duration);
+                return Err(ApiError::InvalidArgument {
+                    argument: "duration".to_string(),
+                    message: "Duration is too long.".to_string(),
+                });
+            },
+        },
+        None => None,
+    };
+
+    // check if access right exists (for the environment or all environments)
     let exists = AccessRightRepository::check_access_right_exists(
         &server_state.db_conn, 
         &payload.admin,
         &payload.app, 
+        Some(&payload.env),
     ).await?;
 
     if exists {
-        warn!("Access right already exists for user {} and app {} but try to create one.", &payload.admin, &payload.app);
+        warn!("Access right already exists for user {} and app {} ({}) but try to create one.", &payload.admin, &payload.app, &payload.env);
         return Err(ApiError::DuplicateRecord);
     } 
 
     AccessRightRepository::add_access_right(
         &server_state.db_conn, 
+        &server_state.config,
         &payload.admin,
         &payload.app, 
+        &payload.env,
+        expires_at,
     ).await?;
 
     Ok(())
@@ -70,26 +99,103 @@ pub async fn delete_admin_access_handler(
     claims: JwtClaims,
     State(server_state): State<Arc<ServerState>>,
     Json(payload): Json<AdminAccessParam>,
-) -> Result<(), ApiError> {
+) -> Result<(StatusCode, Json<PendingOperationResponse>), ApiError> {
     basic_auth_for_admin_api(&server_state, &claims, &payload).await?;
     
-    // check if access right exists
-    let exists = AccessRightRepository::check_access_right_exists(
+    // check if the access right of the exact environment exists
+    let access_rights = AccessRightRepository::get_access_rights(
         &server_state.db_conn, 
-        &payload.admin,
-        &payload.app, 
+        Some(&payload.admin),
+        Some(&payload.app), 
     ).await?;
 
-    if !exists {
-        warn!("Access right does not exist for user {} and app {} but try to delete one.", &payload.admin, &payload.app);
+    if !access_rights.iter().any(|access_right| access_right.env == payload.env) {
+        warn!("Access right does not exist for user {} and app {} ({}) but try to delete one.", &payload.admin, &payload.app, &payload.env);
         return Err(ApiError::RecordNotFound);
     }
 
-    AccessRightRepository::delete_access_right(
-        &server_state.db_conn, 
-        &payload.admin,
+    // revoking access purges the admin's data, so it requires a second admin's approval
+    let pending_operation = ApprovalRepository::request_operation(
+        &server_state.db_conn,
+        &server_state.config,
+        &claims.sub,
+        FlaggedOperation::DeleteAdminAccess { admin: payload.admin, app: payload.app, env: payload.env },
+    ).await?;
+
+    Ok((StatusCode::ACCEPTED, Json(pending_operation)))
+}
+
+#[instrument(skip(server_state))]
+pub async fn list_admin_access_handler(
+    claims: JwtClaims,
+    State(server_state): State<Arc<ServerState>>,
+    Json(payload): Json<ListAdminAccessQuery>,
+) -> Result<Json<Vec<AdminAccessResponse>>, ApiError> {
+    let authorized = RoleValidationUtil::can_view_metadata(&claims.role);
+    RoleValidationUtil::throw_if_unauthorized(authorized, &claims.sub, "list admin access right")?;
+
+    let access_rights = AccessRightRepository::get_access_rights(
+        &server_state.db_conn,
+        payload.admin.as_deref(),
+        payload.app.as_deref(),
+    ).await?;
+
+    let now = Utc::now().timestamp();
+    let response = access_rights.into_iter()
+        .map(|access_right| AdminAccessResponse {
+            admin: access_right.username,
+            app: access_right.app_name,
+            env: access_right.env,
+            expires_at: access_right.expires_at,
+            remaining_seconds: access_right.expires_at.map(|expires_at| (expires_at - now).max(0)),
+        })
+        .collect();
+
+    Ok(Json(response))
+}
+
+/// Emergency access to an app by an admin without a prior access right.
+#[instrument(skip(server_state))]
+pub async fn break_glass_handler(
+    claims: JwtClaims,
+    State(server_state): State<Arc<ServerState>>,
+    Json(payload): Json<BreakGlassParam>,
+) -> Result<Json<BreakGlassResponse>, ApiError> {
+    // root has access to all apps already
+    let authorized = RoleValidationUtil::authorized_role(&claims.role, &[UserRole::Admin]);
+    RoleValidationUtil::throw_if_unauthorized(authorized, &claims.sub, "break glass")?;
+
+    let expires_at = BreakGlassRepository::break_glass(
+        &server_state.db_conn,
+        &server_state.config,
+        &claims.sub,
         &payload.app,
+        &payload.justification,
     ).await?;
 
-    Ok(())
+    Ok(Json(BreakGlassResponse { expires_at }))
+}
+
+/// List the break-glass events for root / auditor to review.
+#[instrument(skip(server_state))]
+pub async fn list_break_glass_events_handler(
+    claims: JwtClaims,
+    State(server_state): State<Arc<ServerState>>,
+) -> Result<Json<Vec<BreakGlassEventResponse>>, ApiError> {
+    let authorized = RoleValidationUtil::is_root(&claims.role) || RoleValidationUtil::is_auditor(&claims.role);
+    RoleValidationUtil::throw_if_unauthorized(authorized, &claims.sub, "list break-glass events")?;
+
+    let events = BreakGlassRepository::get_events(&server_state.db_conn).await?;
+    let response = events.into_iter()
+        .map(|event| BreakGlassEventResponse {
+            id: event.id,
+            admin: event.username,
+            app: event.app_name,
+            justification: event.justification,
+            created_at: event.created_at,
+            expires_at: event.expires_at,
+        })
+        .collect();
+
+    Ok(Json(response))
 }
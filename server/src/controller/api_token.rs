@@ -0,0 +1,84 @@
+use crate::auth::jwt_claim::JwtClaims;
+use crate::auth::role_validation::RoleValidationUtil;
+use crate::error::ApiError;
+use crate::repository::api_token::ApiTokenRepository;
+use crate::repository::user::UserRepository;
+use crate::server_state::ServerState;
+use well_i_known_core::api::token::*;
+
+use axum::Json;
+use axum::extract::State;
+use tracing::*;
+use std::sync::Arc;
+
+/// Throw Unauthorized if the requester cannot manage the API tokens of the owner.
+async fn throw_if_cannot_manage_token(server_state: &ServerState, claims: &JwtClaims, owner: &str, operation: &str) -> Result<(), ApiError> {
+    let owner_role = UserRepository::get_user_role(&server_state.db_conn, owner).await?;
+    RoleValidationUtil::throw_if_unauthorized(
+        RoleValidationUtil::can_manage_api_token(&claims.get_role(), &claims.sub, &owner_role, owner),
+        &claims.sub,
+        operation,
+    )
+}
+
+#[instrument(skip(server_state))]
+pub async fn create_api_token_handler(
+    claims: JwtClaims,
+    State(server_state): State<Arc<ServerState>>,
+    Json(payload): Json<CreateApiTokenParam>,
+) -> Result<Json<CreateApiTokenResponse>, ApiError> {
+    let owner = payload.username.as_deref().unwrap_or(&claims.sub);
+    RoleValidationUtil::throw_if_unauthorized(
+        RoleValidationUtil::can_issue_api_token(&claims.get_role(), &claims.sub, owner),
+        &claims.sub,
+        "issue API token",
+    )?;
+    if !UserRepository::check_user_exists(&server_state.db_conn, owner).await? {
+        warn!("User '{}' not found.", owner);
+        return Err(ApiError::RecordNotFound);
+    }
+
+    let (token, detail) = ApiTokenRepository::create_token(
+        &server_state.db_conn,
+        owner,
+        &payload.name,
+        &payload.scope.unwrap_or_default(),
+        payload.duration,
+    ).await?;
+
+    Ok(Json(CreateApiTokenResponse { token, detail }))
+}
+
+#[instrument(skip(server_state))]
+pub async fn list_api_tokens_handler(
+    claims: JwtClaims,
+    State(server_state): State<Arc<ServerState>>,
+    Json(payload): Json<ListApiTokenQuery>,
+) -> Result<Json<Vec<ApiTokenResponse>>, ApiError> {
+    let tokens = match payload.username.as_deref() {
+        Some(owner) => {
+            throw_if_cannot_manage_token(&server_state, &claims, owner, "list API tokens").await?;
+            ApiTokenRepository::get_tokens(&server_state.db_conn, Some(owner)).await?
+        },
+        None if RoleValidationUtil::is_root(&claims.role) => {
+            ApiTokenRepository::get_tokens(&server_state.db_conn, None).await?
+        },
+        None => ApiTokenRepository::get_tokens(&server_state.db_conn, Some(&claims.sub)).await?,
+    };
+
+    Ok(Json(tokens))
+}
+
+#[instrument(skip(server_state))]
+pub async fn revoke_api_token_handler(
+    claims: JwtClaims,
+    State(server_state): State<Arc<ServerState>>,
+    Json(payload): Json<DeleteApiTokenParam>,
+) -> Result<(), ApiError> {
+    let token = ApiTokenRepository::get_token(&server_state.db_conn, &payload.id).await?;
+    throw_if_cannot_manage_token(&server_state, &claims, &token.username, "revoke API token").await?;
+
+    ApiTokenRepository::revoke_token(&server_state.db_conn, &token.id).await?;
+    info!("API token {} of user {} revoked by {}.", token.id, token.username, claims.sub);
+    Ok(())
+}
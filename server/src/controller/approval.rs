@@ -0,0 +1,61 @@
+use std::sync::Arc;
+
+use crate::auth::jwt_claim::JwtClaims;
+use crate::server_state::ServerState;
+use crate::auth::role_validation::RoleValidationUtil;
+use crate::error::ApiError;
+use crate::repository::approval::ApprovalRepository;
+use well_i_known_core::api::approval::*;
+
+use axum::extract::State;
+use axum::Json;
+use tracing::*;
+
+/// Authorization for the requester calling the approval API.
+fn basic_auth_for_approval_api(claims: &JwtClaims, operation: &str) -> Result<(), ApiError> {
+    let authorized = RoleValidationUtil::is_admin(&claims.role);
+    RoleValidationUtil::throw_if_unauthorized(authorized, &claims.sub, operation)
+}
+
+/// List the operations waiting for approval.
+#[instrument(skip(server_state))]
+pub async fn list_approvals_handler(
+    claims: JwtClaims,
+    State(server_state): State<Arc<ServerState>>,
+) -> Result<Json<Vec<PendingOperationResponse>>, ApiError> {
+    let authorized = RoleValidationUtil::can_view_metadata(&claims.role);
+    RoleValidationUtil::throw_if_unauthorized(authorized, &claims.sub, "list pending operations")?;
+    let operations = ApprovalRepository::get_pending_operations(&server_state.db_conn).await?;
+    Ok(Json(operations))
+}
+
+/// Approve and execute a pending operation requested by another admin.
+/// Return the approved operation.
+#[instrument(skip(server_state))]
+pub async fn approve_operation_handler(
+    claims: JwtClaims,
+    State(server_state): State<Arc<ServerState>>,
+    Json(payload): Json<ApprovalParam>,
+) -> Result<Json<PendingOperationResponse>, ApiError> {
+    basic_auth_for_approval_api(&claims, "approve pending operation")?;
+    let operation = ApprovalRepository::approve(
+        &server_state.db_conn,
+        &server_state.config,
+        &claims.sub,
+        payload.id,
+    ).await?;
+    Ok(Json(operation))
+}
+
+/// Reject a pending operation requested by another admin.
+/// Return the rejected operation.
+#[instrument(skip(server_state))]
+pub async fn reject_operation_handler(
+    claims: JwtClaims,
+    State(server_state): State<Arc<ServerState>>,
+    Json(payload): Json<ApprovalParam>,
+) -> Result<Json<PendingOperationResponse>, ApiError> {
+    basic_auth_for_approval_api(&claims, "reject pending operation")?;
+    let operation = ApprovalRepository::reject(&server_state.db_conn, &claims.sub, payload.id).await?;
+    Ok(Json(operation))
+}
@@ -4,11 +4,22 @@ use crate::auth::jwt_claim::JwtClaims;
 use crate::server_state::ServerState;
 use crate::auth::role_validation::RoleValidationUtil;
 use crate::error::ApiError;
-use crate::repository::config_data::ConfigDataRepository;
+use crate::repository::access_right::AccessRightRepository;
+use crate::repository::approval::ApprovalRepository;
+use crate::repository::config_data::{ConfigChangeSet, ConfigDataRepository};
+use crate::repository::config_expiry::ConfigExpiryRepository;
+use crate::repository::config_version::ConfigVersionRepository;
+use well_i_known_core::api::approval::{FlaggedOperation, PendingOperationResponse};
 use well_i_known_core::api::data::*;
+use well_i_known_core::modal::util::config_key::{is_in_subtree, truncate_key};
+use well_i_known_core::modal::user::UserRole;
 
 use axum::extract::State;
+use axum::http::{header, HeaderMap, HeaderName, StatusCode};
+use axum::response::{IntoResponse, Response};
 use axum::Json;
+use chrono::Utc;
+use std::collections::HashMap;
 use tracing::*;
 
 /// Verify if the requester has access to that app's config.
@@ -21,6 +32,79 @@ pub async fn basic_auth_for_data_api(claims: &JwtClaims, app_name: &str) -> Resu
     Ok(())
 }
 
+/// Verify if the scope of the API token (if used) allows the operation on the key.
+pub fn scope_auth_for_data_api(claims: &JwtClaims, key: &str, alter: bool) -> Result<(), ApiError> {
+    if let Some(scope) = &claims.scope {
+        RoleValidationUtil::throw_if_unauthorized(
+            !(alter && scope.read_only),
+            &claims.sub,
+            "alter data with a read-only API token",
+        )?;
+        RoleValidationUtil::throw_if_unauthorized(
+            scope.allows_key(key),
+            &claims.sub,
+            &format!("access key '{}' with the API token", key),
+        )?;
+    }
+    Ok(())
+}
+
+/// Verify if the environment exists, and the requester can access it:
+/// an admin needs the access right to the environment of the app (or to all its environments).
+pub async fn env_auth_for_data_api(claims: &JwtClaims, server_state: &ServerState, app_name: &str, env: &str) -> Result<(), ApiError> {
+    ConfigDataRepository::validate_environment(&server_state.config, env)?;
+    if claims.get_role() == UserRole::Admin {
+        let has_access = AccessRightRepository::check_effective_access_right(&server_state.db_conn, &claims.sub, app_name, Some(env)).await?;
+        RoleValidationUtil::throw_if_unauthorized(has_access, &claims.sub, &format!("access environment '{}' of app '{}'", env, app_name))?;
+    }
+    Ok(())
+}
+
+/// Verify if the requester can read the non-secret entries of the environment of the app,
+/// i.e. the app itself, root, or admin with access to the environment of the app.
+/// (The secrets are protected by the encryption for the users with access.)
+pub async fn plain_data_auth_for_data_api(claims: &JwtClaims, server_state: &ServerState, app_name: &str, env: &str) -> Result<bool, ApiError> {
+    if claims.get_role() == UserRole::Admin {
+        return AccessRightRepository::check_effective_access_right(&server_state.db_conn, &claims.sub, app_name, Some(env)).await;
+    }
+    Ok(RoleValidationUtil::is_admin_or_self(&claims.role, &claims.sub, app_name))
+}
+
+/// Get the revision expected by the writer, from the request body or the If-Match header
+/// (the ETag returned by the reads, `*` matches any revision).
+pub fn expected_revision(headers: &HeaderMap, revision: Option<i64>) -> Result<Option<i64>, ApiError> {
+    let if_match = match headers.get(header::IF_MATCH) {
+        Some(if_match) => if_match,
+        None => return Ok(revision),
+    };
+    let invalid_if_match = || ApiError::InvalidArgument {
+        argument: "If-Match".to_string(),
+        message: "Expect the ETag of a revision, e.g. \"3\".".to_string(),
+    };
+
+    let if_match = if_match.to_str().map_err(|_| invalid_if_match())?.trim();
+    if if_match == "*" {
+        return Ok(revision);
+    }
+    let header_revision: i64 = if_match.trim_start_matches("W/").trim_matches('"')
+        .parse().map_err(|_| invalid_if_match())?;
+
+    match revision {
+        Some(revision) if revision != header_revision => Err(ApiError::InvalidArgument {
+            argument: "revision".to_string(),
+            message: "The revision does not match the If-Match header.".to_string(),
+        }),
+        _ => Ok(Some(header_revision)),
+    }
+}
+
+/// The ETag header of the revision of a key.
+fn revision_etag(revision: i64) -> [(HeaderName, String); 1] {
+    [(header::ETAG, format!("\"{}\"", revision))]
+}
+
+/// Get the value of the key, encrypted for the requester (or in plaintext for a non-secret entry, see SECRET_HEADER).
+/// The keys of all data endpoints may use `.` as the separator, it is the same key as with `/`, e.g. `db.url` is `db/url`.
 #[instrument(skip(server_state))] // tracing of function start and end
 pub async fn get_data_handler(
     // provided by axum extractors jwt::controller::JwtClaims
@@ -30,30 +114,597 @@ pub async fn get_data_handler(
     State(server_state): State<Arc<ServerState>>,
     // provided by axum extractors, which converts the request body to a json object of the specified struct type
     Json(payload): Json<GetDataQuery>
-) -> Result<String, ApiError> { // the return is converted to a Response by axum
+) -> Result<([(HeaderName, String); 2], String), ApiError> { // the return is converted to a Response by axum
+    // auditor only sees metadata, never the ciphertexts
+    RoleValidationUtil::throw_if_unauthorized(
+        !RoleValidationUtil::is_auditor(&claims.role),
+        &claims.sub,
+        "get data",
+    )?;
     basic_auth_for_data_api(&claims, &payload.app).await?;
-    let result = ConfigDataRepository::get_config_data(&server_state.db_conn, &payload.app, &claims.sub, &payload.key).await?;
-    Ok(result)
+    env_auth_for_data_api(&claims, &server_state, &payload.app, &payload.env).await?;
+    scope_auth_for_data_api(&claims, &payload.key, false)?;
+    ConfigExpiryRepository::check_not_expired(&server_state.db_conn, &server_state.config, &payload.app, &payload.env, &payload.key).await?;
+    // a non-secret entry is returned in plaintext, a secret as the ciphertext of the requester's copy
+    let plain_value = ConfigDataRepository::get_plain_config_data(&server_state.db_conn, &payload.app, &payload.env, &payload.key).await?;
+    let secret = plain_value.is_none();
+    let result = match plain_value {
+        Some(value) => {
+            let authorized = plain_data_auth_for_data_api(&claims, &server_state, &payload.app, &payload.env).await?;
+            RoleValidationUtil::throw_if_unauthorized(authorized, &claims.sub, &format!("get data of app '{}'", payload.app))?;
+            value
+        },
+        None => ConfigDataRepository::get_config_data(&server_state.db_conn, &payload.app, &payload.env, &claims.sub, &payload.key).await?,
+    };
+    // the revision to send back with the change, as the ETag
+    let revision = ConfigDataRepository::get_config_revision(&server_state.db_conn, &payload.app, &payload.env, &payload.key).await?;
+    let [etag] = revision_etag(revision);
+    Ok(([etag, (HeaderName::from_static(SECRET_HEADER), secret.to_string())], result))
 }
 
+/// Get all ciphertexts of the app owned by the requester (or of the given keys) in one call,
+/// e.g. for an app to load its whole config at start up.
+#[instrument(skip(server_state))]
+pub async fn get_bulk_data_handler(
+    claims: JwtClaims,
+    State(server_state): State<Arc<ServerState>>,
+    Json(payload): Json<GetBulkDataQuery>,
+) -> Result<Json<BulkDataResponse>, ApiError> {
+    RoleValidationUtil::throw_if_unauthorized(
+        !RoleValidationUtil::is_auditor(&claims.role),
+        &claims.sub,
+        "get data",
+    )?;
+    basic_auth_for_data_api(&claims, &payload.app).await?;
+    env_auth_for_data_api(&claims, &server_state, &payload.app, &payload.env).await?;
+    for key in payload.keys.iter().flatten() {
+        scope_auth_for_data_api(&claims, key, false)?;
+    }
+    if let Some(prefix) = &payload.prefix {
+        ConfigDataRepository::validate_key_prefix(prefix)?;
+    }
+
+    let mut data = ConfigDataRepository::get_bulk_config_data(
+        &server_state.db_conn,
+        &payload.app,
+        &payload.env,
+        &claims.sub,
+        payload.keys.as_deref(),
+    ).await?;
+    let mut plain = HashMap::new();
+    if plain_data_auth_for_data_api(&claims, &server_state, &payload.app, &payload.env).await? {
+        plain = ConfigDataRepository::get_bulk_plain_config_data(&server_state.db_conn, &payload.app, &payload.env, payload.keys.as_deref()).await?;
+    }
+    // only the keys within the scope of the API token (if used)
+    if let Some(scope) = &claims.scope {
+        data.retain(|key, _| scope.allows_key(key));
+        plain.retain(|key, _| scope.allows_key(key));
+    }
+    if let Some(prefix) = &payload.prefix {
+        data.retain(|key, _| is_in_subtree(key, prefix));
+        plain.retain(|key, _| is_in_subtree(key, prefix));
+    }
+    // the expiries of the returned keys, the expired ones are withheld if the server refuses to serve them
+    let mut expires_at = ConfigExpiryRepository::get_app_expiries(&server_state.db_conn, &payload.app, &payload.env).await?;
+    expires_at.retain(|key, _| data.contains_key(key) || plain.contains_key(key));
+    if server_state.config.config.config_expiry.refuse_expired {
+        let now = Utc::now().timestamp();
+        for key in expires_at.iter().filter(|(_, expires_at)| **expires_at <= now).map(|(key, _)| key) {
+            data.remove(key);
+            plain.remove(key);
+        }
+    }
+    let mut revisions = ConfigDataRepository::get_app_revisions(&server_state.db_conn, &payload.app, &payload.env).await?;
+    revisions.retain(|key, _| data.contains_key(key) || plain.contains_key(key));
+
+    Ok(Json(BulkDataResponse { app: payload.app, env: payload.env, data, plain, revisions, expires_at }))
+}
+
+/// Set the value of the key. A key given with `.` as the separator is stored with `/`,
+/// e.g. setting `db.url` sets `db/url`, and the keys are always listed with `/`.
 #[instrument(skip(server_state))]
 pub async fn alter_data_handler(
     claims: JwtClaims,
     State(server_state): State<Arc<ServerState>>,
+    headers: HeaderMap,
     Json(payload): Json<UpdateDataParam>,
-) -> Result<(), ApiError> {
+) -> Result<[(HeaderName, String); 1], ApiError> {
     basic_auth_for_data_api(&claims, &payload.app).await?;
-    ConfigDataRepository::alter_config_data(&server_state.db_conn, &server_state.config, &payload.app, &payload.key, &payload.value).await?;
-    Ok(())
+    env_auth_for_data_api(&claims, &server_state, &payload.app, &payload.env).await?;
+    scope_auth_for_data_api(&claims, &payload.key, true)?;
+    let expected_revision = expected_revision(&headers, payload.revision)?;
+    let mut changes = ConfigChangeSet::default();
+    if payload.secret {
+        changes.set.insert(payload.key.clone(), payload.value);
+    } else {
+        changes.set_plain.insert(payload.key.clone(), payload.value);
+    }
+    if let Some(revision) = expected_revision {
+        changes.expected_revisions.insert(payload.key.clone(), revision);
+    }
+    if let Some(expires_at) = payload.expires_at {
+        changes.expires_at.insert(payload.key.clone(), expires_at);
+    }
+    let revisions = ConfigDataRepository::alter_config_data(&server_state.db_conn, &server_state.config,
+        &payload.app, &payload.env, &claims.sub, &changes).await?;
+    Ok(revision_etag(revisions[&payload.key]))
+}
+
+/// Set and delete several keys of the app in one transaction.
+/// Deleting more than one key is a bulk delete: it requires a second admin's approval (202 with the pending operation),
+/// and the whole batch is applied in one transaction once approved.
+#[instrument(skip(server_state))]
+pub async fn batch_data_handler(
+    claims: JwtClaims,
+    State(server_state): State<Arc<ServerState>>,
+    Json(payload): Json<BatchDataParam>,
+) -> Result<Response, ApiError> {
+    basic_auth_for_data_api(&claims, &payload.app).await?;
+    env_auth_for_data_api(&claims, &server_state, &payload.app, &payload.env).await?;
+    for key in payload.set.keys().chain(payload.plain.keys()).chain(&payload.delete) {
+        scope_auth_for_data_api(&claims, key, true)?;
+    }
+
+    let changes = ConfigChangeSet {
+        set: payload.set,
+        set_plain: payload.plain,
+        delete: payload.delete,
+        expected_revisions: payload.revisions,
+        expires_at: payload.expires_at,
+    };
+    if changes.delete.len() > 1 {
+        let pending_operation = ApprovalRepository::request_batch(
+            &server_state.db_conn,
+            &server_state.config,
+            &claims.sub,
+            &payload.app,
+            &payload.env,
+            changes,
+        ).await?;
+        return Ok((StatusCode::ACCEPTED, Json(pending_operation)).into_response());
+    }
+
+    let revisions = ConfigDataRepository::alter_config_data_batch(
+        &server_state.db_conn,
+        &server_state.config,
+        &payload.app,
+        &payload.env,
+        &claims.sub,
+        &changes,
+    ).await?;
+    Ok(Json(BatchDataResponse { app: payload.app, env: payload.env, revisions }).into_response())
 }
 
 #[instrument(skip(server_state))]
 pub async fn delete_data_handler(
     claims: JwtClaims,
     State(server_state): State<Arc<ServerState>>,
+    headers: HeaderMap,
     Json(payload): Json<DeleteDataParam>,
+) -> Result<[(HeaderName, String); 1], ApiError> {
+    basic_auth_for_data_api(&claims, &payload.app).await?;
+    env_auth_for_data_api(&claims, &server_state, &payload.app, &payload.env).await?;
+    scope_auth_for_data_api(&claims, &payload.key, true)?;
+    let expected_revision = expected_revision(&headers, payload.revision)?;
+    let revision = ConfigDataRepository::delete_config_data(&server_state.db_conn, &server_state.config,
+        &payload.app, &payload.env, &claims.sub, &payload.key, expected_revision).await?;
+    Ok(revision_etag(revision))
+}
+
+/// Request to delete all keys of the app in the subtree of the prefix, e.g. "db/primary" for "db/primary/*".
+/// A bulk delete requires a second admin's approval, the keys are deleted in one transaction once approved.
+#[instrument(skip(server_state))]
+pub async fn delete_subtree_handler(
+    claims: JwtClaims,
+    State(server_state): State<Arc<ServerState>>,
+    Json(payload): Json<DeleteSubtreeParam>,
+) -> Result<(StatusCode, Json<PendingOperationResponse>), ApiError> {
+    basic_auth_for_data_api(&claims, &payload.app).await?;
+    env_auth_for_data_api(&claims, &server_state, &payload.app, &payload.env).await?;
+    ConfigDataRepository::validate_key_prefix(&payload.prefix)?;
+    let keys = ConfigDataRepository::get_subtree_keys(&server_state.db_conn, &server_state.config,
+        &payload.app, &payload.env, &payload.prefix).await?;
+    if keys.is_empty() {
+        warn!("No key of '{}' ({}) in the subtree '{}' to delete.", payload.app, payload.env, payload.prefix);
+        return Err(ApiError::RecordNotFound);
+    }
+    for key in &keys {
+        scope_auth_for_data_api(&claims, key, true)?;
+    }
+
+    let pending_operation = ApprovalRepository::request_operation(
+        &server_state.db_conn,
+        &server_state.config,
+        &claims.sub,
+        FlaggedOperation::DeleteSubtree { app: payload.app, env: payload.env, prefix: payload.prefix },
+    ).await?;
+
+    Ok((StatusCode::ACCEPTED, Json(pending_operation)))
+}
+
+/// Copy the value of the key from the environment to the next one, e.g. from staging to prod.
+/// The requester needs access to both environments.
+#[instrument(skip(server_state))]
+pub async fn promote_data_handler(
+    claims: JwtClaims,
+    State(server_state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Json(payload): Json<PromoteDataParam>,
+) -> Result<Json<PromoteDataResponse>, ApiError> {
+    basic_auth_for_data_api(&claims, &payload.app).await?;
+    env_auth_for_data_api(&claims, &server_state, &payload.app, &payload.env).await?;
+    if let Some(target_env) = server_state.config.config.next_environment(&payload.env) {
+        env_auth_for_data_api(&claims, &server_state, &payload.app, target_env).await?;
+    }
+    scope_auth_for_data_api(&claims, &payload.key, true)?;
+    let expected_revision = expected_revision(&headers, payload.revision)?;
+    let (target_env, revision) = ConfigDataRepository::promote_config_data(&server_state.db_conn, &server_state.config,
+        &payload.app, &payload.env, &claims.sub, &payload.key, expected_revision).await?;
+    Ok(Json(PromoteDataResponse { app: payload.app, key: payload.key, env: target_env, revision }))
+}
+
+/// List the apps whose data is visible to the requester.
+#[instrument(skip(server_state))]
+pub async fn list_apps_handler(
+    claims: JwtClaims,
+    State(server_state): State<Arc<ServerState>>,
+    Json(payload): Json<ListAppsQuery>,
+) -> Result<Json<ListPageResponse>, ApiError> {
+    let apps = ConfigDataRepository::get_visible_apps(&server_state.db_conn, &claims.sub, &claims.get_role()).await?;
+    Ok(Json(payload.page.apply(apps)))
+}
+
+/// List the config keys of the app (with the separator `/`), the values are never returned.
+#[instrument(skip(server_state))]
+pub async fn list_keys_handler(
+    claims: JwtClaims,
+    State(server_state): State<Arc<ServerState>>,
+    Json(payload): Json<ListKeysQuery>,
+) -> Result<Json<ListPageResponse>, ApiError> {
+    // auditor sees the keys of every app, never the values
+    let auditor = RoleValidationUtil::is_auditor(&claims.role);
+    if auditor {
+        ConfigDataRepository::validate_environment(&server_state.config, &payload.env)?;
+    } else {
+        basic_auth_for_data_api(&claims, &payload.app).await?;
+        env_auth_for_data_api(&claims, &server_state, &payload.app, &payload.env).await?;
+    }
+
+    let owner = if auditor { None } else { Some(claims.sub.as_str()) };
+    let mut keys = ConfigDataRepository::get_app_keys(&server_state.db_conn, &payload.app, &payload.env, owner).await?;
+    // only the keys within the scope of the API token (if used)
+    if let Some(scope) = &claims.scope {
+        keys.retain(|key| scope.allows_key(key));
+    }
+    if let Some(subtree) = &payload.subtree {
+        ConfigDataRepository::validate_key_prefix(subtree)?;
+        keys.retain(|key| is_in_subtree(key, subtree));
+    }
+    // the keys deeper than the depth are listed once by their path in the subtree
+    if let Some(depth) = payload.depth {
+        let subtree = payload.subtree.as_deref().unwrap_or("");
+        keys = keys.iter().map(|key| truncate_key(key, subtree, depth)).collect();
+        keys.sort();
+        keys.dedup();
+    }
+    let mut page = payload.page.apply(keys);
+    // the values of the non-secret keys are listed, the expired ones are withheld if the server refuses to serve them
+    if !auditor {
+        page.values = ConfigDataRepository::get_bulk_plain_config_data(&server_state.db_conn, &payload.app, &payload.env, Some(&page.items)).await?;
+        if server_state.config.config.config_expiry.refuse_expired && !page.values.is_empty() {
+            let now = Utc::now().timestamp();
+            let expires_at = ConfigExpiryRepository::get_app_expiries(&server_state.db_conn, &payload.app, &payload.env).await?;
+            page.values.retain(|key, _| expires_at.get(key).is_none_or(|expires_at| *expires_at > now));
+        }
+    }
+    Ok(Json(page))
+}
+
+/// List the keys expiring soon (or expired) of the apps & environments the requester has access to, the earliest first.
+/// Root and the auditor get the report of all apps (key names and dates only).
+#[instrument(skip(server_state))]
+pub async fn list_expiring_data_handler(
+    claims: JwtClaims,
+    State(server_state): State<Arc<ServerState>>,
+    Json(payload): Json<ExpiringDataQuery>,
+) -> Result<Json<Vec<ExpiringDataResponse>>, ApiError> {
+    if let Some(app) = &payload.app {
+        if !RoleValidationUtil::is_auditor(&claims.role) {
+            basic_auth_for_data_api(&claims, app).await?;
+        }
+    }
+    let within = payload.within.unwrap_or_else(|| {
+        server_state.config.config.config_expiry.warning_lead_times.iter().max().copied().unwrap_or(0)
+    });
+
+    let mut expiring_data = ConfigExpiryRepository::get_expiring_data(
+        &server_state.db_conn,
+        &claims.sub,
+        &claims.get_role(),
+        payload.app.as_deref(),
+        within,
+    ).await?;
+    // only the keys within the scope of the API token (if used)
+    if let Some(scope) = &claims.scope {
+        expiring_data.retain(|expiry| scope.allows_key(&expiry.key));
+    }
+    Ok(Json(expiring_data))
+}
+
+/// List the kept versions of the key, latest first.
+#[instrument(skip(server_state))]
+pub async fn list_versions_handler(
+    claims: JwtClaims,
+    State(server_state): State<Arc<ServerState>>,
+    Json(payload): Json<ListVersionsQuery>,
+) -> Result<Json<Vec<ConfigVersionResponse>>, ApiError> {
+    RoleValidationUtil::throw_if_unauthorized(
+        !RoleValidationUtil::is_auditor(&claims.role),
+        &claims.sub,
+        "list versions",
+    )?;
+    basic_auth_for_data_api(&claims, &payload.app).await?;
+    env_auth_for_data_api(&claims, &server_state, &payload.app, &payload.env).await?;
+    scope_auth_for_data_api(&claims, &payload.key, false)?;
+    let versions = ConfigVersionRepository::get_versions(&server_state.db_conn, &payload.app, &payload.env, &payload.key, &claims.sub).await?;
+    Ok(Json(versions))
+}
+
+/// Get the value of one version of the key, encrypted for the user (or in plaintext for a non-secret version, see SECRET_HEADER).
+#[instrument(skip(server_state))]
+pub async fn get_version_handler(
+    claims: JwtClaims,
+    State(server_state): State<Arc<ServerState>>,
+    Json(payload): Json<GetVersionQuery>,
+) -> Result<([(HeaderName, String); 1], String), ApiError> {
+    RoleValidationUtil::throw_if_unauthorized(
+        !RoleValidationUtil::is_auditor(&claims.role),
+        &claims.sub,
+        "get version",
+    )?;
+    basic_auth_for_data_api(&claims, &payload.app).await?;
+    env_auth_for_data_api(&claims, &server_state, &payload.app, &payload.env).await?;
+    scope_auth_for_data_api(&claims, &payload.key, false)?;
+    let (value, secret) = ConfigVersionRepository::get_version_value(
+        &server_state.db_conn, &payload.app, &payload.env, &payload.key, payload.version, &claims.sub).await?;
+    Ok(([(HeaderName::from_static(SECRET_HEADER), secret.to_string())], value))
+}
+
+/// Set the key back to the value of a kept version.
+#[instrument(skip(server_state))]
+pub async fn rollback_data_handler(
+    claims: JwtClaims,
+    State(server_state): State<Arc<ServerState>>,
+    Json(payload): Json<RollbackDataParam>,
 ) -> Result<(), ApiError> {
     basic_auth_for_data_api(&claims, &payload.app).await?;
-    ConfigDataRepository::delete_config_data(&server_state.db_conn, &payload.app, &payload.key).await?;
-    Ok(())
+    env_auth_for_data_api(&claims, &server_state, &payload.app, &payload.env).await?;
+    scope_auth_for_data_api(&claims, &payload.key, true)?;
+    ConfigVersionRepository::rollback(
+        &server_state.db_conn,
+        &server_state.config,
+        &payload.app,
+        &payload.env,
+        &payload.key,
+        payload.version,
+        &claims.sub,
+    ).await
+}
+
+/// Set the number of versions kept per key of the app.
+#[instrument(skip(server_state))]
+pub async fn history_retention_handler(
+    claims: JwtClaims,
+    State(server_state): State<Arc<ServerState>>,
+    Json(payload): Json<HistoryRetentionParam>,
+) -> Result<(), ApiError> {
+    // a setting of the whole app, not allowed with an API token
+    RoleValidationUtil::throw_if_unauthorized(
+        RoleValidationUtil::is_admin(&claims.role) && claims.scope.is_none(),
+        &claims.sub,
+        "set history retention",
+    )?;
+    if claims.get_role() == UserRole::Admin {
+        let has_access = AccessRightRepository::check_effective_access_right(&server_state.db_conn, &claims.sub, &payload.app, None).await?;
+        RoleValidationUtil::throw_if_unauthorized(has_access, &claims.sub, &format!("set history retention of app '{}'", payload.app))?;
+    }
+    ConfigVersionRepository::set_history_retention(&server_state.db_conn, &payload.app, payload.retention).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::jwt_key::JwtKeys;
+    use crate::config::server_config::WIKServerConfig;
+    use crate::dao::config_data::ConfigDataTable;
+    use crate::dao::config_data_expiry::{ConfigDataExpiry, ConfigDataExpiryTable};
+    use crate::dao::config_plain_data::{ConfigPlainData, ConfigPlainDataTable};
+    use crate::dao::user::UserTable;
+    use crate::db::db_test_util::*;
+    use crate::WIKServerEnvironmentConfig;
+    use well_i_known_core::api::token::ApiTokenScope;
+    use well_i_known_core::crypto::cryptography::WikRsaKeyPair;
+    use well_i_known_core::modal::user::UserKeyModal;
+    use axum::http::HeaderValue;
+
+    async fn create_test_server_state(test_case_name: &str) -> Arc<ServerState> {
+        let db_conn = create_test_db(test_case_name).await;
+        Arc::new(ServerState {
+            db_conn,
+            config: WIKServerEnvironmentConfig {
+                base_dir: get_test_path("output"),
+                config: WIKServerConfig::default(),
+                root_user: None,
+            },
+            jwt_keys: JwtKeys::new(b"secret"),
+            oidc: None,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_auditor_lists_keys_without_values() {
+        let server_state = create_test_server_state("auditor_lists_keys_without_values").await;
+        let db_conn = &server_state.db_conn;
+        UserTable::create_user(db_conn, "u_root", &UserRole::Root, "password").await.unwrap();
+        UserTable::create_user(db_conn, "u_app", &UserRole::App, "password").await.unwrap();
+        ConfigDataTable::set_data_value(db_conn, "u_app", DEFAULT_ENVIRONMENT, "u_root", "db/password", "encrypted").await.unwrap();
+        let mut conn = db_conn.pool.acquire().await.unwrap();
+        ConfigPlainDataTable::add_data(&mut conn, &ConfigPlainData {
+            app_name: "u_app".to_string(),
+            env: DEFAULT_ENVIRONMENT.to_string(),
+            key: "log_level".to_string(),
+            value: "info".to_string(),
+        }).await.unwrap();
+
+        let claims = || JwtClaims {
+            sub: "u_auditor".to_string(),
+            exp: 0,
+            role: UserRole::Auditor.to_string(),
+            jti: "jti".to_string(),
+            scope: None,
+        };
+        let Json(apps) = list_apps_handler(claims(), State(server_state.clone()),
+            Json(ListAppsQuery { page: ListPageParam::default() })).await.unwrap();
+        assert_eq!(apps.items, vec!["u_app".to_string()]);
+
+        let query = ListKeysQuery {
+            app: "u_app".to_string(),
+            env: DEFAULT_ENVIRONMENT.to_string(),
+            subtree: None,
+            depth: None,
+            page: ListPageParam::default(),
+        };
+        let Json(keys) = list_keys_handler(claims(), State(server_state.clone()), Json(query)).await.unwrap();
+        assert_eq!(keys.items, vec!["db/password".to_string(), "log_level".to_string()]);
+        assert!(keys.values.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_expired_values_and_scope_in_listings() {
+        let db_conn = create_test_db("expired_values_and_scope_in_listings").await;
+        let mut config = WIKServerConfig::default();
+        config.config_expiry.refuse_expired = true;
+        let server_state = Arc::new(ServerState {
+            db_conn,
+            config: WIKServerEnvironmentConfig { base_dir: get_test_path("output"), config, root_user: None },
+            jwt_keys: JwtKeys::new(b"secret"),
+            oidc: None,
+        });
+        let db_conn = &server_state.db_conn;
+        UserTable::create_user(db_conn, "u_app", &UserRole::App, "password").await.unwrap();
+        let mut conn = db_conn.pool.acquire().await.unwrap();
+        for (key, value) in [("log_level", "info"), ("region", "eu")] {
+            ConfigPlainDataTable::add_data(&mut conn, &ConfigPlainData {
+                app_name: "u_app".to_string(),
+                env: DEFAULT_ENVIRONMENT.to_string(),
+                key: key.to_string(),
+                value: value.to_string(),
+            }).await.unwrap();
+        }
+        ConfigDataExpiryTable::add_expiry(&mut conn, &ConfigDataExpiry {
+            app_name: "u_app".to_string(),
+            env: DEFAULT_ENVIRONMENT.to_string(),
+            key: "log_level".to_string(),
+            expires_at: Utc::now().timestamp() - 60,
+            warned_lead_time: None,
+        }).await.unwrap();
+
+        let claims = |scope: Option<ApiTokenScope>| JwtClaims {
+            sub: "u_app".to_string(),
+            exp: 0,
+            role: UserRole::App.to_string(),
+            jti: "jti".to_string(),
+            scope,
+        };
+        let query = ListKeysQuery {
+            app: "u_app".to_string(),
+            env: DEFAULT_ENVIRONMENT.to_string(),
+            subtree: None,
+            depth: None,
+            page: ListPageParam::default(),
+        };
+        let Json(keys) = list_keys_handler(claims(None), State(server_state.clone()), Json(query)).await.unwrap();
+        assert_eq!(keys.items, vec!["log_level".to_string(), "region".to_string()]);
+        assert_eq!(keys.values, HashMap::from([("region".to_string(), "eu".to_string())]));
+
+        let query = || ExpiringDataQuery { app: None, within: Some(3600) };
+        let Json(expiring) = list_expiring_data_handler(claims(None), State(server_state.clone()), Json(query())).await.unwrap();
+        assert_eq!(expiring.len(), 1);
+        let scope = ApiTokenScope { read_only: true, keys: Some(vec!["region".to_string()]) };
+        let Json(expiring) = list_expiring_data_handler(claims(Some(scope)), State(server_state.clone()), Json(query())).await.unwrap();
+        assert!(expiring.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_batch_with_bulk_delete_applied_once_approved() {
+        let db_conn = create_test_db("batch_with_bulk_delete_applied_once_approved").await;
+        let server_state = Arc::new(ServerState {
+            db_conn,
+            config: WIKServerEnvironmentConfig {
+                base_dir: get_test_path("output"),
+                config: WIKServerConfig::default(),
+                root_user: Some(UserKeyModal { username: "u_root".to_string(), key: WikRsaKeyPair::new().unwrap() }),
+            },
+            jwt_keys: JwtKeys::new(b"secret"),
+            oidc: None,
+        });
+        let db_conn = &server_state.db_conn;
+        UserTable::create_user(db_conn, "u_root", &UserRole::Root, "password").await.unwrap();
+        UserTable::create_user(db_conn, "u_app", &UserRole::App, "password").await.unwrap();
+        let changes = ConfigChangeSet {
+            set_plain: HashMap::from([
+                ("db/user".to_string(), "app".to_string()),
+                ("db/host".to_string(), "db-1".to_string()),
+            ]),
+            ..Default::default()
+        };
+        ConfigDataRepository::alter_config_data_batch(db_conn, &server_state.config, "u_app", DEFAULT_ENVIRONMENT, "u_app", &changes).await.unwrap();
+
+        let claims = JwtClaims {
+            sub: "u_app".to_string(),
+            exp: 0,
+            role: UserRole::App.to_string(),
+            jti: "jti".to_string(),
+            scope: None,
+        };
+        let payload = BatchDataParam {
+            app: "u_app".to_string(),
+            env: DEFAULT_ENVIRONMENT.to_string(),
+            set: HashMap::new(),
+            plain: HashMap::from([("db/url".to_string(), "postgres://db-2".to_string())]),
+            delete: vec!["db/user".to_string(), "db/host".to_string()],
+            revisions: HashMap::new(),
+            expires_at: HashMap::new(),
+        };
+        let response = batch_data_handler(claims, State(server_state.clone()), Json(payload)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        // nothing is applied before the approval
+        let value = ConfigDataRepository::get_plain_config_data(db_conn, "u_app", DEFAULT_ENVIRONMENT, "db/user").await.unwrap();
+        assert_eq!(value, Some("app".to_string()));
+        let value = ConfigDataRepository::get_plain_config_data(db_conn, "u_app", DEFAULT_ENVIRONMENT, "db/url").await.unwrap();
+        assert_eq!(value, None);
+
+        let pending = ApprovalRepository::get_pending_operations(db_conn).await.unwrap();
+        assert_eq!(pending.len(), 1);
+        ApprovalRepository::approve(db_conn, &server_state.config, "u_root", pending[0].id).await.unwrap();
+        let value = ConfigDataRepository::get_plain_config_data(db_conn, "u_app", DEFAULT_ENVIRONMENT, "db/user").await.unwrap();
+        assert_eq!(value, None);
+        let value = ConfigDataRepository::get_plain_config_data(db_conn, "u_app", DEFAULT_ENVIRONMENT, "db/url").await.unwrap();
+        assert_eq!(value, Some("postgres://db-2".to_string()));
+    }
+
+    #[test]
+    fn test_expected_revision() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(expected_revision(&headers, Some(2)).unwrap(), Some(2));
+
+        headers.insert(header::IF_MATCH, HeaderValue::from_static("W/\"3\""));
+        assert_eq!(expected_revision(&headers, None).unwrap(), Some(3));
+        assert!(expected_revision(&headers, Some(2)).is_err());
+
+        headers.insert(header::IF_MATCH, HeaderValue::from_static("*"));
+        assert_eq!(expected_revision(&headers, None).unwrap(), None);
+        headers.insert(header::IF_MATCH, HeaderValue::from_static("\"abc\""));
+        assert!(expected_revision(&headers, None).is_err());
+    }
 }
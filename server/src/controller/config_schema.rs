@@ -0,0 +1,75 @@
+use std::sync::Arc;
+
+use crate::auth::jwt_claim::JwtClaims;
+use crate::server_state::ServerState;
+use crate::auth::role_validation::RoleValidationUtil;
+use crate::error::ApiError;
+use crate::repository::access_right::AccessRightRepository;
+use crate::repository::config_schema::ConfigSchemaRepository;
+use well_i_known_core::api::data::*;
+use well_i_known_core::modal::config_schema::AppConfigSchema;
+use well_i_known_core::modal::user::UserRole;
+
+use axum::extract::State;
+use axum::Json;
+use tracing::*;
+
+/// Verify if the requester can change the schema of the app:
+/// root, or admin with access to the app, not with an API token.
+async fn auth_for_schema_change(claims: &JwtClaims, server_state: &ServerState, app_name: &str) -> Result<(), ApiError> {
+    RoleValidationUtil::throw_if_unauthorized(
+        RoleValidationUtil::is_admin(&claims.role) && claims.scope.is_none(),
+        &claims.sub,
+        "alter schema",
+    )?;
+    if claims.get_role() == UserRole::Admin {
+        let has_access = AccessRightRepository::check_effective_access_right(&server_state.db_conn, &claims.sub, app_name, None).await?;
+        RoleValidationUtil::throw_if_unauthorized(has_access, &claims.sub, &format!("alter schema of app '{}'", app_name))?;
+    }
+    Ok(())
+}
+
+/// Get the schema of the app, for the app itself and the metadata viewers.
+#[instrument(skip(server_state))]
+pub async fn get_schema_handler(
+    claims: JwtClaims,
+    State(server_state): State<Arc<ServerState>>,
+    Json(payload): Json<AppSchemaQuery>,
+) -> Result<Json<AppConfigSchema>, ApiError> {
+    RoleValidationUtil::throw_if_unauthorized(
+        RoleValidationUtil::can_view_metadata(&claims.role) || claims.sub == payload.app,
+        &claims.sub,
+        "get schema",
+    )?;
+    if claims.get_role() == UserRole::Admin {
+        let has_access = AccessRightRepository::check_effective_access_right(&server_state.db_conn, &claims.sub, &payload.app, None).await?;
+        RoleValidationUtil::throw_if_unauthorized(has_access, &claims.sub, &format!("get schema of app '{}'", payload.app))?;
+    }
+
+    match ConfigSchemaRepository::get_schema(&server_state.db_conn, &payload.app).await? {
+        Some(schema) => Ok(Json(schema)),
+        None => Err(ApiError::RecordNotFound),
+    }
+}
+
+/// Set (or replace) the schema of the app.
+#[instrument(skip(server_state))]
+pub async fn set_schema_handler(
+    claims: JwtClaims,
+    State(server_state): State<Arc<ServerState>>,
+    Json(payload): Json<AppSchemaParam>,
+) -> Result<(), ApiError> {
+    auth_for_schema_change(&claims, &server_state, &payload.app).await?;
+    ConfigSchemaRepository::set_schema(&server_state.db_conn, &server_state.config, &payload.app, &claims.sub, &payload.schema).await
+}
+
+/// Remove the schema of the app.
+#[instrument(skip(server_state))]
+pub async fn delete_schema_handler(
+    claims: JwtClaims,
+    State(server_state): State<Arc<ServerState>>,
+    Json(payload): Json<AppSchemaQuery>,
+) -> Result<(), ApiError> {
+    auth_for_schema_change(&claims, &server_state, &payload.app).await?;
+    ConfigSchemaRepository::delete_schema(&server_state.db_conn, &payload.app).await
+}
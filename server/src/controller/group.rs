@@ -0,0 +1,124 @@
+use std::sync::Arc;
+
+use crate::auth::jwt_claim::JwtClaims;
+use crate::server_state::ServerState;
+use crate::auth::role_validation::RoleValidationUtil;
+use crate::error::ApiError;
+use crate::repository::approval::ApprovalRepository;
+use crate::repository::group::GroupRepository;
+use well_i_known_core::api::approval::{FlaggedOperation, PendingOperationResponse};
+use well_i_known_core::api::group::*;
+
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Json;
+use tracing::*;
+
+/// Authorization for the requester calling the group API.
+fn basic_auth_for_group_api(claims: &JwtClaims, operation: &str) -> Result<(), ApiError> {
+    let authorized = RoleValidationUtil::is_admin(&claims.role);
+    RoleValidationUtil::throw_if_unauthorized(authorized, &claims.sub, operation)
+}
+
+#[instrument(skip(server_state))]
+pub async fn create_group_handler(
+    claims: JwtClaims,
+    State(server_state): State<Arc<ServerState>>,
+    Json(payload): Json<GroupParam>,
+) -> Result<(), ApiError> {
+    basic_auth_for_group_api(&claims, "create group")?;
+    GroupRepository::create_group(&server_state.db_conn, &payload.group).await?;
+    Ok(())
+}
+
+/// Request to delete the group, which revokes all its access rights at once.
+/// Only executes after a second admin approved it.
+#[instrument(skip(server_state))]
+pub async fn delete_group_handler(
+    claims: JwtClaims,
+    State(server_state): State<Arc<ServerState>>,
+    Json(payload): Json<GroupParam>,
+) -> Result<(StatusCode, Json<PendingOperationResponse>), ApiError> {
+    basic_auth_for_group_api(&claims, "delete group")?;
+    GroupRepository::throw_if_group_not_exists(&server_state.db_conn, &payload.group).await?;
+
+    let pending_operation = ApprovalRepository::request_operation(
+        &server_state.db_conn,
+        &server_state.config,
+        &claims.sub,
+        FlaggedOperation::DeleteGroup { group: payload.group },
+    ).await?;
+    Ok((StatusCode::ACCEPTED, Json(pending_operation)))
+}
+
+#[instrument(skip(server_state))]
+pub async fn add_group_member_handler(
+    claims: JwtClaims,
+    State(server_state): State<Arc<ServerState>>,
+    Json(payload): Json<GroupMemberParam>,
+) -> Result<(), ApiError> {
+    basic_auth_for_group_api(&claims, "add group member")?;
+    GroupRepository::add_member(
+        &server_state.db_conn,
+        &server_state.config,
+        &payload.group,
+        &payload.admin,
+    ).await?;
+    Ok(())
+}
+
+/// Request to remove the admin from the group, which revokes his access through the group.
+/// Only executes after a second admin approved it.
+#[instrument(skip(server_state))]
+pub async fn delete_group_member_handler(
+    claims: JwtClaims,
+    State(server_state): State<Arc<ServerState>>,
+    Json(payload): Json<GroupMemberParam>,
+) -> Result<(StatusCode, Json<PendingOperationResponse>), ApiError> {
+    basic_auth_for_group_api(&claims, "remove group member")?;
+    GroupRepository::throw_if_member_not_exists(&server_state.db_conn, &payload.group, &payload.admin).await?;
+
+    let pending_operation = ApprovalRepository::request_operation(
+        &server_state.db_conn,
+        &server_state.config,
+        &claims.sub,
+        FlaggedOperation::DeleteGroupMember { group: payload.group, admin: payload.admin },
+    ).await?;
+    Ok((StatusCode::ACCEPTED, Json(pending_operation)))
+}
+
+#[instrument(skip(server_state))]
+pub async fn create_group_access_handler(
+    claims: JwtClaims,
+    State(server_state): State<Arc<ServerState>>,
+    Json(payload): Json<GroupAccessParam>,
+) -> Result<(), ApiError> {
+    basic_auth_for_group_api(&claims, "create group access right")?;
+    GroupRepository::add_access_right(
+        &server_state.db_conn,
+        &server_state.config,
+        &payload.group,
+        &payload.app,
+    ).await?;
+    Ok(())
+}
+
+/// Request to revoke the group's access to the app.
+/// Only executes after a second admin approved it.
+#[instrument(skip(server_state))]
+pub async fn delete_group_access_handler(
+    claims: JwtClaims,
+    State(server_state): State<Arc<ServerState>>,
+    Json(payload): Json<GroupAccessParam>,
+) -> Result<(StatusCode, Json<PendingOperationResponse>), ApiError> {
+    basic_auth_for_group_api(&claims, "delete group access right")?;
+    GroupRepository::throw_if_access_right_not_exists(&server_state.db_conn, &payload.group, &payload.app).await?;
+
+    let pending_operation = ApprovalRepository::request_operation(
+        &server_state.db_conn,
+        &server_state.config,
+        &claims.sub,
+        FlaggedOperation::DeleteGroupAccess { group: payload.group, app: payload.app },
+    ).await?;
+    Ok((StatusCode::ACCEPTED, Json(pending_operation)))
+}
@@ -1,3 +1,8 @@
 pub mod user;
 pub mod config_data;
+pub mod config_schema;
 pub mod admin;
+pub mod api_token;
+pub mod approval;
+pub mod group;
+pub mod notification;
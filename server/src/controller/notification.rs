@@ -0,0 +1,42 @@
+use std::sync::Arc;
+
+use crate::auth::jwt_claim::JwtClaims;
+use crate::server_state::ServerState;
+use crate::error::ApiError;
+use crate::repository::notification::NotificationRepository;
+use well_i_known_core::api::notification::*;
+
+use axum::extract::State;
+use axum::Json;
+use tracing::*;
+
+/// Get the notifications sent to the requester.
+#[instrument(skip(server_state))]
+pub async fn get_notifications_handler(
+    claims: JwtClaims,
+    State(server_state): State<Arc<ServerState>>,
+) -> Result<Json<Vec<NotificationResponse>>, ApiError> {
+    let notifications = NotificationRepository::get_notifications(&server_state.db_conn, &claims.sub).await?;
+    let response = notifications.into_iter()
+        .map(|notification| NotificationResponse {
+            id: notification.id,
+            recipient: notification.recipient,
+            category: notification.category,
+            message: notification.message,
+            created_at: notification.created_at,
+        })
+        .collect();
+
+    Ok(Json(response))
+}
+
+/// Dismiss one notification of the requester.
+#[instrument(skip(server_state))]
+pub async fn delete_notification_handler(
+    claims: JwtClaims,
+    State(server_state): State<Arc<ServerState>>,
+    Json(payload): Json<DeleteNotificationParam>,
+) -> Result<(), ApiError> {
+    NotificationRepository::delete_notification(&server_state.db_conn, &claims.sub, payload.id).await?;
+    Ok(())
+}
@@ -1,12 +1,19 @@
 use crate::auth::jwt_claim::JwtClaims;
+use crate::auth::role_validation::RoleValidationUtil;
 use crate::error::ApiError;
-use crate::repository::user::UserRepository;
+use crate::repository::approval::ApprovalRepository;
+use crate::repository::login_challenge::LoginChallengeRepository;
+use crate::repository::login_throttle::LoginThrottleRepository;
+use crate::repository::password_reset::PasswordResetRepository;
+use crate::repository::user::{NewUser, UserRepository};
 use crate::server_state::ServerState;
+use well_i_known_core::api::approval::{FlaggedOperation, PendingOperationResponse};
 use well_i_known_core::api::user::*;
 use well_i_known_core::modal::user::UserRole;
 
 use axum::Json;
 use axum::extract::State;
+use axum::http::StatusCode;
 use tracing::*;
 use std::{str::FromStr, sync::Arc};
 
@@ -32,20 +39,38 @@ pub async fn alter_user_handler(
             });
         }
 
-        if !payload.public_key.is_none() {
-            warn!("User public key cannot be updated.");
+        if payload.password.is_none() && payload.public_key.is_none() {
+            warn!("Password is required when updating user.");
             return Err(ApiError::InvalidArgument {
-                argument: "public_key".to_string(),
-                // TODO see if we can update public key (by decrypting the old config with root key)
-                message: "Public key cannot be updated after created.".to_string(),
+                argument: "password".to_string(),
+                message: "Password is required".to_string(),
             });
         }
 
-        UserRepository::update_user(
-            &server_state.db_conn,
-            &payload.username,
-            &payload.password
-        ).await?;
+        if let Some(public_key) = &payload.public_key {
+            // only the user without a public key (provisioned from OIDC) can register his own,
+            // then he activates his account by the handshake (`/users/activate`) to receive the data he can access
+            // TODO see if we can update public key (by decrypting the old config with root key)
+            RoleValidationUtil::throw_if_unauthorized(
+                claims.sub == payload.username,
+                &claims.sub,
+                "register the public key of another user",
+            )?;
+            UserRepository::register_public_key(
+                &payload.username,
+                public_key,
+                &server_state.config.get_users_certs_path(&payload.username)
+            ).await?;
+        }
+
+        if let Some(password) = &payload.password {
+            UserRepository::update_user(
+                &server_state.db_conn,
+                &server_state.config.config.password_policy,
+                &payload.username,
+                password
+            ).await?;
+        }
     } else {
         // Case: create user 
         info!("User {} does not exist, creating user.", &payload.username);
@@ -87,12 +112,16 @@ pub async fn alter_user_handler(
             let user_cert_dir = server_state.config.get_users_certs_dir_path();
             UserRepository::create_user(
                 &server_state.db_conn,
+                &server_state.config.config.password_policy,
                 &claims.sub,
                 &claims.get_role(),
-                &payload.username,
-                &role.unwrap(),
-                &payload.password,
-                &payload.public_key.unwrap(),
+                &NewUser {
+                    username: &payload.username,
+                    role: &role.unwrap(),
+                    // without password, the user can only login with the private key challenge
+                    password: payload.password.as_deref(),
+                    public_key: &payload.public_key.unwrap(),
+                },
                 &user_cert_dir
             ).await?;
         }
@@ -101,24 +130,94 @@ pub async fn alter_user_handler(
     Ok(())
 }
 
+/// Request to delete the user.
+/// The deletion is irreversible, so it only executes after a second admin approved it.
 #[instrument(skip(server_state))]
 pub async fn delete_user_handler(
     claims: JwtClaims,
     State(server_state): State<Arc<ServerState>>,
     Json(payload): Json<DeleteUserParam>
-) -> Result<(), ApiError> {
-    UserRepository::delete_user(
+) -> Result<(StatusCode, Json<PendingOperationResponse>), ApiError> {
+    let authorized = RoleValidationUtil::is_admin(&claims.role);
+    RoleValidationUtil::throw_if_unauthorized(authorized, &claims.sub, "delete user")?;
+
+    // validate the target before asking for approval
+    let exists = UserRepository::check_user_exists(&server_state.db_conn, &payload.username).await?;
+    if !exists {
+        warn!("User {} does not exist.", &payload.username);
+        return Err(ApiError::RecordNotFound);
+    }
+
+    let user = UserRepository::get_user(
         &server_state.db_conn,
         &payload.username,
         &server_state.config.get_users_certs_path(&payload.username)
     ).await?;
+    if user.role == UserRole::Root {
+        warn!("Try to delete root user, which is not allowed.");
+        return Err(ApiError::InvalidArgument {
+            argument: "username".to_string(),
+            message: "Root user cannot be deleted.".to_string()
+        });
+    }
 
+    let pending_operation = ApprovalRepository::request_operation(
+        &server_state.db_conn,
+        &server_state.config,
+        &claims.sub,
+        FlaggedOperation::DeleteUser { username: payload.username },
+    ).await?;
+
+    Ok((StatusCode::ACCEPTED, Json(pending_operation)))
+}
+
+/// List all users with their roles.
+#[instrument(skip(server_state))]
+pub async fn list_users_handler(
+    claims: JwtClaims,
+    State(server_state): State<Arc<ServerState>>,
+) -> Result<Json<Vec<UserResponse>>, ApiError> {
+    let authorized = RoleValidationUtil::can_view_metadata(&claims.role);
+    RoleValidationUtil::throw_if_unauthorized(authorized, &claims.sub, "list users")?;
+
+    let users = UserRepository::get_all_users(&server_state.db_conn).await?;
+    let response = users.into_iter()
+        .map(|user| UserResponse {
+            username: user.username,
+            role: user.role,
+            active: user.active,
+            disabled: user.disabled,
+        })
+        .collect();
+
+    Ok(Json(response))
+}
+
+/// Clear the lock of the user after too many failed login attempts.
+#[instrument(skip(server_state))]
+pub async fn unlock_user_handler(
+    claims: JwtClaims,
+    State(server_state): State<Arc<ServerState>>,
+    Json(payload): Json<UnlockUserParam>
+) -> Result<(), ApiError> {
+    let authorized = RoleValidationUtil::is_root(&claims.role);
+    RoleValidationUtil::throw_if_unauthorized(authorized, &claims.sub, "unlock user")?;
+
+    let unlocked = LoginThrottleRepository::unlock_user(&server_state.db_conn, &payload.username).await?;
+    if !unlocked {
+        warn!("User {} is not locked.", &payload.username);
+        return Err(ApiError::RecordNotFound);
+    }
+
+    info!("User {} unlocked by {}.", &payload.username, &claims.sub);
     Ok(())
 }
 
+/// First step of the activation handshake.
+/// Return a random plaintext encrypted with the user's public key,
+/// only the holder of the private key can answer it to activate the user.
 #[instrument(skip(server_state))]
 pub async fn validate_user_handler(
-    claims: JwtClaims,
     State(server_state): State<Arc<ServerState>>,
     Json(payload): Json<ValidateUserParam>
 ) -> Result<Json<ValidateUserResponse>, ApiError> {
@@ -130,28 +229,98 @@ pub async fn validate_user_handler(
         return Err(ApiError::RecordNotFound);
     }
 
-    let user = UserRepository::get_user(
+    let (challenge_id, encrypted) = LoginChallengeRepository::create_challenge(
         &server_state.db_conn,
+        &server_state.config,
         &payload.username,
-        &server_state.config.get_users_certs_path(&payload.username)
     ).await?;
 
-    let key = user.get_public_key();
-    if let Err(error) = key {
-        warn!("Fail to get user's public key. Error: {}", error);
-        return Err(ApiError::ServerError);
-    }
-    let (plaintext, encrypted) = key.unwrap().generate_validate_string();
-
     // pack the response
     let response = ValidateUserResponse {
-        plaintext,
+        challenge_id,
         encrypted,
     };
 
     Ok(Json(response))
 }
 
+/// Second step of the activation handshake.
+/// Activate the user if the plaintext of the challenge is answered.
+#[instrument(skip(server_state))]
+pub async fn activate_user_handler(
+    State(server_state): State<Arc<ServerState>>,
+    Json(payload): Json<ActivateUserParam>
+) -> Result<(), ApiError> {
+    let username = UserRepository::activate_user(
+        &server_state.db_conn,
+        &server_state.config,
+        &payload.challenge_id,
+        &payload.plaintext,
+    ).await?;
+
+    info!("User {} activated by handshake.", username);
+    Ok(())
+}
+
+/// Disable / enable the user without deleting his data.
+#[instrument(skip(server_state))]
+pub async fn user_status_handler(
+    claims: JwtClaims,
+    State(server_state): State<Arc<ServerState>>,
+    Json(payload): Json<UserStatusParam>
+) -> Result<(), ApiError> {
+    let authorized = RoleValidationUtil::is_root(&claims.role);
+    RoleValidationUtil::throw_if_unauthorized(authorized, &claims.sub, "disable / enable user")?;
+
+    UserRepository::set_user_disabled(
+        &server_state.db_conn,
+        &server_state.config,
+        &payload.username,
+        payload.disabled,
+    ).await?;
+
+    info!("User {} is {} by {}.", &payload.username, if payload.disabled { "disabled" } else { "enabled" }, &claims.sub);
+    Ok(())
+}
+
+/// Issue a one-time password reset token to the user.
+/// Root delivers the token to the user out of band, the user chooses the new password with it.
+#[instrument(skip(server_state))]
+pub async fn create_reset_token_handler(
+    claims: JwtClaims,
+    State(server_state): State<Arc<ServerState>>,
+    Json(payload): Json<CreateResetTokenParam>
+) -> Result<Json<ResetTokenResponse>, ApiError> {
+    let authorized = RoleValidationUtil::is_root(&claims.role);
+    RoleValidationUtil::throw_if_unauthorized(authorized, &claims.sub, "issue password reset token")?;
+
+    let (token, expires_at) = PasswordResetRepository::issue_token(
+        &server_state.db_conn,
+        &server_state.config,
+        &payload.username,
+    ).await?;
+
+    info!("Password reset token of user {} issued by {}.", &payload.username, &claims.sub);
+    Ok(Json(ResetTokenResponse { token, expires_at }))
+}
+
+/// Set the new password with the reset token, the token is invalidated after use.
+#[instrument(skip(server_state))]
+pub async fn reset_password_handler(
+    State(server_state): State<Arc<ServerState>>,
+    Json(payload): Json<ResetPasswordParam>
+) -> Result<(), ApiError> {
+    let username = PasswordResetRepository::redeem_token(
+        &server_state.db_conn,
+        &server_state.config,
+        &payload.token,
+        &payload.password,
+    ).await?;
+
+    info!("User {} reset the password.", username);
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     #[tokio::test]
@@ -1,8 +1,10 @@
+use well_i_known_core::api::data::ALL_ENVIRONMENTS;
 use crate::dao::user::UserIden;
 use crate::db::{db_base::DbTable, db_connection::DbConnection};
 
 use sqlx::FromRow;
-use sea_query::{enum_def, ColumnDef, Expr, ForeignKey, ForeignKeyAction, Query, SqliteQueryBuilder, Table};
+use sea_query::{enum_def, ColumnDef, Cond, Condition, Expr, ForeignKey, ForeignKeyAction, OnConflict, Query, SqliteQueryBuilder, Table};
+use chrono::Utc;
 use tracing::info;
 use anyhow::Result;
 
@@ -11,11 +13,15 @@ use anyhow::Result;
 pub struct AccessRight {
     pub username: String,
     pub app_name: String,
+    pub env: String,                // environment of the app, "*" for all environments
+    pub expires_at: Option<i64>,    // UTC timestamp, never expires if none
 }
 
-const ACCESS_RIGHT_COLUMNS: [AccessRightIden; 2] = [
+const ACCESS_RIGHT_COLUMNS: [AccessRightIden; 4] = [
     AccessRightIden::Username,
     AccessRightIden::AppName,
+    AccessRightIden::Env,
+    AccessRightIden::ExpiresAt,
 ];
 
 pub struct AccessRightTable {}
@@ -27,9 +33,12 @@ impl DbTable for AccessRightTable {
             .if_not_exists()
             .col(ColumnDef::new(AccessRightIden::Username).string())
             .col(ColumnDef::new(AccessRightIden::AppName).string())
+            .col(ColumnDef::new(AccessRightIden::Env).string().default(ALL_ENVIRONMENTS))
+            .col(ColumnDef::new(AccessRightIden::ExpiresAt).big_integer().null())
             .primary_key(sea_query::Index::create()
                 .col(AccessRightIden::Username)
                 .col(AccessRightIden::AppName)
+                .col(AccessRightIden::Env)
             )
             .foreign_key(ForeignKey::create()
                 // .name("fk_access_right_username")
@@ -48,13 +57,29 @@ impl DbTable for AccessRightTable {
 }
 
 impl AccessRightTable {
+    /// Condition matching the access rights that have not expired yet.
+    pub fn not_expired_cond() -> Condition {
+        Cond::any()
+            .add(Expr::col((AccessRightIden::Table, AccessRightIden::ExpiresAt)).is_null())
+            .add(Expr::col((AccessRightIden::Table, AccessRightIden::ExpiresAt)).gt(Utc::now().timestamp()))
+    }
+
+    /// Condition matching the access rights to the given environment, i.e. of the environment or of all environments.
+    pub fn env_cond(env: &str) -> Condition {
+        Cond::any()
+            .add(Expr::col((AccessRightIden::Table, AccessRightIden::Env)).eq(env))
+            .add(Expr::col((AccessRightIden::Table, AccessRightIden::Env)).eq(ALL_ENVIRONMENTS))
+    }
+
     /// Get all app the given user can access to.
     /// Return a list of app names.
     pub async fn get_user_access_rights(db_conn: &DbConnection, username: &str) -> Result<Vec<String>> {
         let sql = Query::select()
+            .distinct()
             .column(AccessRightIden::AppName)
             .from(AccessRightIden::Table)
             .and_where(Expr::col(AccessRightIden::Username).eq(username))
+            .cond_where(AccessRightTable::not_expired_cond())
             .to_string(SqliteQueryBuilder);
 
         let access_rights: Vec<(String, )> = sqlx::query_as(sql.as_str())
@@ -64,6 +89,57 @@ impl AccessRightTable {
         Ok(access_rights.into_iter().map(|(app_name, )| app_name).collect())
     }
 
+    /// Get the environments of the app the given user has a (not expired) access right to, "*" for all environments.
+    pub async fn get_user_app_environments(db_conn: &DbConnection, username: &str, app_name: &str) -> Result<Vec<String>> {
+        let sql = Query::select()
+            .column(AccessRightIden::Env)
+            .from(AccessRightIden::Table)
+            .and_where(Expr::col(AccessRightIden::Username).eq(username))
+            .and_where(Expr::col(AccessRightIden::AppName).eq(app_name))
+            .cond_where(AccessRightTable::not_expired_cond())
+            .to_string(SqliteQueryBuilder);
+
+        let environments: Vec<(String, )> = sqlx::query_as(sql.as_str())
+            .fetch_all(&db_conn.pool)
+            .await?;
+
+        Ok(environments.into_iter().map(|(env, )| env).collect())
+    }
+
+    /// Get the access rights that have not expired yet.
+    /// Filter by the admin's username and / or the app name if given.
+    pub async fn get_access_rights(db_conn: &DbConnection, username: Option<&str>, app_name: Option<&str>) -> Result<Vec<AccessRight>> {
+        let sql = Query::select()
+            .columns(ACCESS_RIGHT_COLUMNS)
+            .from(AccessRightIden::Table)
+            .cond_where(AccessRightTable::not_expired_cond())
+            .and_where_option(username.map(|username| Expr::col(AccessRightIden::Username).eq(username)))
+            .and_where_option(app_name.map(|app_name| Expr::col(AccessRightIden::AppName).eq(app_name)))
+            .to_string(SqliteQueryBuilder);
+
+        let access_rights = sqlx::query_as::<_, AccessRight>(sql.as_str())
+            .fetch_all(&db_conn.pool)
+            .await?;
+
+        Ok(access_rights)
+    }
+
+    /// Get the access rights that expired at or before the given timestamp.
+    pub async fn get_expired_access_rights(db_conn: &DbConnection, timestamp: i64) -> Result<Vec<AccessRight>> {
+        let sql = Query::select()
+            .columns(ACCESS_RIGHT_COLUMNS)
+            .from(AccessRightIden::Table)
+            .and_where(Expr::col(AccessRightIden::ExpiresAt).is_not_null())
+            .and_where(Expr::col(AccessRightIden::ExpiresAt).lte(timestamp))
+            .to_string(SqliteQueryBuilder);
+
+        let access_rights = sqlx::query_as::<_, AccessRight>(sql.as_str())
+            .fetch_all(&db_conn.pool)
+            .await?;
+
+        Ok(access_rights)
+    }
+
     /// Delete all access rights of the given user.
     /// Used when deleting an admin which has access rights to some apps.
     pub async fn delete_all_access_of_user(db_conn: &DbConnection, username: &str) -> Result<()> {
@@ -93,15 +169,23 @@ impl AccessRightTable {
         Ok(())
     }
 
-    /// Add access right of all config of a given app to the given user.
-    pub async fn add_access_right(db_conn: &DbConnection, username: &str, app_name: &str) -> Result<()> {
+    /// Add access right of all config of a given app in the given environment ("*" for all environments) to the given user.
+    /// The access right expires at the given timestamp if provided.
+    /// If an (expired) access right already exists, its expiry is replaced.
+    pub async fn add_access_right(db_conn: &DbConnection, username: &str, app_name: &str, env: &str, expires_at: Option<i64>) -> Result<()> {
         let sql = Query::insert()
             .into_table(AccessRightIden::Table)
             .columns(ACCESS_RIGHT_COLUMNS)
             .values([
                 username.into(),
                 app_name.into(),
+                env.into(),
+                expires_at.into(),
             ])?
+            .on_conflict(OnConflict::columns([AccessRightIden::Username, AccessRightIden::AppName, AccessRightIden::Env])
+                .update_column(AccessRightIden::ExpiresAt)
+                .to_owned()
+            )
             .to_string(SqliteQueryBuilder);
 
         sqlx::query(sql.as_str())
@@ -111,12 +195,13 @@ impl AccessRightTable {
         Ok(())
     }
 
-    /// Delete the access right of the given user to the given app.
-    pub async fn delete_access_right(db_conn: &DbConnection, username: &str, app_name: &str) -> Result<()> {
+    /// Delete the access right of the given user to the given app in the given environment ("*" for the one of all environments).
+    pub async fn delete_access_right(db_conn: &DbConnection, username: &str, app_name: &str, env: &str) -> Result<()> {
         let sql = Query::delete()
             .from_table(AccessRightIden::Table)
             .cond_where(Expr::col(AccessRightIden::Username).eq(username))
             .cond_where(Expr::col(AccessRightIden::AppName).eq(app_name))
+            .cond_where(Expr::col(AccessRightIden::Env).eq(env))
             .to_string(SqliteQueryBuilder);
 
         sqlx::query(sql.as_str())
@@ -126,13 +211,16 @@ impl AccessRightTable {
         Ok(())
     }
 
-    /// Check if the given user has access right to the given app.
-    pub async fn check_access_right_exists(db_conn: &DbConnection, username: &str, app_name: &str) -> Result<bool> {
+    /// Check if the given user has a (not expired) access right to the given app,
+    /// covering the given environment (granted for it or for all environments) if provided, otherwise of any environment.
+    pub async fn check_access_right_exists(db_conn: &DbConnection, username: &str, app_name: &str, env: Option<&str>) -> Result<bool> {
         let sql = Query::select()
             .expr(Expr::col(AccessRightIden::AppName).count())
             .from(AccessRightIden::Table)
             .and_where(Expr::col(AccessRightIden::Username).eq(username))
             .and_where(Expr::col(AccessRightIden::AppName).eq(app_name))
+            .cond_where(AccessRightTable::not_expired_cond())
+            .cond_where(env.map_or(Cond::all(), AccessRightTable::env_cond))
             .to_string(SqliteQueryBuilder);
 
         let count: (i64, ) = sqlx::query_as(sql.as_str())
@@ -162,20 +250,20 @@ mod tests {
     async fn test_add_and_get_access_right(){
         let db_conn = create_access_right_test_db("test_add_access_right").await;
 
-        let has_access = AccessRightTable::check_access_right_exists(&db_conn, "u_admin", "test_app").await.unwrap();
+        let has_access = AccessRightTable::check_access_right_exists(&db_conn, "u_admin", "test_app", None).await.unwrap();
         assert_eq!(has_access, false);
         
         // grant right
-        AccessRightTable::add_access_right(&db_conn, "u_admin", "test_app").await.unwrap();
+        AccessRightTable::add_access_right(&db_conn, "u_admin", "test_app", ALL_ENVIRONMENTS, None).await.unwrap();
 
-        let has_access = AccessRightTable::check_access_right_exists(&db_conn, "u_admin", "test_app").await.unwrap();
+        let has_access = AccessRightTable::check_access_right_exists(&db_conn, "u_admin", "test_app", None).await.unwrap();
         assert_eq!(has_access, true);
 
         let access_rights = AccessRightTable::get_user_access_rights(&db_conn, "u_admin").await.unwrap();
         assert_eq!(access_rights.len(), 1);
         assert_eq!(access_rights[0], "test_app");
 
-        AccessRightTable::add_access_right(&db_conn, "u_admin", "test_app2").await.unwrap();
+        AccessRightTable::add_access_right(&db_conn, "u_admin", "test_app2", ALL_ENVIRONMENTS, None).await.unwrap();
         let access_rights = AccessRightTable::get_user_access_rights(&db_conn, "u_admin").await.unwrap();
         assert_eq!(access_rights.len(), 2);
     }
@@ -184,10 +272,10 @@ mod tests {
     async fn test_delete_one_access(){
         let db_conn = create_access_right_test_db("test_delete_one_access").await;
 
-        AccessRightTable::add_access_right(&db_conn, "u_admin", "test_app").await.unwrap();
-        AccessRightTable::add_access_right(&db_conn, "u_admin", "test_app2").await.unwrap();
-        AccessRightTable::delete_access_right(&db_conn, "u_admin", "test_app").await.unwrap();
-        let has_access = AccessRightTable::check_access_right_exists(&db_conn, "u_admin", "test_app").await.unwrap();
+        AccessRightTable::add_access_right(&db_conn, "u_admin", "test_app", ALL_ENVIRONMENTS, None).await.unwrap();
+        AccessRightTable::add_access_right(&db_conn, "u_admin", "test_app2", ALL_ENVIRONMENTS, None).await.unwrap();
+        AccessRightTable::delete_access_right(&db_conn, "u_admin", "test_app", ALL_ENVIRONMENTS).await.unwrap();
+        let has_access = AccessRightTable::check_access_right_exists(&db_conn, "u_admin", "test_app", None).await.unwrap();
         assert_eq!(has_access, false);
         let access_rights = AccessRightTable::get_user_access_rights(&db_conn, "u_admin").await.unwrap();
         assert_eq!(access_rights.len(), 1);
@@ -197,10 +285,60 @@ mod tests {
     async fn test_delete_all_access(){
         let db_conn = create_access_right_test_db("test_delete_all_access").await;
 
-        AccessRightTable::add_access_right(&db_conn, "u_admin", "test_app").await.unwrap();
-        AccessRightTable::add_access_right(&db_conn, "u_admin", "test_app2").await.unwrap();
+        AccessRightTable::add_access_right(&db_conn, "u_admin", "test_app", ALL_ENVIRONMENTS, None).await.unwrap();
+        AccessRightTable::add_access_right(&db_conn, "u_admin", "test_app2", ALL_ENVIRONMENTS, None).await.unwrap();
         AccessRightTable::delete_all_access_of_user(&db_conn, "u_admin").await.unwrap();
         let access_rights = AccessRightTable::get_user_access_rights(&db_conn, "u_admin").await.unwrap();
         assert_eq!(access_rights.len(), 0);
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_expired_access(){
+        let db_conn = create_access_right_test_db("test_expired_access").await;
+        let now = Utc::now().timestamp();
+
+        AccessRightTable::add_access_right(&db_conn, "u_admin", "test_app", ALL_ENVIRONMENTS, Some(now - 10)).await.unwrap();
+        AccessRightTable::add_access_right(&db_conn, "u_admin", "test_app2", ALL_ENVIRONMENTS, Some(now + 3600)).await.unwrap();
+
+        // expired access right is not effective
+        let has_access = AccessRightTable::check_access_right_exists(&db_conn, "u_admin", "test_app", None).await.unwrap();
+        assert!(!has_access);
+        let has_access = AccessRightTable::check_access_right_exists(&db_conn, "u_admin", "test_app2", None).await.unwrap();
+        assert!(has_access);
+        let access_rights = AccessRightTable::get_access_rights(&db_conn, Some("u_admin"), None).await.unwrap();
+        assert_eq!(access_rights.len(), 1);
+        assert_eq!(access_rights[0].app_name, "test_app2");
+
+        let expired = AccessRightTable::get_expired_access_rights(&db_conn, now).await.unwrap();
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].app_name, "test_app");
+
+        // re-grant replace the expiry
+        AccessRightTable::add_access_right(&db_conn, "u_admin", "test_app", ALL_ENVIRONMENTS, None).await.unwrap();
+        let has_access = AccessRightTable::check_access_right_exists(&db_conn, "u_admin", "test_app", None).await.unwrap();
+        assert!(has_access);
+    }
+
+    #[tokio::test]
+    async fn test_access_right_of_environment(){
+        let db_conn = create_access_right_test_db("test_access_right_of_environment").await;
+
+        AccessRightTable::add_access_right(&db_conn, "u_admin", "test_app", "staging", None).await.unwrap();
+        let has_access = AccessRightTable::check_access_right_exists(&db_conn, "u_admin", "test_app", Some("staging")).await.unwrap();
+        assert!(has_access);
+        let has_access = AccessRightTable::check_access_right_exists(&db_conn, "u_admin", "test_app", Some("prod")).await.unwrap();
+        assert!(!has_access);
+        let has_access = AccessRightTable::check_access_right_exists(&db_conn, "u_admin", "test_app", None).await.unwrap();
+        assert!(has_access);
+
+        // the access right of all environments covers any environment
+        AccessRightTable::add_access_right(&db_conn, "u_admin", "test_app", ALL_ENVIRONMENTS, None).await.unwrap();
+        let has_access = AccessRightTable::check_access_right_exists(&db_conn, "u_admin", "test_app", Some("prod")).await.unwrap();
+        assert!(has_access);
+        let mut environments = AccessRightTable::get_user_app_environments(&db_conn, "u_admin", "test_app").await.unwrap();
+        environments.sort();
+        assert_eq!(environments, vec![ALL_ENVIRONMENTS.to_string(), "staging".to_string()]);
+        let access_rights = AccessRightTable::get_user_access_rights(&db_conn, "u_admin").await.unwrap();
+        assert_eq!(access_rights, vec!["test_app".to_string()]);
+    }
+}
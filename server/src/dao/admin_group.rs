@@ -0,0 +1,99 @@
+use crate::db::{db_base::DbTable, db_connection::DbConnection};
+
+use sqlx::FromRow;
+use sea_query::{enum_def, ColumnDef, Expr, Query, SqliteQueryBuilder, Table};
+use tracing::info;
+use anyhow::Result;
+
+/// A group of admins that can be granted access rights as a whole.
+#[enum_def]
+#[derive(Clone, FromRow, Debug)]
+pub struct AdminGroup {
+    pub group_name: String,
+}
+
+pub struct AdminGroupTable {}
+impl DbTable for AdminGroupTable {
+    async fn create_table(db_conn: &DbConnection) {
+        info!("Creating table: {:?}", AdminGroupIden::Table);
+        let sql = Table::create()
+            .table(AdminGroupIden::Table)
+            .if_not_exists()
+            .col(ColumnDef::new(AdminGroupIden::GroupName).string().primary_key())
+            .to_string(SqliteQueryBuilder);
+
+        sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await.expect("Failed to create table");
+        // FATAL if failed to create table
+    }
+}
+
+impl AdminGroupTable {
+    /// Check if the group with the given name exists.
+    pub async fn check_group_exists(db_conn: &DbConnection, group_name: &str) -> Result<bool> {
+        let sql = Query::select()
+            .expr(Expr::col(AdminGroupIden::GroupName).count())
+            .from(AdminGroupIden::Table)
+            .and_where(Expr::col(AdminGroupIden::GroupName).eq(group_name))
+            .to_string(SqliteQueryBuilder);
+
+        let count: (i32, ) = sqlx::query_as(sql.as_str())
+            .fetch_one(&db_conn.pool)
+            .await?;
+
+        Ok(count.0 == 1)
+    }
+
+    /// Create a new (empty) group.
+    pub async fn create_group(db_conn: &DbConnection, group: &AdminGroup) -> Result<()> {
+        let sql = Query::insert()
+            .into_table(AdminGroupIden::Table)
+            .columns([AdminGroupIden::GroupName])
+            .values([group.group_name.as_str().into()])?
+            .to_string(SqliteQueryBuilder);
+
+        sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Delete the group.
+    /// The members and access rights of the group should be removed separately.
+    pub async fn delete_group(db_conn: &DbConnection, group_name: &str) -> Result<()> {
+        let sql = Query::delete()
+            .from_table(AdminGroupIden::Table)
+            .cond_where(Expr::col(AdminGroupIden::GroupName).eq(group_name))
+            .to_string(SqliteQueryBuilder);
+
+        sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::db_test_util::create_test_db;
+
+    #[tokio::test]
+    async fn test_create_and_delete_group(){
+        let db_conn = create_test_db("test_create_and_delete_group").await;
+
+        let exists = AdminGroupTable::check_group_exists(&db_conn, "g_team").await.unwrap();
+        assert!(!exists);
+
+        AdminGroupTable::create_group(&db_conn, &AdminGroup { group_name: "g_team".to_string() }).await.unwrap();
+        let exists = AdminGroupTable::check_group_exists(&db_conn, "g_team").await.unwrap();
+        assert!(exists);
+
+        AdminGroupTable::delete_group(&db_conn, "g_team").await.unwrap();
+        let exists = AdminGroupTable::check_group_exists(&db_conn, "g_team").await.unwrap();
+        assert!(!exists);
+    }
+}
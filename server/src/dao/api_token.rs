@@ -0,0 +1,171 @@
+use crate::dao::user::UserIden;
+use crate::db::{db_base::DbTable, db_connection::DbConnection};
+
+use sqlx::FromRow;
+use sea_query::{enum_def, ColumnDef, Expr, ForeignKey, ForeignKeyAction, Order, Query, SqliteQueryBuilder, Table};
+use tracing::info;
+use anyhow::Result;
+
+/// A named, long-lived credential of a user.
+/// Only the hash of the token secret is stored.
+#[enum_def]
+#[derive(Clone, FromRow, Debug)]
+pub struct ApiToken {
+    pub id: String,
+    pub username: String,
+    pub name: String,
+    pub token_hash: String,
+    pub token_salt: String,
+    pub scope: String,              // json of the ApiTokenScope
+    pub created_at: i64,            // UTC timestamp
+    pub expires_at: Option<i64>,    // UTC timestamp, never expires if none
+}
+
+const API_TOKEN_COLUMNS: [ApiTokenIden; 8] = [
+    ApiTokenIden::Id,
+    ApiTokenIden::Username,
+    ApiTokenIden::Name,
+    ApiTokenIden::TokenHash,
+    ApiTokenIden::TokenSalt,
+    ApiTokenIden::Scope,
+    ApiTokenIden::CreatedAt,
+    ApiTokenIden::ExpiresAt,
+];
+
+pub struct ApiTokenTable {}
+impl DbTable for ApiTokenTable {
+    async fn create_table(db_conn: &DbConnection) {
+        info!("Creating table: {:?}", ApiTokenIden::Table);
+        let sql = Table::create()
+            .table(ApiTokenIden::Table)
+            .if_not_exists()
+            .col(ColumnDef::new(ApiTokenIden::Id).string().primary_key())
+            .col(ColumnDef::new(ApiTokenIden::Username).string().not_null())
+            .col(ColumnDef::new(ApiTokenIden::Name).string().not_null())
+            .col(ColumnDef::new(ApiTokenIden::TokenHash).string().not_null())
+            .col(ColumnDef::new(ApiTokenIden::TokenSalt).string().not_null())
+            .col(ColumnDef::new(ApiTokenIden::Scope).string().not_null())
+            .col(ColumnDef::new(ApiTokenIden::CreatedAt).big_integer().not_null())
+            .col(ColumnDef::new(ApiTokenIden::ExpiresAt).big_integer().null())
+            .index(sea_query::Index::create()
+                .unique()
+                .col(ApiTokenIden::Username)
+                .col(ApiTokenIden::Name)
+            )
+            .foreign_key(ForeignKey::create()
+                .from(ApiTokenIden::Table, ApiTokenIden::Username)
+                .to(UserIden::Table, UserIden::Username)
+                .on_delete(ForeignKeyAction::Cascade)
+                .on_update(ForeignKeyAction::Cascade)
+                )
+            .to_string(SqliteQueryBuilder);
+
+        sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await.expect("Failed to create table");
+        // FATAL if failed to create table
+    }
+}
+
+impl ApiTokenTable {
+    pub async fn add_token(db_conn: &DbConnection, token: &ApiToken) -> Result<()> {
+        let sql = Query::insert()
+            .into_table(ApiTokenIden::Table)
+            .columns(API_TOKEN_COLUMNS)
+            .values([
+                token.id.as_str().into(),
+                token.username.as_str().into(),
+                token.name.as_str().into(),
+                token.token_hash.as_str().into(),
+                token.token_salt.as_str().into(),
+                token.scope.as_str().into(),
+                token.created_at.into(),
+                token.expires_at.into(),
+            ])?
+            .to_string(SqliteQueryBuilder);
+
+        sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Get one token (if exists) by id.
+    pub async fn get_token(db_conn: &DbConnection, id: &str) -> Result<Option<ApiToken>> {
+        let sql = Query::select()
+            .columns(API_TOKEN_COLUMNS)
+            .from(ApiTokenIden::Table)
+            .and_where(Expr::col(ApiTokenIden::Id).eq(id))
+            .to_string(SqliteQueryBuilder);
+
+        let token = sqlx::query_as::<_, ApiToken>(sql.as_str())
+            .fetch_optional(&db_conn.pool)
+            .await?;
+
+        Ok(token)
+    }
+
+    /// Get the tokens, optionally filtered by the owner.
+    pub async fn get_tokens(db_conn: &DbConnection, username: Option<&str>) -> Result<Vec<ApiToken>> {
+        let sql = Query::select()
+            .columns(API_TOKEN_COLUMNS)
+            .from(ApiTokenIden::Table)
+            .and_where_option(username.map(|username| Expr::col(ApiTokenIden::Username).eq(username)))
+            .order_by(ApiTokenIden::Username, Order::Asc)
+            .order_by(ApiTokenIden::Name, Order::Asc)
+            .to_string(SqliteQueryBuilder);
+
+        let tokens = sqlx::query_as::<_, ApiToken>(sql.as_str())
+            .fetch_all(&db_conn.pool)
+            .await?;
+
+        Ok(tokens)
+    }
+
+    /// Check if the user already has a token with the name.
+    pub async fn check_token_name_exists(db_conn: &DbConnection, username: &str, name: &str) -> Result<bool> {
+        let sql = Query::select()
+            .expr(Expr::col(ApiTokenIden::Id).count())
+            .from(ApiTokenIden::Table)
+            .and_where(Expr::col(ApiTokenIden::Username).eq(username))
+            .and_where(Expr::col(ApiTokenIden::Name).eq(name))
+            .to_string(SqliteQueryBuilder);
+
+        let count: (i64, ) = sqlx::query_as(sql.as_str())
+            .fetch_one(&db_conn.pool)
+            .await?;
+
+        Ok(count.0 > 0)
+    }
+
+    /// Delete the token.
+    /// Return false if the token does not exist.
+    pub async fn delete_token(db_conn: &DbConnection, id: &str) -> Result<bool> {
+        let sql = Query::delete()
+            .from_table(ApiTokenIden::Table)
+            .cond_where(Expr::col(ApiTokenIden::Id).eq(id))
+            .to_string(SqliteQueryBuilder);
+
+        let result = sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    /// Delete all tokens of the given user.
+    /// Used when deleting a user.
+    pub async fn delete_all_tokens_of_user(db_conn: &DbConnection, username: &str) -> Result<()> {
+        let sql = Query::delete()
+            .from_table(ApiTokenIden::Table)
+            .cond_where(Expr::col(ApiTokenIden::Username).eq(username))
+            .to_string(SqliteQueryBuilder);
+
+        sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await?;
+
+        Ok(())
+    }
+}
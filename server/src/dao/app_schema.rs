@@ -0,0 +1,102 @@
+use sea_query::{enum_def, ColumnDef, Expr, ForeignKey, ForeignKeyAction, OnConflict, Query, SqliteQueryBuilder, Table};
+use tracing::info;
+use anyhow::Result;
+
+use crate::db::{db_base::DbTable, db_connection::DbConnection};
+use crate::dao::user::UserIden;
+
+/// Schema of the config of an app, stored as JSON.
+#[enum_def]
+#[derive(Clone, Debug)]
+pub struct AppSchema {
+    pub app_name: String,
+    pub schema: String,         // JSON of AppConfigSchema
+    pub updated_by: String,
+    pub updated_at: i64,        // UTC timestamp
+}
+
+const APP_SCHEMA_COLUMNS: [AppSchemaIden; 4] = [
+    AppSchemaIden::AppName,
+    AppSchemaIden::Schema,
+    AppSchemaIden::UpdatedBy,
+    AppSchemaIden::UpdatedAt,
+];
+
+pub struct AppSchemaTable {}
+impl DbTable for AppSchemaTable {
+    async fn create_table(db_conn: &DbConnection) {
+        info!("Creating table: {:?}", AppSchemaIden::Table);
+        let sql = Table::create()
+            .table(AppSchemaIden::Table)
+            .if_not_exists()
+            .col(ColumnDef::new(AppSchemaIden::AppName).string().primary_key())
+            .col(ColumnDef::new(AppSchemaIden::Schema).string().not_null())
+            .col(ColumnDef::new(AppSchemaIden::UpdatedBy).string().not_null())
+            .col(ColumnDef::new(AppSchemaIden::UpdatedAt).big_integer().not_null())
+            .foreign_key(ForeignKey::create()
+                .from(AppSchemaIden::Table, AppSchemaIden::AppName)
+                .to(UserIden::Table, UserIden::Username)
+                .on_delete(ForeignKeyAction::Cascade)
+                .on_update(ForeignKeyAction::Cascade)
+            )
+            .to_string(SqliteQueryBuilder);
+
+        sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await.expect("Failed to create table app schema");
+    }
+}
+
+impl AppSchemaTable {
+    /// Get the schema (JSON) of the app, none if the app has no schema.
+    pub async fn get_schema(db_conn: &DbConnection, app_name: &str) -> Result<Option<String>> {
+        let sql = Query::select()
+            .column(AppSchemaIden::Schema)
+            .from(AppSchemaIden::Table)
+            .and_where(Expr::col(AppSchemaIden::AppName).eq(app_name))
+            .to_string(SqliteQueryBuilder);
+
+        let schema: Option<(String, )> = sqlx::query_as(sql.as_str())
+            .fetch_optional(&db_conn.pool)
+            .await?;
+
+        Ok(schema.map(|(schema, )| schema))
+    }
+
+    /// Set (or replace) the schema of the app.
+    pub async fn set_schema(db_conn: &DbConnection, app_schema: &AppSchema) -> Result<()> {
+        let sql = Query::insert()
+            .into_table(AppSchemaIden::Table)
+            .columns(APP_SCHEMA_COLUMNS)
+            .values([
+                app_schema.app_name.as_str().into(),
+                app_schema.schema.as_str().into(),
+                app_schema.updated_by.as_str().into(),
+                app_schema.updated_at.into(),
+            ])?
+            .on_conflict(OnConflict::column(AppSchemaIden::AppName)
+                .update_columns([AppSchemaIden::Schema, AppSchemaIden::UpdatedBy, AppSchemaIden::UpdatedAt])
+                .to_owned())
+            .to_string(SqliteQueryBuilder);
+
+        sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Delete the schema of the app.
+    pub async fn delete_schema(db_conn: &DbConnection, app_name: &str) -> Result<()> {
+        let sql = Query::delete()
+            .from_table(AppSchemaIden::Table)
+            .cond_where(Expr::col(AppSchemaIden::AppName).eq(app_name))
+            .to_string(SqliteQueryBuilder);
+
+        sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,81 @@
+use sqlx::FromRow;
+use sea_query::{enum_def, ColumnDef, Expr, ForeignKey, ForeignKeyAction, OnConflict, Query, SqliteQueryBuilder, Table};
+use tracing::info;
+use anyhow::Result;
+
+use crate::db::{db_base::DbTable, db_connection::DbConnection};
+use crate::dao::user::UserIden;
+
+/// Settings of an app overriding the server defaults.
+#[enum_def]
+#[derive(Clone, FromRow, Debug)]
+pub struct AppSetting {
+    pub app_name: String,
+    pub history_retention: i64,     // versions kept per config key
+}
+
+const APP_SETTING_COLUMNS: [AppSettingIden; 2] = [
+    AppSettingIden::AppName,
+    AppSettingIden::HistoryRetention,
+];
+
+pub struct AppSettingTable {}
+impl DbTable for AppSettingTable {
+    async fn create_table(db_conn: &DbConnection) {
+        info!("Creating table: {:?}", AppSettingIden::Table);
+        let sql = Table::create()
+            .table(AppSettingIden::Table)
+            .if_not_exists()
+            .col(ColumnDef::new(AppSettingIden::AppName).string().primary_key())
+            .col(ColumnDef::new(AppSettingIden::HistoryRetention).big_integer().not_null())
+            .foreign_key(ForeignKey::create()
+                .from(AppSettingIden::Table, AppSettingIden::AppName)
+                .to(UserIden::Table, UserIden::Username)
+                .on_delete(ForeignKeyAction::Cascade)
+                .on_update(ForeignKeyAction::Cascade)
+            )
+            .to_string(SqliteQueryBuilder);
+
+        sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await.expect("Failed to create table app setting");
+    }
+}
+
+impl AppSettingTable {
+    /// Get the settings of the app, none if the app uses the server defaults.
+    pub async fn get_setting(db_conn: &DbConnection, app_name: &str) -> Result<Option<AppSetting>> {
+        let sql = Query::select()
+            .columns(APP_SETTING_COLUMNS)
+            .from(AppSettingIden::Table)
+            .and_where(Expr::col(AppSettingIden::AppName).eq(app_name))
+            .to_string(SqliteQueryBuilder);
+
+        let setting = sqlx::query_as::<_, AppSetting>(sql.as_str())
+            .fetch_optional(&db_conn.pool)
+            .await?;
+
+        Ok(setting)
+    }
+
+    /// Set the settings of the app, replacing the previous ones.
+    pub async fn set_setting(db_conn: &DbConnection, setting: &AppSetting) -> Result<()> {
+        let sql = Query::insert()
+            .into_table(AppSettingIden::Table)
+            .columns(APP_SETTING_COLUMNS)
+            .values([
+                setting.app_name.as_str().into(),
+                setting.history_retention.into(),
+            ])?
+            .on_conflict(OnConflict::column(AppSettingIden::AppName)
+                .update_column(AppSettingIden::HistoryRetention)
+                .to_owned())
+            .to_string(SqliteQueryBuilder);
+
+        sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await?;
+
+        Ok(())
+    }
+}
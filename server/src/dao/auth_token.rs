@@ -0,0 +1,218 @@
+use crate::dao::user::UserIden;
+use crate::db::{db_base::DbTable, db_connection::DbConnection};
+
+use sqlx::FromRow;
+use sea_query::{enum_def, ColumnDef, Expr, ForeignKey, ForeignKeyAction, Query, SqliteQueryBuilder, Table};
+use tracing::info;
+use anyhow::Result;
+
+/// A login session of a user, identified by the jti of its tokens.
+/// The access tokens are only accepted while the session is not revoked,
+/// and the refresh token (stored as hash) can be exchanged for new tokens before it expires.
+#[enum_def]
+#[derive(Clone, FromRow, Debug)]
+pub struct AuthToken {
+    pub jti: String,
+    pub username: String,
+    pub refresh_token_hash: String,
+    pub refresh_token_salt: String,
+    pub created_at: i64,    // UTC timestamp
+    pub expires_at: i64,    // UTC timestamp of the refresh token expiry
+    pub revoked: bool,
+}
+
+const AUTH_TOKEN_COLUMNS: [AuthTokenIden; 7] = [
+    AuthTokenIden::Jti,
+    AuthTokenIden::Username,
+    AuthTokenIden::RefreshTokenHash,
+    AuthTokenIden::RefreshTokenSalt,
+    AuthTokenIden::CreatedAt,
+    AuthTokenIden::ExpiresAt,
+    AuthTokenIden::Revoked,
+];
+
+pub struct AuthTokenTable {}
+impl DbTable for AuthTokenTable {
+    async fn create_table(db_conn: &DbConnection) {
+        info!("Creating table: {:?}", AuthTokenIden::Table);
+        let sql = Table::create()
+            .table(AuthTokenIden::Table)
+            .if_not_exists()
+            .col(ColumnDef::new(AuthTokenIden::Jti).string().primary_key())
+            .col(ColumnDef::new(AuthTokenIden::Username).string().not_null())
+            .col(ColumnDef::new(AuthTokenIden::RefreshTokenHash).string().not_null())
+            .col(ColumnDef::new(AuthTokenIden::RefreshTokenSalt).string().not_null())
+            .col(ColumnDef::new(AuthTokenIden::CreatedAt).big_integer().not_null())
+            .col(ColumnDef::new(AuthTokenIden::ExpiresAt).big_integer().not_null())
+            .col(ColumnDef::new(AuthTokenIden::Revoked).boolean().not_null().default(false))
+            .foreign_key(ForeignKey::create()
+                .from(AuthTokenIden::Table, AuthTokenIden::Username)
+                .to(UserIden::Table, UserIden::Username)
+                .on_delete(ForeignKeyAction::Cascade)
+                .on_update(ForeignKeyAction::Cascade)
+                )
+            .to_string(SqliteQueryBuilder);
+
+        sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await.expect("Failed to create table");
+        // FATAL if failed to create table
+    }
+}
+
+impl AuthTokenTable {
+    /// Add a new session.
+    pub async fn add_token(db_conn: &DbConnection, token: &AuthToken) -> Result<()> {
+        let sql = Query::insert()
+            .into_table(AuthTokenIden::Table)
+            .columns(AUTH_TOKEN_COLUMNS)
+            .values([
+                token.jti.as_str().into(),
+                token.username.as_str().into(),
+                token.refresh_token_hash.as_str().into(),
+                token.refresh_token_salt.as_str().into(),
+                token.created_at.into(),
+                token.expires_at.into(),
+                token.revoked.into(),
+            ])?
+            .to_string(SqliteQueryBuilder);
+
+        sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Get one session (if exists) by jti.
+    pub async fn get_token(db_conn: &DbConnection, jti: &str) -> Result<Option<AuthToken>> {
+        let sql = Query::select()
+            .columns(AUTH_TOKEN_COLUMNS)
+            .from(AuthTokenIden::Table)
+            .and_where(Expr::col(AuthTokenIden::Jti).eq(jti))
+            .to_string(SqliteQueryBuilder);
+
+        let token = sqlx::query_as::<_, AuthToken>(sql.as_str())
+            .fetch_optional(&db_conn.pool)
+            .await?;
+
+        Ok(token)
+    }
+
+    /// Check if the session exists and is not revoked.
+    pub async fn check_token_active(db_conn: &DbConnection, jti: &str) -> Result<bool> {
+        let sql = Query::select()
+            .expr(Expr::col(AuthTokenIden::Jti).count())
+            .from(AuthTokenIden::Table)
+            .and_where(Expr::col(AuthTokenIden::Jti).eq(jti))
+            .and_where(Expr::col(AuthTokenIden::Revoked).eq(false))
+            .to_string(SqliteQueryBuilder);
+
+        let count: (i64, ) = sqlx::query_as(sql.as_str())
+            .fetch_one(&db_conn.pool)
+            .await?;
+
+        Ok(count.0 > 0)
+    }
+
+    /// Revoke one session.
+    /// Return false if the session does not exist or is already revoked.
+    pub async fn revoke_token(db_conn: &DbConnection, jti: &str) -> Result<bool> {
+        let sql = Query::update()
+            .table(AuthTokenIden::Table)
+            .value(AuthTokenIden::Revoked, true)
+            .and_where(Expr::col(AuthTokenIden::Jti).eq(jti))
+            .and_where(Expr::col(AuthTokenIden::Revoked).eq(false))
+            .to_string(SqliteQueryBuilder);
+
+        let result = sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    /// Revoke all sessions of the given user.
+    /// Used when the user changed password.
+    pub async fn revoke_all_tokens_of_user(db_conn: &DbConnection, username: &str) -> Result<()> {
+        let sql = Query::update()
+            .table(AuthTokenIden::Table)
+            .value(AuthTokenIden::Revoked, true)
+            .and_where(Expr::col(AuthTokenIden::Username).eq(username))
+            .to_string(SqliteQueryBuilder);
+
+        sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Delete all sessions of the given user.
+    /// Used when deleting a user.
+    pub async fn delete_all_tokens_of_user(db_conn: &DbConnection, username: &str) -> Result<()> {
+        let sql = Query::delete()
+            .from_table(AuthTokenIden::Table)
+            .cond_where(Expr::col(AuthTokenIden::Username).eq(username))
+            .to_string(SqliteQueryBuilder);
+
+        sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Delete the sessions whose refresh token expired before the given timestamp.
+    /// Return the number of deleted sessions.
+    pub async fn delete_expired_tokens(db_conn: &DbConnection, timestamp: i64) -> Result<u64> {
+        let sql = Query::delete()
+            .from_table(AuthTokenIden::Table)
+            .cond_where(Expr::col(AuthTokenIden::ExpiresAt).lte(timestamp))
+            .to_string(SqliteQueryBuilder);
+
+        let result = sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use well_i_known_core::modal::user::UserRole;
+    use crate::db::db_test_util::create_test_db;
+    use crate::dao::user::UserTable;
+
+    #[tokio::test]
+    async fn test_revoke_token(){
+        let db_conn = create_test_db("test_revoke_token").await;
+        UserTable::create_user(&db_conn, "u_admin", &UserRole::Admin, "password").await.unwrap();
+        for (jti, expires_at) in [("jti_1", 200), ("jti_2", 300)] {
+            AuthTokenTable::add_token(&db_conn, &AuthToken {
+                jti: jti.to_string(),
+                username: "u_admin".to_string(),
+                refresh_token_hash: "hash".to_string(),
+                refresh_token_salt: "salt".to_string(),
+                created_at: 100,
+                expires_at,
+                revoked: false,
+            }).await.unwrap();
+        }
+        assert!(AuthTokenTable::check_token_active(&db_conn, "jti_1").await.unwrap());
+
+        assert!(AuthTokenTable::revoke_token(&db_conn, "jti_1").await.unwrap());
+        assert!(!AuthTokenTable::revoke_token(&db_conn, "jti_1").await.unwrap());
+        assert!(!AuthTokenTable::check_token_active(&db_conn, "jti_1").await.unwrap());
+        assert!(AuthTokenTable::check_token_active(&db_conn, "jti_2").await.unwrap());
+
+        AuthTokenTable::revoke_all_tokens_of_user(&db_conn, "u_admin").await.unwrap();
+        assert!(!AuthTokenTable::check_token_active(&db_conn, "jti_2").await.unwrap());
+
+        let deleted = AuthTokenTable::delete_expired_tokens(&db_conn, 250).await.unwrap();
+        assert_eq!(deleted, 1);
+        assert!(AuthTokenTable::get_token(&db_conn, "jti_2").await.unwrap().is_some());
+    }
+}
@@ -0,0 +1,109 @@
+use crate::db::{db_base::DbTable, db_connection::DbConnection};
+
+use sqlx::FromRow;
+use sea_query::{enum_def, ColumnDef, Order, Query, SqliteQueryBuilder, Table};
+use tracing::info;
+use anyhow::Result;
+
+/// Record of an emergency access to an app without a prior access right.
+#[enum_def]
+#[derive(Clone, FromRow, Debug)]
+pub struct BreakGlassEvent {
+    pub id: i64,
+    pub username: String,
+    pub app_name: String,
+    pub justification: String,
+    pub created_at: i64,        // UTC timestamp
+    pub expires_at: i64,        // UTC timestamp of the temporary access right expiry
+}
+
+const BREAK_GLASS_EVENT_COLUMNS: [BreakGlassEventIden; 6] = [
+    BreakGlassEventIden::Id,
+    BreakGlassEventIden::Username,
+    BreakGlassEventIden::AppName,
+    BreakGlassEventIden::Justification,
+    BreakGlassEventIden::CreatedAt,
+    BreakGlassEventIden::ExpiresAt,
+];
+
+pub struct BreakGlassEventTable {}
+impl DbTable for BreakGlassEventTable {
+    async fn create_table(db_conn: &DbConnection) {
+        info!("Creating table: {:?}", BreakGlassEventIden::Table);
+        // no foreign key to the user table, the records should be kept for review after the user is deleted
+        let sql = Table::create()
+            .table(BreakGlassEventIden::Table)
+            .if_not_exists()
+            .col(ColumnDef::new(BreakGlassEventIden::Id).integer().not_null().auto_increment().primary_key())
+            .col(ColumnDef::new(BreakGlassEventIden::Username).string().not_null())
+            .col(ColumnDef::new(BreakGlassEventIden::AppName).string().not_null())
+            .col(ColumnDef::new(BreakGlassEventIden::Justification).string().not_null())
+            .col(ColumnDef::new(BreakGlassEventIden::CreatedAt).big_integer().not_null())
+            .col(ColumnDef::new(BreakGlassEventIden::ExpiresAt).big_integer().not_null())
+            .to_string(SqliteQueryBuilder);
+
+        sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await.expect("Failed to create table");
+        // FATAL if failed to create table
+    }
+}
+
+impl BreakGlassEventTable {
+    /// Record a break-glass event.
+    pub async fn add_event(db_conn: &DbConnection, username: &str, app_name: &str, justification: &str,
+        created_at: i64, expires_at: i64) -> Result<()> {
+        let sql = Query::insert()
+            .into_table(BreakGlassEventIden::Table)
+            .columns(BREAK_GLASS_EVENT_COLUMNS[1..].to_vec())
+            .values([
+                username.into(),
+                app_name.into(),
+                justification.into(),
+                created_at.into(),
+                expires_at.into(),
+            ])?
+            .to_string(SqliteQueryBuilder);
+
+        sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Get all break-glass events, latest first.
+    pub async fn get_events(db_conn: &DbConnection) -> Result<Vec<BreakGlassEvent>> {
+        let sql = Query::select()
+            .columns(BREAK_GLASS_EVENT_COLUMNS)
+            .from(BreakGlassEventIden::Table)
+            .order_by(BreakGlassEventIden::Id, Order::Desc)
+            .to_string(SqliteQueryBuilder);
+
+        let events = sqlx::query_as::<_, BreakGlassEvent>(sql.as_str())
+            .fetch_all(&db_conn.pool)
+            .await?;
+
+        Ok(events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::db_test_util::create_test_db;
+
+    #[tokio::test]
+    async fn test_add_and_get_events(){
+        let db_conn = create_test_db("test_add_and_get_break_glass_events").await;
+
+        BreakGlassEventTable::add_event(&db_conn, "u_admin", "u_app", "incident #1", 100, 200).await.unwrap();
+        BreakGlassEventTable::add_event(&db_conn, "u_admin", "u_app2", "incident #2", 300, 400).await.unwrap();
+
+        let events = BreakGlassEventTable::get_events(&db_conn).await.unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].app_name, "u_app2");
+        assert_eq!(events[0].justification, "incident #2");
+        assert_eq!(events[1].expires_at, 200);
+    }
+}
@@ -1,22 +1,39 @@
 use sqlx::FromRow;
-use sea_query::{enum_def, ColumnDef, Expr, ForeignKey, ForeignKeyAction, Query, SqliteQueryBuilder, Table};
+use sea_query::{enum_def, ColumnDef, DynIden, Expr, ForeignKey, ForeignKeyAction, IntoIden, Query, SqliteQueryBuilder, Table};
 use tracing::info;
 use anyhow::Result;
 
+use std::collections::{BTreeSet, HashMap};
 use crate::db::{db_base::DbTable, db_connection::DbConnection};
+use crate::dao::config_data_expiry::{ConfigDataExpiry, ConfigDataExpiryIden, ConfigDataExpiryTable};
+use crate::dao::config_data_revision::{ConfigDataRevisionIden, ConfigDataRevisionTable};
+use crate::dao::config_plain_data::{ConfigPlainData, ConfigPlainDataIden, ConfigPlainDataTable};
+use crate::dao::config_data_version::{ConfigDataVersionIden, ConfigDataVersionTable};
+use crate::dao::config_plain_data_version::{ConfigPlainDataVersion, ConfigPlainDataVersionIden, ConfigPlainDataVersionTable};
 use crate::dao::user::UserIden;
 
 #[enum_def]
 #[derive(Clone, FromRow, Debug)]
 pub struct ConfigData {
     pub app_name: String,
+    pub env: String,        // environment of the app, e.g. dev / staging / prod
     pub key: String,
     pub owner: String,
     pub value: String,
 }
 
-const CONFIG_DATA_COLUMNS: [ConfigDataIden; 4] = [
+/// The changes of the config of an app, applied in one transaction.
+#[derive(Default)]
+pub struct ConfigDataChanges {
+    pub changed_keys: HashMap<String, Option<i64>>,     // the set / deleted key => the revision expected by the writer (not checked if none)
+    pub records: Vec<ConfigData>,                       // the new records (of all owners) of the secret keys set
+    pub plain_records: Vec<ConfigPlainData>,            // the new non-secret entries
+    pub expiries: Vec<ConfigDataExpiry>,                // the expiries of the keys set, the others do not expire
+}
+
+const CONFIG_DATA_COLUMNS: [ConfigDataIden; 5] = [
     ConfigDataIden::AppName,
+    ConfigDataIden::Env,
     ConfigDataIden::Key,
     ConfigDataIden::Owner,
     ConfigDataIden::Value,
@@ -30,11 +47,13 @@ impl DbTable for ConfigDataTable {
             .table(ConfigDataIden::Table)
             .if_not_exists()
             .col(ColumnDef::new(ConfigDataIden::AppName).string())
+            .col(ColumnDef::new(ConfigDataIden::Env).string())
             .col(ColumnDef::new(ConfigDataIden::Key).string())
             .col(ColumnDef::new(ConfigDataIden::Owner).string())
             .col(ColumnDef::new(ConfigDataIden::Value).string())
             .primary_key(sea_query::Index::create()
                 .col(ConfigDataIden::AppName)
+                .col(ConfigDataIden::Env)
                 .col(ConfigDataIden::Key)
                 .col(ConfigDataIden::Owner)
             )
@@ -60,11 +79,12 @@ impl DbTable for ConfigDataTable {
 
 impl ConfigDataTable {
     /// Get the encrypted value of the given key for the given owner.
-    pub async fn get_data_value(db_conn: &DbConnection, app_name: &str, owner: &str, key: &str) -> Result<Option<String>> {
+    pub async fn get_data_value(db_conn: &DbConnection, app_name: &str, env: &str, owner: &str, key: &str) -> Result<Option<String>> {
         let sql = Query::select()
             .column(ConfigDataIden::Value)
             .from(ConfigDataIden::Table)
             .and_where(Expr::col(ConfigDataIden::AppName).eq(app_name))
+            .and_where(Expr::col(ConfigDataIden::Env).eq(env))
             .and_where(Expr::col(ConfigDataIden::Key).eq(key))
             .and_where(Expr::col(ConfigDataIden::Owner).eq(owner))
             .to_string(SqliteQueryBuilder);
@@ -76,46 +96,101 @@ impl ConfigDataTable {
         Ok(data.map(|(value, )| value))
     }
 
-    /// Check if the records exists for the given 'app, key, owner' pair.
-    pub async fn check_data_exists(db_conn: &DbConnection, app_name: &str, owner: &str, key: &str) -> Result<bool> {
+    /// Get all the records of the given app stored for the given owner.
+    pub async fn get_all_app_data_of_owner(db_conn: &DbConnection, app_name: &str, env: &str, owner: &str) -> Result<Vec<ConfigData>> {
         let sql = Query::select()
-            .expr(Expr::col(ConfigDataIden::Key).count())
+            .columns(CONFIG_DATA_COLUMNS)
             .from(ConfigDataIden::Table)
             .and_where(Expr::col(ConfigDataIden::AppName).eq(app_name))
-            .and_where(Expr::col(ConfigDataIden::Key).eq(key))
+            .and_where(Expr::col(ConfigDataIden::Env).eq(env))
             .and_where(Expr::col(ConfigDataIden::Owner).eq(owner))
             .to_string(SqliteQueryBuilder);
 
-        let count: (i32, ) = sqlx::query_as(sql.as_str())
-            .fetch_one(&db_conn.pool)
+        let data = sqlx::query_as::<_, ConfigData>(sql.as_str())
+            .fetch_all(&db_conn.pool)
             .await?;
 
-        Ok(count.0 == 1)
+        Ok(data)
+    }
+
+    /// Get the records of the given keys of the given app stored for the given owner.
+    pub async fn get_app_data_of_owner_for_keys(db_conn: &DbConnection, app_name: &str, env: &str, owner: &str, keys: &[String]) -> Result<Vec<ConfigData>> {
+        let sql = Query::select()
+            .columns(CONFIG_DATA_COLUMNS)
+            .from(ConfigDataIden::Table)
+            .and_where(Expr::col(ConfigDataIden::AppName).eq(app_name))
+            .and_where(Expr::col(ConfigDataIden::Env).eq(env))
+            .and_where(Expr::col(ConfigDataIden::Owner).eq(owner))
+            .and_where(Expr::col(ConfigDataIden::Key).is_in(keys))
+            .to_string(SqliteQueryBuilder);
+
+        let data = sqlx::query_as::<_, ConfigData>(sql.as_str())
+            .fetch_all(&db_conn.pool)
+            .await?;
+
+        Ok(data)
     }
 
-    /// Check if the records exists for the given 'app, key, owner' pair.
-    pub async fn check_data_exists_for_key(db_conn: &DbConnection, app_name: &str, key: &str) -> Result<bool> {
+    /// Get the keys (without values) of the given app stored for the given owner.
+    pub async fn get_app_keys_of_owner(db_conn: &DbConnection, app_name: &str, env: &str, owner: &str) -> Result<Vec<String>> {
+        let sql = Query::select()
+            .column(ConfigDataIden::Key)
+            .from(ConfigDataIden::Table)
+            .and_where(Expr::col(ConfigDataIden::AppName).eq(app_name))
+            .and_where(Expr::col(ConfigDataIden::Env).eq(env))
+            .and_where(Expr::col(ConfigDataIden::Owner).eq(owner))
+            .to_string(SqliteQueryBuilder);
+
+        let keys: Vec<(String, )> = sqlx::query_as(sql.as_str())
+            .fetch_all(&db_conn.pool)
+            .await?;
+
+        Ok(keys.into_iter().map(|(key, )| key).collect())
+    }
+
+    /// Get the keys of the app in the environment stored for any owner.
+    pub async fn get_app_keys(db_conn: &DbConnection, app_name: &str, env: &str) -> Result<Vec<String>> {
+        let sql = Query::select()
+            .distinct()
+            .column(ConfigDataIden::Key)
+            .from(ConfigDataIden::Table)
+            .and_where(Expr::col(ConfigDataIden::AppName).eq(app_name))
+            .and_where(Expr::col(ConfigDataIden::Env).eq(env))
+            .to_string(SqliteQueryBuilder);
+
+        let keys: Vec<(String, )> = sqlx::query_as(sql.as_str())
+            .fetch_all(&db_conn.pool)
+            .await?;
+
+        Ok(keys.into_iter().map(|(key, )| key).collect())
+    }
+
+    /// Check if the records exists for the given 'app, env, key, owner' pair.
+    pub async fn check_data_exists(db_conn: &DbConnection, app_name: &str, env: &str, owner: &str, key: &str) -> Result<bool> {
         let sql = Query::select()
             .expr(Expr::col(ConfigDataIden::Key).count())
             .from(ConfigDataIden::Table)
             .and_where(Expr::col(ConfigDataIden::AppName).eq(app_name))
+            .and_where(Expr::col(ConfigDataIden::Env).eq(env))
             .and_where(Expr::col(ConfigDataIden::Key).eq(key))
+            .and_where(Expr::col(ConfigDataIden::Owner).eq(owner))
             .to_string(SqliteQueryBuilder);
 
         let count: (i32, ) = sqlx::query_as(sql.as_str())
             .fetch_one(&db_conn.pool)
             .await?;
 
-        Ok(count.0 > 0)
+        Ok(count.0 == 1)
     }
 
-    /// Set the data value for the given 'app, key, owner' pair.
-    pub async fn set_data_value(db_conn: &DbConnection, app_name: &str, owner: &str, key: &str, value: &str) -> Result<()> {
+    /// Set the data value for the given 'app, env, key, owner' pair.
+    pub async fn set_data_value(db_conn: &DbConnection, app_name: &str, env: &str, owner: &str, key: &str, value: &str) -> Result<()> {
         let sql = Query::insert()
             .into_table(ConfigDataIden::Table)
             .columns(CONFIG_DATA_COLUMNS)
             .values([
                 app_name.into(),
+                env.into(),
                 key.into(),
                 owner.into(),
                 value.into(),
@@ -129,14 +204,108 @@ impl ConfigDataTable {
         Ok(())
     }
 
-    /// Update the data value for the given 'app, key, owner' pair.
-    pub async fn update_data_value(db_conn: &DbConnection, app_name: &str, owner: &str, key: &str, value: &str) -> Result<()> {
+    /// Apply the changes of several keys of the given app in the environment in one transaction,
+    /// either all changes apply or none do.
+    /// All existing records (of all owners), non-secret entries and expiries of the changed / deleted keys are replaced by the new ones,
+    /// and a new version of each key set (secret or not) is kept in the history (at most `retention` versions per key).
+    /// Return the new revision of each changed key, or a RevisionConflict error (with nothing changed).
+    pub async fn apply_app_data_batch(db_conn: &DbConnection, app_name: &str, env: &str, changes: &ConfigDataChanges,
+        author: &str, created_at: i64, retention: i64) -> Result<HashMap<String, i64>> {
+        let changed_keys = &changes.changed_keys;
+        let records = &changes.records;
+        let mut transaction = db_conn.pool.begin().await?;
+
+        // the revisions are increased first, so the write lock is held before the revisions are checked
+        let mut revisions = HashMap::new();
+        for (key, expected_revision) in changed_keys {
+            let revision = ConfigDataRevisionTable::increase_revision(&mut transaction, app_name, env, key, *expected_revision).await?;
+            revisions.insert(key.clone(), revision);
+        }
+
+        if !changed_keys.is_empty() {
+            let sql = Query::delete()
+                .from_table(ConfigDataIden::Table)
+                .cond_where(Expr::col(ConfigDataIden::AppName).eq(app_name))
+                .cond_where(Expr::col(ConfigDataIden::Env).eq(env))
+                .cond_where(Expr::col(ConfigDataIden::Key).is_in(changed_keys.keys()))
+                .to_string(SqliteQueryBuilder);
+            sqlx::query(sql.as_str())
+                .execute(&mut *transaction)
+                .await?;
+
+            let changed_keys: Vec<&String> = changed_keys.keys().collect();
+            ConfigPlainDataTable::delete_keys(&mut transaction, app_name, env, &changed_keys).await?;
+            ConfigDataExpiryTable::delete_keys(&mut transaction, app_name, env, &changed_keys).await?;
+        }
+        for plain_record in &changes.plain_records {
+            ConfigPlainDataTable::add_data(&mut transaction, plain_record).await?;
+        }
+        for expiry in &changes.expiries {
+            ConfigDataExpiryTable::add_expiry(&mut transaction, expiry).await?;
+        }
+
+        let mut set_keys: Vec<&str> = Vec::new();
+        for record in records {
+            let sql = Query::insert()
+                .into_table(ConfigDataIden::Table)
+                .columns(CONFIG_DATA_COLUMNS)
+                .values([
+                    record.app_name.as_str().into(),
+                    record.env.as_str().into(),
+                    record.key.as_str().into(),
+                    record.owner.as_str().into(),
+                    record.value.as_str().into(),
+                ])?
+                .to_string(SqliteQueryBuilder);
+            sqlx::query(sql.as_str())
+                .execute(&mut *transaction)
+                .await?;
+
+            if !set_keys.contains(&record.key.as_str()) {
+                set_keys.push(&record.key);
+            }
+        }
+
+        for key in set_keys {
+            let owner_values: Vec<(&str, &str)> = records.iter()
+                .filter(|record| record.key == key)
+                .map(|record| (record.owner.as_str(), record.value.as_str()))
+                .collect();
+            let version = ConfigDataVersionTable::add_version(&mut transaction, app_name, env, key, author, created_at, &owner_values).await?;
+            ConfigDataVersionTable::drop_old_versions(&mut transaction, app_name, env, key, version, retention).await?;
+            ConfigPlainDataVersionTable::drop_old_versions(&mut transaction, app_name, env, key, version, retention).await?;
+        }
+        // the non-secret values are kept in the history once, as the entries
+        for plain_record in &changes.plain_records {
+            let key = plain_record.key.as_str();
+            let version = ConfigDataVersionTable::next_version(&mut transaction, app_name, env, key).await?;
+            ConfigPlainDataVersionTable::add_version(&mut transaction, &ConfigPlainDataVersion {
+                app_name: app_name.to_string(),
+                env: env.to_string(),
+                key: key.to_string(),
+                version,
+                value: plain_record.value.clone(),
+                author: author.to_string(),
+                created_at,
+            }).await?;
+            ConfigDataVersionTable::drop_old_versions(&mut transaction, app_name, env, key, version, retention).await?;
+            ConfigPlainDataVersionTable::drop_old_versions(&mut transaction, app_name, env, key, version, retention).await?;
+        }
+
+        // rolled back when dropped without commit (any error above)
+        transaction.commit().await?;
+        Ok(revisions)
+    }
+
+    /// Update the data value for the given 'app, env, key, owner' pair.
+    pub async fn update_data_value(db_conn: &DbConnection, app_name: &str, env: &str, owner: &str, key: &str, value: &str) -> Result<()> {
         let sql = Query::update()
             .table(ConfigDataIden::Table)
             .values([
                 (ConfigDataIden::Value, value.into())
             ])
             .and_where(Expr::col(ConfigDataIden::AppName).eq(app_name))
+            .and_where(Expr::col(ConfigDataIden::Env).eq(env))
             .and_where(Expr::col(ConfigDataIden::Key).eq(key))
             .and_where(Expr::col(ConfigDataIden::Owner).eq(owner))
             .to_string(SqliteQueryBuilder);
@@ -148,11 +317,12 @@ impl ConfigDataTable {
         Ok(())
     }
 
-    /// Delete the data for the given 'app, key, owner' pair.
-    pub async fn delete_data(db_conn: &DbConnection, app_name: &str, owner: &str, key: &str) -> Result<()> {
+    /// Delete the data for the given 'app, env, key, owner' pair.
+    pub async fn delete_data(db_conn: &DbConnection, app_name: &str, env: &str, owner: &str, key: &str) -> Result<()> {
         let sql = Query::delete()
             .from_table(ConfigDataIden::Table)
             .cond_where(Expr::col(ConfigDataIden::AppName).eq(app_name))
+            .cond_where(Expr::col(ConfigDataIden::Env).eq(env))
             .cond_where(Expr::col(ConfigDataIden::Key).eq(key))
             .cond_where(Expr::col(ConfigDataIden::Owner).eq(owner))
             .to_string(SqliteQueryBuilder);
@@ -164,13 +334,12 @@ impl ConfigDataTable {
         Ok(())
     }
 
-    /// Delete all data for the given 'app, key' pair.
-    /// Useful when deleting a key for an.
-    pub async fn delete_all_app_key_data(db_conn: &DbConnection, app_name: &str, key: &str) -> Result<()> {
+    /// Delete all data for the given 'app'.
+    /// Useful when deleting an app.
+    pub async fn delete_all_app_data(db_conn: &DbConnection, app_name: &str) -> Result<()> {
         let sql = Query::delete()
             .from_table(ConfigDataIden::Table)
             .cond_where(Expr::col(ConfigDataIden::AppName).eq(app_name))
-            .cond_where(Expr::col(ConfigDataIden::Key).eq(key))
             .to_string(SqliteQueryBuilder);
 
         sqlx::query(sql.as_str())
@@ -180,12 +349,14 @@ impl ConfigDataTable {
         Ok(())
     }
 
-    /// Delete all data for the given 'app'.
-    /// Useful when deleting an app.
-    pub async fn delete_all_app_data(db_conn: &DbConnection, app_name: &str) -> Result<()> {
+    /// Delete all data of the given 'app' stored for the given 'owner', except the data of the kept environments.
+    /// Useful when a user lose the access right to an app (or some of its environments).
+    pub async fn delete_all_app_data_for_owner(db_conn: &DbConnection, app_name: &str, owner: &str, kept_envs: &[String]) -> Result<()> {
         let sql = Query::delete()
             .from_table(ConfigDataIden::Table)
             .cond_where(Expr::col(ConfigDataIden::AppName).eq(app_name))
+            .cond_where(Expr::col(ConfigDataIden::Owner).eq(owner))
+            .cond_where(Expr::col(ConfigDataIden::Env).is_not_in(kept_envs))
             .to_string(SqliteQueryBuilder);
 
         sqlx::query(sql.as_str())
@@ -209,6 +380,76 @@ impl ConfigDataTable {
 
         Ok(())
     }
+
+    /// The tables of the config keys, as (table, app name, env, key) columns:
+    /// the data, the non-secret entries, their versions, the revisions and the expiries.
+    fn config_key_tables() -> [(DynIden, DynIden, DynIden, DynIden); 6] {
+        [
+            (ConfigDataIden::Table.into_iden(), ConfigDataIden::AppName.into_iden(), ConfigDataIden::Env.into_iden(), ConfigDataIden::Key.into_iden()),
+            (ConfigPlainDataIden::Table.into_iden(), ConfigPlainDataIden::AppName.into_iden(), ConfigPlainDataIden::Env.into_iden(), ConfigPlainDataIden::Key.into_iden()),
+            (ConfigDataVersionIden::Table.into_iden(), ConfigDataVersionIden::AppName.into_iden(), ConfigDataVersionIden::Env.into_iden(), ConfigDataVersionIden::Key.into_iden()),
+            (ConfigPlainDataVersionIden::Table.into_iden(), ConfigPlainDataVersionIden::AppName.into_iden(), ConfigPlainDataVersionIden::Env.into_iden(), ConfigPlainDataVersionIden::Key.into_iden()),
+            (ConfigDataRevisionIden::Table.into_iden(), ConfigDataRevisionIden::AppName.into_iden(), ConfigDataRevisionIden::Env.into_iden(), ConfigDataRevisionIden::Key.into_iden()),
+            (ConfigDataExpiryIden::Table.into_iden(), ConfigDataExpiryIden::AppName.into_iden(), ConfigDataExpiryIden::Env.into_iden(), ConfigDataExpiryIden::Key.into_iden()),
+        ]
+    }
+
+    /// Get the (app, env, key) of the keys containing the text, in any table of the config keys.
+    pub async fn get_keys_containing(db_conn: &DbConnection, text: &str) -> Result<BTreeSet<(String, String, String)>> {
+        let mut keys = BTreeSet::new();
+        for (table, app_name_col, env_col, key_col) in ConfigDataTable::config_key_tables() {
+            let sql = Query::select()
+                .distinct()
+                .columns([app_name_col, env_col, key_col.clone()])
+                .from(table)
+                .and_where(Expr::col(key_col).like(format!("%{}%", text)))
+                .to_string(SqliteQueryBuilder);
+            let rows: Vec<(String, String, String)> = sqlx::query_as(sql.as_str())
+                .fetch_all(&db_conn.pool)
+                .await?;
+            keys.extend(rows);
+        }
+        Ok(keys)
+    }
+
+    /// Check if the key is used by the environment of the app, in any table of the config keys (e.g. a deleted key with versions).
+    pub async fn check_key_used(db_conn: &DbConnection, app_name: &str, env: &str, key: &str) -> Result<bool> {
+        for (table, app_name_col, env_col, key_col) in ConfigDataTable::config_key_tables() {
+            let sql = Query::select()
+                .expr(Expr::col(key_col.clone()).count())
+                .from(table)
+                .and_where(Expr::col(app_name_col).eq(app_name))
+                .and_where(Expr::col(env_col).eq(env))
+                .and_where(Expr::col(key_col).eq(key))
+                .to_string(SqliteQueryBuilder);
+            let count: (i64, ) = sqlx::query_as(sql.as_str())
+                .fetch_one(&db_conn.pool)
+                .await?;
+            if count.0 > 0 {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Rename the key of the environment of the app in all tables of the config keys, in one transaction.
+    pub async fn rename_key(db_conn: &DbConnection, app_name: &str, env: &str, key: &str, new_key: &str) -> Result<()> {
+        let mut transaction = db_conn.pool.begin().await?;
+        for (table, app_name_col, env_col, key_col) in ConfigDataTable::config_key_tables() {
+            let sql = Query::update()
+                .table(table)
+                .values([(key_col.clone(), new_key.into())])
+                .and_where(Expr::col(app_name_col).eq(app_name))
+                .and_where(Expr::col(env_col).eq(env))
+                .and_where(Expr::col(key_col).eq(key))
+                .to_string(SqliteQueryBuilder);
+            sqlx::query(sql.as_str())
+                .execute(&mut *transaction)
+                .await?;
+        }
+        transaction.commit().await?;
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -216,6 +457,7 @@ mod tests {
     use well_i_known_core::modal::user::UserRole;
 
     use super::*;
+    use crate::dao::config_data_revision::RevisionConflict;
     use crate::dao::user::UserTable;
     use crate::db::db_test_util::*;
 
@@ -233,14 +475,14 @@ mod tests {
     async fn test_add_and_get_data(){
         let db_conn = create_config_data_test_db("test_add_and_get_data").await;
 
-        let exists = ConfigDataTable::check_data_exists(&db_conn, "u_app", "u_root", "test_key").await.unwrap();
+        let exists = ConfigDataTable::check_data_exists(&db_conn, "u_app", "prod", "u_root", "test_key").await.unwrap();
         assert_eq!(exists, false);
         
-        ConfigDataTable::set_data_value(&db_conn, "u_app", "u_root", "test_key", "test_value").await.unwrap();
+        ConfigDataTable::set_data_value(&db_conn, "u_app", "prod", "u_root", "test_key", "test_value").await.unwrap();
 
-        let exists = ConfigDataTable::check_data_exists(&db_conn, "u_app", "u_root", "test_key").await.unwrap();
+        let exists = ConfigDataTable::check_data_exists(&db_conn, "u_app", "prod", "u_root", "test_key").await.unwrap();
         assert_eq!(exists, true);
-        let value = ConfigDataTable::get_data_value(&db_conn, "u_app", "u_root", "test_key").await.unwrap();
+        let value = ConfigDataTable::get_data_value(&db_conn, "u_app", "prod", "u_root", "test_key").await.unwrap();
         assert_eq!(value, Some("test_value".to_string()));
     }
 
@@ -248,14 +490,14 @@ mod tests {
     async fn test_update_data(){
         let db_conn = create_config_data_test_db("test_update_data").await;
 
-        ConfigDataTable::set_data_value(&db_conn, "u_app", "u_root", "test_key", "test_value").await.unwrap();
+        ConfigDataTable::set_data_value(&db_conn, "u_app", "prod", "u_root", "test_key", "test_value").await.unwrap();
 
-        let value = ConfigDataTable::get_data_value(&db_conn, "u_app", "u_root", "test_key").await.unwrap().unwrap();
+        let value = ConfigDataTable::get_data_value(&db_conn, "u_app", "prod", "u_root", "test_key").await.unwrap().unwrap();
         assert_eq!(value, "test_value");
 
-        ConfigDataTable::update_data_value(&db_conn, "u_app", "u_root", "test_key", "new_value").await.unwrap();
+        ConfigDataTable::update_data_value(&db_conn, "u_app", "prod", "u_root", "test_key", "new_value").await.unwrap();
 
-        let value = ConfigDataTable::get_data_value(&db_conn, "u_app", "u_root", "test_key").await.unwrap().unwrap();
+        let value = ConfigDataTable::get_data_value(&db_conn, "u_app", "prod", "u_root", "test_key").await.unwrap().unwrap();
         assert_eq!(value, "new_value");
     }
 
@@ -263,14 +505,14 @@ mod tests {
     async fn test_delete_data(){
         let db_conn = create_config_data_test_db("test_delete_data").await;
 
-        ConfigDataTable::set_data_value(&db_conn, "u_app", "u_root", "test_key", "test_value").await.unwrap();
+        ConfigDataTable::set_data_value(&db_conn, "u_app", "prod", "u_root", "test_key", "test_value").await.unwrap();
 
-        let exists = ConfigDataTable::check_data_exists(&db_conn, "u_app", "u_root", "test_key").await.unwrap();
+        let exists = ConfigDataTable::check_data_exists(&db_conn, "u_app", "prod", "u_root", "test_key").await.unwrap();
         assert_eq!(exists, true);
 
-        ConfigDataTable::delete_data(&db_conn, "u_app", "u_root", "test_key").await.unwrap();
+        ConfigDataTable::delete_data(&db_conn, "u_app", "prod", "u_root", "test_key").await.unwrap();
 
-        let exists = ConfigDataTable::check_data_exists(&db_conn, "u_app", "u_root", "test_key").await.unwrap();
+        let exists = ConfigDataTable::check_data_exists(&db_conn, "u_app", "prod", "u_root", "test_key").await.unwrap();
         assert_eq!(exists, false);
     }
 
@@ -278,39 +520,160 @@ mod tests {
     async fn test_delete_all_app_data(){
         let db_conn = create_config_data_test_db("test_delete_all_app_data").await;
 
-        ConfigDataTable::set_data_value(&db_conn, "u_app", "u_root", "test_key", "test_value").await.unwrap();
-        ConfigDataTable::set_data_value(&db_conn, "u_app", "u_root", "test_key2", "test_value2").await.unwrap();
+        ConfigDataTable::set_data_value(&db_conn, "u_app", "prod", "u_root", "test_key", "test_value").await.unwrap();
+        ConfigDataTable::set_data_value(&db_conn, "u_app", "prod", "u_root", "test_key2", "test_value2").await.unwrap();
 
-        let exists = ConfigDataTable::check_data_exists(&db_conn, "u_app", "u_root", "test_key").await.unwrap();
+        let exists = ConfigDataTable::check_data_exists(&db_conn, "u_app", "prod", "u_root", "test_key").await.unwrap();
         assert_eq!(exists, true);
-        let exists = ConfigDataTable::check_data_exists(&db_conn, "u_app", "u_root", "test_key2").await.unwrap();
+        let exists = ConfigDataTable::check_data_exists(&db_conn, "u_app", "prod", "u_root", "test_key2").await.unwrap();
         assert_eq!(exists, true);
 
         ConfigDataTable::delete_all_app_data(&db_conn, "u_app").await.unwrap();
 
-        let exists = ConfigDataTable::check_data_exists(&db_conn, "u_app", "u_root", "test_key").await.unwrap();
+        let exists = ConfigDataTable::check_data_exists(&db_conn, "u_app", "prod", "u_root", "test_key").await.unwrap();
         assert_eq!(exists, false);
-        let exists = ConfigDataTable::check_data_exists(&db_conn, "u_app", "u_root", "test_key2").await.unwrap();
+        let exists = ConfigDataTable::check_data_exists(&db_conn, "u_app", "prod", "u_root", "test_key2").await.unwrap();
         assert_eq!(exists, false);
     }
 
+    #[tokio::test]
+    async fn test_get_and_delete_all_app_data_of_owner(){
+        let db_conn = create_config_data_test_db("test_get_and_delete_all_app_data_of_owner").await;
+
+        ConfigDataTable::set_data_value(&db_conn, "u_app", "prod", "u_root", "test_key", "test_value").await.unwrap();
+        ConfigDataTable::set_data_value(&db_conn, "u_app", "prod", "u_root", "test_key2", "test_value2").await.unwrap();
+        ConfigDataTable::set_data_value(&db_conn, "u_app", "prod", "u_admin", "test_key", "test_value").await.unwrap();
+        ConfigDataTable::set_data_value(&db_conn, "u_app", "dev", "u_admin", "test_key", "test_value").await.unwrap();
+
+        let data = ConfigDataTable::get_all_app_data_of_owner(&db_conn, "u_app", "prod", "u_root").await.unwrap();
+        assert_eq!(data.len(), 2);
+
+        // the data of the kept environments remains
+        ConfigDataTable::delete_all_app_data_for_owner(&db_conn, "u_app", "u_admin", &["dev".to_string()]).await.unwrap();
+
+        let data = ConfigDataTable::get_all_app_data_of_owner(&db_conn, "u_app", "prod", "u_admin").await.unwrap();
+        assert_eq!(data.len(), 0);
+        let data = ConfigDataTable::get_all_app_data_of_owner(&db_conn, "u_app", "dev", "u_admin").await.unwrap();
+        assert_eq!(data.len(), 1);
+        let data = ConfigDataTable::get_all_app_data_of_owner(&db_conn, "u_app", "prod", "u_root").await.unwrap();
+        assert_eq!(data.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_get_app_data_of_owner_for_keys(){
+        let db_conn = create_config_data_test_db("test_get_app_data_of_owner_for_keys").await;
+
+        ConfigDataTable::set_data_value(&db_conn, "u_app", "prod", "u_root", "test_key", "test_value").await.unwrap();
+        ConfigDataTable::set_data_value(&db_conn, "u_app", "prod", "u_root", "test_key2", "test_value2").await.unwrap();
+        ConfigDataTable::set_data_value(&db_conn, "u_app", "prod", "u_admin", "test_key2", "test_value2").await.unwrap();
+
+        let keys = vec!["test_key2".to_string(), "missing_key".to_string()];
+        let data = ConfigDataTable::get_app_data_of_owner_for_keys(&db_conn, "u_app", "prod", "u_root", &keys).await.unwrap();
+        assert_eq!(data.len(), 1);
+        assert_eq!(data[0].value, "test_value2");
+    }
+
+    #[tokio::test]
+    async fn test_apply_app_data_batch_atomically(){
+        let db_conn = create_config_data_test_db("test_apply_app_data_batch_atomically").await;
+        ConfigDataTable::set_data_value(&db_conn, "u_app", "prod", "u_root", "db_user", "old_user").await.unwrap();
+        ConfigDataTable::set_data_value(&db_conn, "u_app", "prod", "u_root", "db_password", "old_password").await.unwrap();
+        let record = |key: &str, value: &str| ConfigData {
+            app_name: "u_app".to_string(),
+            env: "prod".to_string(),
+            key: key.to_string(),
+            owner: "u_root".to_string(),
+            value: value.to_string(),
+        };
+        let changed_keys = HashMap::from([("db_user".to_string(), Some(0)), ("db_password".to_string(), None)]);
+        let changes = |records: Vec<ConfigData>| ConfigDataChanges {
+            changed_keys: changed_keys.clone(),
+            records,
+            plain_records: vec![],
+            expiries: vec![],
+        };
+
+        // the duplicated record fails the batch, nothing is changed
+        let records = vec![record("db_user", "new_user"), record("db_password", "new_password"), record("db_password", "new_password")];
+        let result = ConfigDataTable::apply_app_data_batch(&db_conn, "u_app", "prod", &changes(records), "u_root", 100, 10).await;
+        assert!(result.is_err());
+        let value = ConfigDataTable::get_data_value(&db_conn, "u_app", "prod", "u_root", "db_user").await.unwrap();
+        assert_eq!(value, Some("old_user".to_string()));
+
+        let records = vec![record("db_user", "new_user")];
+        let revisions = ConfigDataTable::apply_app_data_batch(&db_conn, "u_app", "prod", &changes(records), "u_root", 100, 10).await.unwrap();
+        assert_eq!(revisions["db_user"], 1);
+        let value = ConfigDataTable::get_data_value(&db_conn, "u_app", "prod", "u_root", "db_user").await.unwrap();
+        assert_eq!(value, Some("new_user".to_string()));
+        let value = ConfigDataTable::get_data_value(&db_conn, "u_app", "prod", "u_root", "db_password").await.unwrap();
+        assert_eq!(value, None);
+
+        // db_user is changed since revision 0, nothing is changed
+        let records = vec![record("db_user", "newer_user")];
+        let result = ConfigDataTable::apply_app_data_batch(&db_conn, "u_app", "prod", &changes(records), "u_root", 100, 10).await;
+        assert!(result.unwrap_err().downcast_ref::<RevisionConflict>().is_some());
+        let value = ConfigDataTable::get_data_value(&db_conn, "u_app", "prod", "u_root", "db_user").await.unwrap();
+        assert_eq!(value, Some("new_user".to_string()));
+
+        // db_user becomes a non-secret entry, the encrypted records are removed
+        let changes = ConfigDataChanges {
+            changed_keys: HashMap::from([("db_user".to_string(), None)]),
+            records: vec![],
+            plain_records: vec![ConfigPlainData {
+                app_name: "u_app".to_string(),
+                env: "prod".to_string(),
+                key: "db_user".to_string(),
+                value: "plain_user".to_string(),
+            }],
+            expiries: vec![ConfigDataExpiry {
+                app_name: "u_app".to_string(),
+                env: "prod".to_string(),
+                key: "db_user".to_string(),
+                expires_at: 200,
+                warned_lead_time: None,
+            }],
+        };
+        ConfigDataTable::apply_app_data_batch(&db_conn, "u_app", "prod", &changes, "u_root", 100, 10).await.unwrap();
+        let value = ConfigDataTable::get_data_value(&db_conn, "u_app", "prod", "u_root", "db_user").await.unwrap();
+        assert_eq!(value, None);
+        let value = ConfigPlainDataTable::get_value(&db_conn, "u_app", "prod", "db_user").await.unwrap();
+        assert_eq!(value, Some("plain_user".to_string()));
+        let expires_at = ConfigDataExpiryTable::get_expiry(&db_conn, "u_app", "prod", "db_user").await.unwrap();
+        assert_eq!(expires_at, Some(200));
+    }
+
+    #[tokio::test]
+    async fn test_get_app_keys_of_owner(){
+        let db_conn = create_config_data_test_db("test_get_app_keys_of_owner").await;
+
+        ConfigDataTable::set_data_value(&db_conn, "u_app", "prod", "u_root", "test_key", "test_value").await.unwrap();
+        ConfigDataTable::set_data_value(&db_conn, "u_app", "prod", "u_root", "test_key2", "test_value2").await.unwrap();
+        ConfigDataTable::set_data_value(&db_conn, "u_app", "prod", "u_admin", "test_key", "test_value").await.unwrap();
+
+        let mut keys = ConfigDataTable::get_app_keys_of_owner(&db_conn, "u_app", "prod", "u_root").await.unwrap();
+        keys.sort();
+        assert_eq!(keys, vec!["test_key".to_string(), "test_key2".to_string()]);
+        let keys = ConfigDataTable::get_app_keys_of_owner(&db_conn, "u_app", "prod", "u_admin").await.unwrap();
+        assert_eq!(keys, vec!["test_key".to_string()]);
+    }
+
     #[tokio::test]
     async fn test_delete_all_data_for_owner(){
         let db_conn = create_config_data_test_db("test_delete_all_data_for_owner").await;
 
-        ConfigDataTable::set_data_value(&db_conn, "u_app", "u_root", "test_key", "test_value").await.unwrap();
-        ConfigDataTable::set_data_value(&db_conn, "u_app", "u_root", "test_key2", "test_value2").await.unwrap();
+        ConfigDataTable::set_data_value(&db_conn, "u_app", "prod", "u_root", "test_key", "test_value").await.unwrap();
+        ConfigDataTable::set_data_value(&db_conn, "u_app", "prod", "u_root", "test_key2", "test_value2").await.unwrap();
 
-        let exists = ConfigDataTable::check_data_exists(&db_conn, "u_app", "u_root", "test_key").await.unwrap();
+        let exists = ConfigDataTable::check_data_exists(&db_conn, "u_app", "prod", "u_root", "test_key").await.unwrap();
         assert_eq!(exists, true);
-        let exists = ConfigDataTable::check_data_exists(&db_conn, "u_app", "u_root", "test_key2").await.unwrap();
+        let exists = ConfigDataTable::check_data_exists(&db_conn, "u_app", "prod", "u_root", "test_key2").await.unwrap();
         assert_eq!(exists, true);
 
         ConfigDataTable::delete_all_data_for_owner(&db_conn, "u_root").await.unwrap();
 
-        let exists = ConfigDataTable::check_data_exists(&db_conn, "u_app", "u_root", "test_key").await.unwrap();
+        let exists = ConfigDataTable::check_data_exists(&db_conn, "u_app", "prod", "u_root", "test_key").await.unwrap();
         assert_eq!(exists, false);
-        let exists = ConfigDataTable::check_data_exists(&db_conn, "u_app", "u_root", "test_key2").await.unwrap();
+        let exists = ConfigDataTable::check_data_exists(&db_conn, "u_app", "prod", "u_root", "test_key2").await.unwrap();
         assert_eq!(exists, false);
     }
 }
\ No newline at end of file
@@ -0,0 +1,207 @@
+use sqlx::{FromRow, SqliteConnection};
+use sea_query::{enum_def, ColumnDef, Expr, ForeignKey, ForeignKeyAction, Order, Query, SqliteQueryBuilder, Table};
+use tracing::info;
+use anyhow::Result;
+
+use crate::db::{db_base::DbTable, db_connection::DbConnection};
+use crate::dao::user::UserIden;
+
+/// The expiry of a config value (e.g. a certificate or a vendor API key), set with the value.
+/// It is removed when the key is set again or deleted.
+#[enum_def]
+#[derive(Clone, FromRow, Debug)]
+pub struct ConfigDataExpiry {
+    pub app_name: String,
+    pub env: String,
+    pub key: String,
+    pub expires_at: i64,                    // UTC timestamp
+    pub warned_lead_time: Option<i64>,      // shortest lead time (seconds before the expiry) warned of, 0 once warned of the expiry
+}
+
+const CONFIG_DATA_EXPIRY_COLUMNS: [ConfigDataExpiryIden; 5] = [
+    ConfigDataExpiryIden::AppName,
+    ConfigDataExpiryIden::Env,
+    ConfigDataExpiryIden::Key,
+    ConfigDataExpiryIden::ExpiresAt,
+    ConfigDataExpiryIden::WarnedLeadTime,
+];
+
+pub struct ConfigDataExpiryTable {}
+impl DbTable for ConfigDataExpiryTable {
+    async fn create_table(db_conn: &DbConnection) {
+        info!("Creating table: {:?}", ConfigDataExpiryIden::Table);
+        let sql = Table::create()
+            .table(ConfigDataExpiryIden::Table)
+            .if_not_exists()
+            .col(ColumnDef::new(ConfigDataExpiryIden::AppName).string())
+            .col(ColumnDef::new(ConfigDataExpiryIden::Env).string())
+            .col(ColumnDef::new(ConfigDataExpiryIden::Key).string())
+            .col(ColumnDef::new(ConfigDataExpiryIden::ExpiresAt).integer().not_null())
+            .col(ColumnDef::new(ConfigDataExpiryIden::WarnedLeadTime).integer())
+            .primary_key(sea_query::Index::create()
+                .col(ConfigDataExpiryIden::AppName)
+                .col(ConfigDataExpiryIden::Env)
+                .col(ConfigDataExpiryIden::Key)
+            )
+            .foreign_key(ForeignKey::create()
+                .from(ConfigDataExpiryIden::Table, ConfigDataExpiryIden::AppName)
+                .to(UserIden::Table, UserIden::Username)
+                .on_delete(ForeignKeyAction::Cascade)
+                .on_update(ForeignKeyAction::Cascade)
+            )
+            .to_string(SqliteQueryBuilder);
+
+        sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await.expect("Failed to create table config data expiry");
+    }
+}
+
+impl ConfigDataExpiryTable {
+    /// Get the expiry of the key, none if the key does not expire.
+    pub async fn get_expiry(db_conn: &DbConnection, app_name: &str, env: &str, key: &str) -> Result<Option<i64>> {
+        let sql = Query::select()
+            .column(ConfigDataExpiryIden::ExpiresAt)
+            .from(ConfigDataExpiryIden::Table)
+            .and_where(Expr::col(ConfigDataExpiryIden::AppName).eq(app_name))
+            .and_where(Expr::col(ConfigDataExpiryIden::Env).eq(env))
+            .and_where(Expr::col(ConfigDataExpiryIden::Key).eq(key))
+            .to_string(SqliteQueryBuilder);
+
+        let expires_at: Option<(i64, )> = sqlx::query_as(sql.as_str())
+            .fetch_optional(&db_conn.pool)
+            .await?;
+
+        Ok(expires_at.map(|(expires_at, )| expires_at))
+    }
+
+    /// Get the expiries of all the expiring keys of the app in the environment.
+    pub async fn get_app_expiries(db_conn: &DbConnection, app_name: &str, env: &str) -> Result<Vec<ConfigDataExpiry>> {
+        let sql = Query::select()
+            .columns(CONFIG_DATA_EXPIRY_COLUMNS)
+            .from(ConfigDataExpiryIden::Table)
+            .and_where(Expr::col(ConfigDataExpiryIden::AppName).eq(app_name))
+            .and_where(Expr::col(ConfigDataExpiryIden::Env).eq(env))
+            .to_string(SqliteQueryBuilder);
+
+        let expiries = sqlx::query_as::<_, ConfigDataExpiry>(sql.as_str())
+            .fetch_all(&db_conn.pool)
+            .await?;
+
+        Ok(expiries)
+    }
+
+    /// Get the keys (of all apps) expiring at or before the timestamp, including the expired ones, the earliest first.
+    pub async fn get_expiring(db_conn: &DbConnection, before: i64) -> Result<Vec<ConfigDataExpiry>> {
+        let sql = Query::select()
+            .columns(CONFIG_DATA_EXPIRY_COLUMNS)
+            .from(ConfigDataExpiryIden::Table)
+            .and_where(Expr::col(ConfigDataExpiryIden::ExpiresAt).lte(before))
+            .order_by(ConfigDataExpiryIden::ExpiresAt, Order::Asc)
+            .to_string(SqliteQueryBuilder);
+
+        let expiries = sqlx::query_as::<_, ConfigDataExpiry>(sql.as_str())
+            .fetch_all(&db_conn.pool)
+            .await?;
+
+        Ok(expiries)
+    }
+
+    /// Record the lead time the users are warned of, so each lead time is only warned once.
+    pub async fn set_warned_lead_time(db_conn: &DbConnection, app_name: &str, env: &str, key: &str, lead_time: i64) -> Result<()> {
+        let sql = Query::update()
+            .table(ConfigDataExpiryIden::Table)
+            .values([
+                (ConfigDataExpiryIden::WarnedLeadTime, lead_time.into())
+            ])
+            .and_where(Expr::col(ConfigDataExpiryIden::AppName).eq(app_name))
+            .and_where(Expr::col(ConfigDataExpiryIden::Env).eq(env))
+            .and_where(Expr::col(ConfigDataExpiryIden::Key).eq(key))
+            .to_string(SqliteQueryBuilder);
+
+        sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Delete the expiries of the keys, run within the transaction of the change.
+    pub async fn delete_keys(conn: &mut SqliteConnection, app_name: &str, env: &str, keys: &[&String]) -> Result<()> {
+        let sql = Query::delete()
+            .from_table(ConfigDataExpiryIden::Table)
+            .cond_where(Expr::col(ConfigDataExpiryIden::AppName).eq(app_name))
+            .cond_where(Expr::col(ConfigDataExpiryIden::Env).eq(env))
+            .cond_where(Expr::col(ConfigDataExpiryIden::Key).is_in(keys.iter().map(|key| key.as_str())))
+            .to_string(SqliteQueryBuilder);
+
+        sqlx::query(sql.as_str())
+            .execute(&mut *conn)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Add the expiry of a key (not warned yet), run within the transaction of the change.
+    pub async fn add_expiry(conn: &mut SqliteConnection, expiry: &ConfigDataExpiry) -> Result<()> {
+        let sql = Query::insert()
+            .into_table(ConfigDataExpiryIden::Table)
+            .columns(CONFIG_DATA_EXPIRY_COLUMNS)
+            .values([
+                expiry.app_name.as_str().into(),
+                expiry.env.as_str().into(),
+                expiry.key.as_str().into(),
+                expiry.expires_at.into(),
+                expiry.warned_lead_time.into(),
+            ])?
+            .to_string(SqliteQueryBuilder);
+
+        sqlx::query(sql.as_str())
+            .execute(&mut *conn)
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use well_i_known_core::modal::user::UserRole;
+
+    use super::*;
+    use crate::dao::user::UserTable;
+    use crate::db::db_test_util::*;
+
+    #[tokio::test]
+    async fn test_add_and_warn_expiry(){
+        let db_conn = create_test_db("test_add_and_warn_expiry").await;
+        UserTable::create_user(&db_conn, "u_app", &UserRole::App, "password").await.unwrap();
+
+        let mut conn = db_conn.pool.acquire().await.unwrap();
+        for (key, expires_at) in [("tls/cert", 200), ("vendor/api_key", 100)] {
+            ConfigDataExpiryTable::add_expiry(&mut conn, &ConfigDataExpiry {
+                app_name: "u_app".to_string(),
+                env: "prod".to_string(),
+                key: key.to_string(),
+                expires_at,
+                warned_lead_time: None,
+            }).await.unwrap();
+        }
+
+        let expiring = ConfigDataExpiryTable::get_expiring(&db_conn, 150).await.unwrap();
+        assert_eq!(expiring.len(), 1);
+        assert_eq!(expiring[0].key, "vendor/api_key");
+
+        ConfigDataExpiryTable::set_warned_lead_time(&db_conn, "u_app", "prod", "vendor/api_key", 60).await.unwrap();
+        let expiring = ConfigDataExpiryTable::get_expiring(&db_conn, 200).await.unwrap();
+        assert_eq!(expiring.len(), 2);
+        assert_eq!(expiring[0].warned_lead_time, Some(60));
+
+        let keys = ["vendor/api_key".to_string()];
+        ConfigDataExpiryTable::delete_keys(&mut conn, "u_app", "prod", &keys.iter().collect::<Vec<_>>()).await.unwrap();
+        let expires_at = ConfigDataExpiryTable::get_expiry(&db_conn, "u_app", "prod", "vendor/api_key").await.unwrap();
+        assert!(expires_at.is_none());
+        let expiries = ConfigDataExpiryTable::get_app_expiries(&db_conn, "u_app", "prod").await.unwrap();
+        assert_eq!(expiries.len(), 1);
+    }
+}
@@ -0,0 +1,187 @@
+use sqlx::{FromRow, SqliteConnection};
+use sea_query::{ColumnDef, Expr, ForeignKey, ForeignKeyAction, Iden, OnConflict, Query, SqliteQueryBuilder, Table};
+use tracing::info;
+use anyhow::Result;
+
+use std::fmt;
+use crate::db::{db_base::DbTable, db_connection::DbConnection};
+use crate::dao::user::UserIden;
+
+/// The revision of a config key, increased on every change (set or delete) of the key.
+/// The revision is kept after the key is deleted, so it never goes back.
+/// A key never changed is at revision 0 (no record).
+#[derive(Iden, Debug, Clone, Copy)]
+pub enum ConfigDataRevisionIden {
+    #[iden = "config_data_revision"]
+    Table,
+    AppName,
+    Env,
+    Key,
+    Revision,
+}
+
+/// The revision of a key, read within the app and the environment.
+#[derive(Clone, FromRow, Debug)]
+pub struct ConfigDataRevision {
+    pub key: String,
+    pub revision: i64,
+}
+
+const CONFIG_DATA_REVISION_COLUMNS: [ConfigDataRevisionIden; 4] = [
+    ConfigDataRevisionIden::AppName,
+    ConfigDataRevisionIden::Env,
+    ConfigDataRevisionIden::Key,
+    ConfigDataRevisionIden::Revision,
+];
+
+/// The key is changed since the revision expected by the writer.
+#[derive(Debug)]
+pub struct RevisionConflict {
+    pub key: String,
+    pub expected: i64,
+    pub current: i64,
+}
+
+impl fmt::Display for RevisionConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "key '{}' is at revision {}, expected {}", self.key, self.current, self.expected)
+    }
+}
+
+impl std::error::Error for RevisionConflict {}
+
+pub struct ConfigDataRevisionTable {}
+impl DbTable for ConfigDataRevisionTable {
+    async fn create_table(db_conn: &DbConnection) {
+        info!("Creating table: {:?}", ConfigDataRevisionIden::Table);
+        let sql = Table::create()
+            .table(ConfigDataRevisionIden::Table)
+            .if_not_exists()
+            .col(ColumnDef::new(ConfigDataRevisionIden::AppName).string())
+            .col(ColumnDef::new(ConfigDataRevisionIden::Env).string())
+            .col(ColumnDef::new(ConfigDataRevisionIden::Key).string())
+            .col(ColumnDef::new(ConfigDataRevisionIden::Revision).big_integer().not_null())
+            .primary_key(sea_query::Index::create()
+                .col(ConfigDataRevisionIden::AppName)
+                .col(ConfigDataRevisionIden::Env)
+                .col(ConfigDataRevisionIden::Key)
+            )
+            .foreign_key(ForeignKey::create()
+                .from(ConfigDataRevisionIden::Table, ConfigDataRevisionIden::AppName)
+                .to(UserIden::Table, UserIden::Username)
+                .on_delete(ForeignKeyAction::Cascade)
+                .on_update(ForeignKeyAction::Cascade)
+            )
+            .to_string(SqliteQueryBuilder);
+
+        sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await.expect("Failed to create table config data revision");
+    }
+}
+
+impl ConfigDataRevisionTable {
+    /// Increase the revision of the key, and check it against the revision expected by the writer (if any).
+    /// Run within the transaction of the change. The update takes the write lock first,
+    /// so a concurrent writer only reads the revision after this change is committed.
+    /// Return the new revision, or a RevisionConflict error if the key is changed since the expected revision.
+    pub async fn increase_revision(conn: &mut SqliteConnection, app_name: &str, env: &str, key: &str,
+        expected_revision: Option<i64>) -> Result<i64> {
+        let sql = Query::insert()
+            .into_table(ConfigDataRevisionIden::Table)
+            .columns(CONFIG_DATA_REVISION_COLUMNS)
+            .values([
+                app_name.into(),
+                env.into(),
+                key.into(),
+                1.into(),
+            ])?
+            .on_conflict(
+                OnConflict::columns([ConfigDataRevisionIden::AppName, ConfigDataRevisionIden::Env, ConfigDataRevisionIden::Key])
+                    .value(ConfigDataRevisionIden::Revision, Expr::col(ConfigDataRevisionIden::Revision).add(1))
+                    .to_owned()
+            )
+            .returning_col(ConfigDataRevisionIden::Revision)
+            .to_string(SqliteQueryBuilder);
+
+        let (revision, ): (i64, ) = sqlx::query_as(sql.as_str())
+            .fetch_one(&mut *conn)
+            .await?;
+
+        if let Some(expected) = expected_revision {
+            if expected != revision - 1 {
+                return Err(RevisionConflict {
+                    key: key.to_string(),
+                    expected,
+                    current: revision - 1,
+                }.into());
+            }
+        }
+
+        Ok(revision)
+    }
+
+    /// Get the revision of the key, 0 if the key is never changed.
+    pub async fn get_revision(db_conn: &DbConnection, app_name: &str, env: &str, key: &str) -> Result<i64> {
+        let sql = Query::select()
+            .column(ConfigDataRevisionIden::Revision)
+            .from(ConfigDataRevisionIden::Table)
+            .and_where(Expr::col(ConfigDataRevisionIden::AppName).eq(app_name))
+            .and_where(Expr::col(ConfigDataRevisionIden::Env).eq(env))
+            .and_where(Expr::col(ConfigDataRevisionIden::Key).eq(key))
+            .to_string(SqliteQueryBuilder);
+
+        let revision: Option<(i64, )> = sqlx::query_as(sql.as_str())
+            .fetch_optional(&db_conn.pool)
+            .await?;
+
+        Ok(revision.map(|(revision, )| revision).unwrap_or(0))
+    }
+
+    /// Get the revisions of all keys of the app in the environment ever changed (including the deleted keys).
+    pub async fn get_app_revisions(db_conn: &DbConnection, app_name: &str, env: &str) -> Result<Vec<ConfigDataRevision>> {
+        let sql = Query::select()
+            .columns([ConfigDataRevisionIden::Key, ConfigDataRevisionIden::Revision])
+            .from(ConfigDataRevisionIden::Table)
+            .and_where(Expr::col(ConfigDataRevisionIden::AppName).eq(app_name))
+            .and_where(Expr::col(ConfigDataRevisionIden::Env).eq(env))
+            .to_string(SqliteQueryBuilder);
+
+        let revisions = sqlx::query_as::<_, ConfigDataRevision>(sql.as_str())
+            .fetch_all(&db_conn.pool)
+            .await?;
+
+        Ok(revisions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use well_i_known_core::modal::user::UserRole;
+
+    use super::*;
+    use crate::dao::user::UserTable;
+    use crate::db::db_test_util::*;
+
+    #[tokio::test]
+    async fn test_increase_revision(){
+        let db_conn = create_test_db("test_increase_revision").await;
+        UserTable::create_user(&db_conn, "u_app", &UserRole::App, "password").await.unwrap();
+        assert_eq!(ConfigDataRevisionTable::get_revision(&db_conn, "u_app", "prod", "db_url").await.unwrap(), 0);
+
+        let mut conn = db_conn.pool.acquire().await.unwrap();
+        let revision = ConfigDataRevisionTable::increase_revision(&mut conn, "u_app", "prod", "db_url", Some(0)).await.unwrap();
+        assert_eq!(revision, 1);
+        let revision = ConfigDataRevisionTable::increase_revision(&mut conn, "u_app", "prod", "db_url", None).await.unwrap();
+        assert_eq!(revision, 2);
+
+        // the key is changed since revision 1
+        let error = ConfigDataRevisionTable::increase_revision(&mut conn, "u_app", "prod", "db_url", Some(1)).await.unwrap_err();
+        let conflict = error.downcast_ref::<RevisionConflict>().unwrap();
+        assert_eq!(conflict.current, 2);
+
+        // the revisions of each environment are separate
+        let revision = ConfigDataRevisionTable::increase_revision(&mut conn, "u_app", "dev", "db_url", Some(0)).await.unwrap();
+        assert_eq!(revision, 1);
+    }
+}
@@ -0,0 +1,272 @@
+use sqlx::{FromRow, SqliteConnection};
+use sea_query::{enum_def, ColumnDef, Expr, ForeignKey, ForeignKeyAction, Order, Query, SqliteQueryBuilder, Table};
+use tracing::info;
+use anyhow::Result;
+
+use crate::db::{db_base::DbTable, db_connection::DbConnection};
+use crate::dao::config_plain_data_version::ConfigPlainDataVersionTable;
+use crate::dao::user::UserIden;
+
+/// A previous (or the current) value of a config key, encrypted for each owner.
+/// The versions of a key are numbered from 1.
+#[enum_def]
+#[derive(Clone, FromRow, Debug)]
+pub struct ConfigDataVersion {
+    pub app_name: String,
+    pub env: String,
+    pub key: String,
+    pub version: i64,
+    pub owner: String,
+    pub value: String,
+    pub author: String,
+    pub created_at: i64,    // UTC timestamp
+}
+
+const CONFIG_DATA_VERSION_COLUMNS: [ConfigDataVersionIden; 8] = [
+    ConfigDataVersionIden::AppName,
+    ConfigDataVersionIden::Env,
+    ConfigDataVersionIden::Key,
+    ConfigDataVersionIden::Version,
+    ConfigDataVersionIden::Owner,
+    ConfigDataVersionIden::Value,
+    ConfigDataVersionIden::Author,
+    ConfigDataVersionIden::CreatedAt,
+];
+
+pub struct ConfigDataVersionTable {}
+impl DbTable for ConfigDataVersionTable {
+    async fn create_table(db_conn: &DbConnection) {
+        info!("Creating table: {:?}", ConfigDataVersionIden::Table);
+        let sql = Table::create()
+            .table(ConfigDataVersionIden::Table)
+            .if_not_exists()
+            .col(ColumnDef::new(ConfigDataVersionIden::AppName).string())
+            .col(ColumnDef::new(ConfigDataVersionIden::Env).string())
+            .col(ColumnDef::new(ConfigDataVersionIden::Key).string())
+            .col(ColumnDef::new(ConfigDataVersionIden::Version).big_integer())
+            .col(ColumnDef::new(ConfigDataVersionIden::Owner).string())
+            .col(ColumnDef::new(ConfigDataVersionIden::Value).string().not_null())
+            .col(ColumnDef::new(ConfigDataVersionIden::Author).string().not_null())
+            .col(ColumnDef::new(ConfigDataVersionIden::CreatedAt).big_integer().not_null())
+            .primary_key(sea_query::Index::create()
+                .col(ConfigDataVersionIden::AppName)
+                .col(ConfigDataVersionIden::Env)
+                .col(ConfigDataVersionIden::Key)
+                .col(ConfigDataVersionIden::Version)
+                .col(ConfigDataVersionIden::Owner)
+            )
+            .foreign_key(ForeignKey::create()
+                .from(ConfigDataVersionIden::Table, ConfigDataVersionIden::AppName)
+                .to(UserIden::Table, UserIden::Username)
+                .on_delete(ForeignKeyAction::Cascade)
+                .on_update(ForeignKeyAction::Cascade)
+            )
+            .foreign_key(ForeignKey::create()
+                .from(ConfigDataVersionIden::Table, ConfigDataVersionIden::Owner)
+                .to(UserIden::Table, UserIden::Username)
+                .on_delete(ForeignKeyAction::Cascade)
+                .on_update(ForeignKeyAction::Cascade)
+            )
+            .to_string(SqliteQueryBuilder);
+
+        sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await.expect("Failed to create table config data version");
+    }
+}
+
+impl ConfigDataVersionTable {
+    /// Get the number of the next version of the key, after the latest secret or non-secret version.
+    pub async fn next_version(conn: &mut SqliteConnection, app_name: &str, env: &str, key: &str) -> Result<i64> {
+        let sql = Query::select()
+            .expr(Expr::col(ConfigDataVersionIden::Version).max())
+            .from(ConfigDataVersionIden::Table)
+            .and_where(Expr::col(ConfigDataVersionIden::AppName).eq(app_name))
+            .and_where(Expr::col(ConfigDataVersionIden::Env).eq(env))
+            .and_where(Expr::col(ConfigDataVersionIden::Key).eq(key))
+            .to_string(SqliteQueryBuilder);
+        let latest: (Option<i64>, ) = sqlx::query_as(sql.as_str())
+            .fetch_one(&mut *conn)
+            .await?;
+        let latest_plain = ConfigPlainDataVersionTable::get_latest_version(conn, app_name, env, key).await?;
+
+        Ok(latest.0.max(latest_plain).unwrap_or(0) + 1)
+    }
+
+    /// Record a new version of the key with its value encrypted for each owner.
+    /// Run within the transaction of the change, so the history never diverges from the data.
+    /// Return the new version number.
+    pub async fn add_version(conn: &mut SqliteConnection, app_name: &str, env: &str, key: &str, author: &str, created_at: i64,
+        owner_values: &[(&str, &str)]) -> Result<i64> {
+        let version = ConfigDataVersionTable::next_version(conn, app_name, env, key).await?;
+
+        for (owner, value) in owner_values {
+            let sql = Query::insert()
+                .into_table(ConfigDataVersionIden::Table)
+                .columns(CONFIG_DATA_VERSION_COLUMNS)
+                .values([
+                    app_name.into(),
+                    env.into(),
+                    key.into(),
+                    version.into(),
+                    (*owner).into(),
+                    (*value).into(),
+                    author.into(),
+                    created_at.into(),
+                ])?
+                .to_string(SqliteQueryBuilder);
+            sqlx::query(sql.as_str())
+                .execute(&mut *conn)
+                .await?;
+        }
+
+        Ok(version)
+    }
+
+    /// Drop the versions of the key older than the latest `retention` ones.
+    pub async fn drop_old_versions(conn: &mut SqliteConnection, app_name: &str, env: &str, key: &str,
+        latest_version: i64, retention: i64) -> Result<()> {
+        let sql = Query::delete()
+            .from_table(ConfigDataVersionIden::Table)
+            .cond_where(Expr::col(ConfigDataVersionIden::AppName).eq(app_name))
+            .cond_where(Expr::col(ConfigDataVersionIden::Env).eq(env))
+            .cond_where(Expr::col(ConfigDataVersionIden::Key).eq(key))
+            .cond_where(Expr::col(ConfigDataVersionIden::Version).lte(latest_version - retention))
+            .to_string(SqliteQueryBuilder);
+        sqlx::query(sql.as_str())
+            .execute(&mut *conn)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Add the copy of an existing version for another owner.
+    pub async fn add_version_copy(db_conn: &DbConnection, version: &ConfigDataVersion) -> Result<()> {
+        let sql = Query::insert()
+            .into_table(ConfigDataVersionIden::Table)
+            .columns(CONFIG_DATA_VERSION_COLUMNS)
+            .values([
+                version.app_name.as_str().into(),
+                version.env.as_str().into(),
+                version.key.as_str().into(),
+                version.version.into(),
+                version.owner.as_str().into(),
+                version.value.as_str().into(),
+                version.author.as_str().into(),
+                version.created_at.into(),
+            ])?
+            .to_string(SqliteQueryBuilder);
+
+        sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Get the versions of the key stored for the owner, latest first.
+    pub async fn get_versions(db_conn: &DbConnection, app_name: &str, env: &str, key: &str, owner: &str) -> Result<Vec<ConfigDataVersion>> {
+        let sql = Query::select()
+            .columns(CONFIG_DATA_VERSION_COLUMNS)
+            .from(ConfigDataVersionIden::Table)
+            .and_where(Expr::col(ConfigDataVersionIden::AppName).eq(app_name))
+            .and_where(Expr::col(ConfigDataVersionIden::Env).eq(env))
+            .and_where(Expr::col(ConfigDataVersionIden::Key).eq(key))
+            .and_where(Expr::col(ConfigDataVersionIden::Owner).eq(owner))
+            .order_by(ConfigDataVersionIden::Version, Order::Desc)
+            .to_string(SqliteQueryBuilder);
+
+        let versions = sqlx::query_as::<_, ConfigDataVersion>(sql.as_str())
+            .fetch_all(&db_conn.pool)
+            .await?;
+
+        Ok(versions)
+    }
+
+    /// Get all versions of all keys of the app in the environment stored for the owner.
+    pub async fn get_all_app_versions_of_owner(db_conn: &DbConnection, app_name: &str, env: &str, owner: &str) -> Result<Vec<ConfigDataVersion>> {
+        let sql = Query::select()
+            .columns(CONFIG_DATA_VERSION_COLUMNS)
+            .from(ConfigDataVersionIden::Table)
+            .and_where(Expr::col(ConfigDataVersionIden::AppName).eq(app_name))
+            .and_where(Expr::col(ConfigDataVersionIden::Env).eq(env))
+            .and_where(Expr::col(ConfigDataVersionIden::Owner).eq(owner))
+            .to_string(SqliteQueryBuilder);
+
+        let versions = sqlx::query_as::<_, ConfigDataVersion>(sql.as_str())
+            .fetch_all(&db_conn.pool)
+            .await?;
+
+        Ok(versions)
+    }
+
+    /// Get one version of the key stored for the owner.
+    pub async fn get_version(db_conn: &DbConnection, app_name: &str, env: &str, key: &str, version: i64, owner: &str) -> Result<Option<ConfigDataVersion>> {
+        let sql = Query::select()
+            .columns(CONFIG_DATA_VERSION_COLUMNS)
+            .from(ConfigDataVersionIden::Table)
+            .and_where(Expr::col(ConfigDataVersionIden::AppName).eq(app_name))
+            .and_where(Expr::col(ConfigDataVersionIden::Env).eq(env))
+            .and_where(Expr::col(ConfigDataVersionIden::Key).eq(key))
+            .and_where(Expr::col(ConfigDataVersionIden::Version).eq(version))
+            .and_where(Expr::col(ConfigDataVersionIden::Owner).eq(owner))
+            .to_string(SqliteQueryBuilder);
+
+        let version = sqlx::query_as::<_, ConfigDataVersion>(sql.as_str())
+            .fetch_optional(&db_conn.pool)
+            .await?;
+
+        Ok(version)
+    }
+
+    /// Delete all versions of the app stored for the owner, except the ones of the kept environments.
+    pub async fn delete_all_app_versions_for_owner(db_conn: &DbConnection, app_name: &str, owner: &str, kept_envs: &[String]) -> Result<()> {
+        let sql = Query::delete()
+            .from_table(ConfigDataVersionIden::Table)
+            .cond_where(Expr::col(ConfigDataVersionIden::AppName).eq(app_name))
+            .cond_where(Expr::col(ConfigDataVersionIden::Owner).eq(owner))
+            .cond_where(Expr::col(ConfigDataVersionIden::Env).is_not_in(kept_envs))
+            .to_string(SqliteQueryBuilder);
+
+        sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use well_i_known_core::modal::user::UserRole;
+
+    use super::*;
+    use crate::dao::user::UserTable;
+    use crate::db::db_test_util::*;
+
+    #[tokio::test]
+    async fn test_add_version_with_retention(){
+        let db_conn = create_test_db("test_add_version_with_retention").await;
+        UserTable::create_user(&db_conn, "u_root", &UserRole::Root, "password").await.unwrap();
+        UserTable::create_user(&db_conn, "u_app", &UserRole::App, "password").await.unwrap();
+
+        let mut conn = db_conn.pool.acquire().await.unwrap();
+        for value in ["v1", "v2", "v3"] {
+            let owner_values = [("u_root", value), ("u_app", value)];
+            let version = ConfigDataVersionTable::add_version(&mut conn, "u_app", "prod", "db_url", "u_root", 100, &owner_values).await.unwrap();
+            ConfigDataVersionTable::drop_old_versions(&mut conn, "u_app", "prod", "db_url", version, 2).await.unwrap();
+        }
+
+        // only the latest 2 versions are kept
+        let versions = ConfigDataVersionTable::get_versions(&db_conn, "u_app", "prod", "db_url", "u_app").await.unwrap();
+        assert_eq!(versions.len(), 2);
+        assert_eq!(versions[0].version, 3);
+        assert_eq!(versions[1].value, "v2");
+
+        ConfigDataVersionTable::delete_all_app_versions_for_owner(&db_conn, "u_app", "u_app", &[]).await.unwrap();
+        let version = ConfigDataVersionTable::get_version(&db_conn, "u_app", "prod", "db_url", 3, "u_app").await.unwrap();
+        assert!(version.is_none());
+        let version = ConfigDataVersionTable::get_version(&db_conn, "u_app", "prod", "db_url", 3, "u_root").await.unwrap();
+        assert!(version.is_some());
+    }
+}
@@ -0,0 +1,143 @@
+use sqlx::{FromRow, SqliteConnection};
+use sea_query::{enum_def, ColumnDef, Expr, ForeignKey, ForeignKeyAction, Query, SqliteQueryBuilder, Table};
+use tracing::info;
+use anyhow::Result;
+
+use crate::db::{db_base::DbTable, db_connection::DbConnection};
+use crate::dao::user::UserIden;
+
+/// A non-secret config value, stored once in plaintext (not per recipient).
+/// A key of an app (in an environment) is either a non-secret entry here, or a secret in the config data.
+#[enum_def]
+#[derive(Clone, FromRow, Debug)]
+pub struct ConfigPlainData {
+    pub app_name: String,
+    pub env: String,
+    pub key: String,
+    pub value: String,
+}
+
+const CONFIG_PLAIN_DATA_COLUMNS: [ConfigPlainDataIden; 4] = [
+    ConfigPlainDataIden::AppName,
+    ConfigPlainDataIden::Env,
+    ConfigPlainDataIden::Key,
+    ConfigPlainDataIden::Value,
+];
+
+pub struct ConfigPlainDataTable {}
+impl DbTable for ConfigPlainDataTable {
+    async fn create_table(db_conn: &DbConnection) {
+        info!("Creating table: {:?}", ConfigPlainDataIden::Table);
+        let sql = Table::create()
+            .table(ConfigPlainDataIden::Table)
+            .if_not_exists()
+            .col(ColumnDef::new(ConfigPlainDataIden::AppName).string())
+            .col(ColumnDef::new(ConfigPlainDataIden::Env).string())
+            .col(ColumnDef::new(ConfigPlainDataIden::Key).string())
+            .col(ColumnDef::new(ConfigPlainDataIden::Value).string().not_null())
+            .primary_key(sea_query::Index::create()
+                .col(ConfigPlainDataIden::AppName)
+                .col(ConfigPlainDataIden::Env)
+                .col(ConfigPlainDataIden::Key)
+            )
+            .foreign_key(ForeignKey::create()
+                .from(ConfigPlainDataIden::Table, ConfigPlainDataIden::AppName)
+                .to(UserIden::Table, UserIden::Username)
+                .on_delete(ForeignKeyAction::Cascade)
+                .on_update(ForeignKeyAction::Cascade)
+            )
+            .to_string(SqliteQueryBuilder);
+
+        sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await.expect("Failed to create table config plain data");
+    }
+}
+
+impl ConfigPlainDataTable {
+    /// Get the value of the non-secret key, none if the key is not a non-secret entry.
+    pub async fn get_value(db_conn: &DbConnection, app_name: &str, env: &str, key: &str) -> Result<Option<String>> {
+        let sql = Query::select()
+            .column(ConfigPlainDataIden::Value)
+            .from(ConfigPlainDataIden::Table)
+            .and_where(Expr::col(ConfigPlainDataIden::AppName).eq(app_name))
+            .and_where(Expr::col(ConfigPlainDataIden::Env).eq(env))
+            .and_where(Expr::col(ConfigPlainDataIden::Key).eq(key))
+            .to_string(SqliteQueryBuilder);
+
+        let value: Option<(String, )> = sqlx::query_as(sql.as_str())
+            .fetch_optional(&db_conn.pool)
+            .await?;
+
+        Ok(value.map(|(value, )| value))
+    }
+
+    /// Get all non-secret entries of the app in the environment.
+    pub async fn get_app_data(db_conn: &DbConnection, app_name: &str, env: &str) -> Result<Vec<ConfigPlainData>> {
+        let sql = Query::select()
+            .columns(CONFIG_PLAIN_DATA_COLUMNS)
+            .from(ConfigPlainDataIden::Table)
+            .and_where(Expr::col(ConfigPlainDataIden::AppName).eq(app_name))
+            .and_where(Expr::col(ConfigPlainDataIden::Env).eq(env))
+            .to_string(SqliteQueryBuilder);
+
+        let data = sqlx::query_as::<_, ConfigPlainData>(sql.as_str())
+            .fetch_all(&db_conn.pool)
+            .await?;
+
+        Ok(data)
+    }
+
+    /// Get the non-secret entries of the app in the environment of the given keys, the keys not found are omitted.
+    pub async fn get_app_data_for_keys(db_conn: &DbConnection, app_name: &str, env: &str, keys: &[String]) -> Result<Vec<ConfigPlainData>> {
+        let sql = Query::select()
+            .columns(CONFIG_PLAIN_DATA_COLUMNS)
+            .from(ConfigPlainDataIden::Table)
+            .and_where(Expr::col(ConfigPlainDataIden::AppName).eq(app_name))
+            .and_where(Expr::col(ConfigPlainDataIden::Env).eq(env))
+            .and_where(Expr::col(ConfigPlainDataIden::Key).is_in(keys))
+            .to_string(SqliteQueryBuilder);
+
+        let data = sqlx::query_as::<_, ConfigPlainData>(sql.as_str())
+            .fetch_all(&db_conn.pool)
+            .await?;
+
+        Ok(data)
+    }
+
+    /// Delete the non-secret entries of the keys, run within the transaction of the change.
+    pub async fn delete_keys(conn: &mut SqliteConnection, app_name: &str, env: &str, keys: &[&String]) -> Result<()> {
+        let sql = Query::delete()
+            .from_table(ConfigPlainDataIden::Table)
+            .cond_where(Expr::col(ConfigPlainDataIden::AppName).eq(app_name))
+            .cond_where(Expr::col(ConfigPlainDataIden::Env).eq(env))
+            .cond_where(Expr::col(ConfigPlainDataIden::Key).is_in(keys.iter().map(|key| key.as_str())))
+            .to_string(SqliteQueryBuilder);
+
+        sqlx::query(sql.as_str())
+            .execute(&mut *conn)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Add a non-secret entry, run within the transaction of the change.
+    pub async fn add_data(conn: &mut SqliteConnection, data: &ConfigPlainData) -> Result<()> {
+        let sql = Query::insert()
+            .into_table(ConfigPlainDataIden::Table)
+            .columns(CONFIG_PLAIN_DATA_COLUMNS)
+            .values([
+                data.app_name.as_str().into(),
+                data.env.as_str().into(),
+                data.key.as_str().into(),
+                data.value.as_str().into(),
+            ])?
+            .to_string(SqliteQueryBuilder);
+
+        sqlx::query(sql.as_str())
+            .execute(&mut *conn)
+            .await?;
+
+        Ok(())
+    }
+}
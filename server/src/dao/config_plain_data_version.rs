@@ -0,0 +1,160 @@
+use sqlx::{FromRow, SqliteConnection};
+use sea_query::{enum_def, ColumnDef, Expr, ForeignKey, ForeignKeyAction, Order, Query, SqliteQueryBuilder, Table};
+use tracing::info;
+use anyhow::Result;
+
+use crate::db::{db_base::DbTable, db_connection::DbConnection};
+use crate::dao::user::UserIden;
+
+/// A previous (or the current) value of a non-secret config key, stored once in plaintext.
+/// The versions are numbered together with the secret versions of the key,
+/// so a version number is either a secret or a non-secret version.
+#[enum_def]
+#[derive(Clone, FromRow, Debug)]
+pub struct ConfigPlainDataVersion {
+    pub app_name: String,
+    pub env: String,
+    pub key: String,
+    pub version: i64,
+    pub value: String,
+    pub author: String,
+    pub created_at: i64,    // UTC timestamp
+}
+
+const CONFIG_PLAIN_DATA_VERSION_COLUMNS: [ConfigPlainDataVersionIden; 7] = [
+    ConfigPlainDataVersionIden::AppName,
+    ConfigPlainDataVersionIden::Env,
+    ConfigPlainDataVersionIden::Key,
+    ConfigPlainDataVersionIden::Version,
+    ConfigPlainDataVersionIden::Value,
+    ConfigPlainDataVersionIden::Author,
+    ConfigPlainDataVersionIden::CreatedAt,
+];
+
+pub struct ConfigPlainDataVersionTable {}
+impl DbTable for ConfigPlainDataVersionTable {
+    async fn create_table(db_conn: &DbConnection) {
+        info!("Creating table: {:?}", ConfigPlainDataVersionIden::Table);
+        let sql = Table::create()
+            .table(ConfigPlainDataVersionIden::Table)
+            .if_not_exists()
+            .col(ColumnDef::new(ConfigPlainDataVersionIden::AppName).string())
+            .col(ColumnDef::new(ConfigPlainDataVersionIden::Env).string())
+            .col(ColumnDef::new(ConfigPlainDataVersionIden::Key).string())
+            .col(ColumnDef::new(ConfigPlainDataVersionIden::Version).big_integer())
+            .col(ColumnDef::new(ConfigPlainDataVersionIden::Value).string().not_null())
+            .col(ColumnDef::new(ConfigPlainDataVersionIden::Author).string().not_null())
+            .col(ColumnDef::new(ConfigPlainDataVersionIden::CreatedAt).big_integer().not_null())
+            .primary_key(sea_query::Index::create()
+                .col(ConfigPlainDataVersionIden::AppName)
+                .col(ConfigPlainDataVersionIden::Env)
+                .col(ConfigPlainDataVersionIden::Key)
+                .col(ConfigPlainDataVersionIden::Version)
+            )
+            .foreign_key(ForeignKey::create()
+                .from(ConfigPlainDataVersionIden::Table, ConfigPlainDataVersionIden::AppName)
+                .to(UserIden::Table, UserIden::Username)
+                .on_delete(ForeignKeyAction::Cascade)
+                .on_update(ForeignKeyAction::Cascade)
+            )
+            .to_string(SqliteQueryBuilder);
+
+        sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await.expect("Failed to create table config plain data version");
+    }
+}
+
+impl ConfigPlainDataVersionTable {
+    /// Get the latest non-secret version number of the key, none if the key has no non-secret version.
+    pub async fn get_latest_version(conn: &mut SqliteConnection, app_name: &str, env: &str, key: &str) -> Result<Option<i64>> {
+        let sql = Query::select()
+            .expr(Expr::col(ConfigPlainDataVersionIden::Version).max())
+            .from(ConfigPlainDataVersionIden::Table)
+            .and_where(Expr::col(ConfigPlainDataVersionIden::AppName).eq(app_name))
+            .and_where(Expr::col(ConfigPlainDataVersionIden::Env).eq(env))
+            .and_where(Expr::col(ConfigPlainDataVersionIden::Key).eq(key))
+            .to_string(SqliteQueryBuilder);
+        let latest: (Option<i64>, ) = sqlx::query_as(sql.as_str())
+            .fetch_one(&mut *conn)
+            .await?;
+
+        Ok(latest.0)
+    }
+
+    /// Record a version of the non-secret key, run within the transaction of the change.
+    pub async fn add_version(conn: &mut SqliteConnection, version: &ConfigPlainDataVersion) -> Result<()> {
+        let sql = Query::insert()
+            .into_table(ConfigPlainDataVersionIden::Table)
+            .columns(CONFIG_PLAIN_DATA_VERSION_COLUMNS)
+            .values([
+                version.app_name.as_str().into(),
+                version.env.as_str().into(),
+                version.key.as_str().into(),
+                version.version.into(),
+                version.value.as_str().into(),
+                version.author.as_str().into(),
+                version.created_at.into(),
+            ])?
+            .to_string(SqliteQueryBuilder);
+
+        sqlx::query(sql.as_str())
+            .execute(&mut *conn)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Drop the non-secret versions of the key older than the latest `retention` ones.
+    pub async fn drop_old_versions(conn: &mut SqliteConnection, app_name: &str, env: &str, key: &str,
+        latest_version: i64, retention: i64) -> Result<()> {
+        let sql = Query::delete()
+            .from_table(ConfigPlainDataVersionIden::Table)
+            .cond_where(Expr::col(ConfigPlainDataVersionIden::AppName).eq(app_name))
+            .cond_where(Expr::col(ConfigPlainDataVersionIden::Env).eq(env))
+            .cond_where(Expr::col(ConfigPlainDataVersionIden::Key).eq(key))
+            .cond_where(Expr::col(ConfigPlainDataVersionIden::Version).lte(latest_version - retention))
+            .to_string(SqliteQueryBuilder);
+        sqlx::query(sql.as_str())
+            .execute(&mut *conn)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Get the non-secret versions of the key, latest first.
+    pub async fn get_versions(db_conn: &DbConnection, app_name: &str, env: &str, key: &str) -> Result<Vec<ConfigPlainDataVersion>> {
+        let sql = Query::select()
+            .columns(CONFIG_PLAIN_DATA_VERSION_COLUMNS)
+            .from(ConfigPlainDataVersionIden::Table)
+            .and_where(Expr::col(ConfigPlainDataVersionIden::AppName).eq(app_name))
+            .and_where(Expr::col(ConfigPlainDataVersionIden::Env).eq(env))
+            .and_where(Expr::col(ConfigPlainDataVersionIden::Key).eq(key))
+            .order_by(ConfigPlainDataVersionIden::Version, Order::Desc)
+            .to_string(SqliteQueryBuilder);
+
+        let versions = sqlx::query_as::<_, ConfigPlainDataVersion>(sql.as_str())
+            .fetch_all(&db_conn.pool)
+            .await?;
+
+        Ok(versions)
+    }
+
+    /// Get one non-secret version of the key, none if the version is not a non-secret one.
+    pub async fn get_version(db_conn: &DbConnection, app_name: &str, env: &str, key: &str, version: i64) -> Result<Option<ConfigPlainDataVersion>> {
+        let sql = Query::select()
+            .columns(CONFIG_PLAIN_DATA_VERSION_COLUMNS)
+            .from(ConfigPlainDataVersionIden::Table)
+            .and_where(Expr::col(ConfigPlainDataVersionIden::AppName).eq(app_name))
+            .and_where(Expr::col(ConfigPlainDataVersionIden::Env).eq(env))
+            .and_where(Expr::col(ConfigPlainDataVersionIden::Key).eq(key))
+            .and_where(Expr::col(ConfigPlainDataVersionIden::Version).eq(version))
+            .to_string(SqliteQueryBuilder);
+
+        let version = sqlx::query_as::<_, ConfigPlainDataVersion>(sql.as_str())
+            .fetch_optional(&db_conn.pool)
+            .await?;
+
+        Ok(version)
+    }
+}
@@ -0,0 +1,183 @@
+use crate::dao::admin_group::AdminGroupIden;
+use crate::db::{db_base::DbTable, db_connection::DbConnection};
+
+use sqlx::FromRow;
+use sea_query::{enum_def, ColumnDef, Expr, ForeignKey, ForeignKeyAction, Query, SqliteQueryBuilder, Table};
+use tracing::info;
+use anyhow::Result;
+
+/// Access right of a group to all config of an app.
+/// Every member of the group has access to the app.
+#[enum_def]
+#[derive(Clone, FromRow, Debug)]
+pub struct GroupAccessRight {
+    pub group_name: String,
+    pub app_name: String,
+}
+
+const GROUP_ACCESS_RIGHT_COLUMNS: [GroupAccessRightIden; 2] = [
+    GroupAccessRightIden::GroupName,
+    GroupAccessRightIden::AppName,
+];
+
+pub struct GroupAccessRightTable {}
+impl DbTable for GroupAccessRightTable {
+    async fn create_table(db_conn: &DbConnection) {
+        info!("Creating table: {:?}", GroupAccessRightIden::Table);
+        let sql = Table::create()
+            .table(GroupAccessRightIden::Table)
+            .if_not_exists()
+            .col(ColumnDef::new(GroupAccessRightIden::GroupName).string())
+            .col(ColumnDef::new(GroupAccessRightIden::AppName).string())
+            .primary_key(sea_query::Index::create()
+                .col(GroupAccessRightIden::GroupName)
+                .col(GroupAccessRightIden::AppName)
+            )
+            .foreign_key(ForeignKey::create()
+                .from(GroupAccessRightIden::Table, GroupAccessRightIden::GroupName)
+                .to(AdminGroupIden::Table, AdminGroupIden::GroupName)
+                .on_delete(ForeignKeyAction::Cascade)
+                .on_update(ForeignKeyAction::Cascade)
+                )
+            .to_string(SqliteQueryBuilder);
+
+        sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await.expect("Failed to create table");
+        // FATAL if failed to create table
+    }
+}
+
+impl GroupAccessRightTable {
+    /// Get all app the given group can access to.
+    /// Return a list of app names.
+    pub async fn get_group_access_rights(db_conn: &DbConnection, group_name: &str) -> Result<Vec<String>> {
+        let sql = Query::select()
+            .columns(GROUP_ACCESS_RIGHT_COLUMNS)
+            .from(GroupAccessRightIden::Table)
+            .and_where(Expr::col(GroupAccessRightIden::GroupName).eq(group_name))
+            .to_string(SqliteQueryBuilder);
+
+        let access_rights = sqlx::query_as::<_, GroupAccessRight>(sql.as_str())
+            .fetch_all(&db_conn.pool)
+            .await?;
+
+        Ok(access_rights.into_iter().map(|access_right| access_right.app_name).collect())
+    }
+
+    /// Add access right of all config of a given app to the given group.
+    pub async fn add_access_right(db_conn: &DbConnection, access_right: &GroupAccessRight) -> Result<()> {
+        let sql = Query::insert()
+            .into_table(GroupAccessRightIden::Table)
+            .columns(GROUP_ACCESS_RIGHT_COLUMNS)
+            .values([
+                access_right.group_name.as_str().into(),
+                access_right.app_name.as_str().into(),
+            ])?
+            .to_string(SqliteQueryBuilder);
+
+        sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Delete the access right of the given group to the given app.
+    pub async fn delete_access_right(db_conn: &DbConnection, group_name: &str, app_name: &str) -> Result<()> {
+        let sql = Query::delete()
+            .from_table(GroupAccessRightIden::Table)
+            .cond_where(Expr::col(GroupAccessRightIden::GroupName).eq(group_name))
+            .cond_where(Expr::col(GroupAccessRightIden::AppName).eq(app_name))
+            .to_string(SqliteQueryBuilder);
+
+        sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Delete all access rights of the given group.
+    /// Used when deleting a group.
+    pub async fn delete_all_access_of_group(db_conn: &DbConnection, group_name: &str) -> Result<()> {
+        let sql = Query::delete()
+            .from_table(GroupAccessRightIden::Table)
+            .cond_where(Expr::col(GroupAccessRightIden::GroupName).eq(group_name))
+            .to_string(SqliteQueryBuilder);
+
+        sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Delete all group access rights of the given app.
+    /// Used when deleting an app.
+    pub async fn delete_all_access_of_app(db_conn: &DbConnection, app_name: &str) -> Result<()> {
+        let sql = Query::delete()
+            .from_table(GroupAccessRightIden::Table)
+            .cond_where(Expr::col(GroupAccessRightIden::AppName).eq(app_name))
+            .to_string(SqliteQueryBuilder);
+
+        sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Check if the given group has access right to the given app.
+    pub async fn check_access_right_exists(db_conn: &DbConnection, group_name: &str, app_name: &str) -> Result<bool> {
+        let sql = Query::select()
+            .expr(Expr::col(GroupAccessRightIden::AppName).count())
+            .from(GroupAccessRightIden::Table)
+            .and_where(Expr::col(GroupAccessRightIden::GroupName).eq(group_name))
+            .and_where(Expr::col(GroupAccessRightIden::AppName).eq(app_name))
+            .to_string(SqliteQueryBuilder);
+
+        let count: (i64, ) = sqlx::query_as(sql.as_str())
+            .fetch_one(&db_conn.pool)
+            .await?;
+
+        Ok(count.0 > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::db_test_util::create_test_db;
+    use crate::dao::admin_group::{AdminGroup, AdminGroupTable};
+
+    #[tokio::test]
+    async fn test_add_and_delete_group_access_right(){
+        let db_conn = create_test_db("test_add_and_delete_group_access_right").await;
+        AdminGroupTable::create_group(&db_conn, &AdminGroup { group_name: "g_team".to_string() }).await.unwrap();
+
+        let has_access = GroupAccessRightTable::check_access_right_exists(&db_conn, "g_team", "test_app").await.unwrap();
+        assert!(!has_access);
+
+        GroupAccessRightTable::add_access_right(&db_conn, &GroupAccessRight {
+            group_name: "g_team".to_string(),
+            app_name: "test_app".to_string(),
+        }).await.unwrap();
+        GroupAccessRightTable::add_access_right(&db_conn, &GroupAccessRight {
+            group_name: "g_team".to_string(),
+            app_name: "test_app2".to_string(),
+        }).await.unwrap();
+        let has_access = GroupAccessRightTable::check_access_right_exists(&db_conn, "g_team", "test_app").await.unwrap();
+        assert!(has_access);
+        let access_rights = GroupAccessRightTable::get_group_access_rights(&db_conn, "g_team").await.unwrap();
+        assert_eq!(access_rights.len(), 2);
+
+        GroupAccessRightTable::delete_access_right(&db_conn, "g_team", "test_app").await.unwrap();
+        let access_rights = GroupAccessRightTable::get_group_access_rights(&db_conn, "g_team").await.unwrap();
+        assert_eq!(access_rights, vec!["test_app2".to_string()]);
+
+        GroupAccessRightTable::delete_all_access_of_group(&db_conn, "g_team").await.unwrap();
+        let access_rights = GroupAccessRightTable::get_group_access_rights(&db_conn, "g_team").await.unwrap();
+        assert_eq!(access_rights.len(), 0);
+    }
+}
@@ -0,0 +1,241 @@
+use crate::dao::admin_group::AdminGroupIden;
+use crate::dao::group_access_right::GroupAccessRightIden;
+use crate::dao::user::UserIden;
+use crate::db::{db_base::DbTable, db_connection::DbConnection};
+
+use sqlx::FromRow;
+use sea_query::{enum_def, ColumnDef, Expr, ForeignKey, ForeignKeyAction, Query, SqliteQueryBuilder, Table};
+use tracing::info;
+use anyhow::Result;
+
+/// Membership of an admin in a group.
+#[enum_def]
+#[derive(Clone, FromRow, Debug)]
+pub struct GroupMember {
+    pub group_name: String,
+    pub username: String,
+}
+
+const GROUP_MEMBER_COLUMNS: [GroupMemberIden; 2] = [
+    GroupMemberIden::GroupName,
+    GroupMemberIden::Username,
+];
+
+pub struct GroupMemberTable {}
+impl DbTable for GroupMemberTable {
+    async fn create_table(db_conn: &DbConnection) {
+        info!("Creating table: {:?}", GroupMemberIden::Table);
+        let sql = Table::create()
+            .table(GroupMemberIden::Table)
+            .if_not_exists()
+            .col(ColumnDef::new(GroupMemberIden::GroupName).string())
+            .col(ColumnDef::new(GroupMemberIden::Username).string())
+            .primary_key(sea_query::Index::create()
+                .col(GroupMemberIden::GroupName)
+                .col(GroupMemberIden::Username)
+            )
+            .foreign_key(ForeignKey::create()
+                .from(GroupMemberIden::Table, GroupMemberIden::GroupName)
+                .to(AdminGroupIden::Table, AdminGroupIden::GroupName)
+                .on_delete(ForeignKeyAction::Cascade)
+                .on_update(ForeignKeyAction::Cascade)
+                )
+            .foreign_key(ForeignKey::create()
+                .from(GroupMemberIden::Table, GroupMemberIden::Username)
+                .to(UserIden::Table, UserIden::Username)
+                .on_delete(ForeignKeyAction::Cascade)
+                .on_update(ForeignKeyAction::Cascade)
+                )
+            .to_string(SqliteQueryBuilder);
+
+        sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await.expect("Failed to create table");
+        // FATAL if failed to create table
+    }
+}
+
+impl GroupMemberTable {
+    /// Get the usernames of all members of the given group.
+    pub async fn get_group_members(db_conn: &DbConnection, group_name: &str) -> Result<Vec<String>> {
+        let sql = Query::select()
+            .columns(GROUP_MEMBER_COLUMNS)
+            .from(GroupMemberIden::Table)
+            .and_where(Expr::col(GroupMemberIden::GroupName).eq(group_name))
+            .to_string(SqliteQueryBuilder);
+
+        let members = sqlx::query_as::<_, GroupMember>(sql.as_str())
+            .fetch_all(&db_conn.pool)
+            .await?;
+
+        Ok(members.into_iter().map(|member| member.username).collect())
+    }
+
+    /// Add the given user to the group.
+    pub async fn add_member(db_conn: &DbConnection, member: &GroupMember) -> Result<()> {
+        let sql = Query::insert()
+            .into_table(GroupMemberIden::Table)
+            .columns(GROUP_MEMBER_COLUMNS)
+            .values([
+                member.group_name.as_str().into(),
+                member.username.as_str().into(),
+            ])?
+            .to_string(SqliteQueryBuilder);
+
+        sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Remove the given user from the group.
+    pub async fn delete_member(db_conn: &DbConnection, group_name: &str, username: &str) -> Result<()> {
+        let sql = Query::delete()
+            .from_table(GroupMemberIden::Table)
+            .cond_where(Expr::col(GroupMemberIden::GroupName).eq(group_name))
+            .cond_where(Expr::col(GroupMemberIden::Username).eq(username))
+            .to_string(SqliteQueryBuilder);
+
+        sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Remove all members of the given group.
+    /// Used when deleting a group.
+    pub async fn delete_all_members_of_group(db_conn: &DbConnection, group_name: &str) -> Result<()> {
+        let sql = Query::delete()
+            .from_table(GroupMemberIden::Table)
+            .cond_where(Expr::col(GroupMemberIden::GroupName).eq(group_name))
+            .to_string(SqliteQueryBuilder);
+
+        sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Remove the given user from all groups.
+    /// Used when deleting an admin.
+    pub async fn delete_all_membership_of_user(db_conn: &DbConnection, username: &str) -> Result<()> {
+        let sql = Query::delete()
+            .from_table(GroupMemberIden::Table)
+            .cond_where(Expr::col(GroupMemberIden::Username).eq(username))
+            .to_string(SqliteQueryBuilder);
+
+        sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Check if the given user is a member of the group.
+    pub async fn check_member_exists(db_conn: &DbConnection, group_name: &str, username: &str) -> Result<bool> {
+        let sql = Query::select()
+            .expr(Expr::col(GroupMemberIden::Username).count())
+            .from(GroupMemberIden::Table)
+            .and_where(Expr::col(GroupMemberIden::GroupName).eq(group_name))
+            .and_where(Expr::col(GroupMemberIden::Username).eq(username))
+            .to_string(SqliteQueryBuilder);
+
+        let count: (i64, ) = sqlx::query_as(sql.as_str())
+            .fetch_one(&db_conn.pool)
+            .await?;
+
+        Ok(count.0 > 0)
+    }
+
+    /// Check if the given user has access right to the given app
+    /// through any of the groups he is a member of.
+    pub async fn check_group_access_exists(db_conn: &DbConnection, username: &str, app_name: &str) -> Result<bool> {
+        let sql = Query::select()
+            .expr(Expr::col(GroupMemberIden::Username).count())
+            .from(GroupMemberIden::Table)
+            .and_where(Expr::col(GroupMemberIden::Username).eq(username))
+            .and_where(
+                Expr::col(GroupMemberIden::GroupName).in_subquery(
+                    Query::select()
+                        .column(GroupAccessRightIden::GroupName)
+                        .from(GroupAccessRightIden::Table)
+                        .and_where(Expr::col(GroupAccessRightIden::AppName).eq(app_name))
+                        .take()
+                )
+            )
+            .to_string(SqliteQueryBuilder);
+
+        let count: (i64, ) = sqlx::query_as(sql.as_str())
+            .fetch_one(&db_conn.pool)
+            .await?;
+
+        Ok(count.0 > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use well_i_known_core::modal::user::UserRole;
+    use crate::db::db_test_util::create_test_db;
+    use crate::dao::admin_group::{AdminGroup, AdminGroupTable};
+    use crate::dao::group_access_right::{GroupAccessRight, GroupAccessRightTable};
+    use crate::dao::user::UserTable;
+
+    async fn create_group_member_test_db(test_case_name: &str) -> DbConnection{
+        let db_conn = create_test_db(test_case_name).await;
+        UserTable::create_user(&db_conn, "u_admin", &UserRole::Admin, "password").await.unwrap();
+        UserTable::create_user(&db_conn, "u_admin2", &UserRole::Admin, "password").await.unwrap();
+        UserTable::create_user(&db_conn, "u_app", &UserRole::App, "password").await.unwrap();
+        AdminGroupTable::create_group(&db_conn, &AdminGroup { group_name: "g_team".to_string() }).await.unwrap();
+        db_conn
+    }
+
+    #[tokio::test]
+    async fn test_add_and_delete_member(){
+        let db_conn = create_group_member_test_db("test_add_and_delete_member").await;
+
+        GroupMemberTable::add_member(&db_conn, &GroupMember {
+            group_name: "g_team".to_string(),
+            username: "u_admin".to_string(),
+        }).await.unwrap();
+        GroupMemberTable::add_member(&db_conn, &GroupMember {
+            group_name: "g_team".to_string(),
+            username: "u_admin2".to_string(),
+        }).await.unwrap();
+        assert!(GroupMemberTable::check_member_exists(&db_conn, "g_team", "u_admin").await.unwrap());
+        let members = GroupMemberTable::get_group_members(&db_conn, "g_team").await.unwrap();
+        assert_eq!(members.len(), 2);
+
+        GroupMemberTable::delete_member(&db_conn, "g_team", "u_admin").await.unwrap();
+        assert!(!GroupMemberTable::check_member_exists(&db_conn, "g_team", "u_admin").await.unwrap());
+
+        GroupMemberTable::delete_all_members_of_group(&db_conn, "g_team").await.unwrap();
+        let members = GroupMemberTable::get_group_members(&db_conn, "g_team").await.unwrap();
+        assert_eq!(members.len(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_check_group_access_exists(){
+        let db_conn = create_group_member_test_db("test_check_group_access_exists").await;
+
+        GroupMemberTable::add_member(&db_conn, &GroupMember {
+            group_name: "g_team".to_string(),
+            username: "u_admin".to_string(),
+        }).await.unwrap();
+        assert!(!GroupMemberTable::check_group_access_exists(&db_conn, "u_admin", "u_app").await.unwrap());
+
+        GroupAccessRightTable::add_access_right(&db_conn, &GroupAccessRight {
+            group_name: "g_team".to_string(),
+            app_name: "u_app".to_string(),
+        }).await.unwrap();
+        assert!(GroupMemberTable::check_group_access_exists(&db_conn, "u_admin", "u_app").await.unwrap());
+        assert!(!GroupMemberTable::check_group_access_exists(&db_conn, "u_admin2", "u_app").await.unwrap());
+
+        GroupMemberTable::delete_all_membership_of_user(&db_conn, "u_admin").await.unwrap();
+        assert!(!GroupMemberTable::check_group_access_exists(&db_conn, "u_admin", "u_app").await.unwrap());
+    }
+}
@@ -0,0 +1,138 @@
+use crate::dao::user::UserIden;
+use crate::db::{db_base::DbTable, db_connection::DbConnection};
+
+use sqlx::FromRow;
+use sea_query::{enum_def, ColumnDef, Expr, ForeignKey, ForeignKeyAction, Query, SqliteQueryBuilder, Table};
+use tracing::info;
+use anyhow::Result;
+
+/// A login challenge issued to a user.
+/// The user proves the possession of the private key by answering the plaintext of the encrypted nonce.
+/// Only the hash of the answer is stored.
+#[enum_def]
+#[derive(Clone, FromRow, Debug)]
+pub struct LoginChallenge {
+    pub id: String,
+    pub username: String,
+    pub answer_hash: String,
+    pub answer_salt: String,
+    pub expires_at: i64,    // UTC timestamp
+}
+
+const LOGIN_CHALLENGE_COLUMNS: [LoginChallengeIden; 5] = [
+    LoginChallengeIden::Id,
+    LoginChallengeIden::Username,
+    LoginChallengeIden::AnswerHash,
+    LoginChallengeIden::AnswerSalt,
+    LoginChallengeIden::ExpiresAt,
+];
+
+pub struct LoginChallengeTable {}
+impl DbTable for LoginChallengeTable {
+    async fn create_table(db_conn: &DbConnection) {
+        info!("Creating table: {:?}", LoginChallengeIden::Table);
+        let sql = Table::create()
+            .table(LoginChallengeIden::Table)
+            .if_not_exists()
+            .col(ColumnDef::new(LoginChallengeIden::Id).string().primary_key())
+            .col(ColumnDef::new(LoginChallengeIden::Username).string().not_null())
+            .col(ColumnDef::new(LoginChallengeIden::AnswerHash).string().not_null())
+            .col(ColumnDef::new(LoginChallengeIden::AnswerSalt).string().not_null())
+            .col(ColumnDef::new(LoginChallengeIden::ExpiresAt).big_integer().not_null())
+            .foreign_key(ForeignKey::create()
+                .from(LoginChallengeIden::Table, LoginChallengeIden::Username)
+                .to(UserIden::Table, UserIden::Username)
+                .on_delete(ForeignKeyAction::Cascade)
+                .on_update(ForeignKeyAction::Cascade)
+                )
+            .to_string(SqliteQueryBuilder);
+
+        sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await.expect("Failed to create table");
+        // FATAL if failed to create table
+    }
+}
+
+impl LoginChallengeTable {
+    pub async fn add_challenge(db_conn: &DbConnection, id: &str, username: &str,
+        answer_hash: &str, answer_salt: &str, expires_at: i64) -> Result<()> {
+        let sql = Query::insert()
+            .into_table(LoginChallengeIden::Table)
+            .columns(LOGIN_CHALLENGE_COLUMNS)
+            .values([
+                id.into(),
+                username.into(),
+                answer_hash.into(),
+                answer_salt.into(),
+                expires_at.into(),
+            ])?
+            .to_string(SqliteQueryBuilder);
+
+        sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Get one challenge (if exists) by id.
+    pub async fn get_challenge(db_conn: &DbConnection, id: &str) -> Result<Option<LoginChallenge>> {
+        let sql = Query::select()
+            .columns(LOGIN_CHALLENGE_COLUMNS)
+            .from(LoginChallengeIden::Table)
+            .and_where(Expr::col(LoginChallengeIden::Id).eq(id))
+            .to_string(SqliteQueryBuilder);
+
+        let challenge = sqlx::query_as::<_, LoginChallenge>(sql.as_str())
+            .fetch_optional(&db_conn.pool)
+            .await?;
+
+        Ok(challenge)
+    }
+
+    /// Delete the challenge.
+    /// Return false if the challenge does not exist (e.g. already answered).
+    pub async fn delete_challenge(db_conn: &DbConnection, id: &str) -> Result<bool> {
+        let sql = Query::delete()
+            .from_table(LoginChallengeIden::Table)
+            .cond_where(Expr::col(LoginChallengeIden::Id).eq(id))
+            .to_string(SqliteQueryBuilder);
+
+        let result = sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    /// Delete all challenges of the given user.
+    /// Used when deleting a user.
+    pub async fn delete_all_challenges_of_user(db_conn: &DbConnection, username: &str) -> Result<()> {
+        let sql = Query::delete()
+            .from_table(LoginChallengeIden::Table)
+            .cond_where(Expr::col(LoginChallengeIden::Username).eq(username))
+            .to_string(SqliteQueryBuilder);
+
+        sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Delete the challenges expired before the given timestamp.
+    /// Return the number of deleted challenges.
+    pub async fn delete_expired_challenges(db_conn: &DbConnection, timestamp: i64) -> Result<u64> {
+        let sql = Query::delete()
+            .from_table(LoginChallengeIden::Table)
+            .cond_where(Expr::col(LoginChallengeIden::ExpiresAt).lte(timestamp))
+            .to_string(SqliteQueryBuilder);
+
+        let result = sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+}
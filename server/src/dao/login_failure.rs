@@ -0,0 +1,115 @@
+use crate::db::{db_base::DbTable, db_connection::DbConnection};
+
+use sqlx::FromRow;
+use sea_query::{enum_def, ColumnDef, Expr, OnConflict, Query, SqliteQueryBuilder, Table};
+use tracing::info;
+use anyhow::Result;
+
+/// Failed login attempts of a username / source IP.
+#[enum_def]
+#[derive(Clone, FromRow, Debug)]
+pub struct LoginFailure {
+    pub kind: String,           // "username" / "ip"
+    pub subject: String,        // the username / source IP
+    pub failure_count: i64,
+    pub last_failed_at: i64,    // UTC timestamp
+    pub blocked_until: i64,     // UTC timestamp, no login attempt is accepted before it
+}
+
+const LOGIN_FAILURE_COLUMNS: [LoginFailureIden; 5] = [
+    LoginFailureIden::Kind,
+    LoginFailureIden::Subject,
+    LoginFailureIden::FailureCount,
+    LoginFailureIden::LastFailedAt,
+    LoginFailureIden::BlockedUntil,
+];
+
+pub struct LoginFailureTable {}
+impl DbTable for LoginFailureTable {
+    async fn create_table(db_conn: &DbConnection) {
+        info!("Creating table: {:?}", LoginFailureIden::Table);
+        // no foreign key to the user table, the failures of unknown usernames are counted too
+        let sql = Table::create()
+            .table(LoginFailureIden::Table)
+            .if_not_exists()
+            .col(ColumnDef::new(LoginFailureIden::Kind).string())
+            .col(ColumnDef::new(LoginFailureIden::Subject).string())
+            .col(ColumnDef::new(LoginFailureIden::FailureCount).big_integer().not_null())
+            .col(ColumnDef::new(LoginFailureIden::LastFailedAt).big_integer().not_null())
+            .col(ColumnDef::new(LoginFailureIden::BlockedUntil).big_integer().not_null())
+            .primary_key(sea_query::Index::create()
+                .col(LoginFailureIden::Kind)
+                .col(LoginFailureIden::Subject)
+            )
+            .to_string(SqliteQueryBuilder);
+
+        sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await.expect("Failed to create table");
+        // FATAL if failed to create table
+    }
+}
+
+impl LoginFailureTable {
+    /// Get the failure record (if exists) of the subject.
+    pub async fn get_failure(db_conn: &DbConnection, kind: &str, subject: &str) -> Result<Option<LoginFailure>> {
+        let sql = Query::select()
+            .columns(LOGIN_FAILURE_COLUMNS)
+            .from(LoginFailureIden::Table)
+            .and_where(Expr::col(LoginFailureIden::Kind).eq(kind))
+            .and_where(Expr::col(LoginFailureIden::Subject).eq(subject))
+            .to_string(SqliteQueryBuilder);
+
+        let failure = sqlx::query_as::<_, LoginFailure>(sql.as_str())
+            .fetch_optional(&db_conn.pool)
+            .await?;
+
+        Ok(failure)
+    }
+
+    /// Insert or replace the failure record of the subject.
+    pub async fn set_failure(db_conn: &DbConnection, failure: &LoginFailure) -> Result<()> {
+        let sql = Query::insert()
+            .into_table(LoginFailureIden::Table)
+            .columns(LOGIN_FAILURE_COLUMNS)
+            .values([
+                failure.kind.as_str().into(),
+                failure.subject.as_str().into(),
+                failure.failure_count.into(),
+                failure.last_failed_at.into(),
+                failure.blocked_until.into(),
+            ])?
+            .on_conflict(
+                OnConflict::columns([LoginFailureIden::Kind, LoginFailureIden::Subject])
+                    .update_columns([
+                        LoginFailureIden::FailureCount,
+                        LoginFailureIden::LastFailedAt,
+                        LoginFailureIden::BlockedUntil,
+                    ])
+                    .to_owned()
+            )
+            .to_string(SqliteQueryBuilder);
+
+        sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Clear the failure record of the subject.
+    /// Return false if there is no record.
+    pub async fn delete_failure(db_conn: &DbConnection, kind: &str, subject: &str) -> Result<bool> {
+        let sql = Query::delete()
+            .from_table(LoginFailureIden::Table)
+            .cond_where(Expr::col(LoginFailureIden::Kind).eq(kind))
+            .cond_where(Expr::col(LoginFailureIden::Subject).eq(subject))
+            .to_string(SqliteQueryBuilder);
+
+        let result = sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
@@ -1,3 +1,22 @@
 pub mod user;
 pub mod access_right;
+pub mod api_token;
+pub mod auth_token;
+pub mod admin_group;
+pub mod group_member;
+pub mod group_access_right;
+pub mod login_challenge;
+pub mod login_failure;
+pub mod break_glass_event;
 pub mod config_data;
+pub mod config_data_version;
+pub mod config_data_revision;
+pub mod config_plain_data;
+pub mod config_plain_data_version;
+pub mod config_data_expiry;
+pub mod app_setting;
+pub mod app_schema;
+pub mod notification;
+pub mod password_history;
+pub mod password_reset_token;
+pub mod pending_operation;
@@ -0,0 +1,127 @@
+use crate::db::{db_base::DbTable, db_connection::DbConnection};
+
+use sqlx::FromRow;
+use sea_query::{enum_def, ColumnDef, Expr, Order, Query, SqliteQueryBuilder, Table};
+use tracing::info;
+use anyhow::Result;
+
+/// A message sent to a user by the server.
+#[enum_def]
+#[derive(Clone, FromRow, Debug)]
+pub struct Notification {
+    pub id: i64,
+    pub recipient: String,
+    pub category: String,
+    pub message: String,
+    pub created_at: i64,        // UTC timestamp
+}
+
+const NOTIFICATION_COLUMNS: [NotificationIden; 5] = [
+    NotificationIden::Id,
+    NotificationIden::Recipient,
+    NotificationIden::Category,
+    NotificationIden::Message,
+    NotificationIden::CreatedAt,
+];
+
+pub struct NotificationTable {}
+impl DbTable for NotificationTable {
+    async fn create_table(db_conn: &DbConnection) {
+        info!("Creating table: {:?}", NotificationIden::Table);
+        let sql = Table::create()
+            .table(NotificationIden::Table)
+            .if_not_exists()
+            .col(ColumnDef::new(NotificationIden::Id).integer().not_null().auto_increment().primary_key())
+            .col(ColumnDef::new(NotificationIden::Recipient).string().not_null())
+            .col(ColumnDef::new(NotificationIden::Category).string().not_null())
+            .col(ColumnDef::new(NotificationIden::Message).string().not_null())
+            .col(ColumnDef::new(NotificationIden::CreatedAt).big_integer().not_null())
+            .to_string(SqliteQueryBuilder);
+
+        sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await.expect("Failed to create table");
+        // FATAL if failed to create table
+    }
+}
+
+impl NotificationTable {
+    /// Add a notification for the recipient.
+    pub async fn add_notification(db_conn: &DbConnection, recipient: &str, category: &str, message: &str, created_at: i64) -> Result<()> {
+        let sql = Query::insert()
+            .into_table(NotificationIden::Table)
+            .columns(NOTIFICATION_COLUMNS[1..].to_vec())
+            .values([
+                recipient.into(),
+                category.into(),
+                message.into(),
+                created_at.into(),
+            ])?
+            .to_string(SqliteQueryBuilder);
+
+        sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Get all notifications of the recipient, latest first.
+    pub async fn get_notifications(db_conn: &DbConnection, recipient: &str) -> Result<Vec<Notification>> {
+        let sql = Query::select()
+            .columns(NOTIFICATION_COLUMNS)
+            .from(NotificationIden::Table)
+            .and_where(Expr::col(NotificationIden::Recipient).eq(recipient))
+            .order_by(NotificationIden::Id, Order::Desc)
+            .to_string(SqliteQueryBuilder);
+
+        let notifications = sqlx::query_as::<_, Notification>(sql.as_str())
+            .fetch_all(&db_conn.pool)
+            .await?;
+
+        Ok(notifications)
+    }
+
+    /// Delete the notification of the recipient.
+    /// Return true if the notification existed.
+    pub async fn delete_notification(db_conn: &DbConnection, recipient: &str, id: i64) -> Result<bool> {
+        let sql = Query::delete()
+            .from_table(NotificationIden::Table)
+            .cond_where(Expr::col(NotificationIden::Recipient).eq(recipient))
+            .cond_where(Expr::col(NotificationIden::Id).eq(id))
+            .to_string(SqliteQueryBuilder);
+
+        let result = sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::db_test_util::create_test_db;
+
+    #[tokio::test]
+    async fn test_add_get_and_delete_notification(){
+        let db_conn = create_test_db("test_add_get_and_delete_notification").await;
+
+        NotificationTable::add_notification(&db_conn, "u_root", "BreakGlass", "message 1", 100).await.unwrap();
+        NotificationTable::add_notification(&db_conn, "u_root", "BreakGlass", "message 2", 200).await.unwrap();
+        NotificationTable::add_notification(&db_conn, "u_admin", "BreakGlass", "message 3", 300).await.unwrap();
+
+        let notifications = NotificationTable::get_notifications(&db_conn, "u_root").await.unwrap();
+        assert_eq!(notifications.len(), 2);
+        assert_eq!(notifications[0].message, "message 2");
+
+        // cannot delete other's notification
+        let deleted = NotificationTable::delete_notification(&db_conn, "u_admin", notifications[0].id).await.unwrap();
+        assert!(!deleted);
+        let deleted = NotificationTable::delete_notification(&db_conn, "u_root", notifications[0].id).await.unwrap();
+        assert!(deleted);
+        let notifications = NotificationTable::get_notifications(&db_conn, "u_root").await.unwrap();
+        assert_eq!(notifications.len(), 1);
+    }
+}
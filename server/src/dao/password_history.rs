@@ -0,0 +1,150 @@
+use crate::dao::user::UserIden;
+use crate::db::{db_base::DbTable, db_connection::DbConnection};
+
+use sqlx::FromRow;
+use sea_query::{ColumnDef, Expr, ForeignKey, ForeignKeyAction, Iden, Order, Query, SqliteQueryBuilder, Table};
+use tracing::info;
+use anyhow::Result;
+
+/// The columns of the password history, only the hashed passwords are read back.
+#[derive(Iden, Debug, Clone, Copy)]
+pub enum PasswordHistoryIden {
+    #[iden = "password_history"]
+    Table,
+    Id,
+    Username,
+    EncryptedPassword,
+    PasswordSalt,
+    ReplacedAt,     // UTC timestamp
+}
+
+/// A previous password (hashed) of the user, kept to prevent the reuse.
+#[derive(Clone, FromRow, Debug)]
+pub struct PasswordHistory {
+    pub encrypted_password: String,
+    pub password_salt: String,
+}
+
+pub struct PasswordHistoryTable {}
+impl DbTable for PasswordHistoryTable {
+    async fn create_table(db_conn: &DbConnection) {
+        info!("Creating table: {:?}", PasswordHistoryIden::Table);
+        let sql = Table::create()
+            .table(PasswordHistoryIden::Table)
+            .if_not_exists()
+            .col(ColumnDef::new(PasswordHistoryIden::Id).integer().not_null().auto_increment().primary_key())
+            .col(ColumnDef::new(PasswordHistoryIden::Username).string().not_null())
+            .col(ColumnDef::new(PasswordHistoryIden::EncryptedPassword).string().not_null())
+            .col(ColumnDef::new(PasswordHistoryIden::PasswordSalt).string().not_null())
+            .col(ColumnDef::new(PasswordHistoryIden::ReplacedAt).big_integer().not_null())
+            .foreign_key(ForeignKey::create()
+                .from(PasswordHistoryIden::Table, PasswordHistoryIden::Username)
+                .to(UserIden::Table, UserIden::Username)
+                .on_delete(ForeignKeyAction::Cascade)
+                .on_update(ForeignKeyAction::Cascade)
+                )
+            .to_string(SqliteQueryBuilder);
+
+        sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await.expect("Failed to create table");
+        // FATAL if failed to create table
+    }
+}
+
+impl PasswordHistoryTable {
+    /// Keep the replaced password of the user.
+    pub async fn add_password(db_conn: &DbConnection, username: &str,
+        encrypted_password: &str, password_salt: &str, replaced_at: i64) -> Result<()> {
+        let sql = Query::insert()
+            .into_table(PasswordHistoryIden::Table)
+            .columns([
+                PasswordHistoryIden::Username,
+                PasswordHistoryIden::EncryptedPassword,
+                PasswordHistoryIden::PasswordSalt,
+                PasswordHistoryIden::ReplacedAt,
+            ])
+            .values([
+                username.into(),
+                encrypted_password.into(),
+                password_salt.into(),
+                replaced_at.into(),
+            ])?
+            .to_string(SqliteQueryBuilder);
+
+        sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Get the latest previous passwords of the user, latest first.
+    pub async fn get_passwords(db_conn: &DbConnection, username: &str, limit: u64) -> Result<Vec<PasswordHistory>> {
+        let sql = Query::select()
+            .columns([PasswordHistoryIden::EncryptedPassword, PasswordHistoryIden::PasswordSalt])
+            .from(PasswordHistoryIden::Table)
+            .and_where(Expr::col(PasswordHistoryIden::Username).eq(username))
+            .order_by(PasswordHistoryIden::Id, Order::Desc)
+            .limit(limit)
+            .to_string(SqliteQueryBuilder);
+
+        let passwords = sqlx::query_as::<_, PasswordHistory>(sql.as_str())
+            .fetch_all(&db_conn.pool)
+            .await?;
+
+        Ok(passwords)
+    }
+
+    /// Delete the previous passwords of the user except the latest `keep` ones.
+    pub async fn prune_passwords(db_conn: &DbConnection, username: &str, keep: u64) -> Result<()> {
+        let sql = Query::delete()
+            .from_table(PasswordHistoryIden::Table)
+            .cond_where(Expr::col(PasswordHistoryIden::Username).eq(username))
+            .cond_where(Expr::col(PasswordHistoryIden::Id).not_in_subquery(
+                Query::select()
+                    .column(PasswordHistoryIden::Id)
+                    .from(PasswordHistoryIden::Table)
+                    .and_where(Expr::col(PasswordHistoryIden::Username).eq(username))
+                    .order_by(PasswordHistoryIden::Id, Order::Desc)
+                    .limit(keep)
+                    .to_owned()
+            ))
+            .to_string(SqliteQueryBuilder);
+
+        sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Delete all previous passwords of the user.
+    pub async fn delete_all_passwords_of_user(db_conn: &DbConnection, username: &str) -> Result<()> {
+        PasswordHistoryTable::prune_passwords(db_conn, username, 0).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dao::user::UserTable;
+    use crate::db::db_test_util::create_test_db;
+    use well_i_known_core::modal::user::UserRole;
+
+    #[tokio::test]
+    async fn test_prune_password_history(){
+        let db_conn = create_test_db("test_prune_password_history").await;
+        UserTable::create_user(&db_conn, "u_admin", &UserRole::Admin, "password").await.unwrap();
+
+        for index in 1..=4 {
+            PasswordHistoryTable::add_password(&db_conn, "u_admin", &format!("hash_{}", index), "salt", index).await.unwrap();
+        }
+        PasswordHistoryTable::prune_passwords(&db_conn, "u_admin", 2).await.unwrap();
+
+        let passwords = PasswordHistoryTable::get_passwords(&db_conn, "u_admin", 10).await.unwrap();
+        assert_eq!(passwords.len(), 2);
+        assert_eq!(passwords[0].encrypted_password, "hash_4");
+        assert_eq!(passwords[1].encrypted_password, "hash_3");
+    }
+}
@@ -0,0 +1,140 @@
+use crate::dao::user::UserIden;
+use crate::db::{db_base::DbTable, db_connection::DbConnection};
+
+use sqlx::FromRow;
+use sea_query::{enum_def, ColumnDef, Expr, ForeignKey, ForeignKeyAction, Query, SqliteQueryBuilder, Table};
+use tracing::info;
+use anyhow::Result;
+
+/// A one-time token issued by root for the user to choose a new password.
+/// Only the hash of the token secret is stored.
+#[enum_def]
+#[derive(Clone, FromRow, Debug)]
+pub struct PasswordResetToken {
+    pub id: String,
+    pub username: String,
+    pub token_hash: String,
+    pub token_salt: String,
+    pub created_at: i64,    // UTC timestamp
+    pub expires_at: i64,    // UTC timestamp
+}
+
+const PASSWORD_RESET_TOKEN_COLUMNS: [PasswordResetTokenIden; 6] = [
+    PasswordResetTokenIden::Id,
+    PasswordResetTokenIden::Username,
+    PasswordResetTokenIden::TokenHash,
+    PasswordResetTokenIden::TokenSalt,
+    PasswordResetTokenIden::CreatedAt,
+    PasswordResetTokenIden::ExpiresAt,
+];
+
+pub struct PasswordResetTokenTable {}
+impl DbTable for PasswordResetTokenTable {
+    async fn create_table(db_conn: &DbConnection) {
+        info!("Creating table: {:?}", PasswordResetTokenIden::Table);
+        let sql = Table::create()
+            .table(PasswordResetTokenIden::Table)
+            .if_not_exists()
+            .col(ColumnDef::new(PasswordResetTokenIden::Id).string().primary_key())
+            .col(ColumnDef::new(PasswordResetTokenIden::Username).string().not_null())
+            .col(ColumnDef::new(PasswordResetTokenIden::TokenHash).string().not_null())
+            .col(ColumnDef::new(PasswordResetTokenIden::TokenSalt).string().not_null())
+            .col(ColumnDef::new(PasswordResetTokenIden::CreatedAt).big_integer().not_null())
+            .col(ColumnDef::new(PasswordResetTokenIden::ExpiresAt).big_integer().not_null())
+            .foreign_key(ForeignKey::create()
+                .from(PasswordResetTokenIden::Table, PasswordResetTokenIden::Username)
+                .to(UserIden::Table, UserIden::Username)
+                .on_delete(ForeignKeyAction::Cascade)
+                .on_update(ForeignKeyAction::Cascade)
+                )
+            .to_string(SqliteQueryBuilder);
+
+        sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await.expect("Failed to create table");
+        // FATAL if failed to create table
+    }
+}
+
+impl PasswordResetTokenTable {
+    pub async fn add_token(db_conn: &DbConnection, token: &PasswordResetToken) -> Result<()> {
+        let sql = Query::insert()
+            .into_table(PasswordResetTokenIden::Table)
+            .columns(PASSWORD_RESET_TOKEN_COLUMNS)
+            .values([
+                token.id.as_str().into(),
+                token.username.as_str().into(),
+                token.token_hash.as_str().into(),
+                token.token_salt.as_str().into(),
+                token.created_at.into(),
+                token.expires_at.into(),
+            ])?
+            .to_string(SqliteQueryBuilder);
+
+        sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Get one token (if exists) by id.
+    pub async fn get_token(db_conn: &DbConnection, id: &str) -> Result<Option<PasswordResetToken>> {
+        let sql = Query::select()
+            .columns(PASSWORD_RESET_TOKEN_COLUMNS)
+            .from(PasswordResetTokenIden::Table)
+            .and_where(Expr::col(PasswordResetTokenIden::Id).eq(id))
+            .to_string(SqliteQueryBuilder);
+
+        let token = sqlx::query_as::<_, PasswordResetToken>(sql.as_str())
+            .fetch_optional(&db_conn.pool)
+            .await?;
+
+        Ok(token)
+    }
+
+    /// Delete the token.
+    /// Return false if the token does not exist (e.g. already redeemed).
+    pub async fn delete_token(db_conn: &DbConnection, id: &str) -> Result<bool> {
+        let sql = Query::delete()
+            .from_table(PasswordResetTokenIden::Table)
+            .cond_where(Expr::col(PasswordResetTokenIden::Id).eq(id))
+            .to_string(SqliteQueryBuilder);
+
+        let result = sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    /// Delete all tokens of the given user.
+    /// Used when issuing a new token or deleting a user.
+    pub async fn delete_all_tokens_of_user(db_conn: &DbConnection, username: &str) -> Result<()> {
+        let sql = Query::delete()
+            .from_table(PasswordResetTokenIden::Table)
+            .cond_where(Expr::col(PasswordResetTokenIden::Username).eq(username))
+            .to_string(SqliteQueryBuilder);
+
+        sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Delete the tokens expired before the given timestamp.
+    /// Return the number of deleted tokens.
+    pub async fn delete_expired_tokens(db_conn: &DbConnection, timestamp: i64) -> Result<u64> {
+        let sql = Query::delete()
+            .from_table(PasswordResetTokenIden::Table)
+            .cond_where(Expr::col(PasswordResetTokenIden::ExpiresAt).lte(timestamp))
+            .to_string(SqliteQueryBuilder);
+
+        let result = sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+}
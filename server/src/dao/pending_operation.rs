@@ -0,0 +1,166 @@
+use crate::db::{db_base::DbTable, db_connection::DbConnection};
+use well_i_known_core::api::approval::ApprovalStatus;
+
+use sqlx::FromRow;
+use sea_query::{enum_def, ColumnDef, Expr, Order, Query, SqliteQueryBuilder, Table};
+use tracing::info;
+use anyhow::Result;
+
+/// A flagged operation waiting for the approval of a second admin.
+#[enum_def]
+#[derive(Clone, FromRow, Debug)]
+pub struct PendingOperation {
+    pub id: i64,
+    pub operation: String,          // json of the FlaggedOperation
+    pub requester: String,
+    pub status: String,             // ApprovalStatus
+    pub approver: Option<String>,   // admin who approved / rejected the operation
+    pub created_at: i64,            // UTC timestamp
+    pub decided_at: Option<i64>,    // UTC timestamp
+}
+
+const PENDING_OPERATION_COLUMNS: [PendingOperationIden; 7] = [
+    PendingOperationIden::Id,
+    PendingOperationIden::Operation,
+    PendingOperationIden::Requester,
+    PendingOperationIden::Status,
+    PendingOperationIden::Approver,
+    PendingOperationIden::CreatedAt,
+    PendingOperationIden::DecidedAt,
+];
+
+pub struct PendingOperationTable {}
+impl DbTable for PendingOperationTable {
+    async fn create_table(db_conn: &DbConnection) {
+        info!("Creating table: {:?}", PendingOperationIden::Table);
+        // no foreign key to the user table, the records are kept as audit trail
+        let sql = Table::create()
+            .table(PendingOperationIden::Table)
+            .if_not_exists()
+            .col(ColumnDef::new(PendingOperationIden::Id).integer().not_null().auto_increment().primary_key())
+            .col(ColumnDef::new(PendingOperationIden::Operation).string().not_null())
+            .col(ColumnDef::new(PendingOperationIden::Requester).string().not_null())
+            .col(ColumnDef::new(PendingOperationIden::Status).string().not_null())
+            .col(ColumnDef::new(PendingOperationIden::Approver).string().null())
+            .col(ColumnDef::new(PendingOperationIden::CreatedAt).big_integer().not_null())
+            .col(ColumnDef::new(PendingOperationIden::DecidedAt).big_integer().null())
+            .to_string(SqliteQueryBuilder);
+
+        sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await.expect("Failed to create table");
+        // FATAL if failed to create table
+    }
+}
+
+impl PendingOperationTable {
+    /// Add a new pending operation.
+    /// Return the id of the operation.
+    pub async fn add_operation(db_conn: &DbConnection, operation: &str, requester: &str, created_at: i64) -> Result<i64> {
+        let sql = Query::insert()
+            .into_table(PendingOperationIden::Table)
+            .columns([
+                PendingOperationIden::Operation,
+                PendingOperationIden::Requester,
+                PendingOperationIden::Status,
+                PendingOperationIden::CreatedAt,
+            ])
+            .values([
+                operation.into(),
+                requester.into(),
+                ApprovalStatus::Pending.to_string().into(),
+                created_at.into(),
+            ])?
+            .to_string(SqliteQueryBuilder);
+
+        let result = sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await?;
+
+        Ok(result.last_insert_rowid())
+    }
+
+    /// Get one operation (if exists) by id.
+    pub async fn get_operation(db_conn: &DbConnection, id: i64) -> Result<Option<PendingOperation>> {
+        let sql = Query::select()
+            .columns(PENDING_OPERATION_COLUMNS)
+            .from(PendingOperationIden::Table)
+            .and_where(Expr::col(PendingOperationIden::Id).eq(id))
+            .to_string(SqliteQueryBuilder);
+
+        let operation = sqlx::query_as::<_, PendingOperation>(sql.as_str())
+            .fetch_optional(&db_conn.pool)
+            .await?;
+
+        Ok(operation)
+    }
+
+    /// Get all operations still waiting for approval, oldest first.
+    pub async fn get_pending_operations(db_conn: &DbConnection) -> Result<Vec<PendingOperation>> {
+        let sql = Query::select()
+            .columns(PENDING_OPERATION_COLUMNS)
+            .from(PendingOperationIden::Table)
+            .and_where(Expr::col(PendingOperationIden::Status).eq(ApprovalStatus::Pending.to_string()))
+            .order_by(PendingOperationIden::Id, Order::Asc)
+            .to_string(SqliteQueryBuilder);
+
+        let operations = sqlx::query_as::<_, PendingOperation>(sql.as_str())
+            .fetch_all(&db_conn.pool)
+            .await?;
+
+        Ok(operations)
+    }
+
+    /// Set the decision of an operation that is currently in the given status.
+    /// Return false if the operation is not in that status (e.g. already decided by others).
+    pub async fn update_status(db_conn: &DbConnection, id: i64, current_status: &ApprovalStatus,
+        new_status: &ApprovalStatus, approver: &str, decided_at: i64) -> Result<bool> {
+        let sql = Query::update()
+            .table(PendingOperationIden::Table)
+            .values([
+                (PendingOperationIden::Status, new_status.to_string().into()),
+                (PendingOperationIden::Approver, approver.into()),
+                (PendingOperationIden::DecidedAt, decided_at.into()),
+            ])
+            .and_where(Expr::col(PendingOperationIden::Id).eq(id))
+            .and_where(Expr::col(PendingOperationIden::Status).eq(current_status.to_string()))
+            .to_string(SqliteQueryBuilder);
+
+        let result = sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::db_test_util::create_test_db;
+
+    #[tokio::test]
+    async fn test_add_and_decide_operation(){
+        let db_conn = create_test_db("test_add_and_decide_operation").await;
+
+        let id = PendingOperationTable::add_operation(&db_conn, "{}", "u_admin", 100).await.unwrap();
+        PendingOperationTable::add_operation(&db_conn, "{}", "u_admin", 200).await.unwrap();
+        let pending = PendingOperationTable::get_pending_operations(&db_conn).await.unwrap();
+        assert_eq!(pending.len(), 2);
+        assert_eq!(pending[0].id, id);
+
+        let updated = PendingOperationTable::update_status(&db_conn, id,
+            &ApprovalStatus::Pending, &ApprovalStatus::Approved, "u_admin2", 300).await.unwrap();
+        assert!(updated);
+        // cannot be decided twice
+        let updated = PendingOperationTable::update_status(&db_conn, id,
+            &ApprovalStatus::Pending, &ApprovalStatus::Rejected, "u_admin3", 400).await.unwrap();
+        assert!(!updated);
+
+        let operation = PendingOperationTable::get_operation(&db_conn, id).await.unwrap().unwrap();
+        assert_eq!(operation.status, ApprovalStatus::Approved.to_string());
+        assert_eq!(operation.approver, Some("u_admin2".to_string()));
+        let pending = PendingOperationTable::get_pending_operations(&db_conn).await.unwrap();
+        assert_eq!(pending.len(), 1);
+    }
+}
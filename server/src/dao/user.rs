@@ -1,10 +1,12 @@
 use crate::db::{db_base::DbTable, db_connection::DbConnection};
-use crate::dao::access_right::AccessRightIden;
+use crate::dao::access_right::{AccessRightIden, AccessRightTable};
+use crate::dao::group_access_right::GroupAccessRightIden;
+use crate::dao::group_member::GroupMemberIden;
 use well_i_known_core::crypto::password;
 use well_i_known_core::modal::user::UserRole;
 
 use sqlx::FromRow;
-use sea_query::{enum_def, SqliteQueryBuilder, ColumnDef, Asterisk, Table, Query, Expr};
+use sea_query::{enum_def, SqliteQueryBuilder, ColumnDef, Asterisk, Table, Query, Expr, Cond, Order};
 use anyhow::Result;
 use tracing::*;
 
@@ -15,6 +17,8 @@ pub struct User {
     pub role: String,
     pub encrypted_password: String,
     pub password_salt: String,
+    pub active: bool,       // if the public key is validated by the handshake
+    pub disabled: bool,     // disabled by root, the data is kept
 }
 
 const USER_COLUMNS: [UserIden; 4] = [
@@ -34,6 +38,8 @@ impl DbTable for UserTable {
             .col(ColumnDef::new(UserIden::EncryptedPassword).string().not_null())
             .col(ColumnDef::new(UserIden::PasswordSalt).string().not_null())
             .col(ColumnDef::new(UserIden::Role).string().not_null())
+            .col(ColumnDef::new(UserIden::Active).boolean().not_null().default(false))
+            .col(ColumnDef::new(UserIden::Disabled).boolean().not_null().default(false))
             .to_string(SqliteQueryBuilder);
 
         sqlx::query(sql.as_str())
@@ -73,19 +79,51 @@ impl UserTable {
         Ok(users)
     }
 
-    /// Get all users with admin role and have access to the given app.
-    pub async fn get_admin_with_access(db_conn: &DbConnection, app_name: &str) -> Result<Vec<User>> {
+    /// Get all users ordered by username.
+    pub async fn get_all_users(db_conn: &DbConnection) -> Result<Vec<User>> {
         let sql = Query::select()
             .column(Asterisk)
             .from(UserIden::Table)
-            .and_where(
-                Expr::col(UserIden::Username).in_subquery(
+            .order_by(UserIden::Username, Order::Asc)
+            .to_string(SqliteQueryBuilder);
+
+        let users = sqlx::query_as(sql.as_str())
+            .fetch_all(&db_conn.pool)
+            .await?;
+
+        Ok(users)
+    }
+
+    /// Get all users with admin role and have access to the given environment of the app,
+    /// either granted directly (and not expired) or through the groups they are member of (for all environments).
+    pub async fn get_admin_with_access(db_conn: &DbConnection, app_name: &str, env: &str) -> Result<Vec<User>> {
+        let sql = Query::select()
+            .column(Asterisk)
+            .from(UserIden::Table)
+            .and_where(Expr::col(UserIden::Role).eq(UserRole::Admin.to_string()))
+            .cond_where(Cond::any()
+                .add(Expr::col(UserIden::Username).in_subquery(
                     Query::select()
                         .column(AccessRightIden::Username)
                         .from(AccessRightIden::Table)
                         .and_where(Expr::col(AccessRightIden::AppName).eq(app_name))
+                        .cond_where(AccessRightTable::not_expired_cond())
+                        .cond_where(AccessRightTable::env_cond(env))
+                        .take()
+                ))
+                .add(Expr::col(UserIden::Username).in_subquery(
+                    Query::select()
+                        .column(GroupMemberIden::Username)
+                        .from(GroupMemberIden::Table)
+                        .and_where(Expr::col(GroupMemberIden::GroupName).in_subquery(
+                            Query::select()
+                                .column(GroupAccessRightIden::GroupName)
+                                .from(GroupAccessRightIden::Table)
+                                .and_where(Expr::col(GroupAccessRightIden::AppName).eq(app_name))
+                                .take()
+                        ))
                         .take()
-                )
+                ))
             )
             .to_string(SqliteQueryBuilder);
 
@@ -193,6 +231,36 @@ impl UserTable {
             Ok(())
     }
 
+    /// Set if the user's public key is validated.
+    pub async fn set_active(db_conn: &DbConnection, username: &str, active: bool) -> Result<()> {
+        let sql = Query::update()
+            .table(UserIden::Table)
+            .value(UserIden::Active, active)
+            .and_where(Expr::col(UserIden::Username).eq(username))
+            .to_string(SqliteQueryBuilder);
+
+        sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Disable / enable the user.
+    pub async fn set_disabled(db_conn: &DbConnection, username: &str, disabled: bool) -> Result<()> {
+        let sql = Query::update()
+            .table(UserIden::Table)
+            .value(UserIden::Disabled, disabled)
+            .and_where(Expr::col(UserIden::Username).eq(username))
+            .to_string(SqliteQueryBuilder);
+
+        sqlx::query(sql.as_str())
+            .execute(&db_conn.pool)
+            .await?;
+
+        Ok(())
+    }
+
     /// Delete the user from the database.
     pub async fn delete_user(db_conn: &DbConnection, username: &str) -> Result<()> {
         let sql = Query::delete()
@@ -240,6 +308,10 @@ mod tests {
 
     use super::*;
     use crate::db::db_test_util::*;
+    use well_i_known_core::api::data::ALL_ENVIRONMENTS;
+    use crate::dao::admin_group::{AdminGroup, AdminGroupTable};
+    use crate::dao::group_access_right::{GroupAccessRight, GroupAccessRightTable};
+    use crate::dao::group_member::{GroupMember, GroupMemberTable};
 
     #[tokio::test]
     async fn test_create_and_get_user() {
@@ -279,6 +351,36 @@ mod tests {
         assert_eq!(valid, false);
     }
 
+    #[tokio::test]
+    async fn test_get_admin_with_access() {
+        let db_conn = create_test_db("test_get_admin_with_access").await;
+        UserTable::create_user(&db_conn, "u_admin", &UserRole::Admin, "password").await.unwrap();
+        UserTable::create_user(&db_conn, "u_admin2", &UserRole::Admin, "password").await.unwrap();
+        UserTable::create_user(&db_conn, "u_admin3", &UserRole::Admin, "password").await.unwrap();
+        UserTable::create_user(&db_conn, "u_app", &UserRole::App, "password").await.unwrap();
+
+        // direct access right
+        AccessRightTable::add_access_right(&db_conn, "u_admin", "u_app", ALL_ENVIRONMENTS, None).await.unwrap();
+        AccessRightTable::add_access_right(&db_conn, "u_admin3", "u_app", "staging", None).await.unwrap();
+        // access right through group
+        AdminGroupTable::create_group(&db_conn, &AdminGroup { group_name: "g_team".to_string() }).await.unwrap();
+        GroupMemberTable::add_member(&db_conn, &GroupMember {
+            group_name: "g_team".to_string(),
+            username: "u_admin2".to_string(),
+        }).await.unwrap();
+        GroupAccessRightTable::add_access_right(&db_conn, &GroupAccessRight {
+            group_name: "g_team".to_string(),
+            app_name: "u_app".to_string(),
+        }).await.unwrap();
+
+        let admins = UserTable::get_admin_with_access(&db_conn, "u_app", "prod").await.unwrap();
+        let mut admins: Vec<String> = admins.into_iter().map(|user| user.username).collect();
+        admins.sort();
+        assert_eq!(admins, vec!["u_admin".to_string(), "u_admin2".to_string()]);
+        let admins = UserTable::get_admin_with_access(&db_conn, "u_app", "staging").await.unwrap();
+        assert_eq!(admins.len(), 3);
+    }
+
     #[tokio::test]
     async fn test_update_user() {
         let db_conn = create_test_db("test_update_user").await;
@@ -287,4 +389,19 @@ mod tests {
         let user = UserTable::get_user(&db_conn, "test_user").await.unwrap().unwrap();
         assert_eq!(password::verify_password("new_password", &user.encrypted_password, &user.password_salt), true);
     }
+
+    #[tokio::test]
+    async fn test_user_starts_inactive() {
+        let db_conn = create_test_db("test_user_starts_inactive").await;
+        UserTable::create_user(&db_conn, "u_app", &UserRole::App, "password").await.unwrap();
+        let user = UserTable::get_user(&db_conn, "u_app").await.unwrap().unwrap();
+        assert!(!user.active);
+        assert!(!user.disabled);
+
+        UserTable::set_active(&db_conn, "u_app", true).await.unwrap();
+        UserTable::set_disabled(&db_conn, "u_app", true).await.unwrap();
+        let user = UserTable::get_user(&db_conn, "u_app").await.unwrap().unwrap();
+        assert!(user.active);
+        assert!(user.disabled);
+    }
 }
@@ -17,6 +17,9 @@ pub enum ApiError {
     RecordNotFound,     // Try to update / delete a record that does not exist
     DuplicateRecord,    // Try to create a record with a duplicate primary key
     InvalidArgument { argument: String, message: String },    // Invalid argument provided
+    LoginThrottled { retry_after: i64 },    // Too many failed login attempts, retry after the seconds
+    RevisionConflict { key: String, revision: i64 },    // The key is changed since the revision expected by the writer
+    ValueExpired { key: String, expires_at: i64 },      // The value of the key has expired, and the server refuses to serve it
 }
 
 /// Map the ApiError into a HTTP response
@@ -40,6 +43,18 @@ impl IntoResponse for ApiError {
                 let error_message = format!("Invalid argument: '{}'. {}", argument, message);
                 (StatusCode::BAD_REQUEST, error_message)
             },
+            ApiError::LoginThrottled{ retry_after } => {
+                let error_message = format!("Too many failed login attempts. Retry after {} seconds.", retry_after);
+                (StatusCode::TOO_MANY_REQUESTS, error_message)
+            },
+            ApiError::RevisionConflict{ key, revision } => {
+                let error_message = format!("Conflict: key '{}' is changed, the current revision is {}.", key, revision);
+                (StatusCode::CONFLICT, error_message)
+            },
+            ApiError::ValueExpired{ key, expires_at } => {
+                let error_message = format!("Value of key '{}' has expired at {}.", key, expires_at);
+                (StatusCode::GONE, error_message)
+            },
         };
         let body = Json(json!({
             "error": error_message,
@@ -6,18 +6,30 @@ mod dao;
 mod error;
 mod repository;
 mod server_state;
+mod task;
 pub mod server_init;
 
-use auth::jwt_controller::authorize_handler;
+use auth::jwt_controller::{authorize_handler, jwks_handler, login_challenge_answer_handler, login_challenge_handler, logout_handler, refresh_token_handler};
+use auth::client_cert::ClientCertAcceptor;
+use auth::jwt_key::JwtKeys;
+use auth::oidc::OidcVerifier;
 use controller::user::*;
 use controller::admin::*;
+use controller::api_token::*;
+use controller::approval::*;
+use controller::group::*;
+use controller::notification::*;
 use controller::config_data::*;
+use controller::config_schema::*;
 use repository::user::UserRepository;
 use config::server_config::*;
 use server_state::ServerState;
 
 // HTTP server framework
 use axum::{routing::{delete, get, post}, Router};
+use axum_server::tls_rustls::RustlsAcceptor;
+use std::net::SocketAddr;
+use std::sync::Arc;
 // error handling
 use anyhow::Result;
 // tracing
@@ -76,31 +88,103 @@ impl WIKServer {
             .expect("Fail to get root user.");
         server_config.root_user = Some(root_user);
 
-        let server_state = ServerState {
+        debug!("Loading JWT keys...");
+        let jwt_keys = match server_config.config.jwt_algorithm.as_str() {
+            "HS256" => {
+                warn!("JWT tokens are signed with HS256, they cannot be verified by other services.");
+                JwtKeys::new(server_config.config.jwt_secret.as_bytes())
+            },
+            "RS256" => JwtKeys::from_rsa_key_dir(
+                &server_config.get_jwt_certs_dir_path(),
+                server_config.config.jwt_key_rotation_interval,
+                server_config.config.access_token_duration,
+            ).expect("Fail to load JWT keys."),
+            algorithm => panic!("Unsupported JWT algorithm '{}'.", algorithm),
+        };
+
+        debug!("Loading OIDC provider keys...");
+        let oidc = OidcVerifier::new(&server_config.config.oidc).await
+            .expect("Fail to load OIDC provider keys.");
+
+        let server_state = Arc::new(ServerState {
             db_conn,
             config: server_config.clone(),
-            jwt_keys: auth::jwt_key::JwtKeys::new(server_config.config.jwt_secret.as_bytes()),
-        };
+            jwt_keys,
+            oidc,
+        });
+
+        debug!("Starting background tasks...");
+        task::start_background_tasks(server_state.clone());
 
         // register the routes
         trace!("Registering routes...");
         let app = Router::new()
+            .route("/.well-known/jwks.json", get(jwks_handler))
             .route("/login", post(authorize_handler))
+            .route("/login/challenge", post(login_challenge_handler))
+            .route("/login/challenge/answer", post(login_challenge_answer_handler))
+            .route("/logout", post(logout_handler))
+            .route("/token/refresh", post(refresh_token_handler))
             .route("/data", get(get_data_handler))
             .route("/data", post(alter_data_handler))
             .route("/data", delete(delete_data_handler))
+            .route("/data/bulk", get(get_bulk_data_handler))
+            .route("/data/batch", post(batch_data_handler))
+            .route("/data/subtree", delete(delete_subtree_handler))
+            .route("/data/apps", get(list_apps_handler))
+            .route("/data/keys", get(list_keys_handler))
+            .route("/data/expiring", get(list_expiring_data_handler))
+            .route("/data/versions", get(list_versions_handler))
+            .route("/data/version", get(get_version_handler))
+            .route("/data/promote", post(promote_data_handler))
+            .route("/data/rollback", post(rollback_data_handler))
+            .route("/data/retention", post(history_retention_handler))
+            .route("/data/schema", get(get_schema_handler))
+            .route("/data/schema", post(set_schema_handler))
+            .route("/data/schema", delete(delete_schema_handler))
             .route("/users/validate", post(validate_user_handler))
+            .route("/users/activate", post(activate_user_handler))
+            .route("/users/status", post(user_status_handler))
+            .route("/users/unlock", post(unlock_user_handler))
+            .route("/users/reset-token", post(create_reset_token_handler))
+            .route("/users/reset-password", post(reset_password_handler))
+            .route("/users", get(list_users_handler))
             .route("/users", post(alter_user_handler))
             .route("/users", delete(delete_user_handler))
+            .route("/admin/access", get(list_admin_access_handler))
             .route("/admin/access", post(create_admin_access_handler))
             .route("/admin/access", delete(delete_admin_access_handler))
+            .route("/admin/break-glass", get(list_break_glass_events_handler))
+            .route("/admin/break-glass", post(break_glass_handler))
+            .route("/approvals", get(list_approvals_handler))
+            .route("/approvals/approve", post(approve_operation_handler))
+            .route("/approvals/reject", post(reject_operation_handler))
+            .route("/tokens", get(list_api_tokens_handler))
+            .route("/tokens", post(create_api_token_handler))
+            .route("/tokens", delete(revoke_api_token_handler))
+            .route("/groups", post(create_group_handler))
+            .route("/groups", delete(delete_group_handler))
+            .route("/groups/members", post(add_group_member_handler))
+            .route("/groups/members", delete(delete_group_member_handler))
+            .route("/groups/access", post(create_group_access_handler))
+            .route("/groups/access", delete(delete_group_access_handler))
+            .route("/notifications", get(get_notifications_handler))
+            .route("/notifications", delete(delete_notification_handler))
             // register the server state so that it can be accessed in the handlers
-            .with_state(server_state.into());
+            .with_state(server_state);
         
         info!("Server started at: {}", server_config.config.get_server_ip());
         // start the server
-        axum_server::bind_rustls(server_config.config.get_server_ip(), tls_config)
-            .serve(app.into_make_service())
+        // the acceptor passes the client certificate (mTLS) to the requests
+        let acceptor = ClientCertAcceptor::new(
+            RustlsAcceptor::new(tls_config),
+            &server_config.config.tls.client_cert_identity,
+            &server_config.config.tls.client_cert_san_usernames,
+        );
+        axum_server::bind(server_config.config.get_server_ip())
+            .acceptor(acceptor)
+            // the source IP is needed for the login throttling
+            .serve(app.into_make_service_with_connect_info::<SocketAddr>())
             .await
             .unwrap();
 
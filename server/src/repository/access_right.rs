@@ -1,21 +1,76 @@
 use crate::db::db_connection::DbConnection;
 use crate::db::db_executor::db_result_handler;
-use crate::dao::access_right::AccessRightTable;
+use crate::dao::access_right::{AccessRight, AccessRightTable};
+use crate::dao::group_member::GroupMemberTable;
 use crate::dao::user::UserTable;
+use crate::repository::config_data::ConfigDataRepository;
 use crate::error::ApiError;
+use crate::WIKServerEnvironmentConfig;
 
+use well_i_known_core::api::data::ALL_ENVIRONMENTS;
+use chrono::Utc;
 use tracing::*;
 
 pub struct AccessRightRepository {}
 impl AccessRightRepository {
-    pub async fn check_access_right_exists(db_conn: &DbConnection, username: &str, app_name: &str) -> Result<bool, ApiError>{
+    pub async fn check_access_right_exists(db_conn: &DbConnection, username: &str, app_name: &str, env: Option<&str>) -> Result<bool, ApiError>{
         let exists = db_result_handler(
-            AccessRightTable::check_access_right_exists(db_conn, username, app_name).await,
+            AccessRightTable::check_access_right_exists(db_conn, username, app_name, env).await,
             "check_access_right_exists")?;
         Ok(exists)
     }
 
-    pub async fn add_access_right(db_conn: &DbConnection, username: &str, app_name: &str) -> Result<(), ApiError> {
+    /// Check if the user has access right to the given environment of the app (any environment if none),
+    /// either granted directly or through any group he is a member of (for all environments).
+    pub async fn check_effective_access_right(db_conn: &DbConnection, username: &str, app_name: &str, env: Option<&str>) -> Result<bool, ApiError>{
+        if AccessRightRepository::check_access_right_exists(db_conn, username, app_name, env).await? {
+            return Ok(true);
+        }
+
+        let exists = db_result_handler(
+            GroupMemberTable::check_group_access_exists(db_conn, username, app_name).await,
+            "check_group_access_exists")?;
+        Ok(exists)
+    }
+
+    /// Get the environments of the app the user has access to, directly or through a group.
+    /// Return none if the user has access to all environments.
+    pub async fn get_accessible_environments(db_conn: &DbConnection, username: &str, app_name: &str) -> Result<Option<Vec<String>>, ApiError> {
+        let group_access = db_result_handler(
+            GroupMemberTable::check_group_access_exists(db_conn, username, app_name).await,
+            "check_group_access_exists")?;
+        if group_access {
+            return Ok(None);
+        }
+
+        let environments = db_result_handler(
+            AccessRightTable::get_user_app_environments(db_conn, username, app_name).await,
+            "get_user_app_environments")?;
+        if environments.iter().any(|env| env == ALL_ENVIRONMENTS) {
+            return Ok(None);
+        }
+        Ok(Some(environments))
+    }
+
+    /// Get the (not expired) access rights, optionally filtered by admin and / or app.
+    pub async fn get_access_rights(db_conn: &DbConnection, username: Option<&str>, app_name: Option<&str>) -> Result<Vec<AccessRight>, ApiError> {
+        db_result_handler(
+            AccessRightTable::get_access_rights(db_conn, username, app_name).await,
+            "get_access_rights")
+    }
+
+    /// Grant the user access to the environment ("*" for all environments) of the app, until the given timestamp if provided.
+    /// The existing data of the app in the environment is encrypted for the user.
+    pub async fn add_access_right(db_conn: &DbConnection, server_config: &WIKServerEnvironmentConfig,
+        username: &str, app_name: &str, env: &str, expires_at: Option<i64>) -> Result<(), ApiError> {
+        if env != ALL_ENVIRONMENTS && !server_config.config.has_environment(env) {
+            warn!("Fail to add_access_right. Environment '{}' is not configured.", env);
+            return Err(ApiError::InvalidArgument {
+                argument: "env".to_string(),
+                message: "Given environment does not exist.".to_string(),
+            });
+        }
+
         // check if the app exists
         let app_exists = db_result_handler(
             UserTable::check_user_exists(db_conn, app_name).await,
@@ -30,19 +85,50 @@ impl AccessRightRepository {
         }
 
         db_result_handler(
-            AccessRightTable::add_access_right(db_conn, username, app_name).await, 
+            AccessRightTable::add_access_right(db_conn, username, app_name, env, expires_at).await, 
             "add_access_right")?;
 
+        ConfigDataRepository::share_app_data_with_user(db_conn, server_config, app_name, username).await?;
+
         Ok(())
     }
 
-    pub async fn delete_access_right(db_conn: &DbConnection, username: &str, app_name: &str) -> Result<(), ApiError> {
+    /// Revoke the user's direct access to the environment ("*" for all environments) of the app.
+    /// His data of the app is purged, except in the environments he still has access to (e.g. through a group).
+    pub async fn delete_access_right(db_conn: &DbConnection, username: &str, app_name: &str, env: &str) -> Result<(), ApiError> {
         db_result_handler(
-            AccessRightTable::delete_access_right(db_conn, username, app_name).await, 
+            AccessRightTable::delete_access_right(db_conn, username, app_name, env).await, 
             "delete_access_right")?;
 
+        AccessRightRepository::purge_data_if_no_access(db_conn, username, app_name).await?;
+
         Ok(())
     }
+
+    /// Remove the user's encrypted data of the app in the environments he no longer has access to.
+    pub async fn purge_data_if_no_access(db_conn: &DbConnection, username: &str, app_name: &str) -> Result<(), ApiError> {
+        let environments = AccessRightRepository::get_accessible_environments(db_conn, username, app_name).await?;
+        if let Some(environments) = environments {
+            debug!("User '{}' only has access to environments {:?} of app '{}'. Purging his other data.", username, environments, app_name);
+            ConfigDataRepository::purge_app_data_of_user(db_conn, app_name, username, &environments).await?;
+        }
+        Ok(())
+    }
+
+    /// Revoke all the access rights that have expired and purge their encrypted data.
+    /// Return the number of access rights revoked.
+    pub async fn revoke_expired_access_rights(db_conn: &DbConnection) -> Result<usize, ApiError> {
+        let expired = db_result_handler(
+            AccessRightTable::get_expired_access_rights(db_conn, Utc::now().timestamp()).await,
+            "get_expired_access_rights")?;
+
+        for access_right in &expired {
+            info!("Access right of '{}' to app '{}' ({}) expired.", access_right.username, access_right.app_name, access_right.env);
+            AccessRightRepository::delete_access_right(db_conn, &access_right.username, &access_right.app_name, &access_right.env).await?;
+        }
+
+        Ok(expired.len())
+    }
 }
 
 #[cfg(test)]
@@ -50,7 +136,7 @@ mod tests {
     use super::*;
     use well_i_known_core::modal::user::UserRole;
     use crate::db::db_test_util::*;
-    use crate::dao::user::UserTable;
+    use crate::dao::config_data::ConfigDataTable;
 
     async fn create_access_right_test_db(test_case_name: &str) -> DbConnection{
         // create the connection
@@ -65,7 +151,41 @@ mod tests {
     #[tokio::test]
     async fn test_check_access_right_exists(){
         let db_conn = create_access_right_test_db("check_access_right_exists").await;
-        let has_access = AccessRightRepository::check_access_right_exists(&db_conn, "u_admin", "test_app").await.unwrap();
+        let has_access = AccessRightRepository::check_access_right_exists(&db_conn, "u_admin", "test_app", None).await.unwrap();
         assert_eq!(has_access, false);
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_revoke_expired_access_rights(){
+        let db_conn = create_access_right_test_db("revoke_expired_access_rights").await;
+        let now = Utc::now().timestamp();
+        AccessRightTable::add_access_right(&db_conn, "u_admin", "u_app", ALL_ENVIRONMENTS, Some(now - 1)).await.unwrap();
+        ConfigDataTable::set_data_value(&db_conn, "u_app", "prod", "u_admin", "test_key", "encrypted").await.unwrap();
+
+        let revoked = AccessRightRepository::revoke_expired_access_rights(&db_conn).await.unwrap();
+        assert_eq!(revoked, 1);
+        let expired = AccessRightTable::get_expired_access_rights(&db_conn, now).await.unwrap();
+        assert_eq!(expired.len(), 0);
+        let data = ConfigDataTable::get_all_app_data_of_owner(&db_conn, "u_app", "prod", "u_admin").await.unwrap();
+        assert_eq!(data.len(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_purge_data_of_environment_without_access(){
+        let db_conn = create_access_right_test_db("purge_data_of_environment_without_access").await;
+        AccessRightTable::add_access_right(&db_conn, "u_admin", "u_app", "staging", None).await.unwrap();
+        ConfigDataTable::set_data_value(&db_conn, "u_app", "staging", "u_admin", "test_key", "encrypted").await.unwrap();
+        ConfigDataTable::set_data_value(&db_conn, "u_app", "prod", "u_admin", "test_key", "encrypted").await.unwrap();
+
+        let environments = AccessRightRepository::get_accessible_environments(&db_conn, "u_admin", "u_app").await.unwrap();
+        assert_eq!(environments, Some(vec!["staging".to_string()]));
+        assert!(AccessRightRepository::check_effective_access_right(&db_conn, "u_admin", "u_app", Some("staging")).await.unwrap());
+        assert!(!AccessRightRepository::check_effective_access_right(&db_conn, "u_admin", "u_app", Some("prod")).await.unwrap());
+
+        AccessRightRepository::purge_data_if_no_access(&db_conn, "u_admin", "u_app").await.unwrap();
+        let data = ConfigDataTable::get_all_app_data_of_owner(&db_conn, "u_app", "staging", "u_admin").await.unwrap();
+        assert_eq!(data.len(), 1);
+        let data = ConfigDataTable::get_all_app_data_of_owner(&db_conn, "u_app", "prod", "u_admin").await.unwrap();
+        assert_eq!(data.len(), 0);
+    }
+}
@@ -0,0 +1,201 @@
+use well_i_known_core::api::token::{ApiTokenResponse, ApiTokenScope};
+use well_i_known_core::crypto::password::{generate_random_id, generate_random_token, verify_password, Password};
+use well_i_known_core::modal::user::UserRole;
+use crate::auth::jwt_claim::JwtClaims;
+use crate::db::db_connection::DbConnection;
+use crate::db::db_executor::db_result_handler;
+use crate::dao::api_token::{ApiToken, ApiTokenTable};
+use crate::dao::user::UserTable;
+use crate::error::ApiError;
+
+use chrono::Utc;
+use std::str::FromStr;
+use tracing::*;
+
+/// Prefix of the API tokens, to tell them apart from the JWT tokens.
+pub const API_TOKEN_PREFIX: &str = "wik_";
+const API_TOKEN_ID_LENGTH: usize = 16;
+
+/// Named, long-lived tokens of the users (`wik_<id>_<secret>`, only the hash of the secret is stored).
+/// An API token is an alternative bearer credential of the data API,
+/// restricted by its scope (read-only / specific keys).
+pub struct ApiTokenRepository {}
+impl ApiTokenRepository {
+    fn to_response(token: ApiToken) -> Result<ApiTokenResponse, ApiError> {
+        let scope = serde_json::from_str::<ApiTokenScope>(&token.scope).map_err(|error| {
+            warn!("Fail to parse the scope of API token {}. Error: {}", token.id, error);
+            ApiError::ServerError
+        })?;
+        Ok(ApiTokenResponse {
+            id: token.id,
+            name: token.name,
+            username: token.username,
+            scope,
+            created_at: token.created_at,
+            expires_at: token.expires_at,
+        })
+    }
+
+    /// Issue a new API token to the user.
+    /// Return (token, detail), the token is not recoverable afterwards.
+    pub async fn create_token(db_conn: &DbConnection, username: &str, name: &str,
+        scope: &ApiTokenScope, duration: Option<i64>) -> Result<(String, ApiTokenResponse), ApiError> {
+
+        if name.is_empty() {
+            return Err(ApiError::InvalidArgument {
+                argument: "name".to_string(),
+                message: "Token name cannot be empty.".to_string(),
+            });
+        }
+        if let Some(duration) = duration {
+            if duration <= 0 {
+                return Err(ApiError::InvalidArgument {
+                    argument: "duration".to_string(),
+                    message: "Token duration must be positive.".to_string(),
+                });
+            }
+        }
+
+        let name_exists = db_result_handler(
+            ApiTokenTable::check_token_name_exists(db_conn, username, name).await,
+            "check_api_token_name_exists")?;
+        if name_exists {
+            return Err(ApiError::InvalidArgument {
+                argument: "name".to_string(),
+                message: format!("User '{}' already has a token named '{}'.", username, name),
+            });
+        }
+
+        let id = generate_random_id(API_TOKEN_ID_LENGTH);
+        let secret = generate_random_token();
+        let hashed_secret = Password::new(&secret);
+        if let Err(error) = hashed_secret {
+            warn!("Fail to hash the API token. Error: {}", error);
+            return Err(ApiError::TokenCreation);
+        }
+        let hashed_secret = hashed_secret.unwrap();
+
+        let now = Utc::now().timestamp();
+        let token = ApiToken {
+            id,
+            username: username.to_string(),
+            name: name.to_string(),
+            token_hash: hashed_secret.hash,
+            token_salt: hashed_secret.salt,
+            scope: serde_json::to_string(scope).map_err(|_| ApiError::ServerError)?,
+            created_at: now,
+            expires_at: duration.map(|duration| now + duration),
+        };
+        db_result_handler(
+            ApiTokenTable::add_token(db_conn, &token).await,
+            "add_api_token")?;
+        info!("API token '{}' ({}) is issued to user '{}'.", token.name, token.id, token.username);
+
+        let raw_token = format!("{}{}_{}", API_TOKEN_PREFIX, token.id, secret);
+        Ok((raw_token, ApiTokenRepository::to_response(token)?))
+    }
+
+    /// Authenticate the bearer API token.
+    /// Return the claims of the token's owner, restricted by the token's scope.
+    pub async fn authenticate(db_conn: &DbConnection, raw_token: &str) -> Result<JwtClaims, ApiError> {
+        let (id, secret) = raw_token
+            .strip_prefix(API_TOKEN_PREFIX)
+            .and_then(|token| token.split_once('_'))
+            .ok_or(ApiError::InvalidToken)?;
+
+        let token = db_result_handler(
+            ApiTokenTable::get_token(db_conn, id).await,
+            "get_api_token")?;
+        let token = match token {
+            Some(token) => token,
+            None => {
+                warn!("API token {} not found.", id);
+                return Err(ApiError::InvalidToken);
+            }
+        };
+
+        if let Some(expires_at) = token.expires_at {
+            if expires_at <= Utc::now().timestamp() {
+                warn!("API token {} of user '{}' expired.", token.id, token.username);
+                return Err(ApiError::InvalidToken);
+            }
+        }
+
+        if !verify_password(secret, &token.token_hash, &token.token_salt) {
+            warn!("Wrong secret of API token {}.", token.id);
+            return Err(ApiError::InvalidToken);
+        }
+
+        let user = db_result_handler(
+            UserTable::get_user(db_conn, &token.username).await,
+            "get_user")?
+            .ok_or(ApiError::InvalidToken)?;
+        let role = UserRole::from_str(&user.role).map_err(|_| ApiError::ServerError)?;
+        let response = ApiTokenRepository::to_response(token)?;
+
+        Ok(JwtClaims::from_api_token(&user.username, &role.to_string(), &response.id, response.expires_at, response.scope))
+    }
+
+    /// Get one token (if exists) by id.
+    pub async fn get_token(db_conn: &DbConnection, id: &str) -> Result<ApiTokenResponse, ApiError> {
+        let token = db_result_handler(
+            ApiTokenTable::get_token(db_conn, id).await,
+            "get_api_token")?;
+        match token {
+            Some(token) => ApiTokenRepository::to_response(token),
+            None => Err(ApiError::RecordNotFound),
+        }
+    }
+
+    /// Get the tokens of the user, or of all users if none.
+    pub async fn get_tokens(db_conn: &DbConnection, username: Option<&str>) -> Result<Vec<ApiTokenResponse>, ApiError> {
+        let tokens = db_result_handler(
+            ApiTokenTable::get_tokens(db_conn, username).await,
+            "get_api_tokens")?;
+        tokens.into_iter().map(ApiTokenRepository::to_response).collect()
+    }
+
+    /// Revoke the token, it is no longer accepted.
+    pub async fn revoke_token(db_conn: &DbConnection, id: &str) -> Result<(), ApiError> {
+        let deleted = db_result_handler(
+            ApiTokenTable::delete_token(db_conn, id).await,
+            "delete_api_token")?;
+        if !deleted {
+            return Err(ApiError::RecordNotFound);
+        }
+        info!("API token {} is revoked.", id);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::db_test_util::*;
+
+    #[tokio::test]
+    async fn test_api_token_lifecycle(){
+        let db_conn = create_test_db("test_api_token_lifecycle").await;
+        UserTable::create_user(&db_conn, "u_app", &UserRole::App, "password").await.unwrap();
+        let scope = ApiTokenScope { read_only: true, keys: Some(vec!["db_url".to_string()]) };
+
+        let (raw_token, detail) = ApiTokenRepository::create_token(&db_conn, "u_app", "ci", &scope, None).await.unwrap();
+        assert!(raw_token.starts_with(API_TOKEN_PREFIX));
+        // the name is unique per user
+        let result = ApiTokenRepository::create_token(&db_conn, "u_app", "ci", &scope, None).await;
+        assert!(matches!(result, Err(ApiError::InvalidArgument { .. })));
+
+        let claims = ApiTokenRepository::authenticate(&db_conn, &raw_token).await.unwrap();
+        assert_eq!(claims.sub, "u_app");
+        assert_eq!(claims.scope, Some(scope));
+
+        let wrong_secret = format!("{}{}_wrong", API_TOKEN_PREFIX, detail.id);
+        let result = ApiTokenRepository::authenticate(&db_conn, &wrong_secret).await;
+        assert!(matches!(result, Err(ApiError::InvalidToken)));
+
+        assert_eq!(ApiTokenRepository::get_tokens(&db_conn, Some("u_app")).await.unwrap().len(), 1);
+        ApiTokenRepository::revoke_token(&db_conn, &detail.id).await.unwrap();
+        let result = ApiTokenRepository::authenticate(&db_conn, &raw_token).await;
+        assert!(matches!(result, Err(ApiError::InvalidToken)));
+    }
+}
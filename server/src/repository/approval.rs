@@ -0,0 +1,345 @@
+use well_i_known_core::api::approval::{ApprovalStatus, FlaggedOperation, PendingOperationResponse, SealedValues};
+use well_i_known_core::crypto::cryptography::{Decryption, Encryption};
+use well_i_known_core::modal::notification::NotificationCategory;
+use well_i_known_core::modal::user::UserRole;
+use crate::auth::role_validation::RoleValidationUtil;
+use crate::db::db_connection::DbConnection;
+use crate::db::db_executor::db_result_handler;
+use crate::dao::pending_operation::{PendingOperation, PendingOperationTable};
+use crate::repository::access_right::AccessRightRepository;
+use crate::repository::config_data::{ConfigChangeSet, ConfigDataRepository};
+use crate::repository::group::GroupRepository;
+use crate::repository::notification::NotificationRepository;
+use crate::repository::user::UserRepository;
+use crate::error::ApiError;
+use crate::WIKServerEnvironmentConfig;
+
+use chrono::Utc;
+use std::collections::HashMap;
+use tracing::*;
+
+/// Four-eyes approval of the flagged (destructive) operations.
+/// A flagged operation is recorded as pending when requested
+/// and only executed after a second, distinct admin / root approved it.
+pub struct ApprovalRepository {}
+impl ApprovalRepository {
+    fn to_response(operation: PendingOperation) -> Result<PendingOperationResponse, ApiError> {
+        let flagged_operation = serde_json::from_str::<FlaggedOperation>(&operation.operation);
+        if let Err(error) = flagged_operation {
+            warn!("Fail to parse pending operation {}. Error: {}", operation.id, error);
+            return Err(ApiError::ServerError);
+        }
+
+        Ok(PendingOperationResponse {
+            id: operation.id,
+            operation: flagged_operation.unwrap(),
+            requester: operation.requester,
+            created_at: operation.created_at,
+            status: operation.status,
+            approver: operation.approver,
+            decided_at: operation.decided_at,
+        })
+    }
+
+    /// Get the operation (pending or decided).
+    async fn get_operation(db_conn: &DbConnection, id: i64) -> Result<PendingOperationResponse, ApiError> {
+        let operation = db_result_handler(
+            PendingOperationTable::get_operation(db_conn, id).await,
+            "get_pending_operation")?;
+
+        match operation {
+            Some(operation) => ApprovalRepository::to_response(operation),
+            None => {
+                warn!("Operation #{} not found.", id);
+                Err(ApiError::RecordNotFound)
+            }
+        }
+    }
+
+    /// Record the operation as pending and notify root for approval.
+    pub async fn request_operation(db_conn: &DbConnection, server_config: &WIKServerEnvironmentConfig,
+        requester: &str, operation: FlaggedOperation) -> Result<PendingOperationResponse, ApiError> {
+
+        let operation_json = serde_json::to_string(&operation);
+        if let Err(error) = operation_json {
+            warn!("Fail to serialize operation {:?}. Error: {}", operation, error);
+            return Err(ApiError::ServerError);
+        }
+
+        let created_at = Utc::now().timestamp();
+        let id = db_result_handler(
+            PendingOperationTable::add_operation(db_conn, &operation_json.unwrap(), requester, created_at).await,
+            "add_pending_operation")?;
+        info!("Operation {:?} requested by {} is pending for approval as #{}.", operation, requester, id);
+
+        let root = server_config.root_user.as_ref().unwrap();
+        if root.username != requester {
+            let message = format!("Operation #{} {:?} requested by '{}' is waiting for approval.", id, operation, requester);
+            NotificationRepository::notify(db_conn, &root.username, &NotificationCategory::ApprovalRequest, &message).await?;
+        }
+
+        Ok(PendingOperationResponse {
+            id,
+            operation,
+            requester: requester.to_string(),
+            created_at,
+            status: ApprovalStatus::Pending.to_string(),
+            approver: None,
+            decided_at: None,
+        })
+    }
+
+    /// Request to apply the batch with a bulk delete, the whole batch is applied once approved.
+    /// The values set wait encrypted with the root's key, so the pending operation never exposes them.
+    pub async fn request_batch(db_conn: &DbConnection, server_config: &WIKServerEnvironmentConfig,
+        requester: &str, app_name: &str, env: &str, changes: ConfigChangeSet) -> Result<PendingOperationResponse, ApiError> {
+
+        let operation = FlaggedOperation::ApplyBatch {
+            app: app_name.to_string(),
+            env: env.to_string(),
+            set: ApprovalRepository::seal_values(server_config, &changes.set)?,
+            plain: ApprovalRepository::seal_values(server_config, &changes.set_plain)?,
+            delete: changes.delete,
+            revisions: changes.expected_revisions,
+            expires_at: changes.expires_at,
+        };
+        ApprovalRepository::request_operation(db_conn, server_config, requester, operation).await
+    }
+
+    fn seal_values(server_config: &WIKServerEnvironmentConfig, values: &HashMap<String, String>) -> Result<SealedValues, ApiError> {
+        let root = server_config.root_user.as_ref().unwrap();
+        let mut sealed = HashMap::new();
+        for (config_key, value) in values {
+            let encrypted_value = root.key.public_key.encrypt_string(value);
+            if let Err(error) = encrypted_value {
+                warn!("Fail to encrypt the pending value of '{}'. Error: {}", config_key, error);
+                return Err(ApiError::ServerError);
+            }
+            sealed.insert(config_key.clone(), encrypted_value.unwrap());
+        }
+        Ok(SealedValues(sealed))
+    }
+
+    fn unseal_values(server_config: &WIKServerEnvironmentConfig, values: &SealedValues) -> Result<HashMap<String, String>, ApiError> {
+        let root = server_config.root_user.as_ref().unwrap();
+        let mut unsealed = HashMap::new();
+        for (config_key, encrypted_value) in &values.0 {
+            let value = root.key.private_key.decrypt_string(encrypted_value);
+            if let Err(error) = value {
+                warn!("Fail to decrypt the pending value of '{}'. Error: {}", config_key, error);
+                return Err(ApiError::ServerError);
+            }
+            unsealed.insert(config_key.clone(), value.unwrap());
+        }
+        Ok(unsealed)
+    }
+
+    pub async fn get_pending_operations(db_conn: &DbConnection) -> Result<Vec<PendingOperationResponse>, ApiError> {
+        let operations = db_result_handler(
+            PendingOperationTable::get_pending_operations(db_conn).await,
+            "get_pending_operations")?;
+
+        operations.into_iter()
+            .map(ApprovalRepository::to_response)
+            .collect()
+    }
+
+    /// Get the pending operation and check that the decider is not the requester.
+    async fn get_operation_to_decide(db_conn: &DbConnection, id: i64, decider: &str) -> Result<PendingOperationResponse, ApiError> {
+        let operation = db_result_handler(
+            PendingOperationTable::get_operation(db_conn, id).await,
+            "get_pending_operation")?;
+
+        let operation = match operation {
+            Some(operation) if operation.status == ApprovalStatus::Pending.to_string() => operation,
+            _ => {
+                warn!("Pending operation #{} not found.", id);
+                return Err(ApiError::RecordNotFound);
+            }
+        };
+
+        if operation.requester == decider {
+            let error_message = format!("User '{}' cannot decide the operation #{} requested by himself.", decider, id);
+            warn!(error_message);
+            return Err(ApiError::Unauthorized { message: error_message });
+        }
+
+        ApprovalRepository::to_response(operation)
+    }
+
+    /// Approve and execute the pending operation.
+    /// Return the approved operation, with the approver and the decision time.
+    pub async fn approve(db_conn: &DbConnection, server_config: &WIKServerEnvironmentConfig,
+        approver: &str, id: i64) -> Result<PendingOperationResponse, ApiError> {
+
+        let operation = ApprovalRepository::get_operation_to_decide(db_conn, id, approver).await?;
+
+        // claim the operation so that it cannot be approved twice
+        let claimed = db_result_handler(
+            PendingOperationTable::update_status(db_conn, id,
+                &ApprovalStatus::Pending, &ApprovalStatus::Approved, approver, Utc::now().timestamp()).await,
+            "approve_pending_operation")?;
+        if !claimed {
+            warn!("Pending operation #{} is already decided.", id);
+            return Err(ApiError::RecordNotFound);
+        }
+
+        info!("Operation #{} {:?} approved by {}.", id, operation.operation, approver);
+        let result = ApprovalRepository::execute(db_conn, server_config, &operation.operation, &operation.requester).await;
+        if result.is_err() {
+            warn!("Fail to execute approved operation #{}.", id);
+            db_result_handler(
+                PendingOperationTable::update_status(db_conn, id,
+                    &ApprovalStatus::Approved, &ApprovalStatus::Failed, approver, Utc::now().timestamp()).await,
+                "fail_pending_operation")?;
+        }
+
+        result?;
+        ApprovalRepository::get_operation(db_conn, id).await
+    }
+
+    /// Reject the pending operation. It will never be executed.
+    /// Return the rejected operation, with the approver and the decision time.
+    pub async fn reject(db_conn: &DbConnection, approver: &str, id: i64) -> Result<PendingOperationResponse, ApiError> {
+        ApprovalRepository::get_operation_to_decide(db_conn, id, approver).await?;
+
+        let rejected = db_result_handler(
+            PendingOperationTable::update_status(db_conn, id,
+                &ApprovalStatus::Pending, &ApprovalStatus::Rejected, approver, Utc::now().timestamp()).await,
+            "reject_pending_operation")?;
+        if !rejected {
+            warn!("Pending operation #{} is already decided.", id);
+            return Err(ApiError::RecordNotFound);
+        }
+
+        info!("Operation #{} rejected by {}.", id, approver);
+        ApprovalRepository::get_operation(db_conn, id).await
+    }
+
+    /// Throw Unauthorized if the requester can no longer request the operation,
+    /// e.g. he is disabled, or his access right to the app is revoked / expired since the request.
+    async fn throw_if_requester_cannot_execute(db_conn: &DbConnection, operation: &FlaggedOperation, requester: &str) -> Result<(), ApiError> {
+        let role = match UserRepository::get_user_role(db_conn, requester).await {
+            Ok(role) => role,
+            Err(ApiError::RecordNotFound) => {
+                return RoleValidationUtil::throw_if_unauthorized(false, requester, "execute the operation after being deleted");
+            },
+            Err(error) => return Err(error),
+        };
+        UserRepository::throw_if_disabled(db_conn, requester).await?;
+
+        let authorized = match operation {
+            FlaggedOperation::DeleteSubtree { app, env, .. } | FlaggedOperation::ApplyBatch { app, env, .. } => {
+                match role {
+                    UserRole::Root => true,
+                    UserRole::Admin => AccessRightRepository::check_effective_access_right(db_conn, requester, app, Some(env)).await?,
+                    UserRole::App => requester == app,
+                    UserRole::Auditor => false,
+                }
+            },
+            _ => matches!(role, UserRole::Admin | UserRole::Root),
+        };
+        RoleValidationUtil::throw_if_unauthorized(authorized, requester, &format!("execute the operation {:?}", operation))
+    }
+
+    /// Execute the approved operation, on behalf of the requester (e.g. as the author of the config change).
+    /// The requester's authorization is checked again, it may have changed since the request.
+    async fn execute(db_conn: &DbConnection, server_config: &WIKServerEnvironmentConfig,
+        operation: &FlaggedOperation, requester: &str) -> Result<(), ApiError> {
+        ApprovalRepository::throw_if_requester_cannot_execute(db_conn, operation, requester).await?;
+
+        match operation {
+            FlaggedOperation::DeleteUser { username } => {
+                UserRepository::delete_user(
+                    db_conn,
+                    username,
+                    &server_config.get_users_certs_path(username),
+                ).await
+            },
+            FlaggedOperation::DeleteAdminAccess { admin, app, env } => {
+                AccessRightRepository::delete_access_right(db_conn, admin, app, env).await
+            },
+            FlaggedOperation::DeleteGroupAccess { group, app } => {
+                GroupRepository::delete_access_right(db_conn, group, app).await
+            },
+            FlaggedOperation::DeleteGroup { group } => {
+                GroupRepository::delete_group(db_conn, group).await
+            },
+            FlaggedOperation::DeleteGroupMember { group, admin } => {
+                GroupRepository::delete_member(db_conn, group, admin).await
+            },
+            FlaggedOperation::DeleteSubtree { app, env, prefix } => {
+                ConfigDataRepository::delete_config_subtree(db_conn, server_config, app, env, requester, prefix).await?;
+                Ok(())
+            },
+            FlaggedOperation::ApplyBatch { app, env, set, plain, delete, revisions, expires_at } => {
+                let changes = ConfigChangeSet {
+                    set: ApprovalRepository::unseal_values(server_config, set)?,
+                    set_plain: ApprovalRepository::unseal_values(server_config, plain)?,
+                    delete: delete.clone(),
+                    expected_revisions: revisions.clone(),
+                    expires_at: expires_at.clone(),
+                };
+                ConfigDataRepository::alter_config_data_batch(db_conn, server_config, app, env, requester, &changes).await?;
+                Ok(())
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dao::access_right::AccessRightTable;
+    use crate::dao::user::UserTable;
+    use crate::db::db_test_util::*;
+
+    #[tokio::test]
+    async fn test_requester_cannot_approve_own_operation(){
+        let db_conn = create_test_db("test_requester_cannot_approve_own_operation").await;
+        let operation = serde_json::to_string(&FlaggedOperation::DeleteUser { username: "u_app".to_string() }).unwrap();
+        let id = PendingOperationTable::add_operation(&db_conn, &operation, "u_admin", 100).await.unwrap();
+
+        let result = ApprovalRepository::reject(&db_conn, "u_admin", id).await;
+        assert!(matches!(result, Err(ApiError::Unauthorized { .. })));
+
+        let rejected = ApprovalRepository::reject(&db_conn, "u_admin2", id).await.unwrap();
+        assert_eq!(rejected.status, ApprovalStatus::Rejected.to_string());
+        assert_eq!(rejected.approver.as_deref(), Some("u_admin2"));
+        assert!(rejected.decided_at.is_some());
+        let pending = ApprovalRepository::get_pending_operations(&db_conn).await.unwrap();
+        assert_eq!(pending.len(), 0);
+
+        // already decided
+        let result = ApprovalRepository::reject(&db_conn, "u_admin2", id).await;
+        assert!(matches!(result, Err(ApiError::RecordNotFound)));
+    }
+
+    #[tokio::test]
+    async fn test_requester_checked_again_when_executed(){
+        let db_conn = create_test_db("test_requester_checked_again_when_executed").await;
+        UserTable::create_user(&db_conn, "u_admin", &UserRole::Admin, "password").await.unwrap();
+        UserTable::create_user(&db_conn, "u_app", &UserRole::App, "password").await.unwrap();
+        let operation = FlaggedOperation::DeleteSubtree {
+            app: "u_app".to_string(),
+            env: "prod".to_string(),
+            prefix: "db".to_string(),
+        };
+
+        // the admin has no (or no longer) access to the app
+        let result = ApprovalRepository::throw_if_requester_cannot_execute(&db_conn, &operation, "u_admin").await;
+        assert!(matches!(result, Err(ApiError::Unauthorized { .. })));
+        AccessRightTable::add_access_right(&db_conn, "u_admin", "u_app", "prod", None).await.unwrap();
+        ApprovalRepository::throw_if_requester_cannot_execute(&db_conn, &operation, "u_admin").await.unwrap();
+
+        // the app itself, but not another one
+        ApprovalRepository::throw_if_requester_cannot_execute(&db_conn, &operation, "u_app").await.unwrap();
+        let operation = FlaggedOperation::DeleteGroupMember { group: "g_team".to_string(), admin: "u_admin".to_string() };
+        let result = ApprovalRepository::throw_if_requester_cannot_execute(&db_conn, &operation, "u_app").await;
+        assert!(matches!(result, Err(ApiError::Unauthorized { .. })));
+
+        // deleted requester
+        let result = ApprovalRepository::throw_if_requester_cannot_execute(&db_conn, &operation, "u_gone").await;
+        assert!(matches!(result, Err(ApiError::Unauthorized { .. })));
+    }
+}
@@ -0,0 +1,183 @@
+use well_i_known_core::crypto::password::{generate_random_token, verify_password, Password};
+use well_i_known_core::modal::user::UserRole;
+use crate::auth::jwt_claim::JwtClaims;
+use crate::auth::jwt_key::JwtKeys;
+use crate::db::db_connection::DbConnection;
+use crate::db::db_executor::db_result_handler;
+use crate::dao::auth_token::{AuthToken, AuthTokenTable};
+use crate::dao::user::UserTable;
+use crate::error::ApiError;
+use crate::WIKServerEnvironmentConfig;
+
+use chrono::Utc;
+use tracing::*;
+
+/// Separator between the jti and the secret in the refresh token.
+const REFRESH_TOKEN_SEPARATOR: char = '.';
+
+/// The tokens issued to the user after login / refresh.
+pub struct SessionTokens {
+    pub access_token: String,
+    pub refresh_token: String,
+    pub expires_in: i64,    // seconds the access token is valid for
+}
+
+/// Login sessions of the users.
+/// Each session is identified by a jti shared by its access tokens (short-lived JWT)
+/// and its refresh token (`<jti>.<secret>`, only the hash of the secret is stored).
+pub struct AuthTokenRepository {}
+impl AuthTokenRepository {
+    /// Start a new session for the user and issue its tokens.
+    pub async fn create_session(db_conn: &DbConnection, server_config: &WIKServerEnvironmentConfig, jwt_keys: &JwtKeys,
+        username: &str, role: &UserRole) -> Result<SessionTokens, ApiError> {
+
+        let jti = generate_random_token();
+        let secret = generate_random_token();
+        let hashed_secret = Password::new(&secret);
+        if let Err(error) = hashed_secret {
+            warn!("Fail to hash the refresh token. Error: {}", error);
+            return Err(ApiError::TokenCreation);
+        }
+        let hashed_secret = hashed_secret.unwrap();
+
+        let now = Utc::now().timestamp();
+        let token = AuthToken {
+            jti,
+            username: username.to_string(),
+            refresh_token_hash: hashed_secret.hash,
+            refresh_token_salt: hashed_secret.salt,
+            created_at: now,
+            expires_at: now + server_config.config.refresh_token_duration,
+            revoked: false,
+        };
+        db_result_handler(
+            AuthTokenTable::add_token(db_conn, &token).await,
+            "add_auth_token")?;
+
+        let expires_in = server_config.config.access_token_duration;
+        let access_token = JwtClaims::new(username, &role.to_string(), &token.jti, expires_in).gen_token(jwt_keys)?;
+
+        Ok(SessionTokens {
+            access_token,
+            refresh_token: format!("{}{}{}", token.jti, REFRESH_TOKEN_SEPARATOR, secret),
+            expires_in,
+        })
+    }
+
+    /// Exchange a valid refresh token for new tokens.
+    /// The old session is revoked (refresh token rotation), so each refresh token can only be used once.
+    pub async fn refresh_session(db_conn: &DbConnection, server_config: &WIKServerEnvironmentConfig, jwt_keys: &JwtKeys,
+        refresh_token: &str) -> Result<SessionTokens, ApiError> {
+
+        let (jti, secret) = refresh_token.split_once(REFRESH_TOKEN_SEPARATOR)
+            .ok_or(ApiError::InvalidToken)?;
+
+        let token = db_result_handler(
+            AuthTokenTable::get_token(db_conn, jti).await,
+            "get_auth_token")?;
+        let token = match token {
+            Some(token) => token,
+            None => {
+                warn!("Refresh token of session {} not found.", jti);
+                return Err(ApiError::InvalidToken);
+            }
+        };
+
+        if token.revoked || token.expires_at <= Utc::now().timestamp() {
+            warn!("Refresh token of session {} is revoked / expired.", jti);
+            return Err(ApiError::InvalidToken);
+        }
+
+        if !verify_password(secret, &token.refresh_token_hash, &token.refresh_token_salt) {
+            warn!("Wrong refresh token secret for session {}.", jti);
+            return Err(ApiError::InvalidToken);
+        }
+
+        // revoke first, so that concurrent refresh with the same token only succeeds once
+        let revoked = db_result_handler(
+            AuthTokenTable::revoke_token(db_conn, jti).await,
+            "revoke_auth_token")?;
+        if !revoked {
+            warn!("Session {} is revoked during refresh.", jti);
+            return Err(ApiError::InvalidToken);
+        }
+
+        // the role is read again in case the user has been changed
+        let user = db_result_handler(
+            UserTable::get_user(db_conn, &token.username).await,
+            "get_user")?;
+        let user = match user {
+            Some(user) => user,
+            None => {
+                warn!("User {} of session {} does not exist.", token.username, jti);
+                return Err(ApiError::InvalidToken);
+            }
+        };
+        let role = user.role.parse::<UserRole>().map_err(|_| ApiError::ServerError)?;
+
+        AuthTokenRepository::create_session(db_conn, server_config, jwt_keys, &user.username, &role).await
+    }
+
+    /// Check if the session of the token is not revoked.
+    pub async fn check_session_active(db_conn: &DbConnection, jti: &str) -> Result<bool, ApiError> {
+        db_result_handler(
+            AuthTokenTable::check_token_active(db_conn, jti).await,
+            "check_auth_token_active")
+    }
+
+    /// Revoke the session, i.e. logout.
+    pub async fn revoke_session(db_conn: &DbConnection, jti: &str) -> Result<(), ApiError> {
+        db_result_handler(
+            AuthTokenTable::revoke_token(db_conn, jti).await,
+            "revoke_auth_token")?;
+        Ok(())
+    }
+
+    /// Revoke all sessions of the user, e.g. after password change.
+    pub async fn revoke_all_sessions_of_user(db_conn: &DbConnection, username: &str) -> Result<(), ApiError> {
+        db_result_handler(
+            AuthTokenTable::revoke_all_tokens_of_user(db_conn, username).await,
+            "revoke_all_auth_tokens_of_user")
+    }
+
+    /// Delete the sessions whose refresh token has expired.
+    /// Return the number of deleted sessions.
+    pub async fn delete_expired_sessions(db_conn: &DbConnection) -> Result<u64, ApiError> {
+        db_result_handler(
+            AuthTokenTable::delete_expired_tokens(db_conn, Utc::now().timestamp()).await,
+            "delete_expired_auth_tokens")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use crate::config::server_config::WIKServerConfig;
+    use crate::db::db_test_util::*;
+
+    #[tokio::test]
+    async fn test_refresh_token_rotation(){
+        let db_conn = create_test_db("test_refresh_token_rotation").await;
+        UserTable::create_user(&db_conn, "u_admin", &UserRole::Admin, "password").await.unwrap();
+        let server_config = WIKServerEnvironmentConfig {
+            base_dir: PathBuf::new(),
+            config: WIKServerConfig::default(),
+            root_user: None,
+        };
+        let jwt_keys = JwtKeys::new(b"secret");
+
+        let tokens = AuthTokenRepository::create_session(&db_conn, &server_config, &jwt_keys, "u_admin", &UserRole::Admin).await.unwrap();
+        let refreshed = AuthTokenRepository::refresh_session(&db_conn, &server_config, &jwt_keys, &tokens.refresh_token).await.unwrap();
+        assert_ne!(tokens.refresh_token, refreshed.refresh_token);
+
+        // the old refresh token cannot be used again
+        let result = AuthTokenRepository::refresh_session(&db_conn, &server_config, &jwt_keys, &tokens.refresh_token).await;
+        assert!(matches!(result, Err(ApiError::InvalidToken)));
+
+        // password change revokes all sessions
+        AuthTokenRepository::revoke_all_sessions_of_user(&db_conn, "u_admin").await.unwrap();
+        let result = AuthTokenRepository::refresh_session(&db_conn, &server_config, &jwt_keys, &refreshed.refresh_token).await;
+        assert!(matches!(result, Err(ApiError::InvalidToken)));
+    }
+}
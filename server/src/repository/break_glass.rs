@@ -0,0 +1,104 @@
+use well_i_known_core::api::data::ALL_ENVIRONMENTS;
+use well_i_known_core::modal::notification::NotificationCategory;
+use well_i_known_core::modal::user::UserRole;
+use crate::db::db_connection::DbConnection;
+use crate::db::db_executor::db_result_handler;
+use crate::dao::break_glass_event::{BreakGlassEvent, BreakGlassEventTable};
+use crate::dao::user::UserTable;
+use crate::repository::access_right::AccessRightRepository;
+use crate::repository::notification::NotificationRepository;
+use crate::error::ApiError;
+use crate::WIKServerEnvironmentConfig;
+
+use chrono::Utc;
+use tracing::*;
+
+/// Minimum length of the written justification of a break-glass access.
+const MIN_JUSTIFICATION_LENGTH: usize = 10;
+
+/// Emergency access of an admin to an app without a prior access right.
+pub struct BreakGlassRepository {}
+impl BreakGlassRepository {
+    /// Grant the admin a temporary access right to the app.
+    /// The app's data is re-encrypted for the admin from the root's copy,
+    /// the event is recorded and root is notified.
+    /// The access right is revoked by the access expiry background task.
+    /// Return the expiry timestamp of the access right.
+    pub async fn break_glass(db_conn: &DbConnection, server_config: &WIKServerEnvironmentConfig,
+        username: &str, app_name: &str, justification: &str) -> Result<i64, ApiError> {
+
+        let justification = justification.trim();
+        if justification.len() < MIN_JUSTIFICATION_LENGTH {
+            warn!("Break-glass request of {} without enough justification.", username);
+            return Err(ApiError::InvalidArgument {
+                argument: "justification".to_string(),
+                message: format!("Justification must be at least {} characters.", MIN_JUSTIFICATION_LENGTH),
+            });
+        }
+
+        let app_exists = db_result_handler(
+            UserTable::check_user_with_role_exists(db_conn, app_name, &UserRole::App).await,
+            "check app exists")?;
+        if !app_exists {
+            warn!("Fail to break glass. App '{}' does not exist.", app_name);
+            return Err(ApiError::InvalidArgument {
+                argument: "app".to_string(),
+                message: "Given app does not exist.".to_string(),
+            });
+        }
+
+        // break glass grants all environments, e.g. prod for an admin with access to staging only
+        if AccessRightRepository::get_accessible_environments(db_conn, username, app_name).await?.is_none() {
+            warn!("{} already has access to app {} but try to break glass.", username, app_name);
+            return Err(ApiError::InvalidArgument {
+                argument: "app".to_string(),
+                message: "User already has access to the app.".to_string(),
+            });
+        }
+
+        let now = Utc::now().timestamp();
+        let expires_at = now + server_config.config.break_glass_duration;
+        AccessRightRepository::add_access_right(db_conn, server_config, username, app_name, ALL_ENVIRONMENTS, Some(expires_at)).await?;
+
+        warn!("Break-glass access of {} to app {}. Justification: {}", username, app_name, justification);
+        db_result_handler(
+            BreakGlassEventTable::add_event(db_conn, username, app_name, justification, now, expires_at).await,
+            "add_break_glass_event")?;
+
+        let root = server_config.root_user.as_ref().unwrap();
+        let message = format!("Admin '{}' broke glass to access app '{}'. Justification: {}", username, app_name, justification);
+        NotificationRepository::notify(db_conn, &root.username, &NotificationCategory::BreakGlass, &message).await?;
+
+        Ok(expires_at)
+    }
+
+    pub async fn get_events(db_conn: &DbConnection) -> Result<Vec<BreakGlassEvent>, ApiError> {
+        db_result_handler(
+            BreakGlassEventTable::get_events(db_conn).await,
+            "get_break_glass_events")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::db_test_util::*;
+    use crate::config::server_config::WIKServerConfig;
+
+    #[tokio::test]
+    async fn test_break_glass_require_justification(){
+        let db_conn = create_test_db("test_break_glass_require_justification").await;
+        UserTable::create_user(&db_conn, "u_admin", &UserRole::Admin, "password").await.unwrap();
+        UserTable::create_user(&db_conn, "u_app", &UserRole::App, "password").await.unwrap();
+        let server_config = WIKServerEnvironmentConfig {
+            base_dir: get_test_path("output"),
+            config: WIKServerConfig::default(),
+            root_user: None,
+        };
+
+        let result = BreakGlassRepository::break_glass(&db_conn, &server_config, "u_admin", "u_app", "  urgent  ").await;
+        assert!(matches!(result, Err(ApiError::InvalidArgument { .. })));
+        let events = BreakGlassRepository::get_events(&db_conn).await.unwrap();
+        assert_eq!(events.len(), 0);
+    }
+}
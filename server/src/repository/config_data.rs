@@ -1,85 +1,598 @@
-use well_i_known_core::crypto::cryptography::Encryption;
+use well_i_known_core::crypto::cryptography::{Decryption, Encryption};
+use well_i_known_core::modal::notification::NotificationCategory;
+use well_i_known_core::modal::util::config_key::{is_in_subtree, normalize_config_key, validate_config_key, validate_key_prefix, KEY_ALT_SEPARATOR};
+use well_i_known_core::modal::user::{ServerUserKeyModal, UserRole};
 use crate::db::db_connection::DbConnection;
 use crate::db::db_executor::db_result_handler;
-use crate::dao::config_data::ConfigDataTable;
+use crate::dao::config_data::{ConfigData, ConfigDataChanges, ConfigDataTable};
+use crate::dao::config_plain_data::{ConfigPlainData, ConfigPlainDataTable};
+use crate::dao::config_data_expiry::ConfigDataExpiry;
+use crate::dao::config_data_revision::{ConfigDataRevisionTable, RevisionConflict};
+use crate::dao::user::UserTable;
+use crate::repository::access_right::AccessRightRepository;
+use crate::repository::config_expiry::ConfigExpiryRepository;
+use crate::repository::config_schema::ConfigSchemaRepository;
+use crate::repository::config_version::ConfigVersionRepository;
+use crate::repository::notification::NotificationRepository;
 use crate::repository::user::UserRepository;
 use crate::error::ApiError;
 use crate::WIKServerEnvironmentConfig;
 
+use chrono::Utc;
+use std::collections::HashMap;
 use tracing::*;
 
+/// Convert the revision conflict of a change to ApiError::RevisionConflict,
+/// and the other errors as database errors.
+fn data_change_result_handler<T>(db_result: anyhow::Result<T>, operation_name: &str) -> Result<T, ApiError> {
+    if let Err(error) = &db_result {
+        if let Some(conflict) = error.downcast_ref::<RevisionConflict>() {
+            warn!("Fail to {}. Conflict: {}", operation_name, conflict);
+            return Err(ApiError::RevisionConflict { key: conflict.key.clone(), revision: conflict.current });
+        }
+    }
+    db_result_handler(db_result, operation_name)
+}
+
+/// The changes of the config of an app requested by a user.
+#[derive(Default)]
+pub struct ConfigChangeSet {
+    pub set: HashMap<String, String>,               // secret key => plaintext value, encrypted for each user with access
+    pub set_plain: HashMap<String, String>,         // non-secret key => value, stored once in plaintext
+    pub delete: Vec<String>,
+    pub expected_revisions: HashMap<String, i64>,   // key => revision expected by the writer, only the given keys are checked
+    pub expires_at: HashMap<String, i64>,           // key set => expiry timestamp, the other keys set never expire
+}
+
+impl ConfigChangeSet {
+    pub fn is_set(&self, key: &str) -> bool {
+        self.set.contains_key(key) || self.set_plain.contains_key(key)
+    }
+}
+
 pub struct ConfigDataRepository {}
 impl ConfigDataRepository {
-    /// Get the encrypted data for the given 'app, key, user' pair.
-    pub async fn get_config_data(db_conn: &DbConnection, app_name: &str, username: &str, config_key: &str) -> Result<String, ApiError> {
+    /// Get the encrypted data for the given 'app, env, key, user' pair.
+    pub async fn get_config_data(db_conn: &DbConnection, app_name: &str, env: &str, username: &str, config_key: &str) -> Result<String, ApiError> {
         // get the record
         let config_data = db_result_handler(
-            ConfigDataTable::get_data_value(db_conn, app_name, username, config_key).await,
+            ConfigDataTable::get_data_value(db_conn, app_name, env, username, config_key).await,
             "check app exists")?;
 
         // check if the record exists
         if config_data.is_none() {
-            warn!("Fail to get_config_data. Did not store the '{}'-'{}' for {}.", app_name, config_key, username);
+            warn!("Fail to get_config_data. Did not store the '{}'-'{}' ({}) for {}.", app_name, config_key, env, username);
             return Err(ApiError::RecordNotFound);
         }
 
         Ok(config_data.unwrap())
     }
 
-    /// Check if the record exists for the given 'app, key, user' pair.
-    pub async fn check_data_exists(db_conn: &DbConnection, app_name: &str, username: &str, config_key: &str) -> Result<bool, ApiError>{
+    /// Get the apps whose data is visible to the user.
+    /// Root / Auditor: all apps. Admin: the apps he has (direct or group) access right to. App: itself.
+    pub async fn get_visible_apps(db_conn: &DbConnection, username: &str, role: &UserRole) -> Result<Vec<String>, ApiError> {
+        let apps = match role {
+            UserRole::Root | UserRole::Admin | UserRole::Auditor => db_result_handler(
+                UserTable::get_users_with_role(db_conn, &UserRole::App).await,
+                "get_users_with_role")?,
+            UserRole::App => return Ok(vec![username.to_string()]),
+        };
+
+        let mut visible_apps = vec![];
+        for app in apps {
+            if role != &UserRole::Admin
+                || AccessRightRepository::check_effective_access_right(db_conn, username, &app.username, None).await? {
+                visible_apps.push(app.username);
+            }
+        }
+        Ok(visible_apps)
+    }
+
+    /// Get the keys (without values) of the app in the environment stored for the user (all secret keys if none, e.g. for the auditor),
+    /// and the non-secret keys of the app there.
+    pub async fn get_app_keys(db_conn: &DbConnection, app_name: &str, env: &str, username: Option<&str>) -> Result<Vec<String>, ApiError> {
+        let mut keys = match username {
+            Some(username) => db_result_handler(
+                ConfigDataTable::get_app_keys_of_owner(db_conn, app_name, env, username).await,
+                "get_app_keys_of_owner")?,
+            None => db_result_handler(
+                ConfigDataTable::get_app_keys(db_conn, app_name, env).await,
+                "get_app_keys")?,
+        };
+        keys.extend(ConfigDataRepository::get_bulk_plain_config_data(db_conn, app_name, env, None).await?.into_keys());
+        Ok(keys)
+    }
+
+    /// Get the value of the non-secret key, none if the key is not a non-secret entry (e.g. a secret).
+    pub async fn get_plain_config_data(db_conn: &DbConnection, app_name: &str, env: &str, config_key: &str) -> Result<Option<String>, ApiError> {
+        db_result_handler(
+            ConfigPlainDataTable::get_value(db_conn, app_name, env, config_key).await,
+            "get_plain_config_data")
+    }
+
+    /// Get the non-secret entries of the app in the environment in one query, of the given keys only if provided.
+    /// Return key => plaintext value, the keys not found are omitted.
+    pub async fn get_bulk_plain_config_data(db_conn: &DbConnection, app_name: &str, env: &str,
+        config_keys: Option<&[String]>) -> Result<HashMap<String, String>, ApiError> {
+        let plain_data = match config_keys {
+            Some(config_keys) => db_result_handler(
+                ConfigPlainDataTable::get_app_data_for_keys(db_conn, app_name, env, config_keys).await,
+                "get_plain_app_data_for_keys")?,
+            None => db_result_handler(
+                ConfigPlainDataTable::get_app_data(db_conn, app_name, env).await,
+                "get_plain_app_data")?,
+        };
+
+        Ok(plain_data.into_iter().map(|data| (data.key, data.value)).collect())
+    }
+
+    /// Get the encrypted data of the app in the environment stored for the user in one query,
+    /// of the given keys only if provided.
+    /// Return key => ciphertext, the keys not found are omitted.
+    pub async fn get_bulk_config_data(db_conn: &DbConnection, app_name: &str, env: &str, username: &str,
+        config_keys: Option<&[String]>) -> Result<HashMap<String, String>, ApiError> {
+        let config_data = match config_keys {
+            Some(config_keys) => db_result_handler(
+                ConfigDataTable::get_app_data_of_owner_for_keys(db_conn, app_name, env, username, config_keys).await,
+                "get_app_data_of_owner_for_keys")?,
+            None => db_result_handler(
+                ConfigDataTable::get_all_app_data_of_owner(db_conn, app_name, env, username).await,
+                "get_all_app_data_of_owner")?,
+        };
+
+        Ok(config_data.into_iter().map(|data| (data.key, data.value)).collect())
+    }
+
+    /// Check if the record exists for the given 'app, env, key, user' pair.
+    pub async fn check_data_exists(db_conn: &DbConnection, app_name: &str, env: &str, username: &str, config_key: &str) -> Result<bool, ApiError>{
         let exists = db_result_handler(
-            ConfigDataTable::check_data_exists(db_conn, app_name, username, config_key).await,
+            ConfigDataTable::check_data_exists(db_conn, app_name, env, username, config_key).await,
             "check_data_exists")?;
         Ok(exists)
     }
 
-    /// Add the data for the give 'app, key' pair.
-    /// The data is encrypted for the app & the admin has access to the data.
+    /// Add the data for the give 'app, env, key' pair.
+    /// The secret data is encrypted for the app & the admin has access to the environment of the app,
+    /// the non-secret data is stored once in plaintext.
     /// params:
     /// - db_conn: the database connection
     /// - server_config: the server configuration
     /// - app_name: the app name
-    /// - config_key: the config key
-    /// - config_value: the config value in plaintext
+    /// - env: the environment of the app
+    /// - author: the user setting the value, kept in the history
+    /// - changes: the config key & value (in plaintext), as a secret or a non-secret entry
+    ///
+    /// Return the new revisions of the changed keys.
     pub async fn alter_config_data(db_conn: &DbConnection, server_config: &WIKServerEnvironmentConfig, 
-        app_name: &str, config_key: &str, config_value: &str) -> Result<(), ApiError> {
+        app_name: &str, env: &str, author: &str, changes: &ConfigChangeSet) -> Result<HashMap<String, i64>, ApiError> {
 
-        let exists = db_result_handler(
-            ConfigDataTable::check_data_exists_for_key(db_conn, app_name, config_key).await, "check_data_exists_for_key")?;
+        let (_, revisions) = ConfigDataRepository::apply_changes(db_conn, server_config, app_name, env, author, changes).await?;
+        Ok(revisions)
+    }
+
+    /// Set and delete several keys of the app in the environment in one transaction, either all changes apply or none do.
+    /// One change notification is sent to the other users with access to the environment of the app for the whole batch.
+    /// params:
+    /// - author: the user making the changes, not notified
+    /// - changes: the keys to set (secret or non-secret) / delete, with the expected revisions;
+    ///   the batch fails with RevisionConflict if any key is changed since the expected revision
+    ///
+    /// Return the new revisions of the changed keys.
+    pub async fn alter_config_data_batch(db_conn: &DbConnection, server_config: &WIKServerEnvironmentConfig,
+        app_name: &str, env: &str, author: &str, changes: &ConfigChangeSet) -> Result<HashMap<String, i64>, ApiError> {
 
-        if exists {
-            info!("The '{}'-'{}' already exists.", app_name, config_key);
-            // delete all existing data records for this app-key pair
-            ConfigDataRepository::delete_config_data(db_conn, app_name, config_key).await?;
+        if changes.set.is_empty() && changes.set_plain.is_empty() && changes.delete.is_empty() {
+            return Err(ApiError::InvalidArgument {
+                argument: "set".to_string(),
+                message: "The batch has no change.".to_string(),
+            });
         }
+        if let Some(key) = changes.set_plain.keys().find(|key| changes.set.contains_key(*key)) {
+            return Err(ApiError::InvalidArgument {
+                argument: "plain".to_string(),
+                message: format!("Key '{}' cannot be both secret and non-secret.", key),
+            });
+        }
+        if let Some(key) = changes.delete.iter().find(|key| changes.is_set(key)) {
+            return Err(ApiError::InvalidArgument {
+                argument: "delete".to_string(),
+                message: format!("Key '{}' cannot be both set and deleted.", key),
+            });
+        }
+        if let Some(key) = changes.expected_revisions.keys().find(|key| !changes.is_set(key) && !changes.delete.contains(*key)) {
+            return Err(ApiError::InvalidArgument {
+                argument: "revisions".to_string(),
+                message: format!("Key '{}' is not changed by the batch.", key),
+            });
+        }
+
+        let (recipients, revisions) = ConfigDataRepository::apply_changes(db_conn, server_config, app_name, env, author, changes).await?;
+        let mut set_keys: Vec<&String> = changes.set.keys().chain(changes.set_plain.keys()).collect();
+        set_keys.sort();
+        info!("Batch of {} set / {} deleted keys of '{}' ({}) applied by {}.", set_keys.len(), changes.delete.len(), app_name, env, author);
+
+        let message = format!("Config of app '{}' ({}) is changed by {}. Set: {:?}. Deleted: {:?}.", app_name, env, author, set_keys, changes.delete);
+        for recipient in recipients.iter().filter(|recipient| recipient.as_str() != author) {
+            NotificationRepository::notify(db_conn, recipient, &NotificationCategory::DataChange, &message).await?;
+        }
+        Ok(revisions)
+    }
+
+    /// Validate the new values against the schema of the app, encrypt the secret ones for every user with access to the environment of the app,
+    /// and replace the records of the changed keys in one transaction (with the new versions kept in the history).
+    /// Return the users with access to the environment of the app, and the new revisions of the changed keys.
+    async fn apply_changes(db_conn: &DbConnection, server_config: &WIKServerEnvironmentConfig, app_name: &str, env: &str,
+        author: &str, changes: &ConfigChangeSet) -> Result<(Vec<String>, HashMap<String, i64>), ApiError> {
+
+        ConfigDataRepository::validate_environment(server_config, env)?;
+        // the existing keys can be deleted even if they do not follow the grammar of the keys
+        for config_key in changes.set.keys().chain(changes.set_plain.keys()) {
+            if let Err(error) = validate_config_key(config_key) {
+                return Err(ApiError::InvalidArgument {
+                    argument: "key".to_string(),
+                    message: error,
+                });
+            }
+        }
+        let now = Utc::now().timestamp();
+        for (config_key, expires_at) in &changes.expires_at {
+            if !changes.is_set(config_key) {
+                return Err(ApiError::InvalidArgument {
+                    argument: "expires_at".to_string(),
+                    message: format!("Key '{}' is not set by the change.", config_key),
+                });
+            }
+            if *expires_at <= now {
+                return Err(ApiError::InvalidArgument {
+                    argument: "expires_at".to_string(),
+                    message: format!("Expiry of key '{}' is not in the future.", config_key),
+                });
+            }
+        }
+        // the plaintext values are validated against the app's schema before encryption
+        ConfigSchemaRepository::validate_changes(db_conn, app_name, changes).await?;
 
         let users_with_access_right = UserRepository::get_users_with_access_to(
-            db_conn, server_config, app_name).await?;
+            db_conn, server_config, app_name, env).await?;
+
+        // for each user, encrypt the data as a new config data record
+        let mut records = Vec::new();
+        for (config_key, config_value) in &changes.set {
+            for user in &users_with_access_right {
+                let encrypted_value = user.public_key.encrypt_string(config_value);
+                if let Err(error) = encrypted_value {
+                    warn!("Fail to encrypt data for {}. Error: {}", user.username, error);
+                    return Err(ApiError::ServerError);
+                }
+
+                records.push(ConfigData {
+                    app_name: app_name.to_string(),
+                    env: env.to_string(),
+                    key: config_key.clone(),
+                    owner: user.username.clone(),
+                    value: encrypted_value.unwrap(),
+                });
+            }
+        }
+        let plain_records = changes.set_plain.iter()
+            .map(|(config_key, config_value)| ConfigPlainData {
+                app_name: app_name.to_string(),
+                env: env.to_string(),
+                key: config_key.clone(),
+                value: config_value.clone(),
+            })
+            .collect();
+        let expiries = changes.expires_at.iter()
+            .map(|(config_key, expires_at)| ConfigDataExpiry {
+                app_name: app_name.to_string(),
+                env: env.to_string(),
+                key: config_key.clone(),
+                expires_at: *expires_at,
+                warned_lead_time: None,
+            })
+            .collect();
+
+        // the existing records (and expiries) of the changed keys are replaced
+        let changed_keys = changes.set.keys().chain(changes.set_plain.keys()).chain(&changes.delete)
+            .map(|key| (key.clone(), changes.expected_revisions.get(key).copied()))
+            .collect();
+        let retention = ConfigVersionRepository::get_history_retention(db_conn, server_config, app_name).await?;
+        let revisions = data_change_result_handler(
+            ConfigDataTable::apply_app_data_batch(db_conn, app_name, env, &ConfigDataChanges { changed_keys, records, plain_records, expiries },
+                author, now, retention).await,
+            "apply_app_data_batch")?;
+
+        Ok((users_with_access_right.into_iter().map(|user| user.username).collect(), revisions))
+    }
+
+    /// Encrypt all the existing data of the given app for the given user,
+    /// in the environments the user has access to (all environments for the app itself).
+    /// The values are obtained by decrypting the root's copy.
+    /// Keys that the user already has a record of are skipped.
+    /// Nothing is shared with the inactive / disabled user.
+    pub async fn share_app_data_with_user(db_conn: &DbConnection, server_config: &WIKServerEnvironmentConfig,
+        app_name: &str, username: &str) -> Result<(), ApiError> {
+
+        if !UserRepository::is_data_recipient(db_conn, username).await? {
+            info!("User {} is inactive / disabled, the data of {} is not shared.", username, app_name);
+            return Ok(());
+        }
+
+        let user = ServerUserKeyModal::new(username, &server_config.get_users_certs_path(username));
+        if let Err(error) = user {
+            warn!("Fail to load the public key of {}. Error: {}", username, error);
+            return Err(ApiError::ServerError);
+        }
+        let user = user.unwrap();
+
+        let accessible_environments = match username == app_name {
+            true => None,
+            false => AccessRightRepository::get_accessible_environments(db_conn, username, app_name).await?,
+        };
+        for env in &server_config.config.environments {
+            if accessible_environments.as_ref().is_some_and(|environments| !environments.contains(env)) {
+                continue;
+            }
+            ConfigDataRepository::share_app_env_data_with_user(db_conn, server_config, app_name, env, &user).await?;
+            ConfigVersionRepository::share_app_versions_with_user(db_conn, server_config, app_name, env, &user).await?;
+        }
+        Ok(())
+    }
+
+    /// Encrypt the existing data of the environment of the app for the user, from the root's copy.
+    async fn share_app_env_data_with_user(db_conn: &DbConnection, server_config: &WIKServerEnvironmentConfig,
+        app_name: &str, env: &str, user: &ServerUserKeyModal) -> Result<(), ApiError> {
+        let username = &user.username;
+        let root = server_config.root_user.as_ref().unwrap();
+        let root_data = db_result_handler(
+            ConfigDataTable::get_all_app_data_of_owner(db_conn, app_name, env, &root.username).await,
+            "get_all_app_data_of_owner")?;
 
-        // for each user, encrypt the data & create a new config data record
-        for user in users_with_access_right {
-            let encrypted_value = user.public_key.encrypt_string(config_value);
+        for data in root_data {
+            let exists = ConfigDataRepository::check_data_exists(db_conn, app_name, env, username, &data.key).await?;
+            if exists {
+                continue;
+            }
+
+            let plaintext = root.key.private_key.decrypt_string(&data.value);
+            if let Err(error) = plaintext {
+                warn!("Fail to decrypt the root copy of '{}'-'{}'. Error: {}", app_name, data.key, error);
+                return Err(ApiError::ServerError);
+            }
+
+            let encrypted_value = user.public_key.encrypt_string(&plaintext.unwrap());
             if let Err(error) = encrypted_value {
-                warn!("Fail to encrypt data for {}. Error: {}", user.username, error);
+                warn!("Fail to encrypt data for {}. Error: {}", username, error);
                 return Err(ApiError::ServerError);
             }
 
             db_result_handler(
-                ConfigDataTable::set_data_value(db_conn, app_name, &user.username, config_key, &encrypted_value.unwrap()).await,
+                ConfigDataTable::set_data_value(db_conn, app_name, env, username, &data.key, &encrypted_value.unwrap()).await,
                 "add_config_data")?;
         }
+        Ok(())
+    }
+
+    /// Encrypt the data of all apps the user can access for the user,
+    /// e.g. after the user is activated / enabled.
+    pub async fn share_all_data_with_user(db_conn: &DbConnection, server_config: &WIKServerEnvironmentConfig,
+        username: &str, role: &UserRole) -> Result<(), ApiError> {
 
+        let apps = match role {
+            UserRole::App => vec![username.to_string()],
+            UserRole::Admin => {
+                let apps = db_result_handler(
+                    UserTable::get_users_with_role(db_conn, &UserRole::App).await,
+                    "get_users_with_role")?;
+                let mut accessible_apps = Vec::new();
+                for app in apps {
+                    if AccessRightRepository::check_effective_access_right(db_conn, username, &app.username, None).await? {
+                        accessible_apps.push(app.username);
+                    }
+                }
+                accessible_apps
+            },
+            // root has all data already, auditor never has any
+            UserRole::Root | UserRole::Auditor => vec![],
+        };
+
+        for app in &apps {
+            ConfigDataRepository::share_app_data_with_user(db_conn, server_config, app, username).await?;
+        }
         Ok(())
     }
 
-    /// Remove the data for the give 'app, key' pair.
-    /// All the encrypted data for the app & the admin has access to the data will be deleted.
-    pub async fn delete_config_data(db_conn: &DbConnection, app_name: &str, config_key: &str) -> Result<(), ApiError> {
+    /// Remove all the encrypted data (and its history) of the given app that is stored for the given user,
+    /// except the data of the kept environments.
+    pub async fn purge_app_data_of_user(db_conn: &DbConnection, app_name: &str, username: &str, kept_envs: &[String]) -> Result<(), ApiError> {
         db_result_handler(
-            ConfigDataTable::delete_all_app_key_data(db_conn, app_name, config_key).await, 
-            "delete_config_data")?;
+            ConfigDataTable::delete_all_app_data_for_owner(db_conn, app_name, username, kept_envs).await,
+            "delete_all_app_data_for_owner")?;
+        ConfigVersionRepository::purge_app_versions_of_user(db_conn, app_name, username, kept_envs).await?;
 
         Ok(())
     }
+
+    /// Remove the data for the give 'app, env, key' pair.
+    /// All the encrypted data for the app & the admin has access to the data (or the non-secret entry) will be deleted.
+    /// Fails with RevisionConflict if the key is changed since the expected revision (if any).
+    /// Return the new revision of the key.
+    pub async fn delete_config_data(db_conn: &DbConnection, server_config: &WIKServerEnvironmentConfig,
+        app_name: &str, env: &str, author: &str, config_key: &str, expected_revision: Option<i64>) -> Result<i64, ApiError> {
+        let changes = ConfigChangeSet {
+            delete: vec![config_key.to_string()],
+            expected_revisions: expected_revision.map(|revision| (config_key.to_string(), revision)).into_iter().collect(),
+            ..Default::default()
+        };
+        let (_, revisions) = ConfigDataRepository::apply_changes(db_conn, server_config, app_name, env, author, &changes).await?;
+
+        Ok(revisions[config_key])
+    }
+
+    /// Copy the value of the key from the environment to the next one (in the server's order of environments),
+    /// as a change of the target environment: validated, kept in the history and notified like a normal write.
+    /// The secret is copied from the root's copy, the non-secret entry as it is, with the expiry of the value (if any).
+    /// Fails with RevisionConflict if the key of the target environment is changed since the expected revision (if any).
+    /// Return the target environment and the new revision of the key there.
+    pub async fn promote_config_data(db_conn: &DbConnection, server_config: &WIKServerEnvironmentConfig,
+        app_name: &str, env: &str, author: &str, config_key: &str, expected_revision: Option<i64>) -> Result<(String, i64), ApiError> {
+        ConfigDataRepository::validate_environment(server_config, env)?;
+        let target_env = match server_config.config.next_environment(env) {
+            Some(target_env) => target_env.to_string(),
+            None => {
+                warn!("Fail to promote '{}'-'{}'. Environment '{}' is the last one.", app_name, config_key, env);
+                return Err(ApiError::InvalidArgument {
+                    argument: "env".to_string(),
+                    message: "The last environment cannot be promoted.".to_string(),
+                });
+            },
+        };
+
+        let mut changes = ConfigChangeSet::default();
+        match ConfigDataRepository::get_plain_config_data(db_conn, app_name, env, config_key).await? {
+            Some(value) => {
+                changes.set_plain.insert(config_key.to_string(), value);
+            },
+            None => {
+                let root = server_config.root_user.as_ref().unwrap();
+                let encrypted_value = ConfigDataRepository::get_config_data(db_conn, app_name, env, &root.username, config_key).await?;
+                let plaintext = root.key.private_key.decrypt_string(&encrypted_value);
+                if let Err(error) = plaintext {
+                    warn!("Fail to decrypt the root copy of '{}'-'{}' ({}). Error: {}", app_name, config_key, env, error);
+                    return Err(ApiError::ServerError);
+                }
+                changes.set.insert(config_key.to_string(), plaintext.unwrap());
+            },
+        }
+        if let Some(revision) = expected_revision {
+            changes.expected_revisions.insert(config_key.to_string(), revision);
+        }
+        if let Some(expires_at) = ConfigExpiryRepository::get_expiry(db_conn, app_name, env, config_key).await? {
+            changes.expires_at.insert(config_key.to_string(), expires_at);
+        }
+
+        let revisions = ConfigDataRepository::alter_config_data_batch(db_conn, server_config, app_name, &target_env, author, &changes).await?;
+        info!("'{}'-'{}' is promoted from {} to {} by {}.", app_name, config_key, env, target_env, author);
+        let revision = revisions[config_key];
+        Ok((target_env, revision))
+    }
+
+    /// Delete all keys of the app in the environment in the subtree of the prefix in one transaction,
+    /// e.g. when the bulk delete is approved.
+    /// Return the new revisions of the deleted keys.
+    pub async fn delete_config_subtree(db_conn: &DbConnection, server_config: &WIKServerEnvironmentConfig,
+        app_name: &str, env: &str, author: &str, prefix: &str) -> Result<HashMap<String, i64>, ApiError> {
+        let keys = ConfigDataRepository::get_subtree_keys(db_conn, server_config, app_name, env, prefix).await?;
+        if keys.is_empty() {
+            warn!("No key of '{}' ({}) in the subtree '{}' to delete.", app_name, env, prefix);
+            return Err(ApiError::RecordNotFound);
+        }
+
+        let changes = ConfigChangeSet { delete: keys, ..Default::default() };
+        ConfigDataRepository::alter_config_data_batch(db_conn, server_config, app_name, env, author, &changes).await
+    }
+
+    /// Get the keys of the app in the environment in the subtree of the prefix, secret or not.
+    /// Every secret key has a copy for the root, so the root's keys are all the secret keys.
+    pub async fn get_subtree_keys(db_conn: &DbConnection, server_config: &WIKServerEnvironmentConfig,
+        app_name: &str, env: &str, prefix: &str) -> Result<Vec<String>, ApiError> {
+        let root = server_config.root_user.as_ref().unwrap();
+        let mut keys = ConfigDataRepository::get_app_keys(db_conn, app_name, env, Some(&root.username)).await?;
+        keys.retain(|key| is_in_subtree(key, prefix));
+        keys.sort();
+        Ok(keys)
+    }
+
+    /// Rename the keys stored with the alternative separator, e.g. `db.url` to `db/url`,
+    /// since the keys of the requests are normalized (the keys were not validated before the key grammar).
+    /// A key is kept as it is (and warned of) if its normalized key is already used in the environment of the app.
+    /// Return the number of keys renamed.
+    pub async fn normalize_stored_keys(db_conn: &DbConnection) -> Result<usize, ApiError> {
+        let keys = db_result_handler(
+            ConfigDataTable::get_keys_containing(db_conn, &KEY_ALT_SEPARATOR.to_string()).await,
+            "get_keys_containing")?;
+
+        let mut renamed = 0;
+        for (app_name, env, config_key) in keys {
+            let new_key = normalize_config_key(&config_key);
+            let used = db_result_handler(
+                ConfigDataTable::check_key_used(db_conn, &app_name, &env, &new_key).await,
+                "check_key_used")?;
+            if used {
+                warn!("Key '{}' of app '{}' ({}) cannot be renamed to '{}', which is already used. Please resolve it manually.",
+                    config_key, app_name, env, new_key);
+                continue;
+            }
+
+            db_result_handler(
+                ConfigDataTable::rename_key(db_conn, &app_name, &env, &config_key, &new_key).await,
+                "rename_key")?;
+            info!("Key '{}' of app '{}' ({}) is renamed to '{}'.", config_key, app_name, env, new_key);
+            renamed += 1;
+        }
+        Ok(renamed)
+    }
+
+    /// Throw InvalidArgument if the prefix is not a valid prefix of a subtree of keys.
+    pub fn validate_key_prefix(prefix: &str) -> Result<(), ApiError> {
+        if let Err(error) = validate_key_prefix(prefix) {
+            return Err(ApiError::InvalidArgument {
+                argument: "prefix".to_string(),
+                message: error,
+            });
+        }
+        Ok(())
+    }
+
+    /// Throw InvalidArgument if the environment is not one of the server's environments.
+    pub fn validate_environment(server_config: &WIKServerEnvironmentConfig, env: &str) -> Result<(), ApiError> {
+        if !server_config.config.has_environment(env) {
+            warn!("Environment '{}' is not configured.", env);
+            return Err(ApiError::InvalidArgument {
+                argument: "env".to_string(),
+                message: format!("Environment '{}' does not exist.", env),
+            });
+        }
+        Ok(())
+    }
+
+    /// Get the revision of the key in the environment, 0 if the key is never changed.
+    pub async fn get_config_revision(db_conn: &DbConnection, app_name: &str, env: &str, config_key: &str) -> Result<i64, ApiError> {
+        db_result_handler(
+            ConfigDataRevisionTable::get_revision(db_conn, app_name, env, config_key).await,
+            "get_config_revision")
+    }
+
+    /// Get the revisions of all keys of the app in the environment ever changed.
+    pub async fn get_app_revisions(db_conn: &DbConnection, app_name: &str, env: &str) -> Result<HashMap<String, i64>, ApiError> {
+        let revisions = db_result_handler(
+            ConfigDataRevisionTable::get_app_revisions(db_conn, app_name, env).await,
+            "get_app_revisions")?;
+
+        Ok(revisions.into_iter().map(|revision| (revision.key, revision.revision)).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::db_test_util::*;
+
+    #[tokio::test]
+    async fn test_normalize_stored_keys() {
+        let db_conn = create_test_db("test_normalize_stored_keys").await;
+        UserTable::create_user(&db_conn, "u_admin", &UserRole::Admin, "password").await.unwrap();
+        UserTable::create_user(&db_conn, "u_app", &UserRole::App, "password").await.unwrap();
+        // stored before the key grammar
+        ConfigDataTable::set_data_value(&db_conn, "u_app", "default", "u_admin", "db.url", "encrypted").await.unwrap();
+        ConfigDataTable::set_data_value(&db_conn, "u_app", "default", "u_admin", "db.user", "encrypted").await.unwrap();
+        ConfigDataTable::set_data_value(&db_conn, "u_app", "default", "u_admin", "db/user", "encrypted").await.unwrap();
+
+        let renamed = ConfigDataRepository::normalize_stored_keys(&db_conn).await.unwrap();
+        assert_eq!(renamed, 1);
+        let mut keys = ConfigDataTable::get_app_keys(&db_conn, "u_app", "default").await.unwrap();
+        keys.sort();
+        // 'db.user' is kept, 'db/user' is already used
+        assert_eq!(keys, vec!["db.user".to_string(), "db/url".to_string(), "db/user".to_string()]);
+    }
 }
@@ -0,0 +1,200 @@
+use well_i_known_core::api::data::ExpiringDataResponse;
+use well_i_known_core::modal::notification::NotificationCategory;
+use well_i_known_core::modal::user::UserRole;
+use crate::db::db_connection::DbConnection;
+use crate::db::db_executor::db_result_handler;
+use crate::dao::config_data_expiry::ConfigDataExpiryTable;
+use crate::repository::access_right::AccessRightRepository;
+use crate::repository::notification::NotificationRepository;
+use crate::repository::user::UserRepository;
+use crate::error::ApiError;
+use crate::WIKServerEnvironmentConfig;
+
+use chrono::Utc;
+use std::collections::HashMap;
+use tracing::*;
+
+/// Get the lead time to warn of for the value expiring in the remaining seconds:
+/// the shortest lead time reached, 0 if expired, none if no lead time is reached yet.
+fn reached_lead_time(lead_times: &[i64], remaining: i64) -> Option<i64> {
+    if remaining <= 0 {
+        return Some(0);
+    }
+    lead_times.iter()
+        .filter(|lead_time| remaining <= **lead_time)
+        .min()
+        .copied()
+}
+
+/// Expiry of the config values (e.g. certificates, vendor API keys).
+/// The expiry is set with the value of a key, and removed when the key is set again or deleted.
+pub struct ConfigExpiryRepository {}
+impl ConfigExpiryRepository {
+    /// Get the expiry of the key in the environment, none if the key does not expire.
+    pub async fn get_expiry(db_conn: &DbConnection, app_name: &str, env: &str, config_key: &str) -> Result<Option<i64>, ApiError> {
+        db_result_handler(
+            ConfigDataExpiryTable::get_expiry(db_conn, app_name, env, config_key).await,
+            "get_config_expiry")
+    }
+
+    /// Get the expiries of all the expiring keys of the app in the environment.
+    pub async fn get_app_expiries(db_conn: &DbConnection, app_name: &str, env: &str) -> Result<HashMap<String, i64>, ApiError> {
+        let expiries = db_result_handler(
+            ConfigDataExpiryTable::get_app_expiries(db_conn, app_name, env).await,
+            "get_app_expiries")?;
+
+        Ok(expiries.into_iter().map(|expiry| (expiry.key, expiry.expires_at)).collect())
+    }
+
+    /// Throw ValueExpired if the value of the key has expired, and the server refuses to serve the expired values.
+    pub async fn check_not_expired(db_conn: &DbConnection, server_config: &WIKServerEnvironmentConfig,
+        app_name: &str, env: &str, config_key: &str) -> Result<(), ApiError> {
+        if !server_config.config.config_expiry.refuse_expired {
+            return Ok(());
+        }
+
+        match ConfigExpiryRepository::get_expiry(db_conn, app_name, env, config_key).await? {
+            Some(expires_at) if expires_at <= Utc::now().timestamp() => {
+                warn!("Refuse to serve '{}'-'{}' ({}), expired at {}.", app_name, config_key, env, expires_at);
+                Err(ApiError::ValueExpired { key: config_key.to_string(), expires_at })
+            },
+            _ => Ok(()),
+        }
+    }
+
+    /// Get the keys expiring within the seconds (or expired) of the apps & environments visible to the user, the earliest first.
+    /// The app sees its own keys, the admin the keys of the environments he has access to, and root / auditor all keys
+    /// (the report only has the key names and dates, never the values).
+    pub async fn get_expiring_data(db_conn: &DbConnection, username: &str, role: &UserRole,
+        app_name: Option<&str>, within: i64) -> Result<Vec<ExpiringDataResponse>, ApiError> {
+        let now = Utc::now().timestamp();
+        let mut expiries = db_result_handler(
+            ConfigDataExpiryTable::get_expiring(db_conn, now + within).await,
+            "get_expiring")?;
+        if let Some(app_name) = app_name {
+            expiries.retain(|expiry| expiry.app_name == app_name);
+        }
+
+        // app => the environments accessible by the admin, none for all environments
+        let mut accessible_environments: HashMap<String, Option<Vec<String>>> = HashMap::new();
+        let mut expiring_data = Vec::new();
+        for expiry in expiries {
+            let visible = match role {
+                UserRole::Root | UserRole::Auditor => true,
+                UserRole::App => expiry.app_name == username,
+                UserRole::Admin => {
+                    if !accessible_environments.contains_key(&expiry.app_name) {
+                        let environments = AccessRightRepository::get_accessible_environments(db_conn, username, &expiry.app_name).await?;
+                        accessible_environments.insert(expiry.app_name.clone(), environments);
+                    }
+                    match &accessible_environments[&expiry.app_name] {
+                        Some(environments) => environments.contains(&expiry.env),
+                        None => true,
+                    }
+                },
+            };
+            if !visible {
+                continue;
+            }
+
+            expiring_data.push(ExpiringDataResponse {
+                remaining_seconds: (expiry.expires_at - now).max(0),
+                app: expiry.app_name,
+                env: expiry.env,
+                key: expiry.key,
+                expires_at: expiry.expires_at,
+            });
+        }
+        Ok(expiring_data)
+    }
+
+    /// Notify the users with access to the expiring values, once at each warning lead time reached and once expired.
+    /// Return the number of warnings sent (one per value).
+    pub async fn warn_expiring_data(db_conn: &DbConnection, server_config: &WIKServerEnvironmentConfig) -> Result<usize, ApiError> {
+        let lead_times = &server_config.config.config_expiry.warning_lead_times;
+        let now = Utc::now().timestamp();
+        let longest_lead_time = lead_times.iter().max().copied().unwrap_or(0).max(0);
+        let expiries = db_result_handler(
+            ConfigDataExpiryTable::get_expiring(db_conn, now + longest_lead_time).await,
+            "get_expiring")?;
+
+        let mut warned = 0;
+        for expiry in expiries {
+            let remaining = expiry.expires_at - now;
+            let lead_time = match reached_lead_time(lead_times, remaining) {
+                Some(lead_time) => lead_time,
+                None => continue,
+            };
+            // already warned of this (or a shorter) lead time
+            if expiry.warned_lead_time.is_some_and(|warned_lead_time| warned_lead_time <= lead_time) {
+                continue;
+            }
+
+            let message = if remaining <= 0 {
+                format!("Config '{}' of app '{}' ({}) has expired at {}.", expiry.key, expiry.app_name, expiry.env, expiry.expires_at)
+            } else {
+                format!("Config '{}' of app '{}' ({}) expires at {}, in {} hours.",
+                    expiry.key, expiry.app_name, expiry.env, expiry.expires_at, remaining / 3600)
+            };
+            let recipients = UserRepository::get_users_with_access_to(db_conn, server_config, &expiry.app_name, &expiry.env).await?;
+            for recipient in &recipients {
+                NotificationRepository::notify(db_conn, &recipient.username, &NotificationCategory::DataExpiry, &message).await?;
+            }
+
+            db_result_handler(
+                ConfigDataExpiryTable::set_warned_lead_time(db_conn, &expiry.app_name, &expiry.env, &expiry.key, lead_time).await,
+                "set_warned_lead_time")?;
+            info!("{} Warned {} users.", message, recipients.len());
+            warned += 1;
+        }
+
+        Ok(warned)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dao::config_data_expiry::ConfigDataExpiry;
+    use crate::dao::user::UserTable;
+    use crate::db::db_test_util::*;
+
+    #[tokio::test]
+    async fn test_expiring_data_of_all_apps_for_root_and_auditor() {
+        let db_conn = create_test_db("test_expiring_data_of_all_apps_for_root_and_auditor").await;
+        let now = Utc::now().timestamp();
+        let mut conn = db_conn.pool.acquire().await.unwrap();
+        for app_name in ["u_app", "u_app2"] {
+            UserTable::create_user(&db_conn, app_name, &UserRole::App, "password").await.unwrap();
+            ConfigDataExpiryTable::add_expiry(&mut conn, &ConfigDataExpiry {
+                app_name: app_name.to_string(),
+                env: "default".to_string(),
+                key: "tls/cert".to_string(),
+                expires_at: now + 3600,
+                warned_lead_time: None,
+            }).await.unwrap();
+        }
+
+        for (username, role) in [("u_root", UserRole::Root), ("u_auditor", UserRole::Auditor)] {
+            let expiring = ConfigExpiryRepository::get_expiring_data(&db_conn, username, &role, None, 7200).await.unwrap();
+            assert_eq!(expiring.len(), 2);
+            let expiring = ConfigExpiryRepository::get_expiring_data(&db_conn, username, &role, Some("u_app2"), 7200).await.unwrap();
+            assert_eq!(expiring.len(), 1);
+            assert_eq!(expiring[0].key, "tls/cert");
+        }
+
+        let expiring = ConfigExpiryRepository::get_expiring_data(&db_conn, "u_app", &UserRole::App, None, 7200).await.unwrap();
+        assert_eq!(expiring.len(), 1);
+        assert_eq!(expiring[0].app, "u_app");
+    }
+
+    #[test]
+    fn test_reached_lead_time() {
+        let lead_times = [30 * 24 * 3600, 7 * 24 * 3600, 24 * 3600];
+        assert_eq!(reached_lead_time(&lead_times, 60 * 24 * 3600), None);
+        assert_eq!(reached_lead_time(&lead_times, 10 * 24 * 3600), Some(30 * 24 * 3600));
+        assert_eq!(reached_lead_time(&lead_times, 3600), Some(24 * 3600));
+        assert_eq!(reached_lead_time(&lead_times, 0), Some(0));
+        assert_eq!(reached_lead_time(&[], 3600), None);
+    }
+}
@@ -0,0 +1,152 @@
+use well_i_known_core::crypto::cryptography::Decryption;
+use well_i_known_core::modal::config_schema::AppConfigSchema;
+use crate::db::db_connection::DbConnection;
+use crate::db::db_executor::db_result_handler;
+use crate::dao::app_schema::{AppSchema, AppSchemaTable};
+use crate::repository::config_data::{ConfigChangeSet, ConfigDataRepository};
+use crate::error::ApiError;
+use crate::WIKServerEnvironmentConfig;
+
+use chrono::Utc;
+use std::collections::HashMap;
+use tracing::*;
+
+/// Schemas of the apps' config.
+/// The writes of the config of an app with a schema are validated against it before encryption.
+pub struct ConfigSchemaRepository {}
+impl ConfigSchemaRepository {
+    /// Get the schema of the app, none if the app has no schema.
+    pub async fn get_schema(db_conn: &DbConnection, app_name: &str) -> Result<Option<AppConfigSchema>, ApiError> {
+        let schema = db_result_handler(
+            AppSchemaTable::get_schema(db_conn, app_name).await,
+            "get_app_schema")?;
+
+        match schema {
+            Some(schema) => match serde_json::from_str(&schema) {
+                Ok(schema) => Ok(Some(schema)),
+                Err(error) => {
+                    warn!("Fail to parse the schema of app '{}'. Error: {}", app_name, error);
+                    Err(ApiError::ServerError)
+                },
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// Set the schema of the app, shared by all environments of the app.
+    /// The schema is rejected if the current config of the app in any environment does not match it,
+    /// the environments without any config yet are not checked.
+    pub async fn set_schema(db_conn: &DbConnection, server_config: &WIKServerEnvironmentConfig,
+        app_name: &str, author: &str, schema: &AppConfigSchema) -> Result<(), ApiError> {
+        schema.validate_definition().map_err(|message| ApiError::InvalidArgument {
+            argument: "schema".to_string(),
+            message,
+        })?;
+
+        // the current config (root's copy) of every environment must match the new schema
+        let root = server_config.root_user.as_ref().unwrap();
+        for env in &server_config.config.environments {
+            let current_data = ConfigDataRepository::get_bulk_config_data(db_conn, app_name, env, &root.username, None).await?;
+            let mut current_config = HashMap::new();
+            for (key, encrypted_value) in current_data {
+                let plaintext = root.key.private_key.decrypt_string(&encrypted_value);
+                if let Err(error) = plaintext {
+                    warn!("Fail to decrypt the root copy of '{}'-'{}' ({}). Error: {}", app_name, key, env, error);
+                    return Err(ApiError::ServerError);
+                }
+                current_config.insert(key, plaintext.unwrap());
+            }
+            current_config.extend(ConfigDataRepository::get_bulk_plain_config_data(db_conn, app_name, env, None).await?);
+            if current_config.is_empty() {
+                continue;
+            }
+            ConfigSchemaRepository::validate_config(schema, &current_config)?;
+        }
+
+        let schema_json = serde_json::to_string(schema);
+        if let Err(error) = schema_json {
+            warn!("Fail to serialize the schema of app '{}'. Error: {}", app_name, error);
+            return Err(ApiError::ServerError);
+        }
+        db_result_handler(
+            AppSchemaTable::set_schema(db_conn, &AppSchema {
+                app_name: app_name.to_string(),
+                schema: schema_json.unwrap(),
+                updated_by: author.to_string(),
+                updated_at: Utc::now().timestamp(),
+            }).await,
+            "set_app_schema")?;
+        info!("Schema of app '{}' is set by {}.", app_name, author);
+        Ok(())
+    }
+
+    /// Remove the schema of the app, the writes are no longer validated.
+    pub async fn delete_schema(db_conn: &DbConnection, app_name: &str) -> Result<(), ApiError> {
+        db_result_handler(
+            AppSchemaTable::delete_schema(db_conn, app_name).await,
+            "delete_app_schema")
+    }
+
+    /// Validate the changes of the app's config against its schema (if any).
+    /// Throw InvalidArgument (of the key) if a value does not match the schema, or a required key is deleted.
+    pub async fn validate_changes(db_conn: &DbConnection, app_name: &str, changes: &ConfigChangeSet) -> Result<(), ApiError> {
+        let schema = match ConfigSchemaRepository::get_schema(db_conn, app_name).await? {
+            Some(schema) => schema,
+            None => return Ok(()),
+        };
+
+        let invalid = |key: &str, message: String| {
+            warn!("Change of '{}'-'{}' rejected by the schema: {}", app_name, key, message);
+            ApiError::InvalidArgument { argument: key.to_string(), message }
+        };
+        for (key, value) in changes.set.iter().chain(&changes.set_plain) {
+            schema.validate_value(key, value).map_err(|message| invalid(key, message))?;
+        }
+        for key in &changes.delete {
+            schema.validate_delete(key).map_err(|message| invalid(key, message))?;
+        }
+        Ok(())
+    }
+
+    /// Validate the whole config of an app against the schema.
+    fn validate_config(schema: &AppConfigSchema, config: &HashMap<String, String>) -> Result<(), ApiError> {
+        let invalid = |key: &str, message: String| ApiError::InvalidArgument { argument: key.to_string(), message };
+
+        for (key, value) in config {
+            schema.validate_value(key, value).map_err(|message| invalid(key, message))?;
+        }
+        if let Some(key) = schema.required_keys().into_iter().find(|key| !config.contains_key(*key)) {
+            return Err(invalid(key, format!("Key '{}' is required but not set.", key)));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use well_i_known_core::modal::config_schema::{ConfigKeySchema, ConfigValueType};
+
+    #[test]
+    fn test_validate_config() {
+        let schema = AppConfigSchema {
+            keys: vec![ConfigKeySchema {
+                key: "max_connections".to_string(),
+                value_type: ConfigValueType::Int,
+                required: true,
+                default: None,
+                pattern: None,
+                min: Some(1),
+                max: None,
+            }],
+            allow_unknown_keys: true,
+        };
+
+        let config = HashMap::from([("db_url".to_string(), "sqlite://app.db".to_string())]);
+        assert!(ConfigSchemaRepository::validate_config(&schema, &config).is_err());
+        let config = HashMap::from([("max_connections".to_string(), "lots".to_string())]);
+        assert!(ConfigSchemaRepository::validate_config(&schema, &config).is_err());
+        let config = HashMap::from([("max_connections".to_string(), "10".to_string())]);
+        assert!(ConfigSchemaRepository::validate_config(&schema, &config).is_ok());
+    }
+}
@@ -0,0 +1,227 @@
+use well_i_known_core::api::data::ConfigVersionResponse;
+use well_i_known_core::crypto::cryptography::{Decryption, Encryption};
+use well_i_known_core::modal::user::ServerUserKeyModal;
+use crate::db::db_connection::DbConnection;
+use crate::db::db_executor::db_result_handler;
+use crate::dao::app_setting::{AppSetting, AppSettingTable};
+use crate::dao::config_data_version::{ConfigDataVersion, ConfigDataVersionTable};
+use crate::dao::config_plain_data_version::ConfigPlainDataVersionTable;
+use crate::repository::config_data::{ConfigChangeSet, ConfigDataRepository};
+use crate::error::ApiError;
+use crate::WIKServerEnvironmentConfig;
+
+use std::collections::HashMap;
+use tracing::*;
+
+/// History of the config values.
+/// Every value set is kept as a version of the key, up to the retention of the app (or the server default):
+/// a secret encrypted for the same users as the value, a non-secret entry once in plaintext (as the entry itself).
+pub struct ConfigVersionRepository {}
+impl ConfigVersionRepository {
+    /// Get the number of versions kept per key of the app.
+    pub async fn get_history_retention(db_conn: &DbConnection, server_config: &WIKServerEnvironmentConfig,
+        app_name: &str) -> Result<i64, ApiError> {
+        let setting = db_result_handler(
+            AppSettingTable::get_setting(db_conn, app_name).await,
+            "get_app_setting")?;
+        Ok(setting
+            .map(|setting| setting.history_retention)
+            .unwrap_or(server_config.config.config_history_retention))
+    }
+
+    /// Set the number of versions kept per key of the app.
+    /// The older versions are dropped on the next change of each key.
+    pub async fn set_history_retention(db_conn: &DbConnection, app_name: &str, retention: i64) -> Result<(), ApiError> {
+        if retention < 1 {
+            return Err(ApiError::InvalidArgument {
+                argument: "retention".to_string(),
+                message: "At least 1 version (the current value) is kept.".to_string(),
+            });
+        }
+
+        db_result_handler(
+            AppSettingTable::set_setting(db_conn, &AppSetting {
+                app_name: app_name.to_string(),
+                history_retention: retention,
+            }).await,
+            "set_app_setting")
+    }
+
+    /// Get the versions of the key in the environment, latest first:
+    /// the secret versions stored for the user, and the non-secret versions.
+    pub async fn get_versions(db_conn: &DbConnection, app_name: &str, env: &str, config_key: &str,
+        username: &str) -> Result<Vec<ConfigVersionResponse>, ApiError> {
+        let versions = db_result_handler(
+            ConfigDataVersionTable::get_versions(db_conn, app_name, env, config_key, username).await,
+            "get_config_versions")?;
+        let plain_versions = db_result_handler(
+            ConfigPlainDataVersionTable::get_versions(db_conn, app_name, env, config_key).await,
+            "get_config_plain_versions")?;
+
+        let mut versions: Vec<ConfigVersionResponse> = versions.into_iter()
+            .map(|version| ConfigVersionResponse {
+                version: version.version,
+                author: version.author,
+                created_at: version.created_at,
+                secret: true,
+            })
+            .chain(plain_versions.into_iter()
+                .map(|version| ConfigVersionResponse {
+                    version: version.version,
+                    author: version.author,
+                    created_at: version.created_at,
+                    secret: false,
+                }))
+            .collect();
+        versions.sort_by_key(|version| std::cmp::Reverse(version.version));
+        Ok(versions)
+    }
+
+    /// Get the value of one version of the key in the environment, and if it is a secret:
+    /// the plaintext of a non-secret version, or the encrypted value of a secret version stored for the user.
+    pub async fn get_version_value(db_conn: &DbConnection, app_name: &str, env: &str, config_key: &str,
+        version: i64, username: &str) -> Result<(String, bool), ApiError> {
+        let plain_version = db_result_handler(
+            ConfigPlainDataVersionTable::get_version(db_conn, app_name, env, config_key, version).await,
+            "get_config_plain_version")?;
+        if let Some(plain_version) = plain_version {
+            return Ok((plain_version.value, false));
+        }
+
+        let version = db_result_handler(
+            ConfigDataVersionTable::get_version(db_conn, app_name, env, config_key, version, username).await,
+            "get_config_version")?;
+
+        match version {
+            Some(version) => Ok((version.value, true)),
+            None => {
+                warn!("Version of '{}'-'{}' ({}) not found for {}.", app_name, config_key, env, username);
+                Err(ApiError::RecordNotFound)
+            }
+        }
+    }
+
+    /// Set the key of the environment back to the value of the version, secret or not as the version was.
+    /// The value is set as a new version, with the change notification of a normal write.
+    pub async fn rollback(db_conn: &DbConnection, server_config: &WIKServerEnvironmentConfig,
+        app_name: &str, env: &str, config_key: &str, version: i64, author: &str) -> Result<(), ApiError> {
+        let root = server_config.root_user.as_ref().unwrap();
+        let (value, secret) = ConfigVersionRepository::get_version_value(
+            db_conn, app_name, env, config_key, version, &root.username).await?;
+
+        let changes = match secret {
+            true => {
+                let plaintext = root.key.private_key.decrypt_string(&value);
+                if let Err(error) = plaintext {
+                    warn!("Fail to decrypt the root copy of '{}'-'{}' version {}. Error: {}", app_name, config_key, version, error);
+                    return Err(ApiError::ServerError);
+                }
+                ConfigChangeSet {
+                    set: HashMap::from([(config_key.to_string(), plaintext.unwrap())]),
+                    ..Default::default()
+                }
+            },
+            false => ConfigChangeSet {
+                set_plain: HashMap::from([(config_key.to_string(), value)]),
+                ..Default::default()
+            },
+        };
+        ConfigDataRepository::alter_config_data_batch(db_conn, server_config, app_name, env, author, &changes).await?;
+        info!("'{}'-'{}' ({}) is rolled back to version {} by {}.", app_name, config_key, env, version, author);
+        Ok(())
+    }
+
+    /// Encrypt the kept versions of the app in the environment for the user (who is newly granted access),
+    /// from the root's copies. Versions that the user already has are skipped.
+    pub async fn share_app_versions_with_user(db_conn: &DbConnection, server_config: &WIKServerEnvironmentConfig,
+        app_name: &str, env: &str, user: &ServerUserKeyModal) -> Result<(), ApiError> {
+        let root = server_config.root_user.as_ref().unwrap();
+        let root_versions = db_result_handler(
+            ConfigDataVersionTable::get_all_app_versions_of_owner(db_conn, app_name, env, &root.username).await,
+            "get_all_app_versions_of_owner")?;
+        let user_versions = db_result_handler(
+            ConfigDataVersionTable::get_all_app_versions_of_owner(db_conn, app_name, env, &user.username).await,
+            "get_all_app_versions_of_owner")?;
+
+        for root_version in root_versions {
+            let exists = user_versions.iter()
+                .any(|version| version.key == root_version.key && version.version == root_version.version);
+            if exists {
+                continue;
+            }
+
+            let plaintext = root.key.private_key.decrypt_string(&root_version.value);
+            if let Err(error) = plaintext {
+                warn!("Fail to decrypt the root copy of '{}'-'{}' version {}. Error: {}",
+                    app_name, root_version.key, root_version.version, error);
+                return Err(ApiError::ServerError);
+            }
+
+            let encrypted_value = user.public_key.encrypt_string(&plaintext.unwrap());
+            if let Err(error) = encrypted_value {
+                warn!("Fail to encrypt data for {}. Error: {}", user.username, error);
+                return Err(ApiError::ServerError);
+            }
+
+            db_result_handler(
+                ConfigDataVersionTable::add_version_copy(db_conn, &ConfigDataVersion {
+                    owner: user.username.clone(),
+                    value: encrypted_value.unwrap(),
+                    ..root_version
+                }).await,
+                "add_config_version_copy")?;
+        }
+
+        Ok(())
+    }
+
+    /// Remove all the versions of the app that are stored for the user, except the ones of the kept environments.
+    pub async fn purge_app_versions_of_user(db_conn: &DbConnection, app_name: &str, username: &str, kept_envs: &[String]) -> Result<(), ApiError> {
+        db_result_handler(
+            ConfigDataVersionTable::delete_all_app_versions_for_owner(db_conn, app_name, username, kept_envs).await,
+            "delete_all_app_versions_for_owner")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::server_config::WIKServerConfig;
+    use crate::dao::user::UserTable;
+    use crate::db::db_test_util::*;
+    use well_i_known_core::crypto::cryptography::WikRsaKeyPair;
+    use well_i_known_core::modal::user::{UserKeyModal, UserRole};
+
+    #[tokio::test]
+    async fn test_rollback_plain_key() {
+        let db_conn = create_test_db("test_rollback_plain_key").await;
+        UserTable::create_user(&db_conn, "u_root", &UserRole::Root, "password").await.unwrap();
+        UserTable::create_user(&db_conn, "u_app", &UserRole::App, "password").await.unwrap();
+        let server_config = WIKServerEnvironmentConfig {
+            base_dir: get_test_path("output"),
+            config: WIKServerConfig::default(),
+            root_user: Some(UserKeyModal { username: "u_root".to_string(), key: WikRsaKeyPair::new().unwrap() }),
+        };
+
+        for value in ["info", "debug"] {
+            let changes = ConfigChangeSet {
+                set_plain: HashMap::from([("log_level".to_string(), value.to_string())]),
+                ..Default::default()
+            };
+            ConfigDataRepository::alter_config_data_batch(&db_conn, &server_config, "u_app", "default", "u_app", &changes).await.unwrap();
+        }
+        let versions = ConfigVersionRepository::get_versions(&db_conn, "u_app", "default", "log_level", "u_app").await.unwrap();
+        assert_eq!(versions.len(), 2);
+        assert_eq!(versions[0].version, 2);
+        assert!(!versions[0].secret);
+        let value = ConfigVersionRepository::get_version_value(&db_conn, "u_app", "default", "log_level", 1, "u_app").await.unwrap();
+        assert_eq!(value, ("info".to_string(), false));
+
+        ConfigVersionRepository::rollback(&db_conn, &server_config, "u_app", "default", "log_level", 1, "u_root").await.unwrap();
+        let value = ConfigDataRepository::get_plain_config_data(&db_conn, "u_app", "default", "log_level").await.unwrap();
+        assert_eq!(value, Some("info".to_string()));
+        let versions = ConfigVersionRepository::get_versions(&db_conn, "u_app", "default", "log_level", "u_app").await.unwrap();
+        assert_eq!(versions[0].version, 3);
+        assert_eq!(versions[0].author, "u_root");
+    }
+}
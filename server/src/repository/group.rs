@@ -0,0 +1,305 @@
+use well_i_known_core::modal::user::UserRole;
+use well_i_known_core::modal::util::id_validation::validate_id;
+use crate::db::db_connection::DbConnection;
+use crate::db::db_executor::db_result_handler;
+use crate::dao::admin_group::{AdminGroup, AdminGroupTable};
+use crate::dao::group_access_right::{GroupAccessRight, GroupAccessRightTable};
+use crate::dao::group_member::{GroupMember, GroupMemberTable};
+use crate::dao::user::UserTable;
+use crate::repository::access_right::AccessRightRepository;
+use crate::repository::config_data::ConfigDataRepository;
+use crate::error::ApiError;
+use crate::WIKServerEnvironmentConfig;
+
+use tracing::*;
+
+/// Groups of admins.
+/// Access right granted to a group applies to all its members,
+/// so joining / leaving a group also add / purge the encrypted data of the member.
+pub struct GroupRepository {}
+impl GroupRepository {
+    pub async fn check_group_exists(db_conn: &DbConnection, group_name: &str) -> Result<bool, ApiError> {
+        db_result_handler(
+            AdminGroupTable::check_group_exists(db_conn, group_name).await,
+            "check_group_exists")
+    }
+
+    /// Throw RecordNotFound if the group does not exist.
+    pub async fn throw_if_group_not_exists(db_conn: &DbConnection, group_name: &str) -> Result<(), ApiError> {
+        if !GroupRepository::check_group_exists(db_conn, group_name).await? {
+            warn!("Group '{}' does not exist.", group_name);
+            return Err(ApiError::RecordNotFound);
+        }
+        Ok(())
+    }
+
+    pub async fn create_group(db_conn: &DbConnection, group_name: &str) -> Result<(), ApiError> {
+        if let Err(error) = validate_id(group_name) {
+            return Err(ApiError::InvalidArgument {
+                argument: "group".to_string(),
+                message: error,
+            });
+        }
+
+        if GroupRepository::check_group_exists(db_conn, group_name).await? {
+            warn!("Try to create group '{}' which is already exists.", group_name);
+            return Err(ApiError::DuplicateRecord);
+        }
+
+        db_result_handler(
+            AdminGroupTable::create_group(db_conn, &AdminGroup { group_name: group_name.to_string() }).await,
+            "create_group")?;
+
+        Ok(())
+    }
+
+    /// Delete the group, its access rights and memberships.
+    /// Members that no longer have access to the group's apps lose their data of those apps.
+    pub async fn delete_group(db_conn: &DbConnection, group_name: &str) -> Result<(), ApiError> {
+        GroupRepository::throw_if_group_not_exists(db_conn, group_name).await?;
+
+        let members = db_result_handler(
+            GroupMemberTable::get_group_members(db_conn, group_name).await,
+            "get_group_members")?;
+        let apps = db_result_handler(
+            GroupAccessRightTable::get_group_access_rights(db_conn, group_name).await,
+            "get_group_access_rights")?;
+
+        db_result_handler(
+            GroupMemberTable::delete_all_members_of_group(db_conn, group_name).await,
+            "delete_all_members_of_group")?;
+        db_result_handler(
+            GroupAccessRightTable::delete_all_access_of_group(db_conn, group_name).await,
+            "delete_all_access_of_group")?;
+        db_result_handler(
+            AdminGroupTable::delete_group(db_conn, group_name).await,
+            "delete_group")?;
+
+        for member in &members {
+            for app in &apps {
+                AccessRightRepository::purge_data_if_no_access(db_conn, member, app).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Add an admin to the group.
+    /// The data of all apps the group has access to is encrypted for the new member.
+    pub async fn add_member(db_conn: &DbConnection, server_config: &WIKServerEnvironmentConfig,
+        group_name: &str, username: &str) -> Result<(), ApiError> {
+
+        GroupRepository::throw_if_group_not_exists(db_conn, group_name).await?;
+
+        let is_admin = db_result_handler(
+            UserTable::check_user_with_role_exists(db_conn, username, &UserRole::Admin).await,
+            "check_user_with_role_exists")?;
+        if !is_admin {
+            warn!("User {} is not admin / does not exists.", username);
+            return Err(ApiError::InvalidArgument {
+                argument: "admin".to_string(),
+                message: "Given user is not admin.".to_string()
+            });
+        }
+
+        let is_member = db_result_handler(
+            GroupMemberTable::check_member_exists(db_conn, group_name, username).await,
+            "check_member_exists")?;
+        if is_member {
+            warn!("User {} is already a member of group {}.", username, group_name);
+            return Err(ApiError::DuplicateRecord);
+        }
+
+        db_result_handler(
+            GroupMemberTable::add_member(db_conn, &GroupMember {
+                group_name: group_name.to_string(),
+                username: username.to_string(),
+            }).await,
+            "add_member")?;
+
+        let apps = db_result_handler(
+            GroupAccessRightTable::get_group_access_rights(db_conn, group_name).await,
+            "get_group_access_rights")?;
+        for app in &apps {
+            ConfigDataRepository::share_app_data_with_user(db_conn, server_config, app, username).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Throw RecordNotFound if the group does not exist or the user is not its member.
+    pub async fn throw_if_member_not_exists(db_conn: &DbConnection, group_name: &str, username: &str) -> Result<(), ApiError> {
+        GroupRepository::throw_if_group_not_exists(db_conn, group_name).await?;
+
+        let is_member = db_result_handler(
+            GroupMemberTable::check_member_exists(db_conn, group_name, username).await,
+            "check_member_exists")?;
+        if !is_member {
+            warn!("User {} is not a member of group {} but try to remove him.", username, group_name);
+            return Err(ApiError::RecordNotFound);
+        }
+        Ok(())
+    }
+
+    /// Remove an admin from the group.
+    /// The member's data of the group's apps is purged unless he still has access to them.
+    pub async fn delete_member(db_conn: &DbConnection, group_name: &str, username: &str) -> Result<(), ApiError> {
+        GroupRepository::throw_if_member_not_exists(db_conn, group_name, username).await?;
+
+        db_result_handler(
+            GroupMemberTable::delete_member(db_conn, group_name, username).await,
+            "delete_member")?;
+
+        let apps = db_result_handler(
+            GroupAccessRightTable::get_group_access_rights(db_conn, group_name).await,
+            "get_group_access_rights")?;
+        for app in &apps {
+            AccessRightRepository::purge_data_if_no_access(db_conn, username, app).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Grant the group access to the app.
+    /// The app's data is encrypted for all members of the group.
+    pub async fn add_access_right(db_conn: &DbConnection, server_config: &WIKServerEnvironmentConfig,
+        group_name: &str, app_name: &str) -> Result<(), ApiError> {
+
+        GroupRepository::throw_if_group_not_exists(db_conn, group_name).await?;
+
+        let app_exists = db_result_handler(
+            UserTable::check_user_with_role_exists(db_conn, app_name, &UserRole::App).await,
+            "check app exists")?;
+        if !app_exists {
+            warn!("Fail to add group access right. App '{}' does not exist.", app_name);
+            return Err(ApiError::InvalidArgument {
+                argument: "app".to_string(),
+                message: "Given app does not exist.".to_string(),
+            });
+        }
+
+        let exists = db_result_handler(
+            GroupAccessRightTable::check_access_right_exists(db_conn, group_name, app_name).await,
+            "check_group_access_right_exists")?;
+        if exists {
+            warn!("Access right already exists for group {} and app {} but try to create one.", group_name, app_name);
+            return Err(ApiError::DuplicateRecord);
+        }
+
+        db_result_handler(
+            GroupAccessRightTable::add_access_right(db_conn, &GroupAccessRight {
+                group_name: group_name.to_string(),
+                app_name: app_name.to_string(),
+            }).await,
+            "add_group_access_right")?;
+
+        let members = db_result_handler(
+            GroupMemberTable::get_group_members(db_conn, group_name).await,
+            "get_group_members")?;
+        for member in &members {
+            ConfigDataRepository::share_app_data_with_user(db_conn, server_config, app_name, member).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Throw RecordNotFound if the group or its access right to the app does not exist.
+    pub async fn throw_if_access_right_not_exists(db_conn: &DbConnection, group_name: &str, app_name: &str) -> Result<(), ApiError> {
+        GroupRepository::throw_if_group_not_exists(db_conn, group_name).await?;
+
+        let exists = db_result_handler(
+            GroupAccessRightTable::check_access_right_exists(db_conn, group_name, app_name).await,
+            "check_group_access_right_exists")?;
+        if !exists {
+            warn!("Access right does not exist for group {} and app {} but try to delete one.", group_name, app_name);
+            return Err(ApiError::RecordNotFound);
+        }
+        Ok(())
+    }
+
+    /// Revoke the group's access to the app.
+    /// Members that no longer have access to the app lose their data of it.
+    pub async fn delete_access_right(db_conn: &DbConnection, group_name: &str, app_name: &str) -> Result<(), ApiError> {
+        GroupRepository::throw_if_access_right_not_exists(db_conn, group_name, app_name).await?;
+
+        db_result_handler(
+            GroupAccessRightTable::delete_access_right(db_conn, group_name, app_name).await,
+            "delete_group_access_right")?;
+
+        let members = db_result_handler(
+            GroupMemberTable::get_group_members(db_conn, group_name).await,
+            "get_group_members")?;
+        for member in &members {
+            AccessRightRepository::purge_data_if_no_access(db_conn, member, app_name).await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::db_test_util::*;
+    use well_i_known_core::api::data::ALL_ENVIRONMENTS;
+    use crate::dao::access_right::AccessRightTable;
+    use crate::dao::config_data::ConfigDataTable;
+
+    async fn create_group_test_db(test_case_name: &str) -> DbConnection{
+        let db_conn = create_test_db(test_case_name).await;
+        UserTable::create_user(&db_conn, "u_admin", &UserRole::Admin, "password").await.unwrap();
+        UserTable::create_user(&db_conn, "u_app", &UserRole::App, "password").await.unwrap();
+        db_conn
+    }
+
+    #[tokio::test]
+    async fn test_create_group(){
+        let db_conn = create_group_test_db("test_create_group").await;
+        GroupRepository::create_group(&db_conn, "g_team").await.unwrap();
+        assert!(GroupRepository::check_group_exists(&db_conn, "g_team").await.unwrap());
+
+        let result = GroupRepository::create_group(&db_conn, "g_team").await;
+        assert!(matches!(result, Err(ApiError::DuplicateRecord)));
+        let result = GroupRepository::create_group(&db_conn, "g!").await;
+        assert!(matches!(result, Err(ApiError::InvalidArgument { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_leave_group_purge_data(){
+        let db_conn = create_group_test_db("test_leave_group_purge_data").await;
+        GroupRepository::create_group(&db_conn, "g_team").await.unwrap();
+        GroupMemberTable::add_member(&db_conn, &GroupMember {
+            group_name: "g_team".to_string(),
+            username: "u_admin".to_string(),
+        }).await.unwrap();
+        GroupAccessRightTable::add_access_right(&db_conn, &GroupAccessRight {
+            group_name: "g_team".to_string(),
+            app_name: "u_app".to_string(),
+        }).await.unwrap();
+        ConfigDataTable::set_data_value(&db_conn, "u_app", "prod", "u_admin", "test_key", "encrypted").await.unwrap();
+
+        GroupRepository::delete_member(&db_conn, "g_team", "u_admin").await.unwrap();
+        let data = ConfigDataTable::get_all_app_data_of_owner(&db_conn, "u_app", "prod", "u_admin").await.unwrap();
+        assert_eq!(data.len(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_leave_group_keep_direct_access_data(){
+        let db_conn = create_group_test_db("test_leave_group_keep_direct_access_data").await;
+        GroupRepository::create_group(&db_conn, "g_team").await.unwrap();
+        GroupMemberTable::add_member(&db_conn, &GroupMember {
+            group_name: "g_team".to_string(),
+            username: "u_admin".to_string(),
+        }).await.unwrap();
+        GroupAccessRightTable::add_access_right(&db_conn, &GroupAccessRight {
+            group_name: "g_team".to_string(),
+            app_name: "u_app".to_string(),
+        }).await.unwrap();
+        AccessRightTable::add_access_right(&db_conn, "u_admin", "u_app", ALL_ENVIRONMENTS, None).await.unwrap();
+        ConfigDataTable::set_data_value(&db_conn, "u_app", "prod", "u_admin", "test_key", "encrypted").await.unwrap();
+
+        GroupRepository::delete_member(&db_conn, "g_team", "u_admin").await.unwrap();
+        let data = ConfigDataTable::get_all_app_data_of_owner(&db_conn, "u_app", "prod", "u_admin").await.unwrap();
+        assert_eq!(data.len(), 1);
+    }
+}
@@ -0,0 +1,147 @@
+use well_i_known_core::crypto::cryptography::WikRsaPublicKey;
+use well_i_known_core::crypto::password::{generate_random_token, verify_password, Password};
+use well_i_known_core::modal::user::UserRole;
+use crate::db::db_connection::DbConnection;
+use crate::db::db_executor::db_result_handler;
+use crate::dao::login_challenge::LoginChallengeTable;
+use crate::dao::user::UserTable;
+use crate::error::ApiError;
+use crate::WIKServerEnvironmentConfig;
+
+use chrono::Utc;
+use std::str::FromStr;
+use tracing::*;
+
+/// Login by proving the possession of the private key instead of the password.
+/// 1. The server issues a random nonce encrypted with the user's registered public key.
+/// 2. The user decrypts the nonce with the private key and answers the plaintext.
+pub struct LoginChallengeRepository {}
+impl LoginChallengeRepository {
+    /// Get the registered public key of the user.
+    fn get_public_key(server_config: &WIKServerEnvironmentConfig, username: &str, role: &UserRole) -> Result<WikRsaPublicKey, ApiError> {
+        if role == &UserRole::Root {
+            let root = server_config.root_user.as_ref().unwrap();
+            return Ok(root.key.public_key.clone());
+        }
+
+        WikRsaPublicKey::from_file(&server_config.get_users_certs_path(username))
+            .map_err(|error| {
+                warn!("Fail to get user's public key. Error: {}", error);
+                ApiError::ServerError
+            })
+    }
+
+    /// Issue a challenge to the user.
+    /// Return (challenge id, nonce encrypted with the user's public key)
+    pub async fn create_challenge(db_conn: &DbConnection, server_config: &WIKServerEnvironmentConfig,
+        username: &str) -> Result<(String, String), ApiError> {
+
+        let user = db_result_handler(
+            UserTable::get_user(db_conn, username).await,
+            "get_user")?;
+        let user = match user {
+            Some(user) => user,
+            None => {
+                // same error as wrong password, do not tell whether the user exists
+                warn!("Login challenge requested for user '{}' which does not exist.", username);
+                return Err(ApiError::WrongCredentials);
+            }
+        };
+        let role = UserRole::from_str(&user.role).map_err(|_| ApiError::ServerError)?;
+
+        let public_key = LoginChallengeRepository::get_public_key(server_config, username, &role)?;
+        let (plaintext, encrypted) = public_key.generate_validate_string();
+
+        let hashed_answer = Password::new(&plaintext);
+        if let Err(error) = hashed_answer {
+            warn!("Fail to hash the challenge answer. Error: {}", error);
+            return Err(ApiError::ServerError);
+        }
+        let hashed_answer = hashed_answer.unwrap();
+
+        let challenge_id = generate_random_token();
+        let expires_at = Utc::now().timestamp() + server_config.config.login_challenge_duration;
+        db_result_handler(
+            LoginChallengeTable::add_challenge(db_conn, &challenge_id, username,
+                &hashed_answer.hash, &hashed_answer.salt, expires_at).await,
+            "add_login_challenge")?;
+
+        Ok((challenge_id, encrypted))
+    }
+
+    /// Verify the answer of the challenge.
+    /// Each challenge can only be answered once, no matter the answer is correct or not.
+    /// Return (username, role) of the authenticated user.
+    pub async fn verify_challenge(db_conn: &DbConnection, challenge_id: &str, answer: &str) -> Result<(String, UserRole), ApiError> {
+        let challenge = db_result_handler(
+            LoginChallengeTable::get_challenge(db_conn, challenge_id).await,
+            "get_login_challenge")?;
+        let challenge = match challenge {
+            Some(challenge) => challenge,
+            None => {
+                warn!("Login challenge not found.");
+                return Err(ApiError::WrongCredentials);
+            }
+        };
+
+        let deleted = db_result_handler(
+            LoginChallengeTable::delete_challenge(db_conn, &challenge.id).await,
+            "delete_login_challenge")?;
+        if !deleted {
+            warn!("Login challenge of user '{}' is already answered.", challenge.username);
+            return Err(ApiError::WrongCredentials);
+        }
+
+        if challenge.expires_at <= Utc::now().timestamp() {
+            warn!("Login challenge of user '{}' expired.", challenge.username);
+            return Err(ApiError::WrongCredentials);
+        }
+
+        if !verify_password(answer, &challenge.answer_hash, &challenge.answer_salt) {
+            warn!("Wrong answer to the login challenge of user '{}'.", challenge.username);
+            return Err(ApiError::WrongCredentials);
+        }
+
+        let user = db_result_handler(
+            UserTable::get_user(db_conn, &challenge.username).await,
+            "get_user")?
+            .ok_or(ApiError::WrongCredentials)?;
+        let role = UserRole::from_str(&user.role).map_err(|_| ApiError::ServerError)?;
+
+        Ok((user.username, role))
+    }
+
+    /// Delete the expired challenges.
+    /// Return the number of deleted challenges.
+    pub async fn delete_expired_challenges(db_conn: &DbConnection) -> Result<u64, ApiError> {
+        db_result_handler(
+            LoginChallengeTable::delete_expired_challenges(db_conn, Utc::now().timestamp()).await,
+            "delete_expired_login_challenges")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::db_test_util::*;
+
+    #[tokio::test]
+    async fn test_challenge_answered_once(){
+        let db_conn = create_test_db("test_challenge_answered_once").await;
+        UserTable::create_user(&db_conn, "u_app", &UserRole::App, "password").await.unwrap();
+        let answer = Password::new("nonce").unwrap();
+        let expires_at = Utc::now().timestamp() + 60;
+        LoginChallengeTable::add_challenge(&db_conn, "c_1", "u_app", &answer.hash, &answer.salt, expires_at).await.unwrap();
+        LoginChallengeTable::add_challenge(&db_conn, "c_2", "u_app", &answer.hash, &answer.salt, expires_at).await.unwrap();
+
+        let result = LoginChallengeRepository::verify_challenge(&db_conn, "c_1", "wrong").await;
+        assert!(matches!(result, Err(ApiError::WrongCredentials)));
+        // the challenge is consumed by the wrong answer
+        let result = LoginChallengeRepository::verify_challenge(&db_conn, "c_1", "nonce").await;
+        assert!(matches!(result, Err(ApiError::WrongCredentials)));
+
+        let (username, role) = LoginChallengeRepository::verify_challenge(&db_conn, "c_2", "nonce").await.unwrap();
+        assert_eq!(username, "u_app");
+        assert_eq!(role, UserRole::App);
+    }
+}
@@ -0,0 +1,124 @@
+use crate::config::server_config::WIKServerLoginThrottleConfig;
+use crate::db::db_connection::DbConnection;
+use crate::db::db_executor::db_result_handler;
+use crate::dao::login_failure::{LoginFailure, LoginFailureTable};
+use crate::error::ApiError;
+
+use chrono::Utc;
+use tracing::*;
+
+const USERNAME_KIND: &str = "username";
+const SOURCE_IP_KIND: &str = "ip";
+
+/// Throttling of the failed login attempts by username and by source IP.
+/// Each failure doubles the delay before the next attempt is accepted,
+/// and the username is locked for a while after too many failures.
+pub struct LoginThrottleRepository {}
+impl LoginThrottleRepository {
+    async fn throw_if_blocked(db_conn: &DbConnection, kind: &str, subject: &str) -> Result<(), ApiError> {
+        let failure = db_result_handler(
+            LoginFailureTable::get_failure(db_conn, kind, subject).await,
+            "get_login_failure")?;
+
+        let now = Utc::now().timestamp();
+        if let Some(failure) = failure {
+            if failure.blocked_until > now {
+                warn!("Login of {} '{}' is blocked for {} seconds.", kind, subject, failure.blocked_until - now);
+                return Err(ApiError::LoginThrottled { retry_after: failure.blocked_until - now });
+            }
+        }
+        Ok(())
+    }
+
+    /// Throw LoginThrottled if the username or the source IP is not allowed to try login now.
+    pub async fn throw_if_throttled(db_conn: &DbConnection, username: &str, source_ip: &str) -> Result<(), ApiError> {
+        LoginThrottleRepository::throw_if_blocked(db_conn, USERNAME_KIND, username).await?;
+        LoginThrottleRepository::throw_if_blocked(db_conn, SOURCE_IP_KIND, source_ip).await
+    }
+
+    async fn add_failure(db_conn: &DbConnection, config: &WIKServerLoginThrottleConfig,
+        kind: &str, subject: &str) -> Result<(), ApiError> {
+
+        let failure = db_result_handler(
+            LoginFailureTable::get_failure(db_conn, kind, subject).await,
+            "get_login_failure")?;
+
+        let now = Utc::now().timestamp();
+        // the failures are forgotten after a quiet period
+        let failure_count = match failure {
+            Some(failure) if now - failure.last_failed_at < config.lockout_duration => failure.failure_count + 1,
+            _ => 1,
+        };
+
+        let exponent = (failure_count - 1).min(32) as u32;
+        let backoff = config.backoff_base.saturating_mul(2_i64.saturating_pow(exponent)).min(config.backoff_max);
+        let mut blocked_until = now + backoff;
+
+        if kind == USERNAME_KIND && failure_count >= config.lockout_threshold {
+            warn!("User '{}' is locked after {} failed login attempts.", subject, failure_count);
+            blocked_until = blocked_until.max(now + config.lockout_duration);
+        }
+
+        db_result_handler(
+            LoginFailureTable::set_failure(db_conn, &LoginFailure {
+                kind: kind.to_string(),
+                subject: subject.to_string(),
+                failure_count,
+                last_failed_at: now,
+                blocked_until,
+            }).await,
+            "set_login_failure")
+    }
+
+    /// Count a failed login attempt of the username from the source IP.
+    pub async fn record_failure(db_conn: &DbConnection, config: &WIKServerLoginThrottleConfig,
+        username: &str, source_ip: &str) -> Result<(), ApiError> {
+        LoginThrottleRepository::add_failure(db_conn, config, USERNAME_KIND, username).await?;
+        LoginThrottleRepository::add_failure(db_conn, config, SOURCE_IP_KIND, source_ip).await
+    }
+
+    /// Reset the failures of the username after a successful login.
+    pub async fn record_success(db_conn: &DbConnection, username: &str) -> Result<(), ApiError> {
+        db_result_handler(
+            LoginFailureTable::delete_failure(db_conn, USERNAME_KIND, username).await,
+            "delete_login_failure")?;
+        Ok(())
+    }
+
+    /// Clear the lock of the username.
+    /// Return false if the username is not locked / has no failure.
+    pub async fn unlock_user(db_conn: &DbConnection, username: &str) -> Result<bool, ApiError> {
+        db_result_handler(
+            LoginFailureTable::delete_failure(db_conn, USERNAME_KIND, username).await,
+            "delete_login_failure")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::db_test_util::*;
+
+    #[tokio::test]
+    async fn test_lock_after_failures(){
+        let db_conn = create_test_db("test_lock_after_failures").await;
+        let config = WIKServerLoginThrottleConfig {
+            backoff_base: 0,
+            backoff_max: 0,
+            lockout_threshold: 3,
+            lockout_duration: 900,
+        };
+
+        for _ in 0..2 {
+            LoginThrottleRepository::record_failure(&db_conn, &config, "u_admin", "127.0.0.1").await.unwrap();
+        }
+        LoginThrottleRepository::throw_if_throttled(&db_conn, "u_admin", "127.0.0.1").await.unwrap();
+
+        LoginThrottleRepository::record_failure(&db_conn, &config, "u_admin", "127.0.0.1").await.unwrap();
+        let result = LoginThrottleRepository::throw_if_throttled(&db_conn, "u_admin", "127.0.0.1").await;
+        assert!(matches!(result, Err(ApiError::LoginThrottled { .. })));
+
+        assert!(LoginThrottleRepository::unlock_user(&db_conn, "u_admin").await.unwrap());
+        LoginThrottleRepository::throw_if_throttled(&db_conn, "u_admin", "127.0.0.1").await.unwrap();
+    }
+}
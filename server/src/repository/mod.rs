@@ -1,3 +1,16 @@
 pub mod user;
 pub mod access_right;
+pub mod api_token;
+pub mod approval;
+pub mod auth_token;
+pub mod break_glass;
+pub mod group;
+pub mod login_challenge;
+pub mod login_throttle;
 pub mod config_data;
+pub mod config_version;
+pub mod config_expiry;
+pub mod config_schema;
+pub mod notification;
+pub mod password_policy;
+pub mod password_reset;
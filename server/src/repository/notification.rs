@@ -0,0 +1,39 @@
+use well_i_known_core::modal::notification::NotificationCategory;
+use crate::db::db_connection::DbConnection;
+use crate::db::db_executor::db_result_handler;
+use crate::dao::notification::{Notification, NotificationTable};
+use crate::error::ApiError;
+
+use chrono::Utc;
+use tracing::*;
+
+pub struct NotificationRepository {}
+impl NotificationRepository {
+    /// Send a notification to the recipient.
+    pub async fn notify(db_conn: &DbConnection, recipient: &str, category: &NotificationCategory, message: &str) -> Result<(), ApiError> {
+        debug!("Notify {} of {}: {}", recipient, category, message);
+        db_result_handler(
+            NotificationTable::add_notification(db_conn, recipient, &category.to_string(), message, Utc::now().timestamp()).await,
+            "add_notification")
+    }
+
+    pub async fn get_notifications(db_conn: &DbConnection, recipient: &str) -> Result<Vec<Notification>, ApiError> {
+        db_result_handler(
+            NotificationTable::get_notifications(db_conn, recipient).await,
+            "get_notifications")
+    }
+
+    /// Delete (dismiss) one notification of the recipient.
+    pub async fn delete_notification(db_conn: &DbConnection, recipient: &str, id: i64) -> Result<(), ApiError> {
+        let deleted = db_result_handler(
+            NotificationTable::delete_notification(db_conn, recipient, id).await,
+            "delete_notification")?;
+
+        if !deleted {
+            warn!("Notification {} of {} does not exist.", id, recipient);
+            return Err(ApiError::RecordNotFound);
+        }
+
+        Ok(())
+    }
+}
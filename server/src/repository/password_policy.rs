@@ -0,0 +1,116 @@
+use well_i_known_core::crypto::password::verify_password;
+use crate::config::server_config::WIKServerPasswordPolicyConfig;
+use crate::db::db_connection::DbConnection;
+use crate::db::db_executor::db_result_handler;
+use crate::dao::password_history::PasswordHistoryTable;
+use crate::dao::user::UserTable;
+use crate::error::ApiError;
+
+use chrono::Utc;
+use tracing::*;
+
+/// Enforce the password policy on every password set by the users.
+/// The generated random passwords (of the users logging in by private key only) are not checked.
+pub struct PasswordPolicyRepository {}
+impl PasswordPolicyRepository {
+    /// Check the password against the rules of the policy, without the history.
+    /// Return the violated rules, empty if the password is accepted.
+    pub fn check_rules(policy: &WIKServerPasswordPolicyConfig, password: &str) -> Vec<String> {
+        let mut violations = vec![];
+
+        if password.chars().count() < policy.min_length {
+            violations.push(format!("Password must be at least {} characters long.", policy.min_length));
+        }
+        if policy.require_lowercase && !password.chars().any(char::is_lowercase) {
+            violations.push("Password must contain a lowercase letter.".to_string());
+        }
+        if policy.require_uppercase && !password.chars().any(char::is_uppercase) {
+            violations.push("Password must contain an uppercase letter.".to_string());
+        }
+        if policy.require_digit && !password.chars().any(|c| c.is_ascii_digit()) {
+            violations.push("Password must contain a digit.".to_string());
+        }
+        if policy.require_symbol && password.chars().all(char::is_alphanumeric) {
+            violations.push("Password must contain a symbol.".to_string());
+        }
+        if policy.deny_list.iter().any(|denied| denied.eq_ignore_ascii_case(password)) {
+            violations.push("Password is too common.".to_string());
+        }
+
+        violations
+    }
+
+    /// Validate the new password of the user against the policy,
+    /// including the reuse of the current and the previous passwords.
+    /// Throw InvalidArgument listing all violated rules if the password is rejected.
+    pub async fn validate_password(db_conn: &DbConnection, policy: &WIKServerPasswordPolicyConfig,
+        username: &str, password: &str) -> Result<(), ApiError> {
+
+        let mut violations = PasswordPolicyRepository::check_rules(policy, password);
+
+        if policy.history_size > 0 && violations.is_empty() {
+            let user = db_result_handler(
+                UserTable::get_user(db_conn, username).await,
+                "get_user")?;
+            let previous_passwords = db_result_handler(
+                PasswordHistoryTable::get_passwords(db_conn, username, (policy.history_size - 1) as u64).await,
+                "get_password_history")?;
+
+            let current_password = user.iter().map(|user| (&user.encrypted_password, &user.password_salt));
+            let reused = current_password
+                .chain(previous_passwords.iter().map(|previous| (&previous.encrypted_password, &previous.password_salt)))
+                .any(|(hash, salt)| verify_password(password, hash, salt));
+            if reused {
+                violations.push(format!("Password cannot be one of the last {} passwords.", policy.history_size));
+            }
+        }
+
+        if violations.is_empty() {
+            return Ok(());
+        }
+        warn!("Password of user '{}' rejected by the policy: {:?}", username, violations);
+        Err(ApiError::InvalidArgument {
+            argument: "password".to_string(),
+            message: format!("Rejected by the password policy. {}", violations.join(" ")),
+        })
+    }
+
+    /// Keep the current password of the user in the history before it is replaced,
+    /// only the latest passwords within the history size are kept.
+    pub async fn remember_current_password(db_conn: &DbConnection, policy: &WIKServerPasswordPolicyConfig,
+        username: &str) -> Result<(), ApiError> {
+
+        if policy.history_size > 0 {
+            let user = db_result_handler(
+                UserTable::get_user(db_conn, username).await,
+                "get_user")?
+                .ok_or(ApiError::RecordNotFound)?;
+            db_result_handler(
+                PasswordHistoryTable::add_password(db_conn, username,
+                    &user.encrypted_password, &user.password_salt, Utc::now().timestamp()).await,
+                "add_password_history")?;
+        }
+
+        // the current password is also checked, so one less previous password is needed
+        db_result_handler(
+            PasswordHistoryTable::prune_passwords(db_conn, username, policy.history_size.saturating_sub(1) as u64).await,
+            "prune_password_history")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_password_rules() {
+        let policy = WIKServerPasswordPolicyConfig::default();
+        assert!(PasswordPolicyRepository::check_rules(&policy, "Correct-Horse-42").is_empty());
+        // too short, no uppercase, no digit
+        assert_eq!(PasswordPolicyRepository::check_rules(&policy, "a").len(), 3);
+        assert_eq!(
+            PasswordPolicyRepository::check_rules(&policy, "Welcome12345"),
+            vec!["Password is too common.".to_string()],
+        );
+    }
+}
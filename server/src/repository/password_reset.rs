@@ -0,0 +1,168 @@
+use well_i_known_core::crypto::password::{generate_random_id, generate_random_token, verify_password, Password};
+use well_i_known_core::modal::user::UserRole;
+use crate::config::server_config::WIKServerEnvironmentConfig;
+use crate::db::db_connection::DbConnection;
+use crate::db::db_executor::db_result_handler;
+use crate::dao::password_reset_token::{PasswordResetToken, PasswordResetTokenTable};
+use crate::error::ApiError;
+use crate::repository::login_throttle::LoginThrottleRepository;
+use crate::repository::password_policy::PasswordPolicyRepository;
+use crate::repository::user::UserRepository;
+
+use chrono::Utc;
+use tracing::*;
+
+const RESET_TOKEN_ID_LENGTH: usize = 16;
+
+/// One-time tokens (`<id>.<secret>`, only the hash of the secret is stored) issued by root,
+/// for the user to choose a new password without root knowing it.
+/// The token is delivered to the user out of band.
+pub struct PasswordResetRepository {}
+impl PasswordResetRepository {
+    /// Issue a reset token to the user, the previous tokens of the user are invalidated.
+    /// Return (token, expires_at), the token is not recoverable afterwards.
+    pub async fn issue_token(db_conn: &DbConnection, server_config: &WIKServerEnvironmentConfig,
+        username: &str) -> Result<(String, i64), ApiError> {
+
+        let role = UserRepository::get_user_role(db_conn, username).await?;
+        if role == UserRole::Root {
+            warn!("Try to issue password reset token to root user, which is not allowed.");
+            return Err(ApiError::InvalidArgument {
+                argument: "username".to_string(),
+                message: "Password of root user cannot be reset.".to_string(),
+            });
+        }
+
+        let secret = generate_random_token();
+        let hashed_secret = Password::new(&secret);
+        if let Err(error) = hashed_secret {
+            warn!("Fail to hash the password reset token. Error: {}", error);
+            return Err(ApiError::TokenCreation);
+        }
+        let hashed_secret = hashed_secret.unwrap();
+
+        db_result_handler(
+            PasswordResetTokenTable::delete_all_tokens_of_user(db_conn, username).await,
+            "delete_all_password_reset_tokens_of_user")?;
+
+        let now = Utc::now().timestamp();
+        let token = PasswordResetToken {
+            id: generate_random_id(RESET_TOKEN_ID_LENGTH),
+            username: username.to_string(),
+            token_hash: hashed_secret.hash,
+            token_salt: hashed_secret.salt,
+            created_at: now,
+            expires_at: now + server_config.config.password_reset_token_duration,
+        };
+        db_result_handler(
+            PasswordResetTokenTable::add_token(db_conn, &token).await,
+            "add_password_reset_token")?;
+        info!("Password reset token is issued to user '{}'.", token.username);
+
+        Ok((format!("{}.{}", token.id, secret), token.expires_at))
+    }
+
+    /// Set the new password of the token's owner.
+    /// Each token can only be redeemed once. An expired token is also invalidated,
+    /// but a wrong secret or a password rejected by the policy keeps the token for another try.
+    /// Return the username of the token's owner.
+    pub async fn redeem_token(db_conn: &DbConnection, server_config: &WIKServerEnvironmentConfig,
+        raw_token: &str, password: &str) -> Result<String, ApiError> {
+
+        let (id, secret) = raw_token.split_once('.').ok_or(ApiError::InvalidToken)?;
+        let token = db_result_handler(
+            PasswordResetTokenTable::get_token(db_conn, id).await,
+            "get_password_reset_token")?;
+        let token = match token {
+            Some(token) => token,
+            None => {
+                warn!("Password reset token not found.");
+                return Err(ApiError::InvalidToken);
+            }
+        };
+
+        if token.expires_at <= Utc::now().timestamp() {
+            warn!("Password reset token of user '{}' is expired.", token.username);
+            db_result_handler(
+                PasswordResetTokenTable::delete_token(db_conn, &token.id).await,
+                "delete_password_reset_token")?;
+            return Err(ApiError::InvalidToken);
+        }
+        // the token is kept, so a wrong guess cannot revoke it; the secret is too long to be guessed before expiry
+        if !verify_password(secret, &token.token_hash, &token.token_salt) {
+            warn!("Wrong secret of the password reset token of user '{}'.", token.username);
+            return Err(ApiError::InvalidToken);
+        }
+
+        let password_policy = &server_config.config.password_policy;
+        PasswordPolicyRepository::validate_password(db_conn, password_policy, &token.username, password).await?;
+
+        // invalidate the token before using it, a token cannot be redeemed twice concurrently
+        let deleted = db_result_handler(
+            PasswordResetTokenTable::delete_token(db_conn, &token.id).await,
+            "delete_password_reset_token")?;
+        if !deleted {
+            warn!("Password reset token of user '{}' is already redeemed.", token.username);
+            return Err(ApiError::InvalidToken);
+        }
+
+        // also revokes the sessions logged in with the old password
+        UserRepository::update_user(db_conn, password_policy, &token.username, password).await?;
+        // the user may be locked out for forgetting the old password
+        LoginThrottleRepository::unlock_user(db_conn, &token.username).await?;
+        info!("Password of user '{}' is reset with a reset token.", token.username);
+
+        Ok(token.username)
+    }
+
+    /// Delete the tokens not redeemed in time.
+    /// Return the number of deleted tokens.
+    pub async fn delete_expired_tokens(db_conn: &DbConnection) -> Result<u64, ApiError> {
+        db_result_handler(
+            PasswordResetTokenTable::delete_expired_tokens(db_conn, Utc::now().timestamp()).await,
+            "delete_expired_password_reset_tokens")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::server_config::WIKServerConfig;
+    use crate::dao::user::UserTable;
+    use crate::db::db_test_util::*;
+    use std::path::PathBuf;
+
+    #[tokio::test]
+    async fn test_reset_token_redeemed_once(){
+        let db_conn = create_test_db("test_reset_token_redeemed_once").await;
+        UserTable::create_user(&db_conn, "u_admin", &UserRole::Admin, "password").await.unwrap();
+        let server_config = WIKServerEnvironmentConfig {
+            base_dir: PathBuf::new(),
+            config: WIKServerConfig::default(),
+            root_user: None,
+        };
+
+        // a new token invalidates the previous one
+        let (old_token, _) = PasswordResetRepository::issue_token(&db_conn, &server_config, "u_admin").await.unwrap();
+        let (token, _) = PasswordResetRepository::issue_token(&db_conn, &server_config, "u_admin").await.unwrap();
+        let result = PasswordResetRepository::redeem_token(&db_conn, &server_config, &old_token, "New-Password-1").await;
+        assert!(matches!(result, Err(ApiError::InvalidToken)));
+
+        // the token is kept if the secret is wrong
+        let (id, _) = token.split_once('.').unwrap();
+        let result = PasswordResetRepository::redeem_token(&db_conn, &server_config, &format!("{}.wrong", id), "New-Password-1").await;
+        assert!(matches!(result, Err(ApiError::InvalidToken)));
+
+        // the token is kept if the password is rejected by the policy
+        let result = PasswordResetRepository::redeem_token(&db_conn, &server_config, &token, "weak").await;
+        assert!(matches!(result, Err(ApiError::InvalidArgument { message, .. }) if message.matches("Password must").count() == 3));
+
+        let username = PasswordResetRepository::redeem_token(&db_conn, &server_config, &token, "New-Password-1").await.unwrap();
+        assert_eq!(username, "u_admin");
+        let user = UserTable::get_user(&db_conn, "u_admin").await.unwrap().unwrap();
+        assert!(verify_password("New-Password-1", &user.encrypted_password, &user.password_salt));
+
+        let result = PasswordResetRepository::redeem_token(&db_conn, &server_config, &token, "Another-Password-2").await;
+        assert!(matches!(result, Err(ApiError::InvalidToken)));
+    }
+}
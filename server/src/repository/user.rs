@@ -1,18 +1,39 @@
 use well_i_known_core::modal::user::{self, UserKeyModal, ServerUserKeyModal, SeverUserModal, UserRole};
+use well_i_known_core::crypto::cryptography::WikRsaPublicKey;
+use well_i_known_core::crypto::password::generate_random_token;
 use well_i_known_core::modal::util::id_validation::validate_id;
 use crate::config::server_config::*;
 use crate::db::db_connection::DbConnection;
 use crate::db::db_executor::db_result_handler;
 use crate::dao::access_right::AccessRightTable;
-use crate::dao::user::UserTable;
+use crate::dao::api_token::ApiTokenTable;
+use crate::dao::auth_token::AuthTokenTable;
+use crate::dao::group_access_right::GroupAccessRightTable;
+use crate::dao::group_member::GroupMemberTable;
+use crate::dao::login_challenge::LoginChallengeTable;
+use crate::dao::password_history::PasswordHistoryTable;
+use crate::dao::password_reset_token::PasswordResetTokenTable;
+use crate::dao::user::{User, UserTable};
 
 use crate::auth::role_validation::RoleValidationUtil;
+use crate::repository::auth_token::AuthTokenRepository;
+use crate::repository::config_data::ConfigDataRepository;
+use crate::repository::login_challenge::LoginChallengeRepository;
+use crate::repository::password_policy::PasswordPolicyRepository;
 use crate::error::ApiError;
 
 use std::path::PathBuf;
 use std::str::FromStr;
 use tracing::*;
 
+/// The app / admin user to create.
+pub struct NewUser<'a> {
+    pub username: &'a str,
+    pub role: &'a UserRole,
+    pub password: Option<&'a str>,      // none: the user can only login with the private key challenge
+    pub public_key: &'a str,            // PEM of the user's public key
+}
+
 pub struct UserRepository {}
 impl UserRepository {
     /// Check if the given username is a valid user.
@@ -24,7 +45,8 @@ impl UserRepository {
     }
 
     /// Create a root user. Can only be called once.
-    pub async fn create_root_user(db_conn: &DbConnection, username: &str, password: &str) -> Result<(), ApiError> {
+    pub async fn create_root_user(db_conn: &DbConnection, password_policy: &WIKServerPasswordPolicyConfig,
+        username: &str, password: &str) -> Result<(), ApiError> {
         debug!("Creating a root user.");
 
         // check if root already exists
@@ -37,9 +59,15 @@ impl UserRepository {
             return Err(ApiError::DuplicateRecord);
         }
 
+        PasswordPolicyRepository::validate_password(db_conn, password_policy, username, password).await?;
+
         db_result_handler(
             UserTable::create_user(db_conn, username, &UserRole::Root, password).await,
             "create_root_user")?;
+        // root's key is generated by the server itself, no handshake is needed
+        db_result_handler(
+            UserTable::set_active(db_conn, username, true).await,
+            "set_user_active")?;
 
         Ok(())
     }
@@ -66,11 +94,12 @@ impl UserRepository {
     }
 
     /// Create a user with role 'app' or 'admin'.
-    pub async fn create_user(db_conn: &DbConnection, 
+    /// Without password, the user can only login with the private key challenge.
+    pub async fn create_user(db_conn: &DbConnection, password_policy: &WIKServerPasswordPolicyConfig,
         creator: &str, creator_role: &UserRole,
-        username: &str, role: &UserRole, password: &str,
-        public_key: &str, user_cert_path: &PathBuf) -> Result<(), ApiError> {
-        
+        new_user: &NewUser<'_>, user_cert_path: &PathBuf) -> Result<(), ApiError> {
+        let NewUser { username, role, password, public_key } = *new_user;
+
         if role == &UserRole::Root {
             return Err(ApiError::InvalidArgument { 
                 argument: "role".to_string(), 
@@ -97,6 +126,14 @@ impl UserRepository {
             return Err(ApiError::DuplicateRecord);
         }
 
+        let password = match password {
+            Some(password) => {
+                PasswordPolicyRepository::validate_password(db_conn, password_policy, username, password).await?;
+                password.to_string()
+            },
+            None => generate_random_token(),
+        };
+
         // compute the users cert path
         // store the public key in the pem file
         if let Err(error) = std::fs::write(&user_cert_path, public_key) {
@@ -106,27 +143,100 @@ impl UserRepository {
 
         // create the user
         db_result_handler(
-            UserTable::create_user(db_conn, username, &role, password).await,
+            UserTable::create_user(db_conn, username, role, &password).await,
             "create user")?;
 
         Ok(())
     }
 
+    /// Match the user federated from the OIDC provider with the wellik user.
+    /// The wellik role must be the one mapped from the token.
+    /// An unknown admin is created just in time (if enabled), without a password nor public key:
+    /// he can only sign in through the provider until he registers his public key.
+    pub async fn provision_federated_user(db_conn: &DbConnection, username: &str, role: &UserRole,
+        auto_provision: bool) -> Result<(), ApiError> {
+
+        let user = db_result_handler(
+            UserTable::get_user(db_conn, username).await,
+            "get_user")?;
+        if let Some(user) = user {
+            RoleValidationUtil::throw_if_unauthorized(
+                user.role == role.to_string(),
+                username,
+                &format!("sign in as {} through OIDC with role {}", user.role, role),
+            )?;
+            return Ok(());
+        }
+
+        RoleValidationUtil::throw_if_unauthorized(
+            auto_provision && role == &UserRole::Admin,
+            username,
+            "sign in through OIDC before being provisioned",
+        )?;
+        if let Err(error) = validate_id(username) {
+            return Err(ApiError::InvalidArgument {
+                argument: "username".to_string(),
+                message: error,
+            });
+        }
+
+        db_result_handler(
+            UserTable::create_user(db_conn, username, role, &generate_random_token()).await,
+            "create user")?;
+        info!("Admin '{}' is provisioned from OIDC.", username);
+        Ok(())
+    }
+
+    /// Register the public key of the user who has none (e.g. provisioned from OIDC).
+    /// The registered public key cannot be replaced, so it must be a valid PEM public key.
+    /// No data is shared with the user yet: he activates his account by the handshake (`/users/activate`) afterwards,
+    /// which encrypts the data he can access for him.
+    pub async fn register_public_key(username: &str, public_key: &str, user_cert_path: &PathBuf) -> Result<(), ApiError> {
+        if user_cert_path.exists() {
+            warn!("User '{}' already has a public key.", username);
+            return Err(ApiError::InvalidArgument {
+                argument: "public_key".to_string(),
+                message: "Public key cannot be updated after created.".to_string(),
+            });
+        }
+
+        if let Err(error) = WikRsaPublicKey::from_pem_str(public_key) {
+            warn!("User '{}' registers an invalid public key. Error: {}", username, error);
+            return Err(ApiError::InvalidArgument {
+                argument: "public_key".to_string(),
+                message: "Public key is not a valid PEM public key.".to_string(),
+            });
+        }
+
+        if let Err(error) = std::fs::write(user_cert_path, public_key) {
+            warn!("Fail to write public key to file. Error: {}", error);
+            return Err(ApiError::ServerError);
+        }
+        Ok(())
+    }
+
     /// Update the user data for the user id by the username.
     /// The updatable attributes are:
     /// - password
-    pub async fn update_user(db_conn: &DbConnection, username: &str, password: &str) -> Result<(), ApiError>{
+    pub async fn update_user(db_conn: &DbConnection, password_policy: &WIKServerPasswordPolicyConfig,
+        username: &str, password: &str) -> Result<(), ApiError>{
         // check if user already exists
         if !UserRepository::check_user_exists(db_conn, username).await? {
             warn!("Try to update user '{}' which does not exist.", username);
             return Err(ApiError::RecordNotFound);
         }
 
+        PasswordPolicyRepository::validate_password(db_conn, password_policy, username, password).await?;
+        PasswordPolicyRepository::remember_current_password(db_conn, password_policy, username).await?;
+
         // update the user
         db_result_handler(
             UserTable::update_user(db_conn, username, password).await,
             "update user")?;
 
+        // the tokens issued with the old password are no longer valid
+        AuthTokenRepository::revoke_all_sessions_of_user(db_conn, username).await?;
+
         Ok(())
     }
 
@@ -153,6 +263,10 @@ impl UserRepository {
                 db_result_handler(
                     AccessRightTable::delete_all_access_of_user(db_conn, username).await,
                     "delete_all_access_of_user")?;
+                // remove the admin from all groups
+                db_result_handler(
+                    GroupMemberTable::delete_all_membership_of_user(db_conn, username).await,
+                    "delete_all_membership_of_user")?;
                 }
                 
             user::UserRole::App => {
@@ -160,9 +274,33 @@ impl UserRepository {
                 db_result_handler(
                     AccessRightTable::delete_all_access_of_app(db_conn, username).await,
                     "delete_all_access_of_app")?;
+                db_result_handler(
+                    GroupAccessRightTable::delete_all_access_of_app(db_conn, username).await,
+                    "delete_all_group_access_of_app")?;
+            }
+
+            user::UserRole::Auditor => {
+                // auditor holds no access right nor data
             }
         }
 
+        // invalidate all tokens, pending login challenges and password reset tokens of the user
+        db_result_handler(
+            AuthTokenTable::delete_all_tokens_of_user(db_conn, username).await,
+            "delete_all_tokens_of_user")?;
+        db_result_handler(
+            ApiTokenTable::delete_all_tokens_of_user(db_conn, username).await,
+            "delete_all_api_tokens_of_user")?;
+        db_result_handler(
+            LoginChallengeTable::delete_all_challenges_of_user(db_conn, username).await,
+            "delete_all_challenges_of_user")?;
+        db_result_handler(
+            PasswordResetTokenTable::delete_all_tokens_of_user(db_conn, username).await,
+            "delete_all_password_reset_tokens_of_user")?;
+        db_result_handler(
+            PasswordHistoryTable::delete_all_passwords_of_user(db_conn, username).await,
+            "delete_all_password_history_of_user")?;
+
         // delete the user
         db_result_handler(
             UserTable::delete_user(db_conn, username).await,
@@ -199,6 +337,96 @@ impl UserRepository {
         }
     }
 
+    /// Get the role of the user.
+    pub async fn get_user_role(db_conn: &DbConnection, username: &str) -> Result<UserRole, ApiError> {
+        let user = db_result_handler(
+            UserTable::get_user(db_conn, username).await,
+            "get_user")?;
+
+        match user {
+            Some(user) => UserRole::from_str(&user.role).map_err(|_| ApiError::ServerError),
+            None => {
+                warn!("User '{}' not found.", username);
+                Err(ApiError::RecordNotFound)
+            }
+        }
+    }
+
+    /// Throw Unauthorized if the user is disabled by root.
+    pub async fn throw_if_disabled(db_conn: &DbConnection, username: &str) -> Result<(), ApiError> {
+        let user = db_result_handler(
+            UserTable::get_user(db_conn, username).await,
+            "get_user")?
+            .ok_or(ApiError::RecordNotFound)?;
+        RoleValidationUtil::throw_if_unauthorized(!user.disabled, username, "login with a disabled account")
+    }
+
+    /// Throw Unauthorized if the user is disabled, or has not validated his public key by the handshake.
+    pub async fn throw_if_not_active(db_conn: &DbConnection, username: &str) -> Result<(), ApiError> {
+        let user = db_result_handler(
+            UserTable::get_user(db_conn, username).await,
+            "get_user")?
+            .ok_or(ApiError::RecordNotFound)?;
+        RoleValidationUtil::throw_if_unauthorized(!user.disabled, username, "login with a disabled account")?;
+        RoleValidationUtil::throw_if_unauthorized(user.active, username, "login before activating the account")
+    }
+
+    /// Check if the data can be encrypted for the user,
+    /// i.e. his public key is validated and he is not disabled.
+    pub async fn is_data_recipient(db_conn: &DbConnection, username: &str) -> Result<bool, ApiError> {
+        let user = db_result_handler(
+            UserTable::get_user(db_conn, username).await,
+            "get_user")?;
+        Ok(user.is_some_and(|user| user.active && !user.disabled))
+    }
+
+    /// Activate the user who answered the handshake challenge,
+    /// i.e. proved the possession of the private key of his registered public key.
+    /// The data he can access is encrypted for him afterwards.
+    /// Return the activated username.
+    pub async fn activate_user(db_conn: &DbConnection, server_config: &WIKServerEnvironmentConfig,
+        challenge_id: &str, plaintext: &str) -> Result<String, ApiError> {
+
+        let (username, role) = LoginChallengeRepository::verify_challenge(db_conn, challenge_id, plaintext).await?;
+        db_result_handler(
+            UserTable::set_active(db_conn, &username, true).await,
+            "set_user_active")?;
+        info!("User '{}' is activated.", username);
+
+        if UserRepository::is_data_recipient(db_conn, &username).await? {
+            ConfigDataRepository::share_all_data_with_user(db_conn, server_config, &username, &role).await?;
+        }
+        Ok(username)
+    }
+
+    /// Disable / enable the user.
+    /// A disabled user cannot login and no data is encrypted for him, but his existing data is kept.
+    /// Root cannot be disabled.
+    pub async fn set_user_disabled(db_conn: &DbConnection, server_config: &WIKServerEnvironmentConfig,
+        username: &str, disabled: bool) -> Result<(), ApiError> {
+
+        let role = UserRepository::get_user_role(db_conn, username).await?;
+        if role == UserRole::Root {
+            warn!("Try to disable root user, which is not allowed.");
+            return Err(ApiError::InvalidArgument {
+                argument: "username".to_string(),
+                message: "Root user cannot be disabled.".to_string(),
+            });
+        }
+
+        db_result_handler(
+            UserTable::set_disabled(db_conn, username, disabled).await,
+            "set_user_disabled")?;
+
+        if disabled {
+            AuthTokenRepository::revoke_all_sessions_of_user(db_conn, username).await?;
+        } else if UserRepository::is_data_recipient(db_conn, username).await? {
+            // catch up the data changed while disabled
+            ConfigDataRepository::share_all_data_with_user(db_conn, server_config, username, &role).await?;
+        }
+        Ok(())
+    }
+
     /// Check if the given username is a exiting user with the given role.
     pub async fn is_valid_user_of_role(db_conn: &DbConnection, username: &str, role: &UserRole) -> Result<bool, ApiError> {
         db_result_handler(
@@ -206,12 +434,22 @@ impl UserRepository {
             "check_user_with_role_exists")
     }
 
-    /// Get all the user that can access to the given app's config, include
+    /// Get all users (without credentials) for the metadata listing.
+    pub async fn get_all_users(db_conn: &DbConnection) -> Result<Vec<User>, ApiError> {
+        db_result_handler(
+            UserTable::get_all_users(db_conn).await,
+            "get_all_users")
+    }
+
+    /// Get all the user that can access to the given app's config in the environment, include
     /// - Root
     /// - The app
-    /// - All admin with access right
+    /// - All admin with access right to the environment
+    ///
+    /// Auditors never appear in the list, so no ciphertext is ever made for them.
+    /// Neither do the inactive / disabled users.
     pub async fn get_users_with_access_to(db_conn: &DbConnection, server_config: &WIKServerEnvironmentConfig,
-            app_name: &str) -> Result<Vec<ServerUserKeyModal>, ApiError> {
+            app_name: &str, env: &str) -> Result<Vec<ServerUserKeyModal>, ApiError> {
 
         let app_users_exists = db_result_handler(
             UserTable::check_user_with_role_exists(db_conn, app_name, &UserRole::App).await,
@@ -223,7 +461,7 @@ impl UserRepository {
         }
 
         let admin_users = db_result_handler(
-            UserTable::get_admin_with_access(db_conn, app_name).await,
+            UserTable::get_admin_with_access(db_conn, app_name, env).await,
             "get_admin_with_access")?;
 
         // map the users to ServerUserKeyModal
@@ -234,16 +472,18 @@ impl UserRepository {
         users.push(ServerUserKeyModal::new_from_key(&root.username, &root.key.public_key));
 
         // map app
-        let app = ServerUserKeyModal::new(&app_name, &server_config.get_users_certs_path(app_name));
+        if UserRepository::is_data_recipient(db_conn, app_name).await? {
+            let app = ServerUserKeyModal::new(&app_name, &server_config.get_users_certs_path(app_name));
 
-        if let Err(error) = app {
-            warn!("Fail to create app user. Error: {}", error);
-            return Err(ApiError::ServerError);
+            if let Err(error) = app {
+                warn!("Fail to create app user. Error: {}", error);
+                return Err(ApiError::ServerError);
+            }
+            users.push(app.unwrap());
         }
-        users.push(app.unwrap());
 
         // map admin
-        for admin in admin_users {
+        for admin in admin_users.into_iter().filter(|admin| admin.active && !admin.disabled) {
             let admin = ServerUserKeyModal::new(&admin.username, &server_config.get_users_certs_path(&admin.username));
             if let Err(error) = admin {
                 warn!("Fail to create admin user. Error: {}", error);
@@ -267,4 +507,82 @@ impl UserRepository {
             return Err(ApiError::WrongCredentials);
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::db_test_util::*;
+    use well_i_known_core::crypto::password::Password;
+
+    #[tokio::test]
+    async fn test_provision_federated_user(){
+        let db_conn = create_test_db("test_provision_federated_user").await;
+        UserTable::create_user(&db_conn, "u_app", &UserRole::App, "password").await.unwrap();
+
+        UserRepository::provision_federated_user(&db_conn, "u_admin", &UserRole::Admin, true).await.unwrap();
+        assert!(UserRepository::is_valid_user_of_role(&db_conn, "u_admin", &UserRole::Admin).await.unwrap());
+
+        // the mapped role must match the existing user
+        let result = UserRepository::provision_federated_user(&db_conn, "u_app", &UserRole::Admin, true).await;
+        assert!(matches!(result, Err(ApiError::Unauthorized { .. })));
+        // only admins are provisioned
+        let result = UserRepository::provision_federated_user(&db_conn, "u_auditor", &UserRole::Auditor, true).await;
+        assert!(matches!(result, Err(ApiError::Unauthorized { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_register_invalid_public_key(){
+        let user_cert_path = get_test_path("output/test_register_invalid_public_key.pem");
+        if user_cert_path.exists() {
+            std::fs::remove_file(&user_cert_path).unwrap();
+        }
+
+        let result = UserRepository::register_public_key("u_admin", "not a public key", &user_cert_path).await;
+        assert!(matches!(result, Err(ApiError::InvalidArgument { .. })));
+        // the user can still register a valid key
+        assert!(!user_cert_path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_activate_and_disable_user(){
+        let db_conn = create_test_db("test_activate_and_disable_user").await;
+        let server_config = WIKServerEnvironmentConfig {
+            base_dir: PathBuf::new(),
+            config: WIKServerConfig::default(),
+            root_user: None,
+        };
+        UserTable::create_user(&db_conn, "u_auditor", &UserRole::Auditor, "password").await.unwrap();
+        let result = UserRepository::throw_if_not_active(&db_conn, "u_auditor").await;
+        assert!(matches!(result, Err(ApiError::Unauthorized { .. })));
+
+        let answer = Password::new("nonce").unwrap();
+        let expires_at = chrono::Utc::now().timestamp() + 60;
+        LoginChallengeTable::add_challenge(&db_conn, "c_1", "u_auditor", &answer.hash, &answer.salt, expires_at).await.unwrap();
+        UserRepository::activate_user(&db_conn, &server_config, "c_1", "nonce").await.unwrap();
+        UserRepository::throw_if_not_active(&db_conn, "u_auditor").await.unwrap();
+
+        UserRepository::set_user_disabled(&db_conn, &server_config, "u_auditor", true).await.unwrap();
+        let result = UserRepository::throw_if_not_active(&db_conn, "u_auditor").await;
+        assert!(matches!(result, Err(ApiError::Unauthorized { .. })));
+        assert!(!UserRepository::is_data_recipient(&db_conn, "u_auditor").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_update_user_password_policy(){
+        let db_conn = create_test_db("test_update_user_password_policy").await;
+        let policy = WIKServerPasswordPolicyConfig { history_size: 2, ..Default::default() };
+        UserTable::create_user(&db_conn, "u_admin", &UserRole::Admin, "Password-0001").await.unwrap();
+
+        let result = UserRepository::update_user(&db_conn, &policy, "u_admin", "short").await;
+        assert!(matches!(result, Err(ApiError::InvalidArgument { .. })));
+
+        // the current password and the one before cannot be reused
+        let result = UserRepository::update_user(&db_conn, &policy, "u_admin", "Password-0001").await;
+        assert!(matches!(result, Err(ApiError::InvalidArgument { .. })));
+        UserRepository::update_user(&db_conn, &policy, "u_admin", "Password-0002").await.unwrap();
+        let result = UserRepository::update_user(&db_conn, &policy, "u_admin", "Password-0001").await;
+        assert!(matches!(result, Err(ApiError::InvalidArgument { .. })));
+        UserRepository::update_user(&db_conn, &policy, "u_admin", "Password-0003").await.unwrap();
+        UserRepository::update_user(&db_conn, &policy, "u_admin", "Password-0001").await.unwrap();
+    }
+}
@@ -1,8 +1,13 @@
-use crate::dao::{access_right::AccessRightTable, config_data::ConfigDataTable, user::UserTable};
+use crate::dao::{access_right::AccessRightTable, api_token::ApiTokenTable, auth_token::AuthTokenTable, config_data::ConfigDataTable, user::UserTable};
+use crate::dao::{app_schema::AppSchemaTable, app_setting::AppSettingTable, config_data_revision::ConfigDataRevisionTable, config_plain_data::ConfigPlainDataTable, config_plain_data_version::ConfigPlainDataVersionTable, config_data_expiry::ConfigDataExpiryTable, config_data_version::ConfigDataVersionTable};
+use crate::dao::{admin_group::AdminGroupTable, group_access_right::GroupAccessRightTable, group_member::GroupMemberTable};
+use crate::dao::{break_glass_event::BreakGlassEventTable, notification::NotificationTable, pending_operation::PendingOperationTable};
+use crate::dao::{login_challenge::LoginChallengeTable, login_failure::LoginFailureTable, password_history::PasswordHistoryTable, password_reset_token::PasswordResetTokenTable};
+use crate::repository::config_data::ConfigDataRepository;
 use crate::repository::user::UserRepository;
 use crate::db::db_base::DbTable;
 use crate::db::db_connection::DbConnection;
-use crate::config::server_config::{self, WIKServerEnvironmentConfig};
+use crate::config::server_config::{self, WIKServerEnvironmentConfig, WIKServerPasswordPolicyConfig};
 use well_i_known_core::crypto::cryptography::WikRsaKeyPair;
 
 use tracing::*;
@@ -34,6 +39,7 @@ impl ServerInit {
         let root_certs_dir = config.get_root_certs_dir_path();
         create_dir_if_not_exists(&root_certs_dir);
         create_dir_if_not_exists(&config.get_users_certs_dir_path());
+        create_dir_if_not_exists(&config.get_jwt_certs_dir_path());
         create_dir_if_not_exists(&config.get_data_dir_path());
         create_dir_if_not_exists(&config.get_log_dir_path());
 
@@ -55,20 +61,44 @@ impl ServerInit {
     }
 
     /// Initialize the server database.
-    /// Create database tables.
+    /// Create database tables, and migrate the config keys stored with the alternative separator.
     pub async fn init_server_database(db_conn: &DbConnection) {
         info!("Enabling sqlite foreign key support...");
         DbConnection::enable_sqlite_foreign_key_support(db_conn).await.expect("Fail to enable sqlite foreign key support.");
         info!("Creating database tables...");
         UserTable::create_table(db_conn).await;
+        AuthTokenTable::create_table(db_conn).await;
+        ApiTokenTable::create_table(db_conn).await;
+        LoginChallengeTable::create_table(db_conn).await;
+        LoginFailureTable::create_table(db_conn).await;
+        PasswordResetTokenTable::create_table(db_conn).await;
+        PasswordHistoryTable::create_table(db_conn).await;
         AccessRightTable::create_table(db_conn).await;
+        AdminGroupTable::create_table(db_conn).await;
+        GroupMemberTable::create_table(db_conn).await;
+        GroupAccessRightTable::create_table(db_conn).await;
         ConfigDataTable::create_table(db_conn).await;
+        ConfigDataVersionTable::create_table(db_conn).await;
+        ConfigDataRevisionTable::create_table(db_conn).await;
+        ConfigPlainDataTable::create_table(db_conn).await;
+        ConfigPlainDataVersionTable::create_table(db_conn).await;
+        ConfigDataExpiryTable::create_table(db_conn).await;
+        AppSettingTable::create_table(db_conn).await;
+        AppSchemaTable::create_table(db_conn).await;
+        BreakGlassEventTable::create_table(db_conn).await;
+        NotificationTable::create_table(db_conn).await;
+        PendingOperationTable::create_table(db_conn).await;
         info!("Tables created.");
+
+        info!("Normalizing the separators of the stored config keys...");
+        let renamed = ConfigDataRepository::normalize_stored_keys(db_conn).await.expect("Fail to normalize the config keys.");
+        info!("{} config keys renamed.", renamed);
     }
 
     /// Create the root user. Should only be called once.
-    pub async fn init_root_user(db_conn: &DbConnection, username: &str, password: &str) {
-        UserRepository::create_root_user(db_conn, username, password).await.expect("Fail to create root user.");
+    /// The password must follow the password policy of the server.
+    pub async fn init_root_user(db_conn: &DbConnection, password_policy: &WIKServerPasswordPolicyConfig, username: &str, password: &str) {
+        UserRepository::create_root_user(db_conn, password_policy, username, password).await.expect("Fail to create root user.");
     }
 
     /// Write the server pid to the pid file.
@@ -1,4 +1,4 @@
-use crate::{auth::jwt_key::JwtKeys, db::db_connection::DbConnection, WIKServerEnvironmentConfig};
+use crate::{auth::{jwt_key::JwtKeys, oidc::OidcVerifier}, db::db_connection::DbConnection, WIKServerEnvironmentConfig};
 
 /// The server state that will be shared across the api controllers.
 #[derive(Clone)]
@@ -6,4 +6,5 @@ pub struct ServerState {
     pub db_conn: DbConnection,
     pub config: WIKServerEnvironmentConfig,
     pub jwt_keys: JwtKeys,
+    pub oidc: Option<OidcVerifier>,     // none if OIDC federation is not configured
 }
@@ -0,0 +1,14 @@
+use crate::repository::access_right::AccessRightRepository;
+use crate::server_state::ServerState;
+
+use tracing::*;
+
+/// Revoke the time-bound access rights that have expired.
+/// Errors are logged only, the task will retry in the next run.
+pub async fn revoke_expired_access_rights(server_state: &ServerState) {
+    match AccessRightRepository::revoke_expired_access_rights(&server_state.db_conn).await {
+        Ok(0) => {},
+        Ok(count) => info!("Revoked {} expired access rights.", count),
+        Err(error) => warn!("Fail to revoke expired access rights. Error: {:?}", error),
+    }
+}
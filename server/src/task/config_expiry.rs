@@ -0,0 +1,14 @@
+use crate::repository::config_expiry::ConfigExpiryRepository;
+use crate::server_state::ServerState;
+
+use tracing::*;
+
+/// Warn the users with access of the config values expiring soon (or expired).
+/// Errors are logged only, the task will retry in the next run.
+pub async fn warn_expiring_config(server_state: &ServerState) {
+    match ConfigExpiryRepository::warn_expiring_data(&server_state.db_conn, &server_state.config).await {
+        Ok(0) => {},
+        Ok(count) => info!("Warned of {} expiring config values.", count),
+        Err(error) => warn!("Fail to warn of expiring config values. Error: {:?}", error),
+    }
+}
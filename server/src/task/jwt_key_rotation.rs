@@ -0,0 +1,27 @@
+use crate::server_state::ServerState;
+
+use std::sync::Arc;
+use tracing::*;
+
+/// Replace the JWT signing key when it is older than the rotation interval.
+/// The retired key is still accepted until the tokens signed by it have expired.
+/// The RSA key generation is CPU bound, so it runs on the blocking thread pool.
+/// Errors are logged only, the task will retry in the next run.
+pub async fn rotate_jwt_keys(server_state: &Arc<ServerState>) {
+    let server_state = server_state.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        let config = &server_state.config;
+        server_state.jwt_keys.rotate_if_needed(
+            &config.get_jwt_certs_dir_path(),
+            config.config.jwt_key_rotation_interval,
+            config.config.access_token_duration,
+        )
+    }).await;
+
+    match result {
+        Ok(Ok(false)) => {},
+        Ok(Ok(true)) => info!("Rotated the JWT signing key."),
+        Ok(Err(error)) => warn!("Fail to rotate the JWT signing key. Error: {:?}", error),
+        Err(error) => warn!("JWT signing key rotation is aborted. Error: {}", error),
+    }
+}
@@ -0,0 +1,33 @@
+pub mod access_expiry;
+pub mod config_expiry;
+pub mod jwt_key_rotation;
+pub mod oidc_jwks_refresh;
+pub mod session_cleanup;
+
+use crate::server_state::ServerState;
+
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::*;
+
+/// Start the periodic background tasks of the server.
+/// All tasks are run one by one in every interval set in the server config.
+pub fn start_background_tasks(server_state: Arc<ServerState>) {
+    let interval = Duration::from_secs(server_state.config.config.background_task_interval);
+    debug!("Starting background tasks with interval {:?}...", interval);
+
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            trace!("Running background tasks...");
+            access_expiry::revoke_expired_access_rights(&server_state).await;
+            config_expiry::warn_expiring_config(&server_state).await;
+            session_cleanup::delete_expired_sessions(&server_state).await;
+            session_cleanup::delete_expired_challenges(&server_state).await;
+            session_cleanup::delete_expired_reset_tokens(&server_state).await;
+            jwt_key_rotation::rotate_jwt_keys(&server_state).await;
+            oidc_jwks_refresh::refresh_oidc_jwks(&server_state).await;
+        }
+    });
+}
@@ -0,0 +1,18 @@
+use crate::server_state::ServerState;
+
+use tracing::*;
+
+/// Reload the public keys of the OIDC provider when they are older than the refresh interval.
+/// Errors are logged only, the old keys are kept and the task will retry in the next run.
+pub async fn refresh_oidc_jwks(server_state: &ServerState) {
+    let oidc = match &server_state.oidc {
+        Some(oidc) => oidc,
+        None => return,
+    };
+
+    match oidc.refresh_if_needed().await {
+        Ok(false) => {},
+        Ok(true) => info!("Reloaded the OIDC provider keys."),
+        Err(error) => warn!("Fail to reload the OIDC provider keys. Error: {:?}", error),
+    }
+}
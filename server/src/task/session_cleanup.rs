@@ -0,0 +1,34 @@
+use crate::repository::auth_token::AuthTokenRepository;
+use crate::repository::login_challenge::LoginChallengeRepository;
+use crate::repository::password_reset::PasswordResetRepository;
+use crate::server_state::ServerState;
+
+use tracing::*;
+
+/// Delete the login sessions whose refresh token has expired.
+/// Errors are logged only, the task will retry in the next run.
+pub async fn delete_expired_sessions(server_state: &ServerState) {
+    match AuthTokenRepository::delete_expired_sessions(&server_state.db_conn).await {
+        Ok(0) => {},
+        Ok(count) => info!("Deleted {} expired sessions.", count),
+        Err(error) => warn!("Fail to delete expired sessions. Error: {:?}", error),
+    }
+}
+
+/// Delete the login challenges that are not answered in time.
+pub async fn delete_expired_challenges(server_state: &ServerState) {
+    match LoginChallengeRepository::delete_expired_challenges(&server_state.db_conn).await {
+        Ok(0) => {},
+        Ok(count) => info!("Deleted {} expired login challenges.", count),
+        Err(error) => warn!("Fail to delete expired login challenges. Error: {:?}", error),
+    }
+}
+
+/// Delete the password reset tokens that are not redeemed in time.
+pub async fn delete_expired_reset_tokens(server_state: &ServerState) {
+    match PasswordResetRepository::delete_expired_tokens(&server_state.db_conn).await {
+        Ok(0) => {},
+        Ok(count) => info!("Deleted {} expired password reset tokens.", count),
+        Err(error) => warn!("Fail to delete expired password reset tokens. Error: {:?}", error),
+    }
+}
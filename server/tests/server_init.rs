@@ -32,7 +32,7 @@ async fn server_init(){
     // create a new server database
     ServerInit::init_server_database(&conn).await;
     // create a new server root user
-    ServerInit::init_root_user(&conn, "root", "root_password").await;
+    ServerInit::init_root_user(&conn, &server_env_config.config.password_policy, "root", "Root-Password-1").await;
 
     // start the server
     let _ = WIKServer::start_server(&mut server_env_config).await;